@@ -91,6 +91,7 @@ fn get_jwk_for_authenticator(
     jwks: &PatchedJWKs,
     pk: &KeylessPublicKey,
     sig: &KeylessSignature,
+    features: &Features,
 ) -> Result<JWK, VMStatus> {
     let jwt_header = sig
         .parse_jwt_header()
@@ -107,7 +108,14 @@ fn get_jwk_for_authenticator(
 
     match &jwk {
         JWK::RSA(rsa_jwk) => {
-            if rsa_jwk.alg != jwt_header.alg {
+            // Keys without an `alg` are only matched leniently once the revised JWK parsing
+            // rules are enabled (see `FeatureFlag::JWK_PARSING_V2`).
+            let alg_matches = if features.is_jwk_parsing_v2_enabled() {
+                rsa_jwk.is_compatible_with_jwt_alg(&jwt_header.alg)
+            } else {
+                rsa_jwk.alg == jwt_header.alg
+            };
+            if !alg_matches {
                 return Err(invalid_signature!(format!(
                     "JWK alg ({}) does not match JWT header's alg ({})",
                     rsa_jwk.alg, jwt_header.alg
@@ -192,7 +200,7 @@ pub(crate) fn validate_authenticators(
     };
 
     for (pk, sig) in authenticators {
-        let jwk = get_jwk_for_authenticator(&patched_jwks, pk, sig)?;
+        let jwk = get_jwk_for_authenticator(&patched_jwks, pk, sig, features)?;
 
         match &sig.cert {
             EphemeralCertificate::ZeroKnowledgeSig(zksig) => match jwk {
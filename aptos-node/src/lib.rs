@@ -204,7 +204,7 @@ pub struct AptosHandle {
     _indexer_table_info_runtime: Option<Runtime>,
     _jwk_consensus_runtime: Option<Runtime>,
     _mempool_runtime: Runtime,
-    _network_runtimes: Vec<Runtime>,
+    _network_startup_report: network::NetworkStartupReport,
     _peer_monitoring_service_runtime: Runtime,
     _state_sync_runtimes: StateSyncRuntimes,
     _telemetry_runtime: Option<Runtime>,
@@ -641,16 +641,16 @@ pub fn setup_environment_and_start_node(
 
     // Set up the networks and gather the application network handles
     let peers_and_metadata = network::create_peers_and_metadata(&node_config);
-    let (
-        network_runtimes,
-        consensus_network_interfaces,
-        consensus_observer_network_interfaces,
-        dkg_network_interfaces,
-        jwk_consensus_network_interfaces,
-        mempool_network_interfaces,
-        peer_monitoring_service_network_interfaces,
-        storage_service_network_interfaces,
-    ) = network::setup_networks_and_get_interfaces(
+    let network::ApplicationNetworks {
+        startup_report: network_startup_report,
+        consensus_interfaces: consensus_network_interfaces,
+        consensus_observer_interfaces: consensus_observer_network_interfaces,
+        dkg_interfaces: dkg_network_interfaces,
+        jwk_consensus_interfaces: jwk_consensus_network_interfaces,
+        mempool_interfaces: mempool_network_interfaces,
+        peer_monitoring_service_interfaces: peer_monitoring_service_network_interfaces,
+        storage_service_interfaces: storage_service_network_interfaces,
+    } = network::build_all_application_networks(
         &node_config,
         chain_id,
         peers_and_metadata.clone(),
@@ -772,7 +772,7 @@ pub fn setup_environment_and_start_node(
         _indexer_table_info_runtime: indexer_table_info_runtime,
         _jwk_consensus_runtime: jwk_consensus_runtime,
         _mempool_runtime: mempool_runtime,
-        _network_runtimes: network_runtimes,
+        _network_startup_report: network_startup_report,
         _peer_monitoring_service_runtime: peer_monitoring_service_runtime,
         _state_sync_runtimes: state_sync_runtimes,
         _telemetry_runtime: telemetry_runtime,
@@ -4,7 +4,7 @@
 use crate::services::start_netbench_service;
 use aptos_channels::{self, aptos_channel, message_queues::QueueStyle};
 use aptos_config::{
-    config::{NetworkConfig, NodeConfig},
+    config::{NetworkConfig, NodeConfig, OutboundProtocolRateLimit, RoleType},
     network_id::NetworkId,
 };
 use aptos_consensus::{
@@ -14,16 +14,19 @@ use aptos_consensus::{
 use aptos_dkg_runtime::DKGMessage;
 use aptos_event_notifications::EventSubscriptionService;
 use aptos_jwk_consensus::types::JWKConsensusMsg;
-use aptos_logger::debug;
+use aptos_logger::{debug, info, log_context, warn};
 use aptos_mempool::network::MempoolSyncMsg;
 use aptos_network::{
     application::{
         interface::{NetworkClient, NetworkServiceEvents},
         storage::PeersAndMetadata,
     },
-    protocols::network::{
-        NetworkApplicationConfig, NetworkClientConfig, NetworkEvents, NetworkSender,
-        NetworkServiceConfig,
+    protocols::{
+        network::{
+            NetworkApplicationConfig, NetworkClientConfig, NetworkEvents, NetworkSender,
+            NetworkServiceConfig,
+        },
+        wire::handshake::v1::Protocols,
     },
     ProtocolId,
 };
@@ -32,9 +35,10 @@ use aptos_network_builder::builder::NetworkBuilder;
 use aptos_peer_monitoring_service_types::PeerMonitoringServiceMessage;
 use aptos_storage_service_types::StorageServiceMessage;
 use aptos_time_service::TimeService;
-use aptos_types::chain_id::ChainId;
+use aptos_types::{chain_id::ChainId, network_address::NetworkAddress};
+use futures::future::join_all;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
 
 /// A simple struct that holds both the network client
@@ -44,20 +48,115 @@ pub struct ApplicationNetworkInterfaces<T> {
     pub network_service_events: NetworkServiceEvents<T>,
 }
 
+/// The network interfaces of all applications running on the node,
+/// along with the startup report of the networks they are registered with.
+pub struct ApplicationNetworks {
+    pub startup_report: NetworkStartupReport,
+    pub consensus_interfaces: Option<ApplicationNetworkInterfaces<ConsensusMsg>>,
+    pub consensus_observer_interfaces:
+        Option<ApplicationNetworkInterfaces<ConsensusObserverMessage>>,
+    pub dkg_interfaces: Option<ApplicationNetworkInterfaces<DKGMessage>>,
+    pub jwk_consensus_interfaces: Option<ApplicationNetworkInterfaces<JWKConsensusMsg>>,
+    pub mempool_interfaces: ApplicationNetworkInterfaces<MempoolSyncMsg>,
+    pub peer_monitoring_service_interfaces:
+        ApplicationNetworkInterfaces<PeerMonitoringServiceMessage>,
+    pub storage_service_interfaces: ApplicationNetworkInterfaces<StorageServiceMessage>,
+}
+
+/// The result of setting up all networks at boot. The runtimes and builders
+/// must be kept alive for the lifetime of the node, and the per-network info
+/// summarizes what was set up (e.g., to log it, or expose it to operators).
+pub struct NetworkStartupReport {
+    pub runtimes: Vec<Runtime>,
+    pub builders: Vec<NetworkBuilder>, // Kept to reload each network's config (see `NetworkBuilder::reload`)
+    pub per_network: Vec<NetworkStartupInfo>,
+}
+
+impl NetworkStartupReport {
+    /// Logs a summary of each network (and any warnings) set up at boot
+    pub fn log_summary(&self) {
+        for network_info in &self.per_network {
+            info!(
+                "Network started: {:?} (role: {}), listening on: {:?}, registered protocols: {:?}",
+                network_info.network_id,
+                network_info.role,
+                network_info.listen_addresses,
+                network_info.registered_protocols
+            );
+            for warning in &network_info.warnings {
+                warn!("Network {:?}: {}", network_info.network_id, warning);
+            }
+        }
+    }
+
+    /// Gracefully shuts down all networks (e.g., for a clean node restart, or at the
+    /// end of a test). First, each network is quiesced: new outbound sends are rejected,
+    /// the listener and connectivity manager are stopped (so no new connections are
+    /// made), and the in-flight RPCs are given up to `timeout` to complete (see
+    /// `NetworkBuilder::quiesce`). Then, the runtimes are shut down, waiting for at
+    /// most `timeout` for each runtime's blocking tasks. Dropping the runtimes closes
+    /// all connections (and the application event streams).
+    ///
+    /// Note: this blocks the calling thread, so it must not be called from within
+    /// an async context.
+    pub fn shutdown(self, timeout: Duration) {
+        info!(
+            "Shutting down {} network(s) with timeout: {:?}",
+            self.builders.len(),
+            timeout
+        );
+
+        // Quiesce all networks concurrently (returning once their in-flight RPCs complete)
+        if let Some(runtime) = self.runtimes.first() {
+            let quiesce_networks = self
+                .builders
+                .iter()
+                .map(|network_builder| network_builder.quiesce(timeout));
+            runtime.block_on(join_all(quiesce_networks));
+        }
+
+        // Tear down the networks and their runtimes
+        drop(self.builders);
+        for runtime in self.runtimes {
+            runtime.shutdown_timeout(timeout);
+        }
+        info!("All networks were shut down!");
+    }
+}
+
+/// A summary of a single network set up at boot
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct NetworkStartupInfo {
+    pub network_id: NetworkId,
+    pub role: RoleType,
+    pub listen_addresses: Vec<NetworkAddress>, // The actual bound addresses
+    pub registered_protocols: Vec<ProtocolId>,
+    pub warnings: Vec<String>, // Non-fatal issues detected during setup
+}
+
 /// A simple struct that holds an individual application
 /// network handle (i.e., network id, sender and receiver).
-struct ApplicationNetworkHandle<T> {
+pub(crate) struct ApplicationNetworkHandle<T> {
     pub network_id: NetworkId,
     pub network_sender: NetworkSender<T>,
     pub network_events: NetworkEvents<T>,
 }
 
+/// Returns the given protocols (sorted by preference) in their canonical order (see
+/// `Protocols::canonicalize()`), so that the same protocols are always registered
+/// (and advertised) identically.
+fn canonical_protocols(protocols: &[ProtocolId]) -> Vec<ProtocolId> {
+    let mut protocols = Protocols::from(protocols);
+    protocols.canonicalize();
+    protocols.into_inner()
+}
+
 /// TODO: make this configurable (e.g., for compression)
 /// Returns the network application config for the consensus client and service
 pub fn consensus_network_configuration(node_config: &NodeConfig) -> NetworkApplicationConfig {
-    let direct_send_protocols: Vec<ProtocolId> =
-        aptos_consensus::network_interface::DIRECT_SEND.into();
-    let rpc_protocols: Vec<ProtocolId> = aptos_consensus::network_interface::RPC.into();
+    let direct_send_protocols =
+        canonical_protocols(aptos_consensus::network_interface::DIRECT_SEND);
+    let rpc_protocols = canonical_protocols(aptos_consensus::network_interface::RPC);
 
     let network_client_config =
         NetworkClientConfig::new(direct_send_protocols.clone(), rpc_protocols.clone());
@@ -73,9 +172,9 @@ pub fn consensus_network_configuration(node_config: &NodeConfig) -> NetworkAppli
 
 /// Returns the network application config for the DKG client and service
 pub fn dkg_network_configuration(node_config: &NodeConfig) -> NetworkApplicationConfig {
-    let direct_send_protocols: Vec<ProtocolId> =
-        aptos_dkg_runtime::network_interface::DIRECT_SEND.into();
-    let rpc_protocols: Vec<ProtocolId> = aptos_dkg_runtime::network_interface::RPC.into();
+    let direct_send_protocols =
+        canonical_protocols(aptos_dkg_runtime::network_interface::DIRECT_SEND);
+    let rpc_protocols = canonical_protocols(aptos_dkg_runtime::network_interface::RPC);
 
     let network_client_config =
         NetworkClientConfig::new(direct_send_protocols.clone(), rpc_protocols.clone());
@@ -90,9 +189,9 @@ pub fn dkg_network_configuration(node_config: &NodeConfig) -> NetworkApplication
 
 /// Returns the network application config for the JWK consensus client and service
 pub fn jwk_consensus_network_configuration(node_config: &NodeConfig) -> NetworkApplicationConfig {
-    let direct_send_protocols: Vec<ProtocolId> =
-        aptos_jwk_consensus::network_interface::DIRECT_SEND.into();
-    let rpc_protocols: Vec<ProtocolId> = aptos_jwk_consensus::network_interface::RPC.into();
+    let direct_send_protocols =
+        canonical_protocols(aptos_jwk_consensus::network_interface::DIRECT_SEND);
+    let rpc_protocols = canonical_protocols(aptos_jwk_consensus::network_interface::RPC);
 
     let network_client_config =
         NetworkClientConfig::new(direct_send_protocols.clone(), rpc_protocols.clone());
@@ -236,33 +335,40 @@ fn extract_network_ids(node_config: &NodeConfig) -> Vec<NetworkId> {
         .collect()
 }
 
-/// Creates the global peers and metadata struct
+/// Returns the networks on which the storage service should be registered.
+/// The storage service is currently served on all networks.
+fn storage_service_network_ids(node_config: &NodeConfig) -> Vec<NetworkId> {
+    extract_network_ids(node_config)
+}
+
+/// Creates the global peers and metadata struct (with the peer scoring config of each network)
 pub fn create_peers_and_metadata(node_config: &NodeConfig) -> Arc<PeersAndMetadata> {
     let network_ids = extract_network_ids(node_config);
-    PeersAndMetadata::new(&network_ids)
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+    for network_config in extract_network_configs(node_config) {
+        peers_and_metadata
+            .set_peer_scoring_config(network_config.network_id, network_config.peer_scoring);
+    }
+    peers_and_metadata
 }
 
-/// Sets up all networks and returns the appropriate application network interfaces
-pub fn setup_networks_and_get_interfaces(
+/// Sets up all networks and returns the network interfaces of all applications.
+/// Consensus (and the other validator-only applications) are only registered
+/// if the node has a validator network.
+pub fn build_all_application_networks(
     node_config: &NodeConfig,
     chain_id: ChainId,
     peers_and_metadata: Arc<PeersAndMetadata>,
     event_subscription_service: &mut EventSubscriptionService,
-) -> (
-    Vec<Runtime>,
-    Option<ApplicationNetworkInterfaces<ConsensusMsg>>,
-    Option<ApplicationNetworkInterfaces<ConsensusObserverMessage>>,
-    Option<ApplicationNetworkInterfaces<DKGMessage>>,
-    Option<ApplicationNetworkInterfaces<JWKConsensusMsg>>,
-    ApplicationNetworkInterfaces<MempoolSyncMsg>,
-    ApplicationNetworkInterfaces<PeerMonitoringServiceMessage>,
-    ApplicationNetworkInterfaces<StorageServiceMessage>,
-) {
+) -> ApplicationNetworks {
     // Gather all network configs
     let network_configs = extract_network_configs(node_config);
+    let storage_service_network_ids = storage_service_network_ids(node_config);
 
     // Create each network and register the application handles
+    let mut network_builders = vec![];
     let mut network_runtimes = vec![];
+    let mut per_network_info = vec![];
     let mut consensus_network_handle = None;
     let mut consensus_observer_network_handles: Option<
         Vec<ApplicationNetworkHandle<ConsensusObserverMessage>>,
@@ -277,18 +383,18 @@ pub fn setup_networks_and_get_interfaces(
         // Create a network runtime for the config
         let runtime = create_network_runtime(&network_config);
 
-        // Entering gives us a runtime to instantiate all the pieces of the builder
-        let _enter = runtime.enter();
-
-        // Create a new network builder
-        let mut network_builder = NetworkBuilder::create(
-            chain_id,
-            node_config.base.role,
-            &network_config,
-            TimeService::real(),
-            Some(event_subscription_service),
-            peers_and_metadata.clone(),
-        );
+        // Create a new network builder (entering the runtime to
+        // instantiate all the pieces of the builder).
+        let mut network_builder = with_runtime_entered(&runtime, network_config.network_id, || {
+            NetworkBuilder::create(
+                chain_id,
+                node_config.base.role,
+                &network_config,
+                TimeService::real(),
+                Some(&mut *event_subscription_service),
+                peers_and_metadata.clone(),
+            )
+        });
 
         // Register consensus (both client and server) with the network
         let network_id = network_config.network_id;
@@ -379,14 +485,18 @@ pub fn setup_networks_and_get_interfaces(
         peer_monitoring_service_network_handles.push(peer_monitoring_service_network_handle);
 
         // Register the storage service (both client and server) with the network
-        let storage_service_network_handle = register_client_and_service_with_network(
-            &mut network_builder,
-            network_id,
-            &network_config,
-            storage_service_network_configuration(node_config),
-            true,
-        );
-        storage_service_network_handles.push(storage_service_network_handle);
+        if let Some(storage_service_network_handle) =
+            register_client_and_service_with_network_subset(
+                &mut network_builder,
+                network_id,
+                &network_config,
+                storage_service_network_configuration(node_config),
+                true,
+                &storage_service_network_ids,
+            )
+        {
+            storage_service_network_handles.push(storage_service_network_handle);
+        }
 
         // Register the network benchmark test service
         if let Some(app_config) = netbench_network_configuration(node_config) {
@@ -400,14 +510,31 @@ pub fn setup_networks_and_get_interfaces(
             netbench_handles.push(netbench_handle);
         }
 
+        // Verify the number of registered protocols (before building the network)
+        if let Err(error) = verify_registered_protocols(&network_builder, &network_config) {
+            panic!("{}", error);
+        }
+        let registered_protocols = network_builder.advertised_protocols();
+        let warnings = network_startup_warnings(&network_config, registered_protocols.len());
+
         // Build and start the network on the runtime
-        network_builder.build(runtime.handle().clone());
-        network_builder.start();
+        with_runtime_entered(&runtime, network_config.network_id, || {
+            network_builder.build(runtime.handle().clone());
+            network_builder.start();
+        });
         network_runtimes.push(runtime);
         debug!(
             "Network built for the network context: {}",
             network_builder.network_context()
         );
+        per_network_info.push(NetworkStartupInfo {
+            network_id,
+            role: network_builder.network_context().role(),
+            listen_addresses: network_builder.listen_addresses(),
+            registered_protocols,
+            warnings,
+        });
+        network_builders.push(network_builder);
     }
 
     // Transform all network handles into application interfaces
@@ -444,8 +571,15 @@ pub fn setup_networks_and_get_interfaces(
         network_runtimes.push(netbench_runtime);
     }
 
-    (
-        network_runtimes,
+    let startup_report = NetworkStartupReport {
+        runtimes: network_runtimes,
+        builders: network_builders,
+        per_network: per_network_info,
+    };
+    startup_report.log_summary();
+
+    ApplicationNetworks {
+        startup_report,
         consensus_interfaces,
         consensus_observer_interfaces,
         dkg_interfaces,
@@ -453,11 +587,100 @@ pub fn setup_networks_and_get_interfaces(
         mempool_interfaces,
         peer_monitoring_service_interfaces,
         storage_service_interfaces,
-    )
+    }
+}
+
+/// Verifies that the number of protocols registered on the network (across all
+/// applications) does not exceed the maximum configured for the network.
+pub(crate) fn verify_registered_protocols(
+    network_builder: &NetworkBuilder,
+    network_config: &NetworkConfig,
+) -> anyhow::Result<()> {
+    let num_registered_protocols = network_builder.advertised_protocols().len();
+    let max_registered_protocols = network_config.max_registered_protocols;
+    if num_registered_protocols > max_registered_protocols {
+        anyhow::bail!(
+            "Too many protocols are registered on the {} network: {} (the maximum is {})!",
+            network_config.network_id,
+            num_registered_protocols,
+            max_registered_protocols
+        );
+    }
+    Ok(())
+}
+
+/// Verifies that the number of protocols the application service receives messages
+/// on (i.e., that are merged into its event stream) does not exceed the maximum
+/// configured for the network.
+pub(crate) fn verify_service_protocols(
+    application_config: &NetworkApplicationConfig,
+    network_config: &NetworkConfig,
+) -> anyhow::Result<()> {
+    let service_config = application_config.service_config();
+    let num_service_protocols = service_config.direct_send_protocols_and_preferences.len()
+        + service_config.rpc_protocols_and_preferences.len();
+    let max_protocols_per_service = network_config.max_protocols_per_service;
+    if num_service_protocols > max_protocols_per_service {
+        anyhow::bail!(
+            "Too many protocols are registered for a single service on the {} network: {} (the maximum is {})!",
+            network_config.network_id,
+            num_service_protocols,
+            max_protocols_per_service
+        );
+    }
+    Ok(())
+}
+
+/// Returns the outbound rate limits (configured by protocol name) that apply to
+/// the direct-send protocols of the given application's client
+pub(crate) fn outbound_rate_limits(
+    application_config: &NetworkApplicationConfig,
+    network_config: &NetworkConfig,
+) -> HashMap<ProtocolId, OutboundProtocolRateLimit> {
+    application_config
+        .client_config()
+        .direct_send_protocols_and_preferences
+        .into_iter()
+        .filter_map(|protocol_id| {
+            network_config
+                .outbound_protocol_rate_limits
+                .get(protocol_id.as_str())
+                .map(|rate_limit| (protocol_id, *rate_limit))
+        })
+        .collect()
 }
 
-/// Creates a network runtime for the given network config
-fn create_network_runtime(network_config: &NetworkConfig) -> Runtime {
+/// Returns the non-fatal issues with the given network config (and the
+/// number of protocols registered on the network) to report at startup.
+pub(crate) fn network_startup_warnings(
+    network_config: &NetworkConfig,
+    num_registered_protocols: usize,
+) -> Vec<String> {
+    let mut warnings = vec![];
+    if network_config.seeds.is_empty()
+        && network_config.seed_addrs.is_empty()
+        && network_config.discovery_methods().is_empty()
+    {
+        warnings.push(
+            "No seeds or discovery methods are configured. Only inbound connections will be made!"
+                .into(),
+        );
+    }
+    if num_registered_protocols == network_config.max_registered_protocols {
+        warnings.push(format!(
+            "The maximum number of protocols ({}) is registered. No applications can be registered at runtime!",
+            num_registered_protocols
+        ));
+    }
+    warnings
+}
+
+/// The log context key that identifies the network a log line was emitted for
+pub(crate) const NETWORK_ID_LOG_KEY: &str = "network_id";
+
+/// Creates a network runtime for the given network config. All logs emitted
+/// by tasks running on the runtime carry the network ID (see `NETWORK_ID_LOG_KEY`).
+pub(crate) fn create_network_runtime(network_config: &NetworkConfig) -> Runtime {
     let network_id = network_config.network_id;
     debug!("Creating runtime for network ID: {}", network_id);
 
@@ -466,11 +689,54 @@ fn create_network_runtime(network_config: &NetworkConfig) -> Runtime {
         "network-{}",
         network_id.as_str().chars().take(3).collect::<String>()
     );
-    aptos_runtimes::spawn_named_runtime(thread_name, network_config.runtime_threads)
+    let runtime_thread_nice_value = network_config.runtime_thread_nice_value;
+    aptos_runtimes::spawn_named_runtime_with_start_hook(
+        thread_name,
+        network_config.runtime_threads,
+        move || {
+            log_context::set_thread_log_context(NETWORK_ID_LOG_KEY, network_id.to_string());
+            if let Some(nice_value) = runtime_thread_nice_value {
+                set_thread_nice_value(nice_value);
+            }
+        },
+    )
+}
+
+/// Sets the nice value (i.e., scheduling priority) of the current thread. This is
+/// only supported on Linux, where each thread has its own nice value.
+fn set_thread_nice_value(nice_value: i32) {
+    #[cfg(target_os = "linux")]
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice_value) } != 0 {
+        warn!(
+            "Failed to set the nice value of the network runtime thread to {}! Error: {}",
+            nice_value,
+            std::io::Error::last_os_error()
+        );
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    warn!(
+        "Setting the nice value of the network runtime threads (to {}) is only supported on Linux!",
+        nice_value
+    );
+}
+
+/// Runs the given network construction step with the runtime entered (so that the
+/// network components can spawn tasks on it). The runtime is only entered for the
+/// duration of the step, and the enter guard is dropped even if the step fails
+/// (i.e., panics). So, a failed step never leaves the runtime entered, e.g., while
+/// constructing the next network. Logs emitted during the step carry the network ID.
+pub(crate) fn with_runtime_entered<T>(
+    runtime: &Runtime,
+    network_id: NetworkId,
+    construct: impl FnOnce() -> T,
+) -> T {
+    let _enter = runtime.enter();
+    log_context::with_log_context(NETWORK_ID_LOG_KEY, network_id.to_string(), construct)
 }
 
 /// Registers a new application client and service with the network
-fn register_client_and_service_with_network<
+pub(crate) fn register_client_and_service_with_network<
     T: Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static,
 >(
     network_builder: &mut NetworkBuilder,
@@ -479,16 +745,147 @@ fn register_client_and_service_with_network<
     application_config: NetworkApplicationConfig,
     allow_out_of_order_delivery: bool,
 ) -> ApplicationNetworkHandle<T> {
+    // Verify the number of protocols merged into the service (before registering it)
+    if let Err(error) = verify_service_protocols(&application_config, network_config) {
+        panic!("{}", error);
+    }
+
     let (network_sender, network_events) = network_builder.add_client_and_service(
         &application_config,
         network_config.max_parallel_deserialization_tasks,
         allow_out_of_order_delivery,
     );
-    ApplicationNetworkHandle {
+    create_application_network_handle(
         network_id,
+        network_config,
+        &application_config,
         network_sender,
         network_events,
+        network_builder.peers_and_metadata(),
+    )
+}
+
+/// Creates the network handle for an application that was registered with the
+/// network (at startup or at runtime), i.e., applies the network's outbound
+/// settings to the application's sender, identifies the event sources, and reports
+/// peers that send malformed messages to the peer scoring.
+fn create_application_network_handle<T>(
+    network_id: NetworkId,
+    network_config: &NetworkConfig,
+    application_config: &NetworkApplicationConfig,
+    network_sender: NetworkSender<T>,
+    network_events: NetworkEvents<T>,
+    peers_and_metadata: Arc<PeersAndMetadata>,
+) -> ApplicationNetworkHandle<T> {
+    // Identify the protocols the events are received on (e.g., to report a closed source)
+    let service_config = application_config.service_config();
+    let source_protocols = service_config
+        .direct_send_protocols_and_preferences
+        .into_iter()
+        .chain(service_config.rpc_protocols_and_preferences)
+        .collect();
+
+    // Throttle the application's outbound direct-send messages (if configured), and
+    // share the serialization of identical messages (e.g., mempool broadcasts)
+    let network_sender = network_sender
+        .with_outbound_rate_limits(outbound_rate_limits(application_config, network_config))
+        .with_message_coalescing(Duration::from_millis(
+            network_config.message_coalescing_window_ms,
+        ));
+
+    ApplicationNetworkHandle {
+        network_id,
+        network_sender,
+        network_events: network_events
+            .with_source_protocols(source_protocols)
+            .with_peer_scoring(peers_and_metadata),
+    }
+}
+
+/// Registers a new application client and service with the network, but only
+/// if the network is in the given subset of networks (e.g., to avoid serving an
+/// application on networks that don't require it). Returns `None` otherwise.
+pub(crate) fn register_client_and_service_with_network_subset<
+    T: Serialize + for<'de> Deserialize<'de> + Send + Sync + 'static,
+>(
+    network_builder: &mut NetworkBuilder,
+    network_id: NetworkId,
+    network_config: &NetworkConfig,
+    application_config: NetworkApplicationConfig,
+    allow_out_of_order_delivery: bool,
+    network_ids: &[NetworkId],
+) -> Option<ApplicationNetworkHandle<T>> {
+    if !network_ids.contains(&network_id) {
+        debug!(
+            "Skipping application registration on network {}: not in the subset {:?}",
+            network_id, network_ids
+        );
+        return None;
+    }
+
+    Some(register_client_and_service_with_network(
+        network_builder,
+        network_id,
+        network_config,
+        application_config,
+        allow_out_of_order_delivery,
+    ))
+}
+
+/// Registers a new application with all of the given (already built) networks, and
+/// returns the application's network interfaces. This supports applications that are
+/// enabled after boot (e.g., behind a feature flag), without restarting the networks.
+/// Note: peers that are already connected only learn of the application's protocols
+/// once they reconnect. Until then, the network client won't find a protocol that the
+/// peer supports, and sends to the peer will fail. Returns an error (and registers the
+/// application with no network) if the application's protocols would exceed the max
+/// registered protocols (or the max protocols per service) of any network.
+pub fn register_runtime_application<
+    T: Serialize + for<'de> Deserialize<'de> + Send + Sync + Clone + 'static,
+>(
+    node_config: &NodeConfig,
+    network_builders: &[NetworkBuilder],
+    application_config: NetworkApplicationConfig,
+    allow_out_of_order_delivery: bool,
+    peers_and_metadata: Arc<PeersAndMetadata>,
+) -> anyhow::Result<ApplicationNetworkInterfaces<T>> {
+    // Verify the application can be registered with every network (before registering
+    // it with any), so that a rejected application is never left half registered.
+    let network_configs = extract_network_configs(node_config);
+    let mut networks = vec![];
+    for network_builder in network_builders {
+        let network_id = network_builder.network_context().network_id();
+        let network_config = network_configs
+            .iter()
+            .find(|network_config| network_config.network_id == network_id)
+            .ok_or_else(|| anyhow::anyhow!("No config found for the {} network!", network_id))?;
+        verify_service_protocols(&application_config, network_config)?;
+        network_builder.verify_runtime_application(&application_config)?;
+        networks.push((network_builder, network_config));
     }
+
+    let mut network_handles = vec![];
+    for (network_builder, network_config) in networks {
+        let (network_sender, network_events) = network_builder.add_runtime_client_and_service(
+            &application_config,
+            network_config.max_parallel_deserialization_tasks,
+            allow_out_of_order_delivery,
+        )?;
+        network_handles.push(create_application_network_handle(
+            network_config.network_id,
+            network_config,
+            &application_config,
+            network_sender,
+            network_events,
+            network_builder.peers_and_metadata(),
+        ));
+    }
+
+    Ok(create_network_interfaces(
+        network_handles,
+        application_config,
+        peers_and_metadata,
+    ))
 }
 
 /// Transforms the given network handles into interfaces that can
@@ -597,7 +994,7 @@ fn create_network_interfaces<
     }
 
     // Create the network client
-    let network_client_config = network_application_config.network_client_config;
+    let network_client_config = network_application_config.client_config();
     let network_client = NetworkClient::new(
         network_client_config.direct_send_protocols_and_preferences,
         network_client_config.rpc_protocols_and_preferences,
@@ -2,14 +2,43 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{create_single_node_test_config, network};
-use aptos_config::config::{NodeConfig, WaypointConfig};
+use aptos_channels::aptos_channel;
+use aptos_config::{
+    config::{
+        DiscoveryMethod, Identity, NetworkConfig, NodeConfig, OutboundProtocolRateLimit, PeerSet,
+        RoleType, WaypointConfig, NETWORK_CHANNEL_SIZE,
+    },
+    network_id::{NetworkContext, NetworkId},
+};
+use aptos_consensus::network_interface::ConsensusMsg;
+use aptos_crypto::{x25519, Uniform};
 use aptos_event_notifications::EventSubscriptionService;
 use aptos_infallible::RwLock;
+use aptos_logger::log_context;
+use aptos_mempool::network::MempoolSyncMsg;
+use aptos_network::{
+    application::{interface::NetworkServiceEvents, storage::PeersAndMetadata},
+    peer_manager::builder::AuthenticationMode,
+    protocols::network::{NetworkApplicationConfig, NetworkClientConfig, NetworkServiceConfig},
+    ProtocolId,
+};
+use aptos_network_builder::builder::NetworkBuilder;
 use aptos_storage_interface::{DbReader, DbReaderWriter, DbWriter};
+use aptos_storage_service_types::StorageServiceMessage;
 use aptos_temppath::TempPath;
-use aptos_types::{chain_id::ChainId, waypoint::Waypoint};
+use aptos_time_service::TimeService;
+use aptos_types::{chain_id::ChainId, network_address::NetworkAddress, waypoint::Waypoint, PeerId};
+use futures::{FutureExt, StreamExt};
 use rand::SeedableRng;
-use std::{fs, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::runtime::Handle;
 
 /// A mock database implementing DbReader and DbWriter
 pub struct MockDatabase;
@@ -35,7 +64,7 @@ fn test_mutual_authentication_validators() {
 
     // Set up the networks and gather the application network handles. This should panic.
     let peers_and_metadata = network::create_peers_and_metadata(&node_config);
-    let _ = network::setup_networks_and_get_interfaces(
+    let _ = network::build_all_application_networks(
         &node_config,
         ChainId::test(),
         peers_and_metadata,
@@ -43,6 +72,335 @@ fn test_mutual_authentication_validators() {
     );
 }
 
+#[test]
+fn test_build_all_application_networks() {
+    for (node_config, expected_network_ids) in [
+        (NodeConfig::get_default_validator_config(), vec![
+            NetworkId::Validator,
+            NetworkId::Vfn,
+        ]),
+        (NodeConfig::get_default_vfn_config(), vec![
+            NetworkId::Vfn,
+            NetworkId::Public,
+        ]),
+    ] {
+        // Use local identities and ephemeral ports for all networks
+        let node_config = create_local_network_config(node_config);
+        let is_validator = node_config.validator_network.is_some();
+
+        // Create an event subscription service
+        let mut event_subscription_service = EventSubscriptionService::new(Arc::new(RwLock::new(
+            DbReaderWriter::new(MockDatabase {}),
+        )));
+
+        // Build all application networks
+        let peers_and_metadata = network::create_peers_and_metadata(&node_config);
+        let application_networks = network::build_all_application_networks(
+            &node_config,
+            ChainId::test(),
+            peers_and_metadata,
+            &mut event_subscription_service,
+        );
+
+        // Verify consensus is only registered for validators
+        assert_eq!(
+            application_networks.consensus_interfaces.is_some(),
+            is_validator
+        );
+        assert_eq!(application_networks.dkg_interfaces.is_some(), is_validator);
+        assert_eq!(
+            application_networks.jwk_consensus_interfaces.is_some(),
+            is_validator
+        );
+
+        // Verify the other applications are registered on all networks
+        let mut expected_network_ids = expected_network_ids;
+        expected_network_ids.sort();
+        assert_eq!(
+            sorted_network_ids(
+                application_networks
+                    .mempool_interfaces
+                    .network_service_events
+            ),
+            expected_network_ids
+        );
+        assert_eq!(
+            sorted_network_ids(
+                application_networks
+                    .peer_monitoring_service_interfaces
+                    .network_service_events
+            ),
+            expected_network_ids
+        );
+        assert!(!sorted_network_ids(
+            application_networks
+                .storage_service_interfaces
+                .network_service_events
+        )
+        .is_empty());
+        assert_eq!(
+            application_networks.startup_report.runtimes.len(),
+            expected_network_ids.len()
+        );
+    }
+}
+
+#[test]
+fn test_network_startup_report() {
+    // Create a local validator config (with a validator and a VFN network)
+    let node_config = create_local_network_config(NodeConfig::get_default_validator_config());
+
+    // Create an event subscription service
+    let mut event_subscription_service =
+        EventSubscriptionService::new(Arc::new(RwLock::new(DbReaderWriter::new(MockDatabase {}))));
+
+    // Build all application networks
+    let peers_and_metadata = network::create_peers_and_metadata(&node_config);
+    let application_networks = network::build_all_application_networks(
+        &node_config,
+        ChainId::test(),
+        peers_and_metadata,
+        &mut event_subscription_service,
+    );
+
+    // Verify the report has an entry for each network (in config order)
+    let startup_report = application_networks.startup_report;
+    let network_ids: Vec<_> = startup_report
+        .per_network
+        .iter()
+        .map(|network_info| network_info.network_id)
+        .collect();
+    assert_eq!(network_ids, vec![NetworkId::Validator, NetworkId::Vfn]);
+    assert_eq!(startup_report.builders.len(), 2);
+    assert_eq!(startup_report.runtimes.len(), 2);
+
+    // Verify each entry matches the network it was built for
+    for (network_info, network_builder) in startup_report
+        .per_network
+        .iter()
+        .zip(startup_report.builders.iter())
+    {
+        assert_eq!(network_info.role, RoleType::Validator);
+        assert_eq!(
+            network_info.network_id,
+            network_builder.network_context().network_id()
+        );
+
+        // Verify the actual bound addresses are reported (not the ephemeral port)
+        assert_eq!(
+            network_info.listen_addresses,
+            network_builder.listen_addresses()
+        );
+        assert!(!network_info
+            .listen_addresses
+            .iter()
+            .any(|address| address.to_string().ends_with("/tcp/0")));
+
+        // Verify consensus is only registered on the validator network
+        assert!(network_info
+            .registered_protocols
+            .contains(&ProtocolId::MempoolDirectSend));
+        assert_eq!(
+            network_info
+                .registered_protocols
+                .contains(&ProtocolId::ConsensusRpcBcs),
+            network_info.network_id.is_validator_network()
+        );
+    }
+
+    // Verify a network without seeds or discovery methods is warned about
+    let mut network_config = NetworkConfig::network_with_id(NetworkId::Public);
+    network_config.discovery_method = DiscoveryMethod::None;
+    network_config.discovery_methods = vec![];
+    let warnings = network::network_startup_warnings(&network_config, 1);
+    assert_eq!(warnings.len(), 1);
+    network_config.discovery_methods = vec![DiscoveryMethod::Onchain];
+    assert!(network::network_startup_warnings(&network_config, 1).is_empty());
+}
+
+#[test]
+fn test_network_build_failure_is_isolated() {
+    // Create a local validator network config
+    let node_config = create_local_network_config(NodeConfig::get_default_validator_config());
+    let network_config = node_config.validator_network.clone().unwrap();
+    let peers_and_metadata = network::create_peers_and_metadata(&node_config);
+    let create_network_builder = || {
+        NetworkBuilder::create(
+            ChainId::test(),
+            node_config.base.role,
+            &network_config,
+            TimeService::real(),
+            None,
+            peers_and_metadata.clone(),
+        )
+    };
+
+    // Fail to build the first network (it is started without being built)
+    let failed_runtime = aptos_runtimes::spawn_named_runtime("failed".into(), Some(1));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        network::with_runtime_entered(&failed_runtime, NetworkId::Validator, || {
+            create_network_builder().start();
+        })
+    }));
+    assert!(result.is_err());
+
+    // Verify that the runtime is no longer entered
+    assert!(Handle::try_current().is_err());
+
+    // Verify that the next network still builds and starts successfully
+    let runtime = aptos_runtimes::spawn_named_runtime("network".into(), Some(1));
+    let network_builder = network::with_runtime_entered(&runtime, NetworkId::Validator, || {
+        let mut network_builder = create_network_builder();
+        network_builder.build(runtime.handle().clone());
+        network_builder.start();
+        network_builder
+    });
+    assert_eq!(
+        network_builder.network_context().network_id(),
+        NetworkId::Validator
+    );
+    assert!(Handle::try_current().is_err());
+}
+
+#[test]
+fn test_network_log_context() {
+    // Create a runtime for the VFN network
+    let mut network_config = NetworkConfig::network_with_id(NetworkId::Vfn);
+    network_config.runtime_threads = Some(1);
+    let runtime = network::create_network_runtime(&network_config);
+    let network_id_in_log_context = || {
+        log_context::current_log_context()
+            .get(network::NETWORK_ID_LOG_KEY)
+            .cloned()
+    };
+
+    // Verify logs emitted while constructing the network carry the network ID
+    let network_id =
+        network::with_runtime_entered(&runtime, NetworkId::Vfn, network_id_in_log_context);
+    assert_eq!(network_id, Some(NetworkId::Vfn.to_string()));
+    assert_eq!(network_id_in_log_context(), None);
+
+    // Verify logs emitted by tasks spawned on the network runtime carry the network ID
+    let network_id = runtime
+        .block_on(runtime.spawn(async move { network_id_in_log_context() }))
+        .unwrap();
+    assert_eq!(network_id, Some(NetworkId::Vfn.to_string()));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_network_runtime_thread_nice_value() {
+    // Create a runtime for the validator network with the lowest thread priority
+    // (raising the nice value doesn't require any privileges).
+    let mut network_config = NetworkConfig::network_with_id(NetworkId::Validator);
+    network_config.runtime_threads = Some(2);
+    network_config.runtime_thread_nice_value = Some(19);
+    let current_thread_nice_value = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+    let original_nice_value = current_thread_nice_value();
+    let runtime = network::create_network_runtime(&network_config);
+
+    // Verify the nice value is applied to the threads of the network runtime
+    for _ in 0..10 {
+        let nice_value = runtime
+            .block_on(runtime.spawn(async move { current_thread_nice_value() }))
+            .unwrap();
+        assert_eq!(nice_value, 19);
+    }
+    let nice_value = runtime
+        .block_on(runtime.spawn_blocking(current_thread_nice_value))
+        .unwrap();
+    assert_eq!(nice_value, 19);
+
+    // Verify the nice value of the current thread is unaffected
+    assert_eq!(current_thread_nice_value(), original_nice_value);
+}
+
+#[test]
+fn test_register_application_on_network_subset() {
+    // Create a network builder for each of two networks
+    let node_config = NodeConfig::default();
+    let network_ids = [NetworkId::Validator, NetworkId::Public];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+    let mut rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+    for network_id in network_ids {
+        let mut network_builder = NetworkBuilder::new_for_test(
+            ChainId::test(),
+            PeerSet::new(),
+            NetworkContext::new(RoleType::Validator, network_id, PeerId::random()),
+            TimeService::mock(),
+            NetworkAddress::from_str("/memory/0").unwrap(),
+            AuthenticationMode::Mutual(x25519::PrivateKey::generate(&mut rng)),
+            peers_and_metadata.clone(),
+        );
+
+        // Register the storage service only on the public network
+        let network_handle =
+            network::register_client_and_service_with_network_subset::<StorageServiceMessage>(
+                &mut network_builder,
+                network_id,
+                &NetworkConfig::network_with_id(network_id),
+                network::storage_service_network_configuration(&node_config),
+                true,
+                &[NetworkId::Public],
+            );
+
+        // Verify the application is only registered on the public network
+        match network_id {
+            NetworkId::Public => assert_eq!(network_handle.unwrap().network_id, network_id),
+            _ => assert!(network_handle.is_none()),
+        }
+    }
+}
+
+#[test]
+fn test_advertised_protocols() {
+    // Create a network builder for the validator network
+    let node_config = NodeConfig::default();
+    let network_id = NetworkId::Validator;
+    let mut rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+    let mut network_builder = NetworkBuilder::new_for_test(
+        ChainId::test(),
+        PeerSet::new(),
+        NetworkContext::new(RoleType::Validator, network_id, PeerId::random()),
+        TimeService::mock(),
+        NetworkAddress::from_str("/memory/0").unwrap(),
+        AuthenticationMode::Mutual(x25519::PrivateKey::generate(&mut rng)),
+        PeersAndMetadata::new(&[network_id]),
+    );
+
+    // Register consensus and mempool with the network
+    let network_config = NetworkConfig::network_with_id(network_id);
+    let _consensus_handle = network::register_client_and_service_with_network::<ConsensusMsg>(
+        &mut network_builder,
+        network_id,
+        &network_config,
+        network::consensus_network_configuration(&node_config),
+        false,
+    );
+    let _mempool_handle = network::register_client_and_service_with_network::<MempoolSyncMsg>(
+        &mut network_builder,
+        network_id,
+        &network_config,
+        network::mempool_network_configuration(&node_config),
+        false,
+    );
+
+    // Verify the consensus and mempool protocols are both advertised
+    let advertised_protocols = network_builder.advertised_protocols();
+    let consensus_protocols = aptos_consensus::network_interface::DIRECT_SEND
+        .iter()
+        .chain(aptos_consensus::network_interface::RPC.iter());
+    for protocol_id in consensus_protocols {
+        assert!(advertised_protocols.contains(protocol_id));
+    }
+    assert!(advertised_protocols.contains(&ProtocolId::MempoolDirectSend));
+
+    // Verify the protocols are advertised in ascending wire order
+    let mut sorted_protocols = advertised_protocols.clone();
+    sorted_protocols.sort_by_key(|protocol_id| *protocol_id as u8);
+    assert_eq!(advertised_protocols, sorted_protocols);
+}
+
 #[cfg(feature = "check-vm-features")]
 #[test]
 fn test_aptos_vm_does_not_have_test_natives() {
@@ -114,3 +472,211 @@ fn test_create_single_node_test_config() {
             .bootstrapping_mode
     );
 }
+
+/// Updates the networks of the given node config to use locally generated
+/// identities and ephemeral listen ports (so that they can be built in tests).
+fn create_local_network_config(mut node_config: NodeConfig) -> NodeConfig {
+    let mut rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+    for network_config in node_config
+        .validator_network
+        .iter_mut()
+        .chain(node_config.full_node_networks.iter_mut())
+    {
+        network_config.identity =
+            Identity::from_config(x25519::PrivateKey::generate(&mut rng), PeerId::random());
+        network_config.listen_address = NetworkAddress::from_str("/ip4/127.0.0.1/tcp/0").unwrap();
+    }
+    node_config
+}
+
+/// Returns the (sorted) networks the given application service is registered on
+fn sorted_network_ids<T>(network_service_events: NetworkServiceEvents<T>) -> Vec<NetworkId> {
+    let mut network_ids: Vec<_> = network_service_events
+        .into_network_and_events()
+        .into_keys()
+        .collect();
+    network_ids.sort();
+    network_ids
+}
+
+#[test]
+fn test_registered_protocols_cap() {
+    // Create a network builder for the validator network
+    let node_config = NodeConfig::default();
+    let network_id = NetworkId::Validator;
+    let mut rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+    let mut network_builder = NetworkBuilder::new_for_test(
+        ChainId::test(),
+        PeerSet::new(),
+        NetworkContext::new(RoleType::Validator, network_id, PeerId::random()),
+        TimeService::mock(),
+        NetworkAddress::from_str("/memory/0").unwrap(),
+        AuthenticationMode::Mutual(x25519::PrivateKey::generate(&mut rng)),
+        PeersAndMetadata::new(&[network_id]),
+    );
+
+    // Register consensus with the network
+    let mut network_config = NetworkConfig::network_with_id(network_id);
+    let _consensus_handle = network::register_client_and_service_with_network::<ConsensusMsg>(
+        &mut network_builder,
+        network_id,
+        &network_config,
+        network::consensus_network_configuration(&node_config),
+        false,
+    );
+
+    // Verify the registered protocols are within the default cap
+    network::verify_registered_protocols(&network_builder, &network_config).unwrap();
+
+    // Lower the cap below the number of registered protocols, and verify the error
+    let num_registered_protocols = network_builder.advertised_protocols().len();
+    network_config.max_registered_protocols = num_registered_protocols - 1;
+    let error =
+        network::verify_registered_protocols(&network_builder, &network_config).unwrap_err();
+    assert!(error.to_string().contains("Too many protocols"));
+}
+
+#[test]
+fn test_register_runtime_application_cap() {
+    // Determine the number of protocols registered on the VFN network at startup
+    let mut node_config = create_local_network_config(NodeConfig::get_default_validator_config());
+    let build_networks = |node_config: &NodeConfig| {
+        let mut event_subscription_service = EventSubscriptionService::new(Arc::new(RwLock::new(
+            DbReaderWriter::new(MockDatabase {}),
+        )));
+        let peers_and_metadata = network::create_peers_and_metadata(node_config);
+        let application_networks = network::build_all_application_networks(
+            node_config,
+            ChainId::test(),
+            peers_and_metadata.clone(),
+            &mut event_subscription_service,
+        );
+        (application_networks.startup_report, peers_and_metadata)
+    };
+    let (startup_report, _) = build_networks(&node_config);
+    let num_vfn_protocols = startup_report.per_network[1].registered_protocols.len();
+
+    // Rebuild the networks with the VFN network capped at its startup protocols
+    node_config.full_node_networks[0].max_registered_protocols = num_vfn_protocols;
+    let (startup_report, peers_and_metadata) = build_networks(&node_config);
+    assert!(startup_report.per_network[1]
+        .warnings
+        .iter()
+        .any(|warning| warning.contains("No applications can be registered at runtime")));
+
+    // Verify registering an application at runtime fails
+    let runtime_application_config = || {
+        NetworkApplicationConfig::new(
+            NetworkClientConfig::new(vec![ProtocolId::NetbenchDirectSend], vec![]),
+            NetworkServiceConfig::new(
+                vec![ProtocolId::NetbenchDirectSend],
+                vec![],
+                aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+            ),
+        )
+    };
+    let error = network::register_runtime_application::<MempoolSyncMsg>(
+        &node_config,
+        &startup_report.builders,
+        runtime_application_config(),
+        false,
+        peers_and_metadata,
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("Too many protocols"));
+
+    // Verify the application wasn't registered with any network (including the validator network)
+    for network_builder in &startup_report.builders {
+        assert!(network_builder
+            .remove_runtime_application(&runtime_application_config())
+            .is_empty());
+    }
+}
+
+#[test]
+fn test_verify_service_protocols() {
+    // Verify the consensus service is within the default cap
+    let node_config = NodeConfig::default();
+    let application_config = network::consensus_network_configuration(&node_config);
+    let mut network_config = NetworkConfig::network_with_id(NetworkId::Validator);
+    network::verify_service_protocols(&application_config, &network_config).unwrap();
+
+    // Verify a service with exactly as many protocols as the cap is accepted
+    let service_config = application_config.service_config();
+    let num_service_protocols = service_config.direct_send_protocols_and_preferences.len()
+        + service_config.rpc_protocols_and_preferences.len();
+    network_config.max_protocols_per_service = num_service_protocols;
+    network::verify_service_protocols(&application_config, &network_config).unwrap();
+
+    // Lower the cap below the number of service protocols, and verify the error
+    network_config.max_protocols_per_service = num_service_protocols - 1;
+    let error =
+        network::verify_service_protocols(&application_config, &network_config).unwrap_err();
+    assert!(error.to_string().contains("Too many protocols"));
+}
+
+#[test]
+fn test_outbound_rate_limits() {
+    // Configure rate limits for mempool and an (unknown) protocol name
+    let node_config = NodeConfig::default();
+    let mut network_config = NetworkConfig::network_with_id(NetworkId::Validator);
+    let rate_limit = OutboundProtocolRateLimit {
+        messages_per_second: 10,
+        burst_size: 20,
+    };
+    network_config.outbound_protocol_rate_limits = HashMap::from([
+        (
+            ProtocolId::MempoolDirectSend.as_str().to_string(),
+            rate_limit,
+        ),
+        ("UnknownProtocol".to_string(), rate_limit),
+    ]);
+
+    // Verify the mempool rate limit applies to the mempool application
+    let application_config = network::mempool_network_configuration(&node_config);
+    assert_eq!(
+        network::outbound_rate_limits(&application_config, &network_config),
+        HashMap::from([(ProtocolId::MempoolDirectSend, rate_limit)])
+    );
+
+    // Verify no rate limits apply to the consensus application
+    let application_config = network::consensus_network_configuration(&node_config);
+    assert!(network::outbound_rate_limits(&application_config, &network_config).is_empty());
+}
+
+#[test]
+fn test_network_shutdown() {
+    // Create a local validator config (with a validator and a VFN network)
+    let node_config = create_local_network_config(NodeConfig::get_default_validator_config());
+
+    // Create an event subscription service
+    let mut event_subscription_service =
+        EventSubscriptionService::new(Arc::new(RwLock::new(DbReaderWriter::new(MockDatabase {}))));
+
+    // Build all application networks
+    let peers_and_metadata = network::create_peers_and_metadata(&node_config);
+    let application_networks = network::build_all_application_networks(
+        &node_config,
+        ChainId::test(),
+        peers_and_metadata,
+        &mut event_subscription_service,
+    );
+    let mempool_network_and_events = application_networks
+        .mempool_interfaces
+        .network_service_events
+        .into_network_and_events();
+
+    // Shut down the networks
+    application_networks
+        .startup_report
+        .shutdown(Duration::from_millis(100));
+
+    // Verify the application event streams were closed
+    for (network_id, mut network_events) in mempool_network_and_events {
+        assert!(
+            matches!(network_events.next().now_or_never(), Some(None)),
+            "The mempool events were not closed for network: {}",
+            network_id
+        );
+    }
+}
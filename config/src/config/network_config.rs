@@ -28,6 +28,7 @@ use std::{
     fmt,
     path::PathBuf,
     string::ToString,
+    time::Duration,
 };
 
 // TODO: We could possibly move these constants somewhere else, but since they are defaults for the
@@ -40,9 +41,13 @@ pub const PING_INTERVAL_MS: u64 = 10_000;
 pub const PING_TIMEOUT_MS: u64 = 20_000;
 pub const PING_FAILURES_TOLERATED: u64 = 3;
 pub const CONNECTIVITY_CHECK_INTERVAL_MS: u64 = 5000;
+pub const IDLE_TIMEOUT_MS: u64 = 600_000; /* 10 minutes */
 pub const MAX_CONNECTION_DELAY_MS: u64 = 60_000; /* 1 minute */
 pub const MAX_FULLNODE_OUTBOUND_CONNECTIONS: usize = 6;
 pub const MAX_INBOUND_CONNECTIONS: usize = 100;
+pub const MAX_REGISTERED_PROTOCOLS: usize = 64;
+pub const MAX_PROTOCOLS_PER_SERVICE: usize = 16;
+pub const MESSAGE_COALESCING_WINDOW_MS: u64 = 100;
 pub const MAX_MESSAGE_METADATA_SIZE: usize = 128 * 1024; /* 128 KiB: a buffer for metadata that might be added to messages by networking */
 pub const MESSAGE_PADDING_SIZE: usize = 2 * 1024 * 1024; /* 2 MiB: a safety buffer to allow messages to get larger during serialization */
 pub const MAX_APPLICATION_MESSAGE_SIZE: usize =
@@ -50,6 +55,13 @@ pub const MAX_APPLICATION_MESSAGE_SIZE: usize =
 pub const MAX_FRAME_SIZE: usize = 4 * 1024 * 1024; /* 4 MiB large messages will be chunked into multiple frames and streamed */
 pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024; /* 64 MiB */
 pub const CONNECTION_BACKOFF_BASE: u64 = 2;
+/// In addition to the backoff strategy, we also add some small random jitter to
+/// the delay before each dial. This jitter helps reduce the probability of
+/// simultaneous dials, especially in non-production environments where most nodes
+/// are spun up around the same time. Similarly, it smears the dials out in time
+/// to avoid spiky load / thundering herd issues where all dial requests happen
+/// around the same time at startup.
+pub const MAX_CONNECTION_DELAY_JITTER_MS: u64 = 100;
 pub const IP_BYTE_BUCKET_RATE: usize = 102400 /* 100 KiB */;
 pub const IP_BYTE_BUCKET_SIZE: usize = IP_BYTE_BUCKET_RATE;
 
@@ -82,6 +94,11 @@ pub struct NetworkConfig {
     pub network_id: NetworkId,
     /// Number of threads to run for networking
     pub runtime_threads: Option<usize>,
+    /// The nice value of the network runtime threads (Linux only), e.g., to prevent
+    /// the public network from starving the latency-critical validator network of CPU.
+    /// Lower values are scheduled first (negative values require `CAP_SYS_NICE`).
+    /// If not specified, the threads keep the default nice value of the process.
+    pub runtime_thread_nice_value: Option<i32>,
     /// Overrides for the size of the inbound and outbound buffers for each peer.
     /// NOTE: The defaults are None, so socket options are not called. Change to Some values with
     /// caution. Experiments have shown that relying on Linux's default tcp auto-tuning can perform
@@ -124,6 +141,55 @@ pub struct NetworkConfig {
     pub max_parallel_deserialization_tasks: Option<usize>,
     /// Whether or not to enable latency aware peer dialing
     pub enable_latency_aware_dialing: bool,
+    /// Timeout after which connections without any inbound or outbound application
+    /// traffic are closed (health checker and keep-alive pings don't count as traffic).
+    /// If not specified, defaults to `IDLE_TIMEOUT_MS` on all networks except the
+    /// validator network (which is exempt). A value of 0 disables the timeout.
+    pub idle_timeout_ms: Option<u64>,
+    /// The interval at which keep-alive pings are sent on connections without any
+    /// outbound traffic (e.g., to prevent intermediaries from silently dropping quiet
+    /// connections). If not specified (or 0), keep-alive pings are disabled.
+    pub keep_alive_interval_ms: Option<u64>,
+    /// The timeout for flushing the pending outbound messages of a connection when it is
+    /// closed (e.g., to reduce message loss during churn). If not specified (or 0), any
+    /// pending outbound messages are dropped when the connection is closed.
+    pub outbound_drain_timeout_ms: Option<u64>,
+    /// How to handle inbound messages for protocols that are not registered on this node
+    pub unknown_protocol_policy: UnknownProtocolPolicy,
+    /// The backoff policy for redialing peers (e.g., seeds) after failed connection
+    /// attempts. If not specified, the policy is derived from `connection_backoff_base`
+    /// and `max_connection_delay_ms`.
+    pub reconnect_backoff: Option<ReconnectBackoff>,
+    /// The maximum number of protocols that may be registered on this network (across
+    /// all applications). This is a sanity check to catch misconfiguration (e.g., too
+    /// many experimental protocols being enabled), which would otherwise result in
+    /// runaway channel and task allocation.
+    pub max_registered_protocols: usize,
+    /// The maximum number of protocols that a single application service may receive
+    /// messages on (i.e., that are merged into the service's event stream). This is a
+    /// sanity check to catch misconfigured applications (e.g., with a pathologically
+    /// large protocol list), and is comfortably above what any application needs.
+    pub max_protocols_per_service: usize,
+    /// The rate limits on the direct-send messages sent to each peer, keyed by protocol
+    /// name (e.g., `MempoolDirectSend`). This prevents a misbehaving application (e.g.,
+    /// a mempool broadcast storm) from starving other protocols on the same connection.
+    /// Protocols without a rate limit are not throttled.
+    pub outbound_protocol_rate_limits: HashMap<String, OutboundProtocolRateLimit>,
+    /// The window (in milliseconds) within which direct-send messages that the
+    /// application marks as identical (e.g., the same mempool broadcast sent to
+    /// several peers) share a single serialization. A value of 0 disables coalescing.
+    pub message_coalescing_window_ms: u64,
+    /// The maximum number of bytes of inbound messages that may be buffered for the
+    /// applications at any time (across all peers and protocols). Inbound messages that
+    /// would exceed this budget are dropped. If not specified, there is no global budget
+    /// (i.e., only the individual upstream queues are bounded).
+    pub max_inbound_buffered_bytes: Option<usize>,
+    /// The config for scoring (and banning) peers based on their behavior
+    pub peer_scoring: PeerScoringConfig,
+    /// The algorithm used to compress outbound messages to peers that support
+    /// decompression (as negotiated in the handshake). Messages to other peers are
+    /// sent uncompressed. If not specified, outbound messages are never compressed.
+    pub compression: Option<CompressionAlgo>,
 }
 
 impl Default for NetworkConfig {
@@ -143,6 +209,7 @@ impl NetworkConfig {
             mutual_authentication,
             network_id,
             runtime_threads: None,
+            runtime_thread_nice_value: None,
             seed_addrs: HashMap::new(),
             seeds: PeerSet::default(),
             max_frame_size: MAX_FRAME_SIZE,
@@ -165,6 +232,18 @@ impl NetworkConfig {
             outbound_tx_buffer_size_bytes: None,
             max_parallel_deserialization_tasks: None,
             enable_latency_aware_dialing: true,
+            idle_timeout_ms: None,
+            keep_alive_interval_ms: None,
+            outbound_drain_timeout_ms: None,
+            unknown_protocol_policy: UnknownProtocolPolicy::default(),
+            reconnect_backoff: None,
+            max_registered_protocols: MAX_REGISTERED_PROTOCOLS,
+            max_protocols_per_service: MAX_PROTOCOLS_PER_SERVICE,
+            outbound_protocol_rate_limits: HashMap::new(),
+            message_coalescing_window_ms: MESSAGE_COALESCING_WINDOW_MS,
+            max_inbound_buffered_bytes: None,
+            peer_scoring: PeerScoringConfig::default(),
+            compression: None,
         };
 
         // Configure the number of parallel deserialization tasks
@@ -185,6 +264,40 @@ impl NetworkConfig {
         }
     }
 
+    /// Returns the idle connection timeout for this network (if any)
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        let idle_timeout_ms = match self.idle_timeout_ms {
+            Some(idle_timeout_ms) => idle_timeout_ms,
+            None if self.network_id.is_validator_network() => return None,
+            None => IDLE_TIMEOUT_MS,
+        };
+        (idle_timeout_ms > 0).then(|| Duration::from_millis(idle_timeout_ms))
+    }
+
+    /// Returns the keep-alive ping interval for this network (if any)
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        self.keep_alive_interval_ms
+            .filter(|keep_alive_interval_ms| *keep_alive_interval_ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// Returns the timeout for draining pending outbound messages on disconnect (if any)
+    pub fn outbound_drain_timeout(&self) -> Option<Duration> {
+        self.outbound_drain_timeout_ms
+            .filter(|outbound_drain_timeout_ms| *outbound_drain_timeout_ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// Returns the backoff policy for redialing peers on this network
+    pub fn reconnect_backoff_policy(&self) -> ReconnectBackoff {
+        self.reconnect_backoff.unwrap_or_else(|| {
+            ReconnectBackoff::from_backoff_base(
+                self.connection_backoff_base,
+                self.max_connection_delay_ms,
+            )
+        })
+    }
+
     pub fn identity_key(&self) -> x25519::PrivateKey {
         let key = match &self.identity {
             Identity::FromConfig(config) => Some(config.key.private_key()),
@@ -341,6 +454,75 @@ impl NetworkConfig {
     }
 }
 
+/// The policy for handling inbound messages (direct send or rpc) for
+/// protocols that have not been registered on this node.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownProtocolPolicy {
+    /// Silently drop the message
+    Ignore,
+    /// Drop the message, but count it in the unknown protocol metrics
+    #[default]
+    Count,
+    /// Count the message and disconnect from the misbehaving peer
+    Disconnect,
+}
+
+/// The algorithm used to compress outbound messages on a connection
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionAlgo {
+    /// LZ4 block compression (fast, with a moderate compression ratio)
+    Lz4,
+}
+
+/// The backoff policy for redialing a peer after failed connection attempts. The
+/// delay before the n-th redial is `initial_ms * multiplier^(n-1)`, capped at
+/// `max_ms`, plus a random jitter of up to `jitter_ms`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectBackoff {
+    /// The delay before the first dial attempt
+    pub initial_ms: u64,
+    /// The maximum delay between two consecutive dial attempts (excluding jitter)
+    pub max_ms: u64,
+    /// The factor by which the delay grows after each failed attempt
+    pub multiplier: u32,
+    /// The maximum random jitter added to each delay
+    pub jitter_ms: u64,
+}
+
+impl ReconnectBackoff {
+    /// Returns the policy equivalent to an exponential backoff with the given
+    /// base (in seconds), i.e., delays of `base^n` seconds.
+    pub fn from_backoff_base(backoff_base: u64, max_connection_delay_ms: u64) -> Self {
+        Self {
+            initial_ms: backoff_base.saturating_mul(1000),
+            max_ms: max_connection_delay_ms,
+            multiplier: u32::try_from(backoff_base).unwrap_or(u32::MAX),
+            jitter_ms: MAX_CONNECTION_DELAY_JITTER_MS,
+        }
+    }
+
+    pub fn initial_delay(&self) -> Duration {
+        Duration::from_millis(self.initial_ms)
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_ms)
+    }
+
+    pub fn max_jitter(&self) -> Duration {
+        Duration::from_millis(self.jitter_ms)
+    }
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::from_backoff_base(CONNECTION_BACKOFF_BASE, MAX_CONNECTION_DELAY_MS)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DiscoveryMethod {
@@ -388,6 +570,55 @@ impl Default for RateLimitConfig {
     }
 }
 
+/// A token bucket rate limit on the direct-send messages sent to each peer on a protocol
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutboundProtocolRateLimit {
+    /// Maximum number of messages/s sent to a peer
+    pub messages_per_second: usize,
+    /// Maximum burst of messages sent to a peer (raised to `messages_per_second` if smaller)
+    pub burst_size: usize,
+}
+
+/// The config for scoring the peers of a network based on the events reported
+/// for them (e.g., failed RPCs and malformed messages). Each peer starts with the
+/// max score, which is lowered by penalties and raised by rewards (up to the max).
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeerScoringConfig {
+    /// Whether peers whose score drops to (or below) the ban threshold are banned
+    pub enable_peer_bans: bool,
+    /// The score of a peer without any reported events (i.e., the max score)
+    pub max_score: u64,
+    /// The score at (or below) which peers are banned (if bans are enabled)
+    pub ban_threshold: u64,
+    /// The duration of the bans of low scoring peers (secs)
+    pub ban_duration_secs: u64,
+    /// The score reward for a successful RPC
+    pub rpc_success_reward: u64,
+    /// The score penalty for a failed RPC (e.g., a timeout)
+    pub rpc_failure_penalty: u64,
+    /// The score penalty for a malformed message (e.g., that fails to deserialize)
+    pub malformed_message_penalty: u64,
+    /// The score penalty for a failed handshake
+    pub handshake_error_penalty: u64,
+}
+
+impl Default for PeerScoringConfig {
+    fn default() -> Self {
+        Self {
+            enable_peer_bans: false,
+            max_score: 100,
+            ban_threshold: 20,
+            ban_duration_secs: 600, // 10 minutes
+            rpc_success_reward: 1,
+            rpc_failure_penalty: 5,
+            malformed_message_penalty: 10,
+            handshake_error_penalty: 20,
+        }
+    }
+}
+
 pub type PeerSet = HashMap<PeerId, Peer>;
 
 // TODO: Combine with RoleType?
@@ -528,4 +759,85 @@ mod tests {
         network_config.configure_num_deserialization_tasks();
         assert_eq!(network_config.max_parallel_deserialization_tasks, Some(1));
     }
+
+    #[test]
+    fn test_idle_timeout() {
+        // Verify the validator network is exempt by default
+        let network_config = NetworkConfig::network_with_id(NetworkId::Validator);
+        assert_eq!(network_config.idle_timeout(), None);
+
+        // Verify the other networks use the default idle timeout
+        for network_id in [NetworkId::Vfn, NetworkId::Public] {
+            let network_config = NetworkConfig::network_with_id(network_id);
+            assert_eq!(
+                network_config.idle_timeout(),
+                Some(Duration::from_millis(IDLE_TIMEOUT_MS))
+            );
+        }
+
+        // Verify the idle timeout can be enabled for the validator network
+        let network_config = NetworkConfig {
+            idle_timeout_ms: Some(1000),
+            ..NetworkConfig::network_with_id(NetworkId::Validator)
+        };
+        assert_eq!(
+            network_config.idle_timeout(),
+            Some(Duration::from_millis(1000))
+        );
+
+        // Verify the idle timeout can be disabled for other networks
+        let network_config = NetworkConfig {
+            idle_timeout_ms: Some(0),
+            ..NetworkConfig::network_with_id(NetworkId::Public)
+        };
+        assert_eq!(network_config.idle_timeout(), None);
+    }
+
+    #[test]
+    fn test_keep_alive_interval() {
+        // Verify keep-alive pings are disabled by default
+        for network_id in [NetworkId::Validator, NetworkId::Vfn, NetworkId::Public] {
+            let network_config = NetworkConfig::network_with_id(network_id);
+            assert_eq!(network_config.keep_alive_interval(), None);
+        }
+
+        // Verify keep-alive pings can be enabled
+        let network_config = NetworkConfig {
+            keep_alive_interval_ms: Some(1000),
+            ..NetworkConfig::network_with_id(NetworkId::Public)
+        };
+        assert_eq!(
+            network_config.keep_alive_interval(),
+            Some(Duration::from_millis(1000))
+        );
+
+        // Verify an interval of 0 disables keep-alive pings
+        let network_config = NetworkConfig {
+            keep_alive_interval_ms: Some(0),
+            ..NetworkConfig::network_with_id(NetworkId::Public)
+        };
+        assert_eq!(network_config.keep_alive_interval(), None);
+    }
+
+    #[test]
+    fn test_outbound_drain_timeout() {
+        // Verify draining is disabled by default
+        let network_config = NetworkConfig::network_with_id(NetworkId::Validator);
+        assert_eq!(network_config.outbound_drain_timeout(), None);
+
+        // Verify draining can be enabled, and a timeout of 0 disables it
+        let network_config = NetworkConfig {
+            outbound_drain_timeout_ms: Some(500),
+            ..NetworkConfig::network_with_id(NetworkId::Validator)
+        };
+        assert_eq!(
+            network_config.outbound_drain_timeout(),
+            Some(Duration::from_millis(500))
+        );
+        let network_config = NetworkConfig {
+            outbound_drain_timeout_ms: Some(0),
+            ..NetworkConfig::network_with_id(NetworkId::Validator)
+        };
+        assert_eq!(network_config.outbound_drain_timeout(), None);
+    }
 }
@@ -11,6 +11,7 @@ pub enum CompressionClient {
     DKG,
     JWKConsensus,
     Mempool,
+    Network,
     StateSync,
 }
 
@@ -23,6 +24,7 @@ impl CompressionClient {
             Self::DKG => "dkg",
             Self::JWKConsensus => "jwk_consensus",
             Self::Mempool => "mempool",
+            Self::Network => "network",
             Self::StateSync => "state_sync",
         }
     }
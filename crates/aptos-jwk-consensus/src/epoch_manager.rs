@@ -24,7 +24,7 @@ use aptos_types::{
     account_address::AccountAddress,
     epoch_state::EpochState,
     jwks,
-    jwks::{ObservedJWKs, ObservedJWKsUpdated, SupportedOIDCProviders},
+    jwks::{jwk::ParseOptions, ObservedJWKs, ObservedJWKsUpdated, SupportedOIDCProviders},
     on_chain_config::{
         FeatureFlag, Features, OnChainConfigPayload, OnChainConfigProvider, OnChainConsensusConfig,
         OnChainJWKConsensusConfig, ValidatorSet,
@@ -227,8 +227,15 @@ impl<P: OnChainConfigProvider> EpochManager<P> {
             self.jwk_rpc_msg_tx = Some(jwk_rpc_msg_tx);
             self.jwk_manager_close_tx = Some(jwk_manager_close_tx);
 
+            // The observed keys must be parsed identically by all validators of the epoch
+            let parse_options = ParseOptions {
+                parsing_v2: features.is_jwk_parsing_v2_enabled(),
+                ..ParseOptions::lenient()
+            };
+
             tokio::spawn(jwk_consensus_manager.run(
                 oidc_providers,
+                parse_options,
                 onchain_observed_jwks,
                 jwk_event_rx,
                 jwk_rpc_msg_rx,
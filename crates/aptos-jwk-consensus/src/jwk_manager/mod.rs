@@ -15,8 +15,9 @@ use aptos_types::{
     account_address::AccountAddress,
     epoch_state::EpochState,
     jwks::{
-        jwk::JWKMoveStruct, AllProvidersJWKs, Issuer, OIDCProvider, ObservedJWKs,
-        ObservedJWKsUpdated, ProviderJWKs, QuorumCertifiedUpdate, SupportedOIDCProviders,
+        jwk::{JWKMoveStruct, ParseOptions},
+        AllProvidersJWKs, Issuer, OIDCProvider, ObservedJWKs, ObservedJWKsUpdated, ProviderJWKs,
+        QuorumCertifiedUpdate, SupportedOIDCProviders,
     },
     validator_txn::{Topic, ValidatorTransaction},
 };
@@ -85,6 +86,7 @@ impl JWKManager {
     pub async fn run(
         mut self,
         oidc_providers: Option<SupportedOIDCProviders>,
+        parse_options: ParseOptions,
         observed_jwks: Option<ObservedJWKs>,
         mut jwk_updated_rx: aptos_channel::Receiver<(), ObservedJWKsUpdated>,
         mut rpc_req_rx: aptos_channel::Receiver<
@@ -114,6 +116,7 @@ impl JWKManager {
                         issuer,
                         config_url,
                         Duration::from_secs(10),
+                        parse_options,
                         local_observation_tx.clone(),
                     )),
                     (maybe_issuer, maybe_config_url) => {
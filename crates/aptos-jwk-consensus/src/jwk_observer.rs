@@ -6,7 +6,10 @@ use anyhow::{anyhow, Result};
 use aptos_channels::aptos_channel;
 use aptos_jwk_utils::{fetch_jwks_from_jwks_uri, fetch_jwks_uri_from_openid_config};
 use aptos_logger::{debug, info};
-use aptos_types::jwks::{jwk::JWK, Issuer};
+use aptos_types::jwks::{
+    jwk::{ParseOptions, JWK},
+    Issuer,
+};
 use futures::{FutureExt, StreamExt};
 use move_core_types::account_address::AccountAddress;
 use std::time::{Duration, Instant};
@@ -25,6 +28,7 @@ impl JWKObserver {
         issuer: String,
         config_url: String,
         fetch_interval: Duration,
+        parse_options: ParseOptions,
         observation_tx: aptos_channel::Sender<(), (Issuer, Vec<JWK>)>,
     ) -> Self {
         let (close_tx, close_rx) = oneshot::channel();
@@ -33,6 +37,7 @@ impl JWKObserver {
             my_addr,
             issuer.clone(),
             config_url.clone(),
+            parse_options,
             observation_tx,
             close_rx,
         ));
@@ -53,6 +58,7 @@ impl JWKObserver {
         my_addr: AccountAddress,
         issuer: String,
         open_id_config_url: String,
+        parse_options: ParseOptions,
         observation_tx: aptos_channel::Sender<(), (Issuer, Vec<JWK>)>,
         close_rx: oneshot::Receiver<()>,
     ) {
@@ -71,7 +77,13 @@ impl JWKObserver {
             tokio::select! {
                 _ = interval.tick().fuse() => {
                     let timer = Instant::now();
-                    let result = fetch_jwks(open_id_config_url.as_str(), my_addr).await;
+                    let result = fetch_jwks(
+                        issuer.as_str(),
+                        open_id_config_url.as_str(),
+                        my_addr,
+                        &parse_options,
+                    )
+                    .await;
                     debug!(issuer = issuer, "observe_result={:?}", result);
                     let secs = timer.elapsed().as_secs_f64();
                     if let Ok(mut jwks) = result {
@@ -99,12 +111,21 @@ impl JWKObserver {
     }
 }
 
-async fn fetch_jwks(open_id_config_url: &str, my_addr: Option<AccountAddress>) -> Result<Vec<JWK>> {
+async fn fetch_jwks(
+    issuer: &str,
+    open_id_config_url: &str,
+    my_addr: Option<AccountAddress>,
+    parse_options: &ParseOptions,
+) -> Result<Vec<JWK>> {
     let jwks_uri = fetch_jwks_uri_from_openid_config(open_id_config_url)
         .await
         .map_err(|e| anyhow!("fetch_jwks failed with open-id config request: {e}"))?;
-    let jwks = fetch_jwks_from_jwks_uri(my_addr, jwks_uri.as_str())
+    let jwk_set = fetch_jwks_from_jwks_uri(my_addr, jwks_uri.as_str(), parse_options)
         .await
         .map_err(|e| anyhow!("fetch_jwks failed with jwks uri request: {e}"))?;
-    Ok(jwks)
+    if jwk_set.was_empty {
+        // Providers may temporarily serve an empty key set (e.g., during key rotation)
+        info!(issuer = issuer, "Provider returned an empty key set.");
+    }
+    Ok(jwk_set.keys)
 }
@@ -9,6 +9,7 @@ use crate::{
     counters::{
         PROCESSED_STRUCT_LOG_COUNT, STRUCT_LOG_PARSE_ERROR_COUNT, STRUCT_LOG_QUEUE_ERROR_COUNT,
     },
+    log_context,
     logger::Logger,
     sample,
     sample::SampleRate,
@@ -219,7 +220,9 @@ impl LogEntry {
             None
         };
 
+        // The thread's log context is visited first, so that the event's own pairs take precedence
         let mut data = BTreeMap::new();
+        log_context::visit_log_context(&mut JsonVisitor(&mut data));
         for schema in event.keys_and_values() {
             schema.visit(&mut JsonVisitor(&mut data));
         }
@@ -812,7 +815,7 @@ mod tests {
     use super::{text_format, AptosData, LogEntry};
     use crate::{
         aptos_logger::{json_format, TruncatedLogString, RUST_LOG_TELEMETRY},
-        debug, error, info,
+        debug, error, info, log_context,
         logger::Logger,
         telemetry_log_writer::TelemetryLog,
         trace, warn, AptosDataBuilder, Event, Key, KeyValue, Level, LoggerFilterUpdater, Metadata,
@@ -1174,6 +1177,38 @@ mod tests {
             )));
     }
 
+    #[test]
+    fn test_log_context() {
+        let create_log_entry = || {
+            LogEntry::new(
+                &Event::new(
+                    &Metadata::new(Level::Info, "target", "network", "source_path"),
+                    Some(format_args!("message")),
+                    &[&KeyValue::new("key", Value::Display(&"value"))],
+                ),
+                Some("test_thread"),
+                false,
+            )
+        };
+
+        // Logs emitted within the context should carry the context pair
+        let log_entry =
+            log_context::with_log_context("network_id", "Validator".into(), create_log_entry);
+        assert_eq!(
+            log_entry.data.get(&Key::new("network_id")),
+            Some(&JsonValue::String("Validator".into()))
+        );
+        assert_eq!(
+            log_entry.data.get(&Key::new("key")),
+            Some(&JsonValue::String("value".into()))
+        );
+
+        // Logs emitted after the context is exited should not
+        let log_entry = create_log_entry();
+        assert!(!log_entry.data.contains_key(&Key::new("network_id")));
+        assert!(log_context::current_log_context().is_empty());
+    }
+
     #[test]
     fn test_log_event_truncation() {
         let log_entry = LogEntry::new(
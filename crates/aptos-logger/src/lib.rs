@@ -148,6 +148,7 @@ pub mod aptos_logger;
 mod event;
 mod filter;
 mod kv;
+pub mod log_context;
 mod logger;
 mod macros;
 mod metadata;
@@ -0,0 +1,62 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Contextual key-value pairs that are attached to every log emitted by the current thread
+//! (e.g., the `network_id` of the network runtime a task is running on).
+
+use crate::{Key, Value, Visitor};
+use std::{cell::RefCell, collections::BTreeMap};
+
+thread_local! {
+    static LOG_CONTEXT: RefCell<BTreeMap<&'static str, String>> = RefCell::new(BTreeMap::new());
+}
+
+/// Sets the given context pair for all logs subsequently emitted by the current thread.
+/// This is intended to be called when a thread starts (e.g., in the start hook of a
+/// runtime), so that all tasks running on the thread carry the context.
+pub fn set_thread_log_context(key: &'static str, value: String) {
+    LOG_CONTEXT.with(|log_context| log_context.borrow_mut().insert(key, value));
+}
+
+/// Runs the given function with the context pair set for all logs emitted by the
+/// current thread. The previous value for the key (if any) is restored afterwards.
+pub fn with_log_context<T>(key: &'static str, value: String, f: impl FnOnce() -> T) -> T {
+    struct RestoreGuard {
+        key: &'static str,
+        previous_value: Option<String>,
+    }
+
+    impl Drop for RestoreGuard {
+        fn drop(&mut self) {
+            LOG_CONTEXT.with(|log_context| {
+                let mut log_context = log_context.borrow_mut();
+                match self.previous_value.take() {
+                    Some(previous_value) => log_context.insert(self.key, previous_value),
+                    None => log_context.remove(self.key),
+                };
+            });
+        }
+    }
+
+    let previous_value =
+        LOG_CONTEXT.with(|log_context| log_context.borrow_mut().insert(key, value));
+    let _restore_guard = RestoreGuard {
+        key,
+        previous_value,
+    };
+    f()
+}
+
+/// Returns the context pairs currently set for the current thread
+pub fn current_log_context() -> BTreeMap<&'static str, String> {
+    LOG_CONTEXT.with(|log_context| log_context.borrow().clone())
+}
+
+/// Visits the context pairs currently set for the current thread
+pub(crate) fn visit_log_context(visitor: &mut dyn Visitor) {
+    LOG_CONTEXT.with(|log_context| {
+        for (key, value) in log_context.borrow().iter() {
+            visitor.visit_pair(Key::new(key), Value::from_display(value));
+        }
+    });
+}
@@ -14,6 +14,7 @@ use aptos_infallible::{Mutex, NonZeroUsize};
 use aptos_metrics_core::IntCounterVec;
 use futures::{
     channel::oneshot,
+    future,
     stream::{FusedStream, Stream},
 };
 use std::{
@@ -36,6 +37,10 @@ struct SharedState<K: Eq + Hash + Clone, M> {
     /// as it's tearing down so the `Receiver` can gracefully drain and shutdown
     /// the channel.
     waker: Option<Waker>,
+    /// `Sender`s waiting for space in a full queue register their `Waker`s here
+    /// (see `Sender::push_when_ready()`). They are woken whenever a message is
+    /// dequeued, or when the `Receiver` is cleared or dropped.
+    ready_wakers: Vec<Waker>,
     /// The number of active senders. When this value reaches 0, all senders have
     /// been dropped.
     num_senders: usize,
@@ -47,6 +52,51 @@ struct SharedState<K: Eq + Hash + Clone, M> {
     stream_terminated: bool,
 }
 
+impl<K: Eq + Hash + Clone, M> SharedState<K, M> {
+    /// Pushes the message onto the internal queue, and notifies the status
+    /// channel of the message that was dropped (if any) and the `Receiver`.
+    fn push(
+        &mut self,
+        key: K,
+        message: M,
+        status_ch: Option<oneshot::Sender<ElementStatus<M>>>,
+    ) -> Result<()> {
+        ensure!(!self.receiver_dropped, "Channel is closed");
+        debug_assert!(self.num_senders > 0);
+
+        let dropped = self.internal_queue.push(key, (message, status_ch));
+        // If this or an existing message had to be dropped because of the queue being full, we
+        // notify the corresponding status channel if it was registered.
+        if let Some((dropped_val, Some(dropped_status_ch))) = dropped {
+            // Ignore errors.
+            let _err = dropped_status_ch.send(ElementStatus::Dropped(dropped_val));
+        }
+        if let Some(w) = self.waker.take() {
+            w.wake();
+        }
+        Ok(())
+    }
+
+    /// Pops the next message from the internal queue (if any), and notifies
+    /// the status channel of the message (if one was registered) and the
+    /// `Sender`s waiting for space in the queue.
+    fn pop(&mut self) -> Option<M> {
+        let (val, status_ch) = self.internal_queue.pop()?;
+        if let Some(status_ch) = status_ch {
+            let _err = status_ch.send(ElementStatus::Dequeued);
+        }
+        self.wake_ready_wakers();
+        Some(val)
+    }
+
+    /// Wakes all `Sender`s waiting for space in the queue
+    fn wake_ready_wakers(&mut self) {
+        for waker in self.ready_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
 /// The sending end of the aptos_channel.
 #[derive(Debug)]
 pub struct Sender<K: Eq + Hash + Clone, M> {
@@ -95,21 +145,26 @@ impl<K: Eq + Hash + Clone, M> Sender<K, M> {
         message: M,
         status_ch: Option<oneshot::Sender<ElementStatus<M>>>,
     ) -> Result<()> {
-        let mut shared_state = self.shared_state.lock();
-        ensure!(!shared_state.receiver_dropped, "Channel is closed");
-        debug_assert!(shared_state.num_senders > 0);
+        self.shared_state.lock().push(key, message, status_ch)
+    }
 
-        let dropped = shared_state.internal_queue.push(key, (message, status_ch));
-        // If this or an existing message had to be dropped because of the queue being full, we
-        // notify the corresponding status channel if it was registered.
-        if let Some((dropped_val, Some(dropped_status_ch))) = dropped {
-            // Ignore errors.
-            let _err = dropped_status_ch.send(ElementStatus::Dropped(dropped_val));
-        }
-        if let Some(w) = shared_state.waker.take() {
-            w.wake();
-        }
-        Ok(())
+    /// Same as `push`, but if the queue of the given key is full, this waits until
+    /// a message is dequeued (instead of dropping a message). An error is returned
+    /// if the receiver has been dropped.
+    pub async fn push_when_ready(&self, key: K, message: M) -> Result<()> {
+        let mut message = Some(message);
+        future::poll_fn(|cx| {
+            let mut shared_state = self.shared_state.lock();
+            if !shared_state.receiver_dropped && shared_state.internal_queue.is_full(&key) {
+                shared_state.ready_wakers.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let message = message
+                .take()
+                .expect("push_when_ready polled after completion");
+            Poll::Ready(shared_state.push(key.clone(), message, None))
+        })
+        .await
     }
 }
 
@@ -152,6 +207,34 @@ impl<K: Eq + Hash + Clone, M> Receiver<K, M> {
     pub fn clear(&self) {
         let mut shared_state = self.shared_state.lock();
         shared_state.internal_queue.clear();
+        shared_state.wake_ready_wakers();
+    }
+
+    /// Sets the weight of each key, i.e., the max number of messages received
+    /// from a key per round-robin turn (instead of one). This allows some keys
+    /// to be favored (e.g., those with latency sensitive messages), without
+    /// starving the others. Keys with a weight of zero are treated as one.
+    pub fn set_key_weight(&self, key_weight: impl Fn(&K) -> usize + Send + 'static) {
+        let mut shared_state = self.shared_state.lock();
+        shared_state
+            .internal_queue
+            .set_key_weight(Box::new(key_weight));
+    }
+}
+
+impl<K: Eq + Hash + Clone, M> Receiver<K, M> {
+    /// Converts this receiver into a stream of message batches. Each poll drains
+    /// up to `max_batch` of the messages that are already queued, in the same
+    /// order they would have been received one at a time. This is useful for
+    /// high-throughput consumers that can process messages in bulk.
+    ///
+    /// Panics if `max_batch` is zero.
+    pub fn batched(self, max_batch: usize) -> BatchedReceiver<K, M> {
+        assert!(max_batch > 0, "The maximum batch size must be non-zero!");
+        BatchedReceiver {
+            receiver: self,
+            max_batch,
+        }
     }
 }
 
@@ -160,6 +243,7 @@ impl<K: Eq + Hash + Clone, M> Drop for Receiver<K, M> {
         let mut shared_state = self.shared_state.lock();
         debug_assert!(!shared_state.receiver_dropped);
         shared_state.receiver_dropped = true;
+        shared_state.wake_ready_wakers();
     }
 }
 
@@ -171,10 +255,7 @@ impl<K: Eq + Hash + Clone, M> Stream for Receiver<K, M> {
     /// it sets the waker passed to it by the scheduler/executor and returns Pending
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut shared_state = self.shared_state.lock();
-        if let Some((val, status_ch)) = shared_state.internal_queue.pop() {
-            if let Some(status_ch) = status_ch {
-                let _err = status_ch.send(ElementStatus::Dequeued);
-            }
+        if let Some(val) = shared_state.pop() {
             Poll::Ready(Some(val))
         // all senders have been dropped (and so the stream is terminated)
         } else if shared_state.num_senders == 0 {
@@ -193,6 +274,48 @@ impl<K: Eq + Hash + Clone, M> FusedStream for Receiver<K, M> {
     }
 }
 
+/// A receiving end of the aptos_channel that yields messages in batches
+/// (see `Receiver::batched()`).
+#[derive(Debug)]
+pub struct BatchedReceiver<K: Eq + Hash + Clone, M> {
+    receiver: Receiver<K, M>,
+    max_batch: usize,
+}
+
+impl<K: Eq + Hash + Clone, M> Stream for BatchedReceiver<K, M> {
+    type Item = Vec<M>;
+
+    /// poll_next drains up to `max_batch` messages from the internal queue. If the
+    /// queue is empty, it behaves the same as `Receiver::poll_next()`.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared_state = self.receiver.shared_state.lock();
+        let mut batch = Vec::new();
+        while batch.len() < self.max_batch {
+            match shared_state.pop() {
+                Some(val) => batch.push(val),
+                None => break,
+            }
+        }
+
+        if !batch.is_empty() {
+            Poll::Ready(Some(batch))
+        // all senders have been dropped (and so the stream is terminated)
+        } else if shared_state.num_senders == 0 {
+            shared_state.stream_terminated = true;
+            Poll::Ready(None)
+        } else {
+            shared_state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, M> FusedStream for BatchedReceiver<K, M> {
+    fn is_terminated(&self) -> bool {
+        self.receiver.is_terminated()
+    }
+}
+
 /// Configuration for a new aptos_channel queue.
 #[derive(Clone, Copy)]
 pub struct Config {
@@ -246,6 +369,7 @@ pub fn new<K: Eq + Hash + Clone, M>(
     let shared_state = Arc::new(Mutex::new(SharedState {
         internal_queue: PerKeyQueue::new(queue_style, max_queue_size_per_key, counters),
         waker: None,
+        ready_wakers: Vec::new(),
         num_senders: 1,
         receiver_dropped: false,
         stream_terminated: false,
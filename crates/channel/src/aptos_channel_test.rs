@@ -152,3 +152,85 @@ fn test_feedback_on_drop() {
     };
     block_on(task);
 }
+
+#[test]
+fn test_push_when_ready() {
+    let (sender, mut receiver) = aptos_channel::new(QueueStyle::FIFO, 1, None);
+    sender.push(0, 'a').unwrap();
+
+    // The queue of key 0 is full, so the push waits (instead of dropping a message)
+    let mut push_b = Box::pin(sender.push_when_ready(0, 'b'));
+    assert!((&mut push_b).now_or_never().is_none());
+
+    // Pushes for other keys don't wait
+    block_on(sender.push_when_ready(1, 'c')).unwrap();
+
+    // Once a message of key 0 is dequeued, the waiting push completes
+    assert_eq!(block_on(receiver.select_next_some()), 'a');
+    block_on(push_b).unwrap();
+    assert_eq!(block_on(receiver.select_next_some()), 'c');
+    assert_eq!(block_on(receiver.select_next_some()), 'b');
+
+    // Pushes that are waiting for space fail once the receiver is dropped
+    sender.push(0, 'd').unwrap();
+    let mut push_e = Box::pin(sender.push_when_ready(0, 'e'));
+    assert!((&mut push_e).now_or_never().is_none());
+    drop(receiver);
+    block_on(push_e).unwrap_err();
+}
+
+#[test]
+fn test_weighted_recv() {
+    let (sender, mut receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    receiver.set_key_weight(|key: &u8| if *key == 0 { 3 } else { 1 });
+    for i in 0..4 {
+        sender.push(0, (0, i)).unwrap();
+        sender.push(1, (1, i)).unwrap();
+    }
+    let task = async move {
+        // Ensure that the heavier key gets up to 3 messages per turn (in order)
+        let mut received = vec![];
+        for _ in 0..8 {
+            received.push(receiver.select_next_some().await);
+        }
+        assert_eq!(received, vec![
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (0, 3),
+            (1, 1),
+            (1, 2),
+            (1, 3),
+        ]);
+    };
+    block_on(task);
+}
+
+#[test]
+fn test_batched_recv() {
+    let (sender, receiver) = aptos_channel::new(QueueStyle::FIFO, 100, None);
+    let mut receiver = receiver.batched(10);
+    for i in 0..25 {
+        sender.push(0, i).unwrap();
+    }
+    let task = async move {
+        // Ensure that messages are received in order, in batches no larger than the cap
+        let mut received = vec![];
+        for expected_batch_size in [10, 10, 5] {
+            let batch = receiver.select_next_some().await;
+            assert_eq!(batch.len(), expected_batch_size);
+            received.extend(batch);
+        }
+        assert_eq!(received, (0..25).collect::<Vec<_>>());
+
+        // Ensures that there is no other batch which is ready
+        assert_eq!(receiver.select_next_some().now_or_never(), None);
+
+        // The stream should terminate once the sender is dropped
+        drop(sender);
+        assert_eq!(receiver.next().await, None);
+        assert!(receiver.is_terminated());
+    };
+    block_on(task);
+}
@@ -40,7 +40,8 @@ impl Default for QueueStyle {
 ///
 /// When `pop` is called, the next message is picked from one
 /// of the key's queue and returned. This happens in a round-robin
-/// fashion among keys.
+/// fashion among keys. If key weights are set, each key may have up
+/// to its weight messages popped per turn (i.e., weighted round-robin).
 ///
 /// If there are no messages, in any of the queues, `None` is returned.
 pub(crate) struct PerKeyQueue<K: Eq + Hash + Clone, T> {
@@ -61,6 +62,12 @@ pub(crate) struct PerKeyQueue<K: Eq + Hash + Clone, T> {
     /// Optional counters for recording # enqueued, # dequeued, and # dropped
     /// messages
     counters: Option<&'static IntCounterVec>,
+    /// Optional weight of each key, i.e., the max number of messages popped
+    /// from the key's queue per round-robin turn. Defaults to 1 for all keys.
+    key_weight: Option<Box<dyn Fn(&K) -> usize + Send>>,
+    /// Number of messages popped from the key at the front of the round-robin
+    /// queue in its current turn
+    num_popped_in_turn: usize,
 }
 
 impl<K: Eq + Hash + Clone, T> Debug for PerKeyQueue<K, T> {
@@ -88,9 +95,24 @@ impl<K: Eq + Hash + Clone, T> PerKeyQueue<K, T> {
             round_robin_queue: VecDeque::new(),
             num_popped_since_gc: 0,
             counters,
+            key_weight: None,
+            num_popped_in_turn: 0,
         }
     }
 
+    /// Sets the weight of each key (see `key_weight`). Keys with a weight of
+    /// zero are treated as having a weight of one.
+    pub(crate) fn set_key_weight(&mut self, key_weight: Box<dyn Fn(&K) -> usize + Send>) {
+        self.key_weight = Some(key_weight);
+    }
+
+    /// Returns the weight of the given key
+    fn key_weight(&self, key: &K) -> usize {
+        self.key_weight
+            .as_ref()
+            .map_or(1, |key_weight| key_weight(key).max(1))
+    }
+
     /// Given a key, pops the message from its queue and returns the message
     /// It also returns a boolean indicating whether the keys queue is empty
     /// after popping the message
@@ -107,6 +129,14 @@ impl<K: Eq + Hash + Clone, T> PerKeyQueue<K, T> {
         }
     }
 
+    /// Returns true iff the queue of the given key is at its max capacity (i.e.,
+    /// pushing another message for the key would drop a message)
+    pub(crate) fn is_full(&self, key: &K) -> bool {
+        self.per_key_queue
+            .get(key)
+            .map_or(false, |queue| queue.len() >= self.max_queue_size.get())
+    }
+
     /// push a message to the appropriate queue in per_key_queue
     /// add the key to round_robin_queue if it didnt already exist.
     /// Returns Some(T) if the new or an existing element was dropped. Returns None otherwise.
@@ -162,8 +192,15 @@ impl<K: Eq + Hash + Clone, T> PerKeyQueue<K, T> {
             },
         };
 
+        // The key keeps its turn until it has been popped `weight` times
         let (message, is_q_empty) = self.pop_from_key_queue(&key);
-        if !is_q_empty {
+        self.num_popped_in_turn += 1;
+        if is_q_empty {
+            self.num_popped_in_turn = 0;
+        } else if self.num_popped_in_turn < self.key_weight(&key) {
+            self.round_robin_queue.push_front(key);
+        } else {
+            self.num_popped_in_turn = 0;
             self.round_robin_queue.push_back(key);
         }
 
@@ -210,5 +247,6 @@ impl<K: Eq + Hash + Clone, T> PerKeyQueue<K, T> {
     pub(crate) fn clear(&mut self) {
         self.per_key_queue.clear();
         self.round_robin_queue.clear();
+        self.num_popped_in_turn = 0;
     }
 }
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::Result;
-use aptos_types::jwks::jwk::JWK;
+use aptos_types::jwks::{error::JwkError, jwk::ParseOptions, parse_jwk_set_from_str, JwkSetParse};
 use http::header::COOKIE;
 use move_core_types::account_address::AccountAddress;
 use serde::{Deserialize, Serialize};
@@ -13,27 +13,44 @@ struct OpenIDConfiguration {
     jwks_uri: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct JWKsResponse {
-    keys: Vec<serde_json::Value>,
-}
-
 /// Given a JWK URL, fetch its JWKs.
 ///
+/// An empty key set is returned as such (see `JwkSetParse::was_empty`), rather than as an error.
+/// The keys are parsed with the given options (see `parse_jwk_set_from_str()`), and responses
+/// larger than `max_bytes` are rejected without reading them fully.
+///
 /// Optionally, if an address is given, send it as the cookie payload.
 /// The optional logic is only used in smoke tests, e.g., `jwk_consensus_basic`.
 pub async fn fetch_jwks_from_jwks_uri(
     my_addr: Option<AccountAddress>,
     jwks_uri: &str,
-) -> Result<Vec<JWK>> {
+    parse_options: &ParseOptions,
+) -> Result<JwkSetParse> {
     let client = reqwest::Client::new();
     let mut request_builder = client.get(jwks_uri);
     if let Some(addr) = my_addr {
         request_builder = request_builder.header(COOKIE, addr.to_hex());
     }
-    let JWKsResponse { keys } = request_builder.send().await?.json().await?;
-    let jwks = keys.into_iter().map(JWK::from).collect();
-    Ok(jwks)
+    let mut response = request_builder.send().await?;
+
+    // Reject oversized documents early (if the size is known), and never buffer more
+    // than `max_bytes` of the body (as the advertised size may be missing or wrong).
+    let max_bytes = parse_options.max_bytes;
+    if response
+        .content_length()
+        .map_or(false, |content_length| content_length > max_bytes as u64)
+    {
+        return Err(JwkError::DocumentTooLarge(max_bytes).into());
+    }
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(JwkError::DocumentTooLarge(max_bytes).into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let jwks_document = String::from_utf8(body)?;
+    Ok(parse_jwk_set_from_str(&jwks_document, parse_options)?)
 }
 
 /// Given an Open ID configuration URL, fetch its JWK url.
@@ -51,7 +68,7 @@ async fn test_fetch_real_jwks() {
     )
     .await
     .unwrap();
-    let jwks = fetch_jwks_from_jwks_uri(None, jwks_uri.as_str())
+    let jwks = fetch_jwks_from_jwks_uri(None, jwks_uri.as_str(), &ParseOptions::lenient())
         .await
         .unwrap();
     println!("{:?}", jwks);
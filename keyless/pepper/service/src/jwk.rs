@@ -3,19 +3,192 @@
 
 use crate::{metrics::JWK_FETCH_SECONDS, Issuer, KeyID};
 use anyhow::{anyhow, Result};
+use aptos_infallible::Mutex;
 use aptos_logger::warn;
 use dashmap::DashMap;
-use jsonwebtoken::{jwk::JwkSet, DecodingKey};
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    DecodingKey,
+};
 use once_cell::sync::Lazy;
-use std::{sync::Arc, time::Duration};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 use tokio::time::Instant;
 
 /// The JWK in-mem cache.
 pub static DECODING_KEY_CACHE: Lazy<DashMap<Issuer, DashMap<KeyID, Arc<DecodingKey>>>> =
     Lazy::new(DashMap::new);
 
+/// The freshness metadata of each issuer in the JWK in-mem cache.
+pub static JWK_CACHE_METADATA: Lazy<DashMap<Issuer, JwkCacheMetadata>> = Lazy::new(DashMap::new);
+
+/// The maximum number of entries in the JWK audit log (the oldest entries are rotated out).
+const MAX_JWK_AUDIT_LOG_ENTRIES: usize = 10_000;
+
+/// The append-only audit log of the JWKs ingested into the in-mem cache.
+static JWK_AUDIT_LOG: Lazy<Mutex<JwkAuditLog>> = Lazy::new(|| Mutex::new(JwkAuditLog::default()));
+
+/// Where the cached JWKs of an issuer came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheSource {
+    File,
+    Network,
+    OnChain,
+}
+
+/// The freshness metadata of the cached JWKs of an issuer.
+#[derive(Clone, Copy, Debug)]
+pub struct JwkCacheMetadata {
+    last_refreshed: Instant,
+    source: CacheSource,
+}
+
+impl JwkCacheMetadata {
+    /// Returns the time at which the JWKs were last refreshed
+    pub fn last_refreshed(&self) -> Instant {
+        self.last_refreshed
+    }
+
+    /// Returns where the JWKs came from
+    pub fn source(&self) -> CacheSource {
+        self.source
+    }
+
+    /// Returns the time elapsed since the JWKs were last refreshed
+    pub fn staleness(&self) -> Duration {
+        self.last_refreshed.elapsed()
+    }
+}
+
+/// An entry in the JWK audit log, recording when a key was first trusted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JwkAuditEntry {
+    pub issuer: Issuer,
+    pub kid: KeyID,
+    pub thumbprint: String,
+    pub ingested_at: SystemTime,
+    pub source: CacheSource,
+}
+
+/// The (issuer, kid, thumbprint) of a key in the JWK audit log
+type JwkAuditKey = (Issuer, KeyID, String);
+
+/// The JWK audit log entries (oldest first), along with the keys they record (so
+/// that already recorded keys can be looked up without scanning the entries).
+#[derive(Default)]
+struct JwkAuditLog {
+    entries: VecDeque<JwkAuditEntry>,
+    recorded_keys: HashSet<JwkAuditKey>,
+}
+
+impl JwkAuditEntry {
+    fn audit_key(&self) -> JwkAuditKey {
+        (
+            self.issuer.clone(),
+            self.kid.clone(),
+            self.thumbprint.clone(),
+        )
+    }
+}
+
+/// The JWKs of an issuer, as parsed from a JWK set.
+#[derive(Clone)]
+pub struct ParsedJwks {
+    pub decoding_keys: DashMap<KeyID, Arc<DecodingKey>>,
+    pub thumbprints: HashMap<KeyID, String>,
+}
+
+/// A snapshot of the cached JWKs of an issuer, for the cache status output.
+#[derive(Debug, Serialize)]
+pub struct JwkCacheStatus {
+    pub issuer: Issuer,
+    pub source: CacheSource,
+    pub num_keys: usize,
+    pub seconds_since_refresh: u64,
+}
+
+/// Inserts the JWKs of an issuer into the cache, and records where they came from.
+/// Keys that have not been ingested before are also recorded in the audit log.
+pub fn cache_decoding_keys(issuer: Issuer, jwks: ParsedJwks, source: CacheSource) {
+    record_ingested_keys(&issuer, &jwks.thumbprints, source);
+    DECODING_KEY_CACHE.insert(issuer.clone(), jwks.decoding_keys);
+    JWK_CACHE_METADATA.insert(issuer, JwkCacheMetadata {
+        last_refreshed: Instant::now(),
+        source,
+    });
+}
+
+/// Appends an audit log entry for each of the given keys that is not already in the
+/// log (i.e., periodic refreshes of a trusted key are not recorded again). If the log
+/// is full, the oldest entries are rotated out.
+fn record_ingested_keys(
+    issuer: &Issuer,
+    thumbprints: &HashMap<KeyID, String>,
+    source: CacheSource,
+) {
+    let mut audit_log = JWK_AUDIT_LOG.lock();
+    let ingested_at = SystemTime::now();
+    for (kid, thumbprint) in thumbprints {
+        let entry = JwkAuditEntry {
+            issuer: issuer.clone(),
+            kid: kid.clone(),
+            thumbprint: thumbprint.clone(),
+            ingested_at,
+            source,
+        };
+        if audit_log.recorded_keys.insert(entry.audit_key()) {
+            audit_log.entries.push_back(entry);
+        }
+    }
+    while audit_log.entries.len() > MAX_JWK_AUDIT_LOG_ENTRIES {
+        if let Some(entry) = audit_log.entries.pop_front() {
+            audit_log.recorded_keys.remove(&entry.audit_key());
+        }
+    }
+}
+
+/// Returns the audit log of ingested JWKs (oldest first).
+pub fn jwk_audit_log() -> Vec<JwkAuditEntry> {
+    JWK_AUDIT_LOG.lock().entries.iter().cloned().collect()
+}
+
+/// Returns the (base64url-encoded) RFC 7638 thumbprint of the given JWK (see
+/// `aptos_types::jwks::jwk::thumbprint()`). Returns `None` if the key type is
+/// unknown, or if any of the required members is missing.
+pub fn jwk_thumbprint(jwk: &Jwk) -> Option<String> {
+    let value = serde_json::to_value(jwk).ok()?;
+    aptos_types::jwks::jwk::thumbprint(&value).ok()
+}
+
+/// Returns the freshness metadata of the cached JWKs of an issuer (if any).
+pub fn cached_metadata(issuer: &String) -> Option<JwkCacheMetadata> {
+    JWK_CACHE_METADATA.get(issuer).map(|metadata| *metadata)
+}
+
+/// Returns a snapshot of every issuer in the cache, sorted by issuer.
+pub fn jwk_cache_status() -> Vec<JwkCacheStatus> {
+    let mut statuses: Vec<JwkCacheStatus> = JWK_CACHE_METADATA
+        .iter()
+        .map(|entry| JwkCacheStatus {
+            issuer: entry.key().clone(),
+            source: entry.source(),
+            num_keys: DECODING_KEY_CACHE
+                .get(entry.key())
+                .map_or(0, |key_set| key_set.len()),
+            seconds_since_refresh: entry.staleness().as_secs(),
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.issuer.cmp(&b.issuer));
+    statuses
+}
+
 /// Send a request to a JWK endpoint and return its JWK map.
-pub async fn fetch_jwks(jwk_url: &str) -> Result<DashMap<KeyID, Arc<DecodingKey>>> {
+pub async fn fetch_jwks(jwk_url: &str) -> Result<ParsedJwks> {
     let response = reqwest::get(jwk_url)
         .await
         .map_err(|e| anyhow!("jwk fetch error: {}", e))?;
@@ -26,14 +199,20 @@ pub async fn fetch_jwks(jwk_url: &str) -> Result<DashMap<KeyID, Arc<DecodingKey>
     parse_jwks(&text)
 }
 
-pub fn parse_jwks(text: &str) -> Result<DashMap<KeyID, Arc<DecodingKey>>> {
+pub fn parse_jwks(text: &str) -> Result<ParsedJwks> {
     let JwkSet { keys } =
         serde_json::from_str(text).map_err(|e| anyhow!("error while parsing json: {}", e))?;
-    let key_map: DashMap<KeyID, Arc<DecodingKey>> = keys
+    let mut thumbprints = HashMap::new();
+    let decoding_keys: DashMap<KeyID, Arc<DecodingKey>> = keys
         .into_iter()
         .filter_map(
             |jwk| match (&jwk.common.key_id, DecodingKey::from_jwk(&jwk)) {
-                (Some(kid), Ok(key)) => Some((kid.clone(), Arc::new(key))),
+                (Some(kid), Ok(key)) => {
+                    if let Some(thumbprint) = jwk_thumbprint(&jwk) {
+                        thumbprints.insert(kid.clone(), thumbprint);
+                    }
+                    Some((kid.clone(), Arc::new(key)))
+                },
                 (Some(kid), Err(e)) => {
                     warn!("error while parsing for kid {kid}: {e}");
                     None
@@ -45,7 +224,10 @@ pub fn parse_jwks(text: &str) -> Result<DashMap<KeyID, Arc<DecodingKey>>> {
             },
         )
         .collect();
-    Ok(key_map)
+    Ok(ParsedJwks {
+        decoding_keys,
+        thumbprints,
+    })
 }
 
 pub fn start_jwk_refresh_loop(issuer: &str, jwk_url: &str, refresh_interval: Duration) {
@@ -61,8 +243,8 @@ pub fn start_jwk_refresh_loop(issuer: &str, jwk_url: &str, refresh_interval: Dur
                 .with_label_values(&[issuer.as_str(), succeeded.to_string().as_str()])
                 .observe(fetch_time.as_secs_f64());
             match fetch_result {
-                Ok(key_set) => {
-                    DECODING_KEY_CACHE.insert(issuer.clone(), key_set.clone());
+                Ok(jwks) => {
+                    cache_decoding_keys(issuer.clone(), jwks, CacheSource::Network);
                 },
                 Err(msg) => {
                     warn!(
@@ -87,3 +269,72 @@ pub fn cached_decoding_key(issuer: &String, kid: &String) -> Result<Arc<Decoding
         .ok_or_else(|| anyhow!("unknown kid: {}", kid))?;
     Ok(key.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_metadata_updates_on_insert() {
+        let issuer = "https://jwk-cache-metadata.test".to_string();
+        assert!(cached_metadata(&issuer).is_none());
+
+        // Insert keys from a file, and verify the metadata is recorded
+        let test_jwk = include_str!("../../../../types/src/jwks/rsa/secure_test_jwk.json");
+        let jwks = parse_jwks(test_jwk).unwrap();
+        let num_keys = jwks.decoding_keys.len();
+        cache_decoding_keys(issuer.clone(), jwks.clone(), CacheSource::File);
+        let metadata = cached_metadata(&issuer).unwrap();
+        assert_eq!(metadata.source(), CacheSource::File);
+
+        // Refresh the keys from the network, and verify the metadata is updated
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache_decoding_keys(issuer.clone(), jwks, CacheSource::Network);
+        let refreshed_metadata = cached_metadata(&issuer).unwrap();
+        assert_eq!(refreshed_metadata.source(), CacheSource::Network);
+        assert!(refreshed_metadata.last_refreshed() > metadata.last_refreshed());
+
+        // Verify the issuer shows up in the cache status
+        let status = jwk_cache_status()
+            .into_iter()
+            .find(|status| status.issuer == issuer)
+            .unwrap();
+        assert_eq!(status.source, CacheSource::Network);
+        assert_eq!(status.num_keys, num_keys);
+    }
+
+    #[test]
+    fn test_audit_log_records_ingested_keys() {
+        let issuer = "https://jwk-audit-log.test".to_string();
+        let audit_entries = |issuer: &String| -> Vec<JwkAuditEntry> {
+            jwk_audit_log()
+                .into_iter()
+                .filter(|entry| &entry.issuer == issuer)
+                .collect()
+        };
+        assert!(audit_entries(&issuer).is_empty());
+
+        // Insert the example key from RFC 7638, and verify it is recorded with its thumbprint
+        let test_jwk = r#"{"keys": [{
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        }]}"#;
+        let jwks = parse_jwks(test_jwk).unwrap();
+        cache_decoding_keys(issuer.clone(), jwks.clone(), CacheSource::Network);
+        let entries = audit_entries(&issuer);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kid, "2011-04-29");
+        assert_eq!(
+            entries[0].thumbprint,
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+        assert_eq!(entries[0].source, CacheSource::Network);
+
+        // Refreshing the same key should not record it again
+        cache_decoding_keys(issuer.clone(), jwks, CacheSource::Network);
+        assert_eq!(audit_entries(&issuer), entries);
+    }
+}
@@ -6,7 +6,7 @@ use aptos_keyless_pepper_service::{
     about::ABOUT_JSON,
     account_db::{init_account_db, ACCOUNT_RECOVERY_DB},
     account_managers::ACCOUNT_MANAGERS,
-    jwk::{self, parse_jwks, DECODING_KEY_CACHE},
+    jwk::{self, cache_decoding_keys, jwk_cache_status, parse_jwks, CacheSource},
     metrics::start_metric_server,
     vuf_keys::{PEPPER_VUF_VERIFICATION_KEY_JSON, VUF_SK},
     HandlerTrait,
@@ -37,6 +37,11 @@ async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible
         (&Method::GET, "/about") => {
             build_response(origin, StatusCode::OK, ABOUT_JSON.deref().clone())
         },
+        (&Method::GET, "/jwk-cache-status") => build_response(
+            origin,
+            StatusCode::OK,
+            serde_json::to_string_pretty(&jwk_cache_status()).unwrap(),
+        ),
         (&Method::GET, "/v0/vuf-pub-key") => build_response(
             origin,
             StatusCode::OK,
@@ -87,9 +92,10 @@ async fn main() {
     );
 
     let test_jwk = include_str!("../../../../types/src/jwks/rsa/secure_test_jwk.json");
-    DECODING_KEY_CACHE.insert(
+    cache_decoding_keys(
         get_sample_iss(),
         parse_jwks(test_jwk).expect("test jwk should parse"),
+        CacheSource::File,
     );
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 8000));
@@ -20,6 +20,7 @@ use aptos_config::{
     config::{MempoolConfig, NodeType},
     network_id::PeerNetworkId,
 };
+use aptos_crypto::{hash::DefaultHasher, HashValue};
 use aptos_infallible::RwLock;
 use aptos_logger::prelude::*;
 use aptos_netcore::transport::ConnectionOrigin;
@@ -561,7 +562,12 @@ impl<NetworkClient: NetworkClientInterface<MempoolSyncMsg>> MempoolNetworkInterf
         // For each transaction, we include the ready time in millis since epoch
         transactions: Vec<(SignedTransaction, u64, BroadcastPeerPriority)>,
     ) -> Result<(), BroadcastError> {
-        let request = if self.mempool_config.include_ready_time_in_broadcast {
+        // Identical batches (e.g., the same broadcast to several peers) share a serialization
+        let include_ready_time = self.mempool_config.include_ready_time_in_broadcast;
+        let coalescing_key =
+            broadcast_coalescing_key(include_ready_time, &message_id, &transactions);
+
+        let request = if include_ready_time {
             MempoolSyncMsg::BroadcastTransactionsRequestWithReadyTime {
                 message_id,
                 transactions,
@@ -573,7 +579,10 @@ impl<NetworkClient: NetworkClientInterface<MempoolSyncMsg>> MempoolNetworkInterf
             }
         };
 
-        if let Err(e) = self.network_client.send_to_peer(request, peer) {
+        if let Err(e) = self
+            .network_client
+            .send_to_peer_coalesced(request, peer, coalescing_key)
+        {
             counters::network_send_fail_inc(counters::BROADCAST_TXNS);
             return Err(BroadcastError::NetworkError(peer, e.into()));
         }
@@ -665,3 +674,27 @@ impl<NetworkClient: NetworkClientInterface<MempoolSyncMsg>> MempoolNetworkInterf
         self.sync_states.read().get(peer).is_some()
     }
 }
+
+/// Returns the key that identifies the contents of a broadcast batch, so that
+/// identical batches sent to several peers are only serialized once. The key is
+/// derived from the (cached) transaction hashes, so no serialization is required.
+fn broadcast_coalescing_key(
+    include_ready_time: bool,
+    message_id: &MempoolMessageId,
+    transactions: &[(SignedTransaction, u64, BroadcastPeerPriority)],
+) -> HashValue {
+    let mut hasher = DefaultHasher::new(b"MempoolBroadcastBatch");
+    hasher.update(&[include_ready_time as u8]);
+    for (start, end) in &message_id.0 {
+        hasher.update(&start.to_le_bytes());
+        hasher.update(&end.to_le_bytes());
+    }
+    for (transaction, ready_time, priority) in transactions {
+        hasher.update(transaction.committed_hash().as_ref());
+        if include_ready_time {
+            hasher.update(&ready_time.to_le_bytes());
+            hasher.update(&[matches!(priority, BroadcastPeerPriority::Primary) as u8]);
+        }
+    }
+    hasher.finish()
+}
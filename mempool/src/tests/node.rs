@@ -545,7 +545,7 @@ fn setup_node_network_interface(
         PeerManagerRequestSender::new(network_reqs_tx),
         ConnectionRequestSender::new(connection_reqs_tx),
     );
-    let network_events = NetworkEvents::new(network_notifs_rx, None, true);
+    let network_events = NetworkEvents::new(network_notifs_rx, None, true, None, None);
 
     (
         NodeNetworkInterface {
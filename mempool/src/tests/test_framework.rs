@@ -598,7 +598,7 @@ fn setup_network(
         PeerManagerRequestSender::new(reqs_outbound_sender),
         ConnectionRequestSender::new(connection_outbound_sender),
     );
-    let network_events = NetworkEvents::new(reqs_inbound_receiver, None, true);
+    let network_events = NetworkEvents::new(reqs_inbound_receiver, None, true, None, None);
 
     (
         network_sender,
@@ -332,6 +332,7 @@ async fn connection_listener(
                     let peer_network_id = PeerNetworkId::new(network_id, meta.remote_peer_id);
                     connected_peers.remove(&peer_network_id);
                 },
+                ConnectionNotification::UpdatedPeerProtocols(..) => {},
             },
         }
     }
@@ -10,40 +10,73 @@
 //! authentication -- a network end-point running with remote authentication enabled will
 //! connect to or accept connections from an end-point running in authenticated mode as
 //! long as the latter is in its trusted peers set.
+use aptos_channels::aptos_channel;
 use aptos_config::{
     config::{
-        DiscoveryMethod, NetworkConfig, Peer, PeerRole, PeerSet, RoleType, CONNECTION_BACKOFF_BASE,
-        CONNECTIVITY_CHECK_INTERVAL_MS, MAX_CONNECTION_DELAY_MS, MAX_FRAME_SIZE,
+        CompressionAlgo, DiscoveryMethod, NetworkConfig, Peer, PeerRole, PeerSet, ReconnectBackoff,
+        RoleType, UnknownProtocolPolicy, CONNECTIVITY_CHECK_INTERVAL_MS, MAX_FRAME_SIZE,
         MAX_FULLNODE_OUTBOUND_CONNECTIONS, MAX_INBOUND_CONNECTIONS, NETWORK_CHANNEL_SIZE,
     },
-    network_id::NetworkContext,
+    network_id::{NetworkContext, NetworkId},
 };
 use aptos_event_notifications::{DbBackedOnChainConfig, EventSubscriptionService};
 use aptos_logger::prelude::*;
 use aptos_netcore::transport::tcp::TCPBufferCfg;
 use aptos_network::{
     application::storage::PeersAndMetadata,
-    connectivity_manager::{builder::ConnectivityManagerBuilder, ConnectivityRequest},
+    connectivity_manager::{
+        builder::ConnectivityManagerBuilder, ConnectivityRequest, DiscoverySource,
+    },
     constants::MAX_MESSAGE_SIZE,
     logging::NetworkSchema,
     peer_manager::{
         builder::{AuthenticationMode, PeerManagerBuilder},
-        ConnectionRequestSender,
+        ConnectionRequestSender, PeerManagerError, PeerManagerRequestSender,
     },
     protocols::{
         health_checker::{self, builder::HealthCheckerBuilder},
         network::{
-            NetworkApplicationConfig, NetworkClientConfig, NetworkServiceConfig, NewNetworkEvents,
-            NewNetworkSender,
+            NetworkApplicationConfig, NetworkServiceConfig, NewNetworkEvents, NewNetworkSender,
+            ReceivedMessage,
         },
     },
+    ProtocolId,
 };
 use aptos_network_discovery::DiscoveryChangeListener;
-use aptos_time_service::TimeService;
-use aptos_types::{chain_id::ChainId, network_address::NetworkAddress};
-use std::{clone::Clone, collections::HashSet, sync::Arc, time::Duration};
+use aptos_time_service::{TimeService, TimeServiceTrait};
+use aptos_types::{chain_id::ChainId, network_address::NetworkAddress, PeerId};
+use futures::channel::mpsc;
+use std::{
+    clone::Clone,
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use thiserror::Error;
 use tokio::runtime::Handle;
 
+/// Errors returned when reloading the config of a network (see [`NetworkBuilder::reload`])
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum NetworkReloadError {
+    #[error("The listen address cannot be changed without a restart! Current: {0}, new: {1}")]
+    ListenAddressChanged(NetworkAddress, NetworkAddress),
+
+    #[error("The network id cannot be changed without a restart! Current: {0}, new: {1}")]
+    NetworkIdChanged(NetworkId, NetworkId),
+
+    #[error("The following fields cannot be changed without a restart: {0:?}")]
+    UnreloadableFieldsChanged(Vec<&'static str>),
+
+    #[error("The new seed peers are invalid: {0}")]
+    InvalidSeeds(String),
+
+    #[error("The network has no connectivity manager to update the seed peers!")]
+    MissingConnectivityManager,
+
+    #[error("Failed to send the updated seed peers to the connectivity manager: {0}")]
+    SeedUpdateFailed(String),
+}
+
 #[derive(Debug, PartialEq, PartialOrd)]
 enum State {
     CREATED,
@@ -66,6 +99,9 @@ pub struct NetworkBuilder {
     health_checker_builder: Option<HealthCheckerBuilder>,
     peer_manager_builder: PeerManagerBuilder,
     peers_and_metadata: Arc<PeersAndMetadata>,
+    configured_listen_address: NetworkAddress, // The listen address in the config (i.e., before binding)
+    configured_config: Option<NetworkConfig>,  // The config the network was created from (if any)
+    listen_address_subscribers: Vec<mpsc::UnboundedSender<Vec<NetworkAddress>>>,
 }
 
 impl NetworkBuilder {
@@ -85,6 +121,12 @@ impl NetworkBuilder {
         network_channel_size: usize,
         inbound_connection_limit: usize,
         tcp_buffer_cfg: TCPBufferCfg,
+        idle_timeout: Option<Duration>,
+        keep_alive_interval: Option<Duration>,
+        drain_timeout: Option<Duration>,
+        unknown_protocol_policy: UnknownProtocolPolicy,
+        max_inbound_buffered_bytes: Option<usize>,
+        compression: Option<CompressionAlgo>,
     ) -> Self {
         // A network cannot exist without a PeerManager
         // TODO:  construct this in create and pass it to new() as a parameter. The complication is manual construction of NetworkBuilder in various tests.
@@ -92,7 +134,7 @@ impl NetworkBuilder {
             chain_id,
             network_context,
             time_service.clone(),
-            listen_address,
+            listen_address.clone(),
             peers_and_metadata.clone(),
             authentication_mode,
             network_channel_size,
@@ -101,6 +143,12 @@ impl NetworkBuilder {
             enable_proxy_protocol,
             inbound_connection_limit,
             tcp_buffer_cfg,
+            idle_timeout,
+            keep_alive_interval,
+            drain_timeout,
+            unknown_protocol_policy,
+            max_inbound_buffered_bytes,
+            compression,
         );
 
         NetworkBuilder {
@@ -113,6 +161,9 @@ impl NetworkBuilder {
             health_checker_builder: None,
             peer_manager_builder,
             peers_and_metadata,
+            configured_listen_address: listen_address,
+            configured_config: None,
+            listen_address_subscribers: vec![],
         }
     }
 
@@ -140,14 +191,19 @@ impl NetworkBuilder {
             NETWORK_CHANNEL_SIZE,
             MAX_INBOUND_CONNECTIONS,
             TCPBufferCfg::default(),
+            None, /* Disable idle timeout */
+            None, /* Disable keep-alive pings */
+            None, /* Disable outbound draining */
+            UnknownProtocolPolicy::default(),
+            None, /* Disable inbound memory budget */
+            None, /* Disable compression */
         );
 
         builder.add_connectivity_manager(
             seeds,
             peers_and_metadata,
             MAX_FULLNODE_OUTBOUND_CONNECTIONS,
-            CONNECTION_BACKOFF_BASE,
-            MAX_CONNECTION_DELAY_MS,
+            ReconnectBackoff::default(),
             CONNECTIVITY_CHECK_INTERVAL_MS,
             NETWORK_CHANNEL_SIZE,
             mutual_authentication,
@@ -195,7 +251,17 @@ impl NetworkBuilder {
                 config.outbound_rx_buffer_size_bytes,
                 config.outbound_tx_buffer_size_bytes,
             ),
+            config.idle_timeout(),
+            config.keep_alive_interval(),
+            config.outbound_drain_timeout(),
+            config.unknown_protocol_policy,
+            config.max_inbound_buffered_bytes,
+            config.compression,
         );
+        network_builder
+            .peer_manager_builder
+            .set_max_registered_protocols(config.max_registered_protocols);
+        network_builder.configured_config = Some(config.clone());
 
         network_builder.add_connection_monitoring(
             config.ping_interval_ms,
@@ -211,8 +277,7 @@ impl NetworkBuilder {
             seeds,
             peers_and_metadata,
             config.max_outbound_connections,
-            config.connection_backoff_base,
-            config.max_connection_delay_ms,
+            config.reconnect_backoff_policy(),
             config.connectivity_check_interval_ms,
             config.network_channel_size,
             config.mutual_authentication,
@@ -243,8 +308,10 @@ impl NetworkBuilder {
         assert_eq!(self.state, State::CREATED);
         self.state = State::BUILT;
         self.executor = Some(executor);
+        let unbound_listen_addresses = self.listen_addresses();
         self.peer_manager_builder
             .build(self.executor.as_mut().expect("Executor must exist"));
+        self.notify_listen_addresses_if_changed(unbound_listen_addresses);
         self
     }
 
@@ -284,10 +351,100 @@ impl NetworkBuilder {
         self
     }
 
+    /// Quiesce the network ahead of a full shutdown. All new outbound sends
+    /// (direct-send and RPC) are rejected from this point on, and the network
+    /// stops accepting and dialing new connections (i.e., the listener and the
+    /// connectivity manager are stopped). Existing connections are kept, so
+    /// inbound messages continue to be delivered to the registered services.
+    /// The returned future resolves once all in-flight outbound RPCs have
+    /// completed, or once `grace` has elapsed (whichever comes first).
+    pub async fn quiesce(&self, grace: Duration) {
+        assert_eq!(self.state, State::STARTED);
+        self.peer_manager_builder.quiesce();
+        self.peer_manager_builder.stop_listener();
+        if let Some(conn_mgr_builder) = self.connectivity_manager_builder.as_ref() {
+            conn_mgr_builder.stop();
+        }
+        info!(
+            NetworkSchema::new(&self.network_context),
+            "{} Quiescing network for at most {:?}", self.network_context, grace
+        );
+
+        // Wait for the in-flight RPCs to complete (or give up)
+        let inflight_rpcs = self.peer_manager_builder.inflight_rpcs();
+        if self
+            .time_service
+            .timeout(grace, inflight_rpcs.wait_for_completion())
+            .await
+            .is_err()
+        {
+            warn!(
+                NetworkSchema::new(&self.network_context),
+                "{} Quiesce grace period elapsed with {} RPC(s) still in flight",
+                self.network_context,
+                inflight_rpcs.num_inflight_rpcs()
+            );
+        }
+    }
+
+    /// Reloads the config of the network without restarting it. Only the seed peers
+    /// can be changed on a live network: they are sent to the connectivity manager
+    /// (replacing the previous seeds). Changes to any other field require a restart
+    /// (e.g., the outbound protocol rate limits are baked into the application senders
+    /// when they are registered), and are rejected, in which case nothing is applied.
+    pub fn reload(&self, new_config: &NetworkConfig) -> Result<(), NetworkReloadError> {
+        // Verify the new config doesn't require a restart
+        let network_id = self.network_context.network_id();
+        if new_config.network_id != network_id {
+            return Err(NetworkReloadError::NetworkIdChanged(
+                network_id,
+                new_config.network_id,
+            ));
+        }
+        if new_config.listen_address != self.configured_listen_address {
+            return Err(NetworkReloadError::ListenAddressChanged(
+                self.configured_listen_address.clone(),
+                new_config.listen_address.clone(),
+            ));
+        }
+        if let Some(configured_config) = &self.configured_config {
+            let changed_fields = unreloadable_changes(configured_config, new_config);
+            if !changed_fields.is_empty() {
+                return Err(NetworkReloadError::UnreloadableFieldsChanged(
+                    changed_fields,
+                ));
+            }
+        }
+        new_config
+            .verify_seeds()
+            .map_err(|error| NetworkReloadError::InvalidSeeds(error.to_string()))?;
+
+        // Send the new seeds to the connectivity manager
+        let mut conn_mgr_reqs_tx = self
+            .conn_mgr_reqs_tx()
+            .ok_or(NetworkReloadError::MissingConnectivityManager)?;
+        conn_mgr_reqs_tx
+            .try_send(ConnectivityRequest::UpdateDiscoveredPeers(
+                DiscoverySource::Config,
+                merge_seeds(new_config),
+            ))
+            .map_err(|error| NetworkReloadError::SeedUpdateFailed(error.to_string()))?;
+
+        info!(
+            NetworkSchema::new(&self.network_context),
+            "{} Reloaded the network config", self.network_context
+        );
+        Ok(())
+    }
+
     pub fn network_context(&self) -> NetworkContext {
         self.network_context
     }
 
+    pub fn peers_and_metadata(&self) -> Arc<PeersAndMetadata> {
+        self.peers_and_metadata.clone()
+    }
+
     pub fn conn_mgr_reqs_tx(&self) -> Option<aptos_channels::Sender<ConnectivityRequest>> {
         self.connectivity_manager_builder
             .as_ref()
@@ -298,6 +455,66 @@ impl NetworkBuilder {
         self.peer_manager_builder.listen_address()
     }
 
+    /// Returns the protocols this network advertises to remote peers during
+    /// the handshake, assembled from all registered applications. Protocols
+    /// are returned in ascending wire order. Note: this must be called before
+    /// the network is built.
+    pub fn advertised_protocols(&self) -> Vec<ProtocolId> {
+        self.peer_manager_builder
+            .supported_protocols()
+            .iter()
+            .collect()
+    }
+
+    /// Returns the protocols for which a service has registered to receive
+    /// inbound messages, in ascending wire order. Note: this must be called
+    /// before the network is built.
+    pub fn service_protocols(&self) -> Vec<ProtocolId> {
+        self.peer_manager_builder.service_protocols()
+    }
+
+    /// Returns the addresses this network is listening on. Once the network
+    /// has been built, these are the actual bound addresses (e.g., with the
+    /// concrete port chosen by the OS when configured with port 0).
+    pub fn listen_addresses(&self) -> Vec<NetworkAddress> {
+        // TODO: the peer manager only supports a single listen address for now
+        vec![self.listen_address()]
+    }
+
+    /// Returns a channel for receiving the new listen addresses of this network whenever
+    /// they change, e.g., once the network is built and binds to the concrete port chosen
+    /// by the OS (when configured with port 0). This allows the correct addresses to be
+    /// advertised to peers. Use `listen_addresses()` to get the current addresses.
+    pub fn subscribe_to_listen_addresses(
+        &mut self,
+    ) -> mpsc::UnboundedReceiver<Vec<NetworkAddress>> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.listen_address_subscribers.push(sender);
+        receiver
+    }
+
+    /// Notifies the listen address subscribers if the listen addresses differ from
+    /// the given previous addresses. Subscribers that have gone away are dropped.
+    fn notify_listen_addresses_if_changed(
+        &mut self,
+        previous_listen_addresses: Vec<NetworkAddress>,
+    ) {
+        let listen_addresses = self.listen_addresses();
+        if listen_addresses == previous_listen_addresses {
+            return;
+        }
+
+        info!(
+            NetworkSchema::new(&self.network_context),
+            "{} Listen addresses changed from {:?} to {:?}",
+            self.network_context,
+            previous_listen_addresses,
+            listen_addresses
+        );
+        self.listen_address_subscribers
+            .retain(|subscriber| subscriber.unbounded_send(listen_addresses.clone()).is_ok());
+    }
+
     /// Add a `network::connectivity_manager::ConnectivityManager` to the network.
     ///
     /// `network::connectivity_manager::ConnectivityManager` is responsible for ensuring that we are connected
@@ -312,8 +529,7 @@ impl NetworkBuilder {
         seeds: PeerSet,
         peers_and_metadata: Arc<PeersAndMetadata>,
         max_outbound_connections: usize,
-        connection_backoff_base: u64,
-        max_connection_delay_ms: u64,
+        reconnect_backoff: ReconnectBackoff,
         connectivity_check_interval_ms: u64,
         channel_size: usize,
         mutual_authentication: bool,
@@ -333,8 +549,7 @@ impl NetworkBuilder {
             peers_and_metadata,
             seeds,
             connectivity_check_interval_ms,
-            connection_backoff_base,
-            max_connection_delay_ms,
+            reconnect_backoff,
             channel_size,
             ConnectionRequestSender::new(self.peer_manager_builder.connection_reqs_tx()),
             pm_conn_mgr_notifs_rx,
@@ -428,48 +643,310 @@ impl NetworkBuilder {
 
     /// Register a new client and service application with the network. Return
     /// the client interface for sending messages and the service interface
-    /// for handling network requests.
+    /// for handling network requests. Each protocol is only registered on the
+    /// side(s) required by its `ProtocolRole`.
     pub fn add_client_and_service<SenderT: NewNetworkSender, EventsT: NewNetworkEvents>(
         &mut self,
         config: &NetworkApplicationConfig,
         max_parallel_deserialization_tasks: Option<usize>,
         allow_out_of_order_delivery: bool,
     ) -> (SenderT, EventsT) {
+        self.add_application(
+            config,
+            max_parallel_deserialization_tasks,
+            allow_out_of_order_delivery,
+        )
+        .into_typed()
+    }
+
+    /// Register a new client and service application with the network, without
+    /// committing to the application's message type. This is the non-generic
+    /// counterpart of [`NetworkBuilder::add_client_and_service`]: the returned
+    /// [`ApplicationConnections`] are converted into the typed interfaces by
+    /// the caller (see [`ApplicationConnections::into_typed`]).
+    pub fn add_application(
+        &mut self,
+        config: &NetworkApplicationConfig,
+        max_parallel_deserialization_tasks: Option<usize>,
+        allow_out_of_order_delivery: bool,
+    ) -> ApplicationConnections {
+        let (peer_mgr_reqs_tx, connection_reqs_tx) = self
+            .peer_manager_builder
+            .add_client(&config.client_config());
+        let service_config = config.service_config();
+        let peer_mgr_notifs_rx = self.peer_manager_builder.add_service(&service_config);
+        ApplicationConnections::new(
+            peer_mgr_reqs_tx,
+            connection_reqs_tx,
+            peer_mgr_notifs_rx,
+            &service_config,
+            max_parallel_deserialization_tasks,
+            allow_out_of_order_delivery,
+        )
+    }
+
+    /// Register a new client and service application with the network, where the
+    /// service receives the messages of each protocol on a separate events stream
+    /// (see [`PeerManagerBuilder::add_service_by_protocol`]). This lets applications
+    /// (e.g., consensus) process RPCs and direct-sends with different priorities
+    /// and concurrency, instead of interleaving them in a single stream.
+    pub fn add_client_and_service_by_protocol<
+        SenderT: NewNetworkSender,
+        EventsT: NewNetworkEvents,
+    >(
+        &mut self,
+        config: &NetworkApplicationConfig,
+        max_parallel_deserialization_tasks: Option<usize>,
+        allow_out_of_order_delivery: bool,
+    ) -> (SenderT, BTreeMap<ProtocolId, EventsT>) {
+        let (peer_mgr_reqs_tx, connection_reqs_tx) = self
+            .peer_manager_builder
+            .add_client(&config.client_config());
+        let service_config = config.service_config();
+        let network_events = self
+            .peer_manager_builder
+            .add_service_by_protocol(&service_config)
+            .into_iter()
+            .map(|(protocol_id, peer_mgr_notifs_rx)| {
+                let events = EventsT::new(
+                    peer_mgr_notifs_rx,
+                    max_parallel_deserialization_tasks,
+                    allow_out_of_order_delivery,
+                    service_config.inbound_dedup_window,
+                    service_config.offload_deserialization_protocols.clone(),
+                );
+                (protocol_id, events)
+            })
+            .collect();
         (
-            self.add_client(&config.network_client_config),
-            self.add_service(
-                &config.network_service_config,
+            SenderT::new(peer_mgr_reqs_tx, connection_reqs_tx),
+            network_events,
+        )
+    }
+
+    /// Register a new client and service application with a network that has
+    /// already been built (e.g., for an application that is enabled after boot).
+    /// The application's protocols are advertised on all subsequent handshakes,
+    /// but peers that are already connected only learn of them (and can only
+    /// exchange messages on them) once they reconnect.
+    ///
+    /// Returns an error (and registers nothing) if the application's protocols
+    /// would exceed the network's max registered protocols.
+    pub fn add_runtime_client_and_service<SenderT: NewNetworkSender, EventsT: NewNetworkEvents>(
+        &self,
+        config: &NetworkApplicationConfig,
+        max_parallel_deserialization_tasks: Option<usize>,
+        allow_out_of_order_delivery: bool,
+    ) -> Result<(SenderT, EventsT), PeerManagerError> {
+        Ok(self
+            .add_runtime_application(
+                config,
                 max_parallel_deserialization_tasks,
                 allow_out_of_order_delivery,
-            ),
-        )
+            )?
+            .into_typed())
     }
 
-    /// Register a new client application with the network. Return the client
-    /// interface for sending messages.
-    fn add_client<SenderT: NewNetworkSender>(&mut self, config: &NetworkClientConfig) -> SenderT {
-        let (peer_mgr_reqs_tx, connection_reqs_tx) = self.peer_manager_builder.add_client(config);
-        SenderT::new(peer_mgr_reqs_tx, connection_reqs_tx)
+    /// The non-generic counterpart of [`NetworkBuilder::add_runtime_client_and_service`].
+    pub fn add_runtime_application(
+        &self,
+        config: &NetworkApplicationConfig,
+        max_parallel_deserialization_tasks: Option<usize>,
+        allow_out_of_order_delivery: bool,
+    ) -> Result<ApplicationConnections, PeerManagerError> {
+        // Verify the client and service protocols (together) fit before registering either
+        self.verify_runtime_application(config)?;
+
+        let client_config = config.client_config();
+        let service_config = config.service_config();
+        let (peer_mgr_reqs_tx, connection_reqs_tx) = self
+            .peer_manager_builder
+            .add_runtime_client(&client_config)?;
+        let peer_mgr_notifs_rx = self
+            .peer_manager_builder
+            .add_runtime_service(&service_config)?;
+        Ok(ApplicationConnections::new(
+            peer_mgr_reqs_tx,
+            connection_reqs_tx,
+            peer_mgr_notifs_rx,
+            &service_config,
+            max_parallel_deserialization_tasks,
+            allow_out_of_order_delivery,
+        ))
     }
 
-    /// Register a new service application with the network. Return the service
-    /// interface for handling network requests.
-    // TODO(philiphayes): return new NetworkService (name TBD) interface?
-    fn add_service<EventsT: NewNetworkEvents>(
-        &mut self,
-        config: &NetworkServiceConfig,
+    /// Verifies that the given application can be registered at runtime (i.e., that its
+    /// protocols would not exceed the network's max registered protocols), without
+    /// registering it (see [`NetworkBuilder::add_runtime_client_and_service`]).
+    pub fn verify_runtime_application(
+        &self,
+        config: &NetworkApplicationConfig,
+    ) -> Result<(), PeerManagerError> {
+        assert_ne!(
+            self.state,
+            State::CREATED,
+            "The network must be built first"
+        );
+
+        let client_config = config.client_config();
+        let service_config = config.service_config();
+        let protocols: Vec<ProtocolId> = client_config
+            .direct_send_protocols_and_preferences
+            .into_iter()
+            .chain(client_config.rpc_protocols_and_preferences)
+            .chain(service_config.direct_send_protocols_and_preferences)
+            .chain(service_config.rpc_protocols_and_preferences)
+            .collect();
+        self.peer_manager_builder
+            .verify_runtime_protocols(&protocols)
+    }
+
+    /// Deregister an application that was registered at runtime (see
+    /// [`NetworkBuilder::add_runtime_client_and_service`]), e.g., when an optional
+    /// subsystem is disabled. The application's protocols are no longer advertised
+    /// on subsequent handshakes, but peers that are already connected keep using
+    /// them until they reconnect. Returns the deregistered protocols.
+    pub fn remove_runtime_application(&self, config: &NetworkApplicationConfig) -> Vec<ProtocolId> {
+        assert_ne!(
+            self.state,
+            State::CREATED,
+            "The network must be built first"
+        );
+
+        let client_config = config.client_config();
+        let service_config = config.service_config();
+        let mut protocols: Vec<ProtocolId> = client_config
+            .direct_send_protocols_and_preferences
+            .into_iter()
+            .chain(client_config.rpc_protocols_and_preferences)
+            .chain(service_config.direct_send_protocols_and_preferences)
+            .chain(service_config.rpc_protocols_and_preferences)
+            .collect();
+        protocols.sort_by_key(|protocol_id| *protocol_id as u8);
+        protocols.dedup();
+        self.peer_manager_builder
+            .remove_runtime_protocols(&protocols)
+    }
+}
+
+/// The type-erased channels of an application registered with the network (see
+/// [`NetworkBuilder::add_application`]). All messages are still raw
+/// [`ReceivedMessage`]s at this point; only [`ApplicationConnections::into_typed`]
+/// is generic over the application's sender and events types. This keeps the
+/// registration logic from being monomorphized once per application message type.
+pub struct ApplicationConnections {
+    peer_mgr_reqs_tx: PeerManagerRequestSender,
+    connection_reqs_tx: ConnectionRequestSender,
+    peer_mgr_notifs_rx: aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>,
+    max_parallel_deserialization_tasks: Option<usize>,
+    allow_out_of_order_delivery: bool,
+    inbound_dedup_window: Option<usize>,
+    offload_deserialization_protocols: Option<Vec<ProtocolId>>,
+}
+
+impl ApplicationConnections {
+    fn new(
+        peer_mgr_reqs_tx: PeerManagerRequestSender,
+        connection_reqs_tx: ConnectionRequestSender,
+        peer_mgr_notifs_rx: aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>,
+        service_config: &NetworkServiceConfig,
         max_parallel_deserialization_tasks: Option<usize>,
         allow_out_of_order_delivery: bool,
-    ) -> EventsT {
-        let peer_mgr_reqs_rx = self.peer_manager_builder.add_service(config);
-        EventsT::new(
-            peer_mgr_reqs_rx,
+    ) -> Self {
+        Self {
+            peer_mgr_reqs_tx,
+            connection_reqs_tx,
+            peer_mgr_notifs_rx,
             max_parallel_deserialization_tasks,
             allow_out_of_order_delivery,
+            inbound_dedup_window: service_config.inbound_dedup_window,
+            offload_deserialization_protocols: service_config
+                .offload_deserialization_protocols
+                .clone(),
+        }
+    }
+
+    /// Convert the connections into the typed client and service interfaces
+    /// of the application
+    pub fn into_typed<SenderT: NewNetworkSender, EventsT: NewNetworkEvents>(
+        self,
+    ) -> (SenderT, EventsT) {
+        (
+            SenderT::new(self.peer_mgr_reqs_tx, self.connection_reqs_tx),
+            EventsT::new(
+                self.peer_mgr_notifs_rx,
+                self.max_parallel_deserialization_tasks,
+                self.allow_out_of_order_delivery,
+                self.inbound_dedup_window,
+                self.offload_deserialization_protocols,
+            ),
         )
     }
 }
 
+/// Returns the names of the fields that differ between the current and new configs,
+/// but can't be applied to a live network (see [`NetworkBuilder::reload`]). The new
+/// config is destructured exhaustively, so that new fields must be classified here.
+fn unreloadable_changes(current: &NetworkConfig, new: &NetworkConfig) -> Vec<&'static str> {
+    macro_rules! changed_fields {
+        (ignored: [$($ignored:ident),*], compared: [$($field:ident),*]) => {{
+            let NetworkConfig { $($ignored: _,)* $($field,)* } = new;
+            let mut changed_fields = vec![];
+            $(
+                if *$field != current.$field {
+                    changed_fields.push(stringify!($field));
+                }
+            )*
+            changed_fields
+        }};
+    }
+
+    changed_fields!(
+        // The seeds are reloaded, and the listen address and network id are checked separately
+        ignored: [seeds, seed_addrs, listen_address, network_id],
+        compared: [
+            max_connection_delay_ms,
+            connection_backoff_base,
+            connectivity_check_interval_ms,
+            network_channel_size,
+            discovery_method,
+            discovery_methods,
+            identity,
+            mutual_authentication,
+            runtime_threads,
+            runtime_thread_nice_value,
+            inbound_rx_buffer_size_bytes,
+            inbound_tx_buffer_size_bytes,
+            outbound_rx_buffer_size_bytes,
+            outbound_tx_buffer_size_bytes,
+            max_frame_size,
+            enable_proxy_protocol,
+            ping_interval_ms,
+            ping_timeout_ms,
+            ping_failures_tolerated,
+            max_outbound_connections,
+            max_inbound_connections,
+            inbound_rate_limit_config,
+            outbound_rate_limit_config,
+            max_message_size,
+            max_parallel_deserialization_tasks,
+            enable_latency_aware_dialing,
+            idle_timeout_ms,
+            keep_alive_interval_ms,
+            outbound_drain_timeout_ms,
+            unknown_protocol_policy,
+            reconnect_backoff,
+            max_registered_protocols,
+            max_protocols_per_service,
+            outbound_protocol_rate_limits,
+            message_coalescing_window_ms,
+            max_inbound_buffered_bytes,
+            peer_scoring,
+            compression
+        ]
+    )
+}
+
 /// Retrieve and merge seeds so that they have all keys associated
 fn merge_seeds(config: &NetworkConfig) -> PeerSet {
     config.verify_seeds().expect("Seeds must be well formed");
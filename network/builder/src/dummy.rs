@@ -61,6 +61,7 @@ pub struct DummyNetwork {
     pub listener_peer: PeerNetworkId,
     pub listener_events: DummyNetworkEvents,
     pub listener_network_client: NetworkClient<DummyMsg>,
+    pub listener_network_builder: NetworkBuilder,
 }
 
 /// The following sets up a 2 peer network and verifies connectivity.
@@ -101,7 +102,7 @@ pub fn setup_network() -> DummyNetwork {
     let mut listener_connection_events = listener_peers_and_metadata.subscribe();
     // Set up the listener network
     let network_context = NetworkContext::new(role, network_id, listener_peer.peer_id());
-    let mut network_builder = NetworkBuilder::new_for_test(
+    let mut listener_network_builder = NetworkBuilder::new_for_test(
         chain_id,
         seeds.clone(),
         network_context,
@@ -111,9 +112,11 @@ pub fn setup_network() -> DummyNetwork {
         listener_peers_and_metadata.clone(),
     );
 
-    let (listener_sender, listener_events) = network_builder
+    let (listener_sender, listener_events) = listener_network_builder
         .add_client_and_service::<_, DummyNetworkEvents>(&dummy_network_config(), None, true);
-    network_builder.build(runtime.handle().clone()).start();
+    listener_network_builder
+        .build(runtime.handle().clone())
+        .start();
     let listener_network_client = NetworkClient::new(
         vec![TEST_DIRECT_SEND_PROTOCOL],
         vec![TEST_RPC_PROTOCOL],
@@ -122,7 +125,7 @@ pub fn setup_network() -> DummyNetwork {
     );
 
     // Add the listener address with port
-    let listener_addr = network_builder.listen_address();
+    let listener_addr = listener_network_builder.listen_address();
     seeds.insert(
         listener_peer.peer_id(),
         Peer::from_addrs(PeerRole::Validator, vec![listener_addr]),
@@ -189,5 +192,6 @@ pub fn setup_network() -> DummyNetwork {
         listener_peer,
         listener_events,
         listener_network_client,
+        listener_network_builder,
     }
 }
@@ -3,16 +3,627 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Integration tests for validator_network.
-use crate::dummy::{setup_network, DummyMsg};
-use aptos_network::{application::interface::NetworkClientInterface, protocols::network::Event};
-use futures::{future::join, StreamExt};
-use std::time::Duration;
+use crate::{
+    builder::{NetworkBuilder, NetworkReloadError},
+    dummy::{dummy_network_config, setup_network, DummyMsg, DummyNetworkEvents},
+};
+use aptos_channels::aptos_channel;
+use aptos_config::{
+    config::{
+        NetworkConfig, OutboundProtocolRateLimit, Peer, PeerRole, PeerSet, RateLimitConfig,
+        RoleType, NETWORK_CHANNEL_SIZE,
+    },
+    network_id::{NetworkContext, NetworkId},
+};
+use aptos_crypto::{x25519, Uniform};
+use aptos_network::{
+    application::{interface::NetworkClientInterface, storage::PeersAndMetadata},
+    peer_manager::{builder::AuthenticationMode, ConnectionNotification, PeerManagerError},
+    protocols::network::{
+        Event, NetworkApplicationConfig, NetworkClientConfig, NetworkEvents, NetworkSender,
+        NetworkServiceConfig, ProtocolRole,
+    },
+    ProtocolId,
+};
+use aptos_time_service::TimeService;
+use aptos_types::{
+    chain_id::ChainId,
+    network_address::{NetworkAddress, Protocol},
+    PeerId,
+};
+use futures::{executor::block_on, future::join, StreamExt};
+use maplit::hashset;
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Runtime;
 
 #[test]
 fn test_network_builder() {
     setup_network();
 }
 
+#[test]
+fn test_listen_addresses() {
+    // Create and enter a runtime
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create a network builder that listens on an ephemeral port
+    let network_id = NetworkId::Validator;
+    let network_context = NetworkContext::new(RoleType::Validator, network_id, PeerId::random());
+    let listen_address: NetworkAddress = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        PeerSet::new(),
+        network_context,
+        TimeService::real(),
+        listen_address.clone(),
+        AuthenticationMode::Mutual(x25519::PrivateKey::generate(&mut rng)),
+        PeersAndMetadata::new(&[network_id]),
+    );
+
+    // Before building, the configured address is returned
+    assert_eq!(network_builder.listen_addresses(), vec![listen_address]);
+
+    // Build the network and verify the bound address has a concrete port
+    network_builder.build(runtime.handle().clone());
+    let listen_addresses = network_builder.listen_addresses();
+    assert_eq!(listen_addresses.len(), 1);
+    match listen_addresses[0].as_slice() {
+        [Protocol::Ip4(_), Protocol::Tcp(port)] => assert_ne!(*port, 0),
+        protocols => panic!("Unexpected listen address protocols: {:?}", protocols),
+    }
+}
+
+#[test]
+fn test_listen_address_notifications() {
+    // Create and enter a runtime
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create a network builder that listens on an ephemeral port
+    let network_id = NetworkId::Validator;
+    let network_context = NetworkContext::new(RoleType::Validator, network_id, PeerId::random());
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        PeerSet::new(),
+        network_context,
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(x25519::PrivateKey::generate(&mut rng)),
+        PeersAndMetadata::new(&[network_id]),
+    );
+
+    // Subscribe to listen address changes, and verify there are none before building
+    let mut listen_address_changes = network_builder.subscribe_to_listen_addresses();
+    assert!(listen_address_changes.try_next().is_err());
+
+    // Build the network and verify the subscriber is notified of the bound address
+    network_builder.build(runtime.handle().clone());
+    let listen_addresses = listen_address_changes.try_next().unwrap().unwrap();
+    assert_eq!(listen_addresses, network_builder.listen_addresses());
+    match listen_addresses[0].as_slice() {
+        [Protocol::Ip4(_), Protocol::Tcp(port)] => assert_ne!(*port, 0),
+        protocols => panic!("Unexpected listen address protocols: {:?}", protocols),
+    }
+
+    // Verify there are no further notifications (as the addresses are unchanged)
+    network_builder.start();
+    assert!(listen_address_changes.try_next().is_err());
+}
+
+#[test]
+fn test_reload() {
+    // Create and enter a runtime
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create, build and start a network that listens on an ephemeral port
+    let network_id = NetworkId::Public;
+    let mut network_config = NetworkConfig::network_with_id(network_id);
+    network_config.listen_address = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+    let peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut network_builder = NetworkBuilder::create(
+        ChainId::default(),
+        RoleType::FullNode,
+        &network_config,
+        TimeService::real(),
+        None,
+        peers_and_metadata.clone(),
+    );
+    network_builder.build(runtime.handle().clone());
+    network_builder.start();
+
+    // Reload the network with a new seed peer
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let seed_peer_id = PeerId::random();
+    let seed_public_key = x25519::PrivateKey::generate(&mut rng).public_key();
+    let mut new_network_config = network_config.clone();
+    new_network_config.seeds.insert(
+        seed_peer_id,
+        Peer::new(vec![], hashset! {seed_public_key}, PeerRole::Upstream),
+    );
+    network_builder.reload(&new_network_config).unwrap();
+
+    // Verify the seed peer becomes trusted
+    runtime.block_on(async {
+        loop {
+            let trusted_peers = peers_and_metadata.get_trusted_peers(&network_id).unwrap();
+            if trusted_peers.contains_key(&seed_peer_id) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+
+    // Verify a new listen address is rejected
+    let mut new_network_config = network_config.clone();
+    new_network_config.listen_address = "/ip4/127.0.0.1/tcp/6180".parse().unwrap();
+    assert!(matches!(
+        network_builder.reload(&new_network_config),
+        Err(NetworkReloadError::ListenAddressChanged(..))
+    ));
+
+    // Verify changes to fields that require a restart are rejected (and listed)
+    let mut new_network_config = network_config.clone();
+    new_network_config.outbound_protocol_rate_limits.insert(
+        "MempoolDirectSend".into(),
+        OutboundProtocolRateLimit {
+            messages_per_second: 10,
+            burst_size: 10,
+        },
+    );
+    new_network_config.inbound_rate_limit_config = Some(RateLimitConfig::default());
+    new_network_config.max_outbound_connections += 1;
+    new_network_config.mutual_authentication = !network_config.mutual_authentication;
+    assert_eq!(
+        network_builder.reload(&new_network_config),
+        Err(NetworkReloadError::UnreloadableFieldsChanged(vec![
+            "mutual_authentication",
+            "max_outbound_connections",
+            "inbound_rate_limit_config",
+            "outbound_protocol_rate_limits",
+        ]))
+    );
+
+    // Verify a new network id is rejected
+    let mut new_network_config = network_config;
+    new_network_config.network_id = NetworkId::Vfn;
+    assert_eq!(
+        network_builder.reload(&new_network_config),
+        Err(NetworkReloadError::NetworkIdChanged(
+            NetworkId::Public,
+            NetworkId::Vfn
+        ))
+    );
+}
+
+#[test]
+fn test_runtime_application_protocol_cap() {
+    // Create and enter a runtime
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create, build and start a network that allows a single protocol beyond the
+    // health checker (which is registered at startup)
+    let network_id = NetworkId::Public;
+    let mut network_config = NetworkConfig::network_with_id(network_id);
+    network_config.listen_address = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+    network_config.max_registered_protocols = 2;
+    let mut network_builder = NetworkBuilder::create(
+        ChainId::default(),
+        RoleType::FullNode,
+        &network_config,
+        TimeService::real(),
+        None,
+        PeersAndMetadata::new(&[network_id]),
+    );
+    network_builder.build(runtime.handle().clone());
+    network_builder.start();
+
+    // Register an application at runtime that fits within the cap
+    let mempool_config = NetworkApplicationConfig::new(
+        NetworkClientConfig::new(vec![ProtocolId::MempoolDirectSend], vec![]),
+        NetworkServiceConfig::new(
+            vec![ProtocolId::MempoolDirectSend],
+            vec![],
+            aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+        ),
+    );
+    let _mempool_connections = network_builder
+        .add_runtime_application(&mempool_config, None, true)
+        .unwrap();
+
+    // Verify an application that exceeds the cap is rejected
+    let storage_config = NetworkApplicationConfig::new(
+        NetworkClientConfig::new(vec![], vec![ProtocolId::StorageServiceRpc]),
+        NetworkServiceConfig::new(
+            vec![],
+            vec![ProtocolId::StorageServiceRpc],
+            aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+        ),
+    );
+    assert!(matches!(
+        network_builder.add_runtime_application(&storage_config, None, true),
+        Err(PeerManagerError::TooManyProtocols(3, 2))
+    ));
+
+    // Verify nothing was registered for the rejected application
+    assert!(network_builder
+        .remove_runtime_application(&storage_config)
+        .is_empty());
+}
+
+#[test]
+fn test_client_only_protocol_role() {
+    // Create a network builder
+    let network_id = NetworkId::Validator;
+    let network_context = NetworkContext::new(RoleType::Validator, network_id, PeerId::random());
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        PeerSet::new(),
+        network_context,
+        TimeService::mock(),
+        "/memory/0".parse().unwrap(),
+        AuthenticationMode::Mutual(x25519::PrivateKey::generate(&mut rng)),
+        PeersAndMetadata::new(&[network_id]),
+    );
+
+    // Register an application with a client-only RPC protocol
+    let network_config = dummy_network_config()
+        .with_protocol_role(ProtocolId::ConsensusRpcBcs, ProtocolRole::ClientOnly);
+    let (_network_sender, _network_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        network_builder.add_client_and_service(&network_config, None, false);
+
+    // Verify the client-only protocol is not served (but is still advertised)
+    assert_eq!(network_builder.service_protocols(), vec![
+        ProtocolId::ConsensusDirectSendBcs
+    ]);
+    assert!(network_builder
+        .advertised_protocols()
+        .contains(&ProtocolId::ConsensusRpcBcs));
+}
+
+#[test]
+fn test_runtime_client_and_service() {
+    ::aptos_logger::Logger::init_for_testing();
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create the peer identities
+    let network_id = NetworkId::Validator;
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let listener_peer_id = PeerId::random();
+    let listener_key = x25519::PrivateKey::generate(&mut rng);
+    let dialer_peer_id = PeerId::random();
+    let dialer_key = x25519::PrivateKey::generate(&mut rng);
+
+    // Build and start the listener (which trusts the dialer)
+    let mut seeds = PeerSet::new();
+    seeds.insert(
+        dialer_peer_id,
+        Peer::new(
+            vec![],
+            hashset! {dialer_key.public_key()},
+            PeerRole::Validator,
+        ),
+    );
+    let listener_peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut listener_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds.clone(),
+        NetworkContext::new(RoleType::Validator, network_id, listener_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(listener_key),
+        listener_peers_and_metadata,
+    );
+    let (_listener_sender, _listener_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        listener_network_builder.add_client_and_service(&dummy_network_config(), None, true);
+    listener_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Register a new application with the listener at runtime
+    let runtime_protocol = ProtocolId::MempoolDirectSend;
+    let runtime_network_config = NetworkApplicationConfig::new(
+        NetworkClientConfig::new(vec![runtime_protocol], vec![]),
+        NetworkServiceConfig::new(
+            vec![runtime_protocol],
+            vec![],
+            aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+        ),
+    );
+    let (_runtime_sender, mut runtime_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        listener_network_builder
+            .add_runtime_client_and_service(&runtime_network_config, None, true)
+            .unwrap();
+
+    // Build and start the dialer (which has the application registered from the start)
+    seeds.insert(
+        listener_peer_id,
+        Peer::from_addrs(PeerRole::Validator, vec![
+            listener_network_builder.listen_address()
+        ]),
+    );
+    let dialer_peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut dialer_connection_events = dialer_peers_and_metadata.subscribe();
+    let mut dialer_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds,
+        NetworkContext::new(RoleType::Validator, network_id, dialer_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(dialer_key),
+        dialer_peers_and_metadata.clone(),
+    );
+    let (dialer_sender, _dialer_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        dialer_network_builder.add_client_and_service(&runtime_network_config, None, true);
+    dialer_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Wait for the connection, and verify the listener advertised the runtime protocol
+    match block_on(dialer_connection_events.recv()).unwrap() {
+        ConnectionNotification::NewPeer(metadata, _) => {
+            assert_eq!(metadata.remote_peer_id, listener_peer_id);
+            assert!(metadata.application_protocols.contains(runtime_protocol));
+        },
+        event => panic!("Unexpected connection event: {:?}", event),
+    }
+
+    // Send a message on the runtime protocol and verify the listener receives it
+    let message = DummyMsg(vec![1, 2, 3]);
+    dialer_sender
+        .send_to(listener_peer_id, runtime_protocol, message.clone())
+        .unwrap();
+    match block_on(runtime_events.next()).unwrap() {
+        Event::Message(peer_id, received_message) => {
+            assert_eq!(peer_id, dialer_peer_id);
+            assert_eq!(received_message, message);
+        },
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
+#[test]
+fn test_remove_runtime_application() {
+    ::aptos_logger::Logger::init_for_testing();
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create the peer identities
+    let network_id = NetworkId::Validator;
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let listener_peer_id = PeerId::random();
+    let listener_key = x25519::PrivateKey::generate(&mut rng);
+    let dialer_peer_id = PeerId::random();
+    let dialer_key = x25519::PrivateKey::generate(&mut rng);
+
+    // Build and start the listener (which trusts the dialer)
+    let mut seeds = PeerSet::new();
+    seeds.insert(
+        dialer_peer_id,
+        Peer::new(
+            vec![],
+            hashset! {dialer_key.public_key()},
+            PeerRole::Validator,
+        ),
+    );
+    let listener_peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut listener_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds.clone(),
+        NetworkContext::new(RoleType::Validator, network_id, listener_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(listener_key),
+        listener_peers_and_metadata,
+    );
+    let (_listener_sender, _listener_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        listener_network_builder.add_client_and_service(&dummy_network_config(), None, true);
+    listener_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Register a new application with the listener at runtime, and then deregister it
+    let runtime_protocol = ProtocolId::MempoolDirectSend;
+    let runtime_network_config = NetworkApplicationConfig::new(
+        NetworkClientConfig::new(vec![runtime_protocol], vec![]),
+        NetworkServiceConfig::new(
+            vec![runtime_protocol],
+            vec![],
+            aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+        ),
+    );
+    let (_runtime_sender, mut runtime_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        listener_network_builder
+            .add_runtime_client_and_service(&runtime_network_config, None, true)
+            .unwrap();
+    assert_eq!(
+        listener_network_builder.remove_runtime_application(&runtime_network_config),
+        vec![runtime_protocol]
+    );
+
+    // Verify the application's events terminate (no peers were connected)
+    assert!(block_on(runtime_events.next()).is_none());
+
+    // Verify deregistering again (or deregistering a boot-time application) is a no-op
+    assert!(listener_network_builder
+        .remove_runtime_application(&runtime_network_config)
+        .is_empty());
+    assert!(listener_network_builder
+        .remove_runtime_application(&dummy_network_config())
+        .is_empty());
+
+    // Build and start the dialer (which has the application registered from the start)
+    seeds.insert(
+        listener_peer_id,
+        Peer::from_addrs(PeerRole::Validator, vec![
+            listener_network_builder.listen_address()
+        ]),
+    );
+    let dialer_peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut dialer_connection_events = dialer_peers_and_metadata.subscribe();
+    let mut dialer_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds,
+        NetworkContext::new(RoleType::Validator, network_id, dialer_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(dialer_key),
+        dialer_peers_and_metadata.clone(),
+    );
+    let (_dialer_sender, _dialer_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        dialer_network_builder.add_client_and_service(&runtime_network_config, None, true);
+    dialer_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Wait for the connection, and verify the listener no longer advertises the protocol
+    match block_on(dialer_connection_events.recv()).unwrap() {
+        ConnectionNotification::NewPeer(metadata, _) => {
+            assert_eq!(metadata.remote_peer_id, listener_peer_id);
+            assert!(!metadata.application_protocols.contains(runtime_protocol));
+        },
+        event => panic!("Unexpected connection event: {:?}", event),
+    }
+}
+
+#[test]
+fn test_type_erased_applications() {
+    ::aptos_logger::Logger::init_for_testing();
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create the peer identities
+    let network_id = NetworkId::Validator;
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let listener_peer_id = PeerId::random();
+    let listener_key = x25519::PrivateKey::generate(&mut rng);
+    let dialer_peer_id = PeerId::random();
+    let dialer_key = x25519::PrivateKey::generate(&mut rng);
+
+    // Create two applications, each on its own direct send protocol
+    let dummy_protocol = ProtocolId::ConsensusDirectSendBcs;
+    let string_protocol = ProtocolId::MempoolDirectSend;
+    let application_config = |protocol_id| {
+        NetworkApplicationConfig::new(
+            NetworkClientConfig::new(vec![protocol_id], vec![]),
+            NetworkServiceConfig::new(
+                vec![protocol_id],
+                vec![],
+                aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+            ),
+        )
+    };
+
+    // Build and start the listener, registering both applications via the type-erased path
+    let mut seeds = PeerSet::new();
+    seeds.insert(
+        dialer_peer_id,
+        Peer::new(
+            vec![],
+            hashset! {dialer_key.public_key()},
+            PeerRole::Validator,
+        ),
+    );
+    let mut listener_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds.clone(),
+        NetworkContext::new(RoleType::Validator, network_id, listener_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(listener_key),
+        PeersAndMetadata::new(&[network_id]),
+    );
+    let (_listener_dummy_sender, mut listener_dummy_events): (
+        NetworkSender<DummyMsg>,
+        DummyNetworkEvents,
+    ) = listener_network_builder
+        .add_application(&application_config(dummy_protocol), None, true)
+        .into_typed();
+    let (_listener_string_sender, mut listener_string_events): (
+        NetworkSender<String>,
+        NetworkEvents<String>,
+    ) = listener_network_builder
+        .add_application(&application_config(string_protocol), None, true)
+        .into_typed();
+    listener_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Build and start the dialer, registering both applications via the type-erased path
+    seeds.insert(
+        listener_peer_id,
+        Peer::from_addrs(PeerRole::Validator, vec![
+            listener_network_builder.listen_address()
+        ]),
+    );
+    let dialer_peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut dialer_connection_events = dialer_peers_and_metadata.subscribe();
+    let mut dialer_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds,
+        NetworkContext::new(RoleType::Validator, network_id, dialer_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(dialer_key),
+        dialer_peers_and_metadata,
+    );
+    let (dialer_dummy_sender, _dialer_dummy_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        dialer_network_builder
+            .add_application(&application_config(dummy_protocol), None, true)
+            .into_typed();
+    let (dialer_string_sender, _dialer_string_events): (
+        NetworkSender<String>,
+        NetworkEvents<String>,
+    ) = dialer_network_builder
+        .add_application(&application_config(string_protocol), None, true)
+        .into_typed();
+    dialer_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Wait for the connection
+    match block_on(dialer_connection_events.recv()).unwrap() {
+        ConnectionNotification::NewPeer(metadata, _) => {
+            assert_eq!(metadata.remote_peer_id, listener_peer_id);
+        },
+        event => panic!("Unexpected connection event: {:?}", event),
+    }
+
+    // Send a message for each application and verify each is delivered with its own type
+    let dummy_message = DummyMsg(vec![1, 2, 3]);
+    dialer_dummy_sender
+        .send_to(listener_peer_id, dummy_protocol, dummy_message.clone())
+        .unwrap();
+    let string_message = "hello".to_string();
+    dialer_string_sender
+        .send_to(listener_peer_id, string_protocol, string_message.clone())
+        .unwrap();
+    match block_on(listener_dummy_events.next()).unwrap() {
+        Event::Message(peer_id, received_message) => {
+            assert_eq!(peer_id, dialer_peer_id);
+            assert_eq!(received_message, dummy_message);
+        },
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    match block_on(listener_string_events.next()).unwrap() {
+        Event::Message(peer_id, received_message) => {
+            assert_eq!(peer_id, dialer_peer_id);
+            assert_eq!(received_message, string_message);
+        },
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
 #[test]
 fn test_direct_send() {
     ::aptos_logger::Logger::init_for_testing();
@@ -58,6 +669,90 @@ fn test_direct_send() {
     tn.runtime.block_on(join(f_dialer, f_listener));
 }
 
+#[test]
+fn test_quiesce() {
+    ::aptos_logger::Logger::init_for_testing();
+    let tn = setup_network();
+    let dialer_peer = tn.dialer_peer;
+    let dialer_network_client = tn.dialer_network_client;
+    let listener_peer = tn.listener_peer;
+    let mut listener_events = tn.listener_events;
+    let listener_sender = tn.listener_network_client;
+    let listener_network_builder = tn.listener_network_builder;
+
+    // Quiesce the listener, and verify outbound sends are rejected while
+    // inbound messages are still delivered during the grace period.
+    let msg = DummyMsg(vec![]);
+    tn.runtime.block_on(async {
+        let f_quiesce = listener_network_builder.quiesce(Duration::from_secs(1));
+        let f_check = async {
+            assert!(listener_network_builder.is_quiesced());
+            assert!(listener_sender
+                .send_to_peer(msg.clone(), dialer_peer)
+                .is_err());
+            assert!(listener_sender
+                .send_to_peer_rpc(msg.clone(), Duration::from_secs(10), dialer_peer)
+                .await
+                .is_err());
+
+            dialer_network_client
+                .send_to_peer(msg.clone(), listener_peer)
+                .unwrap();
+            match listener_events.next().await.unwrap() {
+                Event::Message(peer_id, incoming_msg) => {
+                    assert_eq!(peer_id, dialer_peer.peer_id());
+                    assert_eq!(incoming_msg, msg);
+                },
+                event => panic!("Unexpected event {:?}", event),
+            }
+        };
+
+        // The quiesce future is polled first, so the flag is set before the checks run
+        join(f_quiesce, f_check).await
+    });
+}
+
+#[test]
+fn test_quiesce_waits_for_inflight_rpcs() {
+    ::aptos_logger::Logger::init_for_testing();
+    let tn = setup_network();
+    let dialer_peer = tn.dialer_peer;
+    let mut dialer_events = tn.dialer_events;
+    let listener_sender = tn.listener_network_client;
+    let listener_network_builder = tn.listener_network_builder;
+
+    // Send an rpc from the listener, and quiesce the listener while the rpc is in flight
+    let msg = DummyMsg(vec![]);
+    let grace = Duration::from_secs(30);
+    tn.runtime.block_on(async {
+        let f_send = listener_sender.send_to_peer_rpc(msg.clone(), grace * 2, dialer_peer);
+        let f_quiesce = async {
+            let response_sender = match dialer_events.next().await.unwrap() {
+                Event::RpcRequest(_, _, _, response_sender) => response_sender,
+                event => panic!("Unexpected event: {:?}", event),
+            };
+
+            // Respond to the rpc once quiesced, and verify the quiesce returns early
+            let start_time = Instant::now();
+            let f_respond = async {
+                response_sender
+                    .send(Ok(bcs::to_bytes(&msg).unwrap().into()))
+                    .unwrap();
+            };
+            join(listener_network_builder.quiesce(grace), f_respond).await;
+            assert!(start_time.elapsed() < grace);
+        };
+
+        // Verify the in-flight rpc completed (even though new sends are now rejected)
+        let (response, _) = join(f_send, f_quiesce).await;
+        assert_eq!(response.unwrap(), msg);
+        assert!(listener_sender
+            .send_to_peer_rpc(msg.clone(), grace, dialer_peer)
+            .await
+            .is_err());
+    });
+}
+
 #[test]
 fn test_rpc() {
     ::aptos_logger::Logger::init_for_testing();
@@ -107,3 +802,128 @@ fn test_rpc() {
     let (res_msg, _) = tn.runtime.block_on(join(f_send, f_respond));
     assert_eq!(res_msg.unwrap(), msg);
 }
+
+#[test]
+fn test_service_by_protocol() {
+    ::aptos_logger::Logger::init_for_testing();
+    let runtime = Runtime::new().unwrap();
+    let _entered_runtime = runtime.enter();
+
+    // Create the peer identities
+    let network_id = NetworkId::Validator;
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let listener_peer_id = PeerId::random();
+    let listener_key = x25519::PrivateKey::generate(&mut rng);
+    let dialer_peer_id = PeerId::random();
+    let dialer_key = x25519::PrivateKey::generate(&mut rng);
+
+    // Create an application with two direct send protocols
+    let consensus_protocol = ProtocolId::ConsensusDirectSendBcs;
+    let mempool_protocol = ProtocolId::MempoolDirectSend;
+    let application_config = NetworkApplicationConfig::new(
+        NetworkClientConfig::new(vec![mempool_protocol, consensus_protocol], vec![]),
+        NetworkServiceConfig::new(
+            vec![mempool_protocol, consensus_protocol],
+            vec![],
+            aptos_channel::Config::new(NETWORK_CHANNEL_SIZE),
+        ),
+    );
+
+    // Build and start the listener, registering the service with a stream per protocol
+    let mut seeds = PeerSet::new();
+    seeds.insert(
+        dialer_peer_id,
+        Peer::new(
+            vec![],
+            hashset! {dialer_key.public_key()},
+            PeerRole::Validator,
+        ),
+    );
+    let mut listener_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds.clone(),
+        NetworkContext::new(RoleType::Validator, network_id, listener_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(listener_key),
+        PeersAndMetadata::new(&[network_id]),
+    );
+    let (_listener_sender, mut listener_events): (
+        NetworkSender<DummyMsg>,
+        BTreeMap<ProtocolId, DummyNetworkEvents>,
+    ) = listener_network_builder.add_client_and_service_by_protocol(
+        &application_config,
+        None,
+        true,
+    );
+    assert_eq!(listener_events.keys().copied().collect::<Vec<_>>(), vec![
+        consensus_protocol,
+        mempool_protocol
+    ]);
+    assert_eq!(listener_network_builder.service_protocols(), vec![
+        consensus_protocol,
+        mempool_protocol
+    ]);
+    listener_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Build and start the dialer (which registers the service as usual)
+    seeds.insert(
+        listener_peer_id,
+        Peer::from_addrs(PeerRole::Validator, vec![
+            listener_network_builder.listen_address()
+        ]),
+    );
+    let dialer_peers_and_metadata = PeersAndMetadata::new(&[network_id]);
+    let mut dialer_connection_events = dialer_peers_and_metadata.subscribe();
+    let mut dialer_network_builder = NetworkBuilder::new_for_test(
+        ChainId::default(),
+        seeds,
+        NetworkContext::new(RoleType::Validator, network_id, dialer_peer_id),
+        TimeService::real(),
+        "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+        AuthenticationMode::Mutual(dialer_key),
+        dialer_peers_and_metadata,
+    );
+    let (dialer_sender, _dialer_events): (NetworkSender<DummyMsg>, DummyNetworkEvents) =
+        dialer_network_builder.add_client_and_service(&application_config, None, true);
+    dialer_network_builder
+        .build(runtime.handle().clone())
+        .start();
+
+    // Wait for the connection
+    match block_on(dialer_connection_events.recv()).unwrap() {
+        ConnectionNotification::NewPeer(metadata, _) => {
+            assert_eq!(metadata.remote_peer_id, listener_peer_id);
+        },
+        event => panic!("Unexpected connection event: {:?}", event),
+    }
+
+    // Send a message on each protocol, and verify each is delivered on its own stream
+    let mempool_message = DummyMsg(vec![1]);
+    dialer_sender
+        .send_to(listener_peer_id, mempool_protocol, mempool_message.clone())
+        .unwrap();
+    let consensus_message = DummyMsg(vec![2]);
+    dialer_sender
+        .send_to(
+            listener_peer_id,
+            consensus_protocol,
+            consensus_message.clone(),
+        )
+        .unwrap();
+    for (protocol_id, expected_message) in [
+        (consensus_protocol, consensus_message),
+        (mempool_protocol, mempool_message),
+    ] {
+        let network_events = listener_events.get_mut(&protocol_id).unwrap();
+        match block_on(network_events.next()).unwrap() {
+            Event::Message(peer_id, received_message) => {
+                assert_eq!(peer_id, dialer_peer_id);
+                assert_eq!(received_message, expected_message);
+            },
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+}
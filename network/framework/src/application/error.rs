@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{error::NetworkError, protocols::network::RpcError};
+use aptos_config::network_id::NetworkId;
+use aptos_types::account_address::AccountAddress;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -13,6 +15,10 @@ pub enum Error {
     RpcError(String),
     #[error("Unexpected error encountered: {0}")]
     UnexpectedError(String),
+    #[error("Account is not connected on any network (with the required protocol): {0}")]
+    AccountNotConnected(AccountAddress),
+    #[error("Network is not configured for this client: {0}")]
+    UnknownNetwork(NetworkId),
 }
 
 impl From<anyhow::Error> for Error {
@@ -3,19 +3,26 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    application::{error::Error, storage::PeersAndMetadata},
+    application::{error::Error, scoring::PeerScoreEvent, storage::PeersAndMetadata},
     protocols::{
         network::{Message, NetworkEvents, NetworkSender},
+        rpc::error::RpcError,
         wire::handshake::v1::{ProtocolId, ProtocolIdSet},
     },
 };
 use aptos_config::network_id::{NetworkId, PeerNetworkId};
+use aptos_crypto::HashValue;
 use aptos_logger::{prelude::*, sample, sample::SampleRate};
-use aptos_types::{network_address::NetworkAddress, PeerId};
+use aptos_types::{account_address::AccountAddress, network_address::NetworkAddress, PeerId};
 use async_trait::async_trait;
 use bytes::Bytes;
 use itertools::Itertools;
-use std::{collections::HashMap, fmt::Debug, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// A simple definition to handle all the trait bounds for messages.
 // TODO: we should remove the duplication across the different files
@@ -53,6 +60,17 @@ pub trait NetworkClientInterface<Message: NetworkMessageTrait>: Clone + Send + S
     /// method does not guarantee message delivery or handle responses.
     fn send_to_peer(&self, _message: Message, _peer: PeerNetworkId) -> Result<(), Error>;
 
+    /// Sends the given message to the specified peer, sharing the serialized
+    /// bytes with recent messages sent with the same coalescing key (see
+    /// `NetworkSender::send_to_coalesced()`). Messages with the same key must
+    /// be identical. Note: this method does not guarantee message delivery.
+    fn send_to_peer_coalesced(
+        &self,
+        _message: Message,
+        _peer: PeerNetworkId,
+        _coalescing_key: HashValue,
+    ) -> Result<(), Error>;
+
     /// Sends the given message bytes to the specified peer. Note: this
     /// method does not guarantee message delivery or handle responses.
     fn send_to_peer_raw(&self, _message: Bytes, _peer: PeerNetworkId) -> Result<(), Error>;
@@ -89,14 +107,33 @@ pub trait NetworkClientInterface<Message: NetworkMessageTrait>: Clone + Send + S
 
 /// A network component that can be used by client applications (e.g., consensus,
 /// state sync and mempool, etc.) to interact with the network and other peers.
-#[derive(Clone, Debug)]
+///
+/// Clones of a client are cheap handles that share the same network senders
+/// (and the underlying peer manager channels) and peers and metadata container.
+/// Cloning a client does not register anything new with the network, so
+/// multiple application tasks (e.g., separate send and receive tasks) can
+/// each hold their own handle.
+#[derive(Debug)]
 pub struct NetworkClient<Message> {
     direct_send_protocols_and_preferences: Vec<ProtocolId>, // Protocols are sorted by preference (highest to lowest)
     rpc_protocols_and_preferences: Vec<ProtocolId>, // Protocols are sorted by preference (highest to lowest)
-    network_senders: HashMap<NetworkId, NetworkSender<Message>>,
+    network_senders: Arc<HashMap<NetworkId, NetworkSender<Message>>>,
     peers_and_metadata: Arc<PeersAndMetadata>,
 }
 
+impl<Message> Clone for NetworkClient<Message> {
+    fn clone(&self) -> Self {
+        Self {
+            direct_send_protocols_and_preferences: self
+                .direct_send_protocols_and_preferences
+                .clone(),
+            rpc_protocols_and_preferences: self.rpc_protocols_and_preferences.clone(),
+            network_senders: self.network_senders.clone(),
+            peers_and_metadata: self.peers_and_metadata.clone(),
+        }
+    }
+}
+
 impl<Message: NetworkMessageTrait + Clone> NetworkClient<Message> {
     pub fn new(
         direct_send_protocols_and_preferences: Vec<ProtocolId>,
@@ -107,7 +144,7 @@ impl<Message: NetworkMessageTrait + Clone> NetworkClient<Message> {
         Self {
             direct_send_protocols_and_preferences,
             rpc_protocols_and_preferences,
-            network_senders,
+            network_senders: Arc::new(network_senders),
             peers_and_metadata,
         }
     }
@@ -117,20 +154,66 @@ impl<Message: NetworkMessageTrait + Clone> NetworkClient<Message> {
         &self,
         network_id: &NetworkId,
     ) -> Result<&NetworkSender<Message>, Error> {
-        self.network_senders.get(network_id).ok_or_else(|| {
-            Error::UnexpectedError(format!(
-                "Unknown network ID specified for sender: {:?}",
-                network_id
-            ))
-        })
+        self.network_senders
+            .get(network_id)
+            .ok_or(Error::UnknownNetwork(*network_id))
+    }
+
+    /// Reports the result of an RPC (sent at the given time) to the peer's score.
+    /// Only remote failures are penalized, as local errors (e.g., the peer isn't
+    /// connected or the outbound queue is full) say nothing about the peer.
+    fn report_rpc_result<T>(
+        &self,
+        peer: PeerNetworkId,
+        start_time: Instant,
+        result: &Result<T, RpcError>,
+    ) {
+        let event = match result {
+            Ok(_) => PeerScoreEvent::RpcSuccess(start_time.elapsed()),
+            Err(error) if error.is_remote_failure() => PeerScoreEvent::RpcFailure,
+            Err(_) => return,
+        };
+        self.peers_and_metadata.report_peer_event(peer, event);
     }
 
-    /// Identify the supported protocols from the specified peer's connection
-    fn get_supported_protocols(&self, peer: &PeerNetworkId) -> Result<ProtocolIdSet, Error> {
-        let peers_and_metadata = self.get_peers_and_metadata();
-        peers_and_metadata
-            .get_metadata_for_peer(*peer)
-            .map(|peer_metadata| peer_metadata.get_supported_protocols())
+    /// Resolves the given account to a connected peer that supports the specified
+    /// protocol. The validator network is preferred (as validators are identified
+    /// by their account address there), followed by the VFN and public networks.
+    pub fn resolve_account(
+        &self,
+        account: AccountAddress,
+        protocol_id: ProtocolId,
+    ) -> Result<PeerNetworkId, Error> {
+        for network_id in [NetworkId::Validator, NetworkId::Vfn, NetworkId::Public] {
+            if !self.network_senders.contains_key(&network_id) {
+                continue; // The client is not registered on this network
+            }
+
+            let peer_network_id = PeerNetworkId::new(network_id, account);
+            if let Ok(peer_metadata) = self
+                .peers_and_metadata
+                .get_metadata_for_peer(peer_network_id)
+            {
+                if peer_metadata.is_connected() && peer_metadata.supports_protocol(protocol_id) {
+                    return Ok(peer_network_id);
+                }
+            }
+        }
+        Err(Error::AccountNotConnected(account))
+    }
+
+    /// Sends the given message to the peer with the specified account address,
+    /// regardless of the network it is connected on (see `resolve_account()`).
+    /// Note: this method does not guarantee message delivery or handle responses.
+    pub fn send_to_account(
+        &self,
+        account: AccountAddress,
+        protocol_id: ProtocolId,
+        message: Message,
+    ) -> Result<(), Error> {
+        let peer = self.resolve_account(account, protocol_id)?;
+        let network_sender = self.get_sender_for_network_id(&peer.network_id())?;
+        Ok(network_sender.send_to(peer.peer_id(), protocol_id, message)?)
     }
 
     /// Selects the preferred protocol for the specified peer. The preferred protocols
@@ -140,17 +223,7 @@ impl<Message: NetworkMessageTrait + Clone> NetworkClient<Message> {
         peer: &PeerNetworkId,
         preferred_protocols: &[ProtocolId],
     ) -> Result<ProtocolId, Error> {
-        let protocols_supported_by_peer = self.get_supported_protocols(peer)?;
-        for protocol in preferred_protocols {
-            if protocols_supported_by_peer.contains(*protocol) {
-                return Ok(*protocol);
-            }
-        }
-        Err(Error::NetworkError(format!(
-            "None of the preferred protocols are supported by this peer! \
-            Peer: {:?}, supported protocols: {:?}",
-            peer, protocols_supported_by_peer
-        )))
+        get_preferred_protocol_for_peer(&self.peers_and_metadata, peer, preferred_protocols)
     }
 
     fn group_peers_by_protocol(
@@ -187,6 +260,36 @@ impl<Message: NetworkMessageTrait + Clone> NetworkClient<Message> {
     }
 }
 
+/// Identify the supported protocols from the specified peer's connection
+fn get_supported_protocols(
+    peers_and_metadata: &PeersAndMetadata,
+    peer: &PeerNetworkId,
+) -> Result<ProtocolIdSet, Error> {
+    peers_and_metadata
+        .get_metadata_for_peer(*peer)
+        .map(|peer_metadata| peer_metadata.get_supported_protocols())
+}
+
+/// Selects the preferred protocol for the specified peer. The preferred protocols
+/// should be sorted from most to least preferable.
+pub(crate) fn get_preferred_protocol_for_peer(
+    peers_and_metadata: &PeersAndMetadata,
+    peer: &PeerNetworkId,
+    preferred_protocols: &[ProtocolId],
+) -> Result<ProtocolId, Error> {
+    let protocols_supported_by_peer = get_supported_protocols(peers_and_metadata, peer)?;
+    for protocol in preferred_protocols {
+        if protocols_supported_by_peer.contains(*protocol) {
+            return Ok(*protocol);
+        }
+    }
+    Err(Error::NetworkError(format!(
+        "None of the preferred protocols are supported by this peer! \
+        Peer: {:?}, supported protocols: {:?}",
+        peer, protocols_supported_by_peer
+    )))
+}
+
 #[async_trait]
 impl<Message: NetworkMessageTrait> NetworkClientInterface<Message> for NetworkClient<Message> {
     async fn add_peers_to_discovery(
@@ -223,6 +326,23 @@ impl<Message: NetworkMessageTrait> NetworkClientInterface<Message> for NetworkCl
         Ok(network_sender.send_to(peer.peer_id(), direct_send_protocol_id, message)?)
     }
 
+    fn send_to_peer_coalesced(
+        &self,
+        message: Message,
+        peer: PeerNetworkId,
+        coalescing_key: HashValue,
+    ) -> Result<(), Error> {
+        let network_sender = self.get_sender_for_network_id(&peer.network_id())?;
+        let direct_send_protocol_id = self
+            .get_preferred_protocol_for_peer(&peer, &self.direct_send_protocols_and_preferences)?;
+        Ok(network_sender.send_to_coalesced(
+            peer.peer_id(),
+            direct_send_protocol_id,
+            message,
+            coalescing_key,
+        )?)
+    }
+
     fn send_to_peer_raw(&self, message: Bytes, peer: PeerNetworkId) -> Result<(), Error> {
         let network_sender = self.get_sender_for_network_id(&peer.network_id())?;
         let direct_send_protocol_id = self
@@ -231,6 +351,11 @@ impl<Message: NetworkMessageTrait> NetworkClientInterface<Message> for NetworkCl
     }
 
     fn send_to_peers(&self, message: Message, peers: Vec<PeerNetworkId>) -> Result<(), Error> {
+        // Verify all peers are on configured networks (otherwise, they'd be silently skipped)
+        for peer in &peers {
+            self.get_sender_for_network_id(&peer.network_id())?;
+        }
+
         let peers_per_protocol = self.group_peers_by_protocol(peers);
 
         // Send to all peers in each protocol group and network
@@ -256,9 +381,12 @@ impl<Message: NetworkMessageTrait> NetworkClientInterface<Message> for NetworkCl
         let network_sender = self.get_sender_for_network_id(&peer.network_id())?;
         let rpc_protocol_id =
             self.get_preferred_protocol_for_peer(&peer, &self.rpc_protocols_and_preferences)?;
-        Ok(network_sender
+        let start_time = Instant::now();
+        let result = network_sender
             .send_rpc(peer.peer_id(), rpc_protocol_id, message, rpc_timeout)
-            .await?)
+            .await;
+        self.report_rpc_result(peer, start_time, &result);
+        Ok(result?)
     }
 
     async fn send_to_peer_rpc_raw(
@@ -270,9 +398,12 @@ impl<Message: NetworkMessageTrait> NetworkClientInterface<Message> for NetworkCl
         let network_sender = self.get_sender_for_network_id(&peer.network_id())?;
         let rpc_protocol_id =
             self.get_preferred_protocol_for_peer(&peer, &self.rpc_protocols_and_preferences)?;
-        Ok(network_sender
+        let start_time = Instant::now();
+        let result = network_sender
             .send_rpc_raw(peer.peer_id(), rpc_protocol_id, message, rpc_timeout)
-            .await?)
+            .await;
+        self.report_rpc_result(peer, start_time, &result);
+        Ok(result?)
     }
 
     fn to_bytes_by_protocol(
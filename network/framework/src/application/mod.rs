@@ -5,6 +5,8 @@
 pub mod error;
 pub mod interface;
 pub mod metadata;
+pub mod peer_set;
+pub mod scoring;
 pub mod storage;
 
 #[cfg(test)]
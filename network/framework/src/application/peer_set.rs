@@ -0,0 +1,123 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    application::{error::Error, storage::PeersAndMetadata},
+    ProtocolId,
+};
+use aptos_config::network_id::{NetworkId, PeerNetworkId};
+use std::collections::{hash_set, HashSet};
+
+/// A set of peers (possibly spanning multiple networks) that can be used as
+/// the target of a broadcast (e.g., `NetworkClientInterface::send_to_peers`).
+/// Sets can be built from the current connection state in `PeersAndMetadata`
+/// and combined using the standard set operations.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PeerSet {
+    peers: HashSet<PeerNetworkId>,
+}
+
+impl PeerSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the set of all peers currently connected on the given network
+    pub fn all_connected(
+        peers_and_metadata: &PeersAndMetadata,
+        network_id: NetworkId,
+    ) -> Result<Self, Error> {
+        let connected_peers = peers_and_metadata.get_connected_peers_and_metadata()?;
+        Ok(connected_peers
+            .into_keys()
+            .filter(|peer_network_id| peer_network_id.network_id() == network_id)
+            .collect())
+    }
+
+    /// Returns the set of all connected peers (on any network) that support the given protocol
+    pub fn supporting(
+        peers_and_metadata: &PeersAndMetadata,
+        protocol_id: ProtocolId,
+    ) -> Result<Self, Error> {
+        let supported_peers = peers_and_metadata.get_connected_supported_peers(&[protocol_id])?;
+        Ok(supported_peers.into_iter().collect())
+    }
+
+    /// Adds the peer to the set. Returns true iff the peer was not already present.
+    pub fn insert(&mut self, peer_network_id: PeerNetworkId) -> bool {
+        self.peers.insert(peer_network_id)
+    }
+
+    /// Removes the peer from the set. Returns true iff the peer was present.
+    pub fn remove(&mut self, peer_network_id: &PeerNetworkId) -> bool {
+        self.peers.remove(peer_network_id)
+    }
+
+    pub fn contains(&self, peer_network_id: &PeerNetworkId) -> bool {
+        self.peers.contains(peer_network_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn iter(&self) -> hash_set::Iter<'_, PeerNetworkId> {
+        self.peers.iter()
+    }
+
+    /// Returns the peers that are in either set
+    pub fn union(&self, other: &PeerSet) -> PeerSet {
+        self.peers.union(&other.peers).copied().collect()
+    }
+
+    /// Returns the peers that are in both sets
+    pub fn intersection(&self, other: &PeerSet) -> PeerSet {
+        self.peers.intersection(&other.peers).copied().collect()
+    }
+
+    /// Returns the peers that are in this set, but not in the other
+    pub fn difference(&self, other: &PeerSet) -> PeerSet {
+        self.peers.difference(&other.peers).copied().collect()
+    }
+
+    /// Returns the peers that satisfy the given predicate
+    pub fn filter(&self, predicate: impl Fn(&PeerNetworkId) -> bool) -> PeerSet {
+        self.peers
+            .iter()
+            .filter(|peer_network_id| predicate(peer_network_id))
+            .copied()
+            .collect()
+    }
+
+    /// Returns the peers as a vector (e.g., to feed into a broadcast)
+    pub fn into_vec(self) -> Vec<PeerNetworkId> {
+        self.peers.into_iter().collect()
+    }
+}
+
+impl FromIterator<PeerNetworkId> for PeerSet {
+    fn from_iter<I: IntoIterator<Item = PeerNetworkId>>(iter: I) -> Self {
+        Self {
+            peers: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for PeerSet {
+    type IntoIter = hash_set::IntoIter<PeerNetworkId>;
+    type Item = PeerNetworkId;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.peers.into_iter()
+    }
+}
+
+impl From<PeerSet> for Vec<PeerNetworkId> {
+    fn from(peer_set: PeerSet) -> Self {
+        peer_set.into_vec()
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_config::config::PeerScoringConfig;
+use std::time::Duration;
+
+/// The weight of each new RPC latency sample in the average RPC latency of a peer
+const RPC_LATENCY_SAMPLE_WEIGHT: f64 = 0.125;
+
+/// An event (reported by the network or an application) that affects the score of a peer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PeerScoreEvent {
+    /// An RPC to the peer succeeded (with the given latency)
+    RpcSuccess(Duration),
+    /// An RPC to the peer failed (e.g., it timed out)
+    RpcFailure,
+    /// The peer sent a malformed message (e.g., that failed to deserialize)
+    MalformedMessage,
+    /// A handshake with the peer failed
+    HandshakeError,
+}
+
+/// The score of a peer, along with the events that it is based on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeerScore {
+    pub score: u64,
+    pub num_rpc_successes: u64,
+    pub num_rpc_failures: u64,
+    pub num_malformed_messages: u64,
+    pub num_handshake_errors: u64,
+    pub average_rpc_latency_secs: Option<f64>, // A moving average of the successful RPCs
+}
+
+impl PeerScore {
+    /// Creates the score of a peer without any reported events
+    pub fn new(scoring_config: &PeerScoringConfig) -> Self {
+        Self {
+            score: scoring_config.max_score,
+            num_rpc_successes: 0,
+            num_rpc_failures: 0,
+            num_malformed_messages: 0,
+            num_handshake_errors: 0,
+            average_rpc_latency_secs: None,
+        }
+    }
+
+    /// Updates the score with the given event
+    pub fn record_event(&mut self, event: PeerScoreEvent, scoring_config: &PeerScoringConfig) {
+        match event {
+            PeerScoreEvent::RpcSuccess(latency) => {
+                self.num_rpc_successes += 1;
+                let latency_secs = latency.as_secs_f64();
+                self.average_rpc_latency_secs = Some(match self.average_rpc_latency_secs {
+                    Some(average_latency_secs) => {
+                        average_latency_secs
+                            + RPC_LATENCY_SAMPLE_WEIGHT * (latency_secs - average_latency_secs)
+                    },
+                    None => latency_secs,
+                });
+                self.score = self
+                    .score
+                    .saturating_add(scoring_config.rpc_success_reward)
+                    .min(scoring_config.max_score);
+            },
+            PeerScoreEvent::RpcFailure => {
+                self.num_rpc_failures += 1;
+                self.apply_penalty(scoring_config.rpc_failure_penalty);
+            },
+            PeerScoreEvent::MalformedMessage => {
+                self.num_malformed_messages += 1;
+                self.apply_penalty(scoring_config.malformed_message_penalty);
+            },
+            PeerScoreEvent::HandshakeError => {
+                self.num_handshake_errors += 1;
+                self.apply_penalty(scoring_config.handshake_error_penalty);
+            },
+        }
+    }
+
+    /// Returns true iff the peer should be banned (given the scoring config)
+    pub fn should_ban(&self, scoring_config: &PeerScoringConfig) -> bool {
+        scoring_config.enable_peer_bans && self.score <= scoring_config.ban_threshold
+    }
+
+    fn apply_penalty(&mut self, penalty: u64) {
+        self.score = self.score.saturating_sub(penalty);
+    }
+}
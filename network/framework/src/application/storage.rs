@@ -6,6 +6,7 @@ use crate::{
     application::{
         error::Error,
         metadata::{ConnectionState, PeerMetadata},
+        scoring::{PeerScore, PeerScoreEvent},
     },
     counters,
     peer_manager::ConnectionNotification,
@@ -13,19 +14,21 @@ use crate::{
     ProtocolId,
 };
 use aptos_config::{
-    config::{Peer, PeerSet},
+    config::{Peer, PeerScoringConfig, PeerSet},
     network_id::{NetworkId, PeerNetworkId},
 };
 use aptos_infallible::{Mutex, RwLock};
 use aptos_logger::{sample, sample::SampleRate, warn};
 use aptos_peer_monitoring_service_types::PeerMonitoringMetadata;
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{account_address::AccountAddress, PeerId};
 use arc_swap::ArcSwap;
+use futures::channel::mpsc;
 use std::{
     collections::{hash_map::Entry, HashMap},
     ops::Deref,
     sync::{Arc, RwLockWriteGuard},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::sync::mpsc::error::TrySendError;
 
@@ -41,6 +44,7 @@ const NOTIFICATION_BACKLOG: usize = 1000;
 /// applications (e.g., peer monitoring service).
 #[derive(Debug)]
 pub struct PeersAndMetadata {
+    network_ids: Vec<NetworkId>, // The networks the container was built for (in order)
     peers_and_metadata: RwLock<HashMap<NetworkId, HashMap<PeerId, PeerMetadata>>>,
     trusted_peers: HashMap<NetworkId, Arc<ArcSwap<PeerSet>>>,
 
@@ -52,16 +56,41 @@ pub struct PeersAndMetadata {
     cached_peers_and_metadata: Arc<ArcSwap<HashMap<NetworkId, HashMap<PeerId, PeerMetadata>>>>,
 
     subscribers: Mutex<Vec<tokio::sync::mpsc::Sender<ConnectionNotification>>>,
+
+    // The banned peers (and the expiry time of each ban), and the subscriber
+    // for each network that is notified of new bans (see `ban_peer()`).
+    time_service: TimeService,
+    banned_peers: RwLock<HashMap<PeerNetworkId, Instant>>,
+    ban_subscribers: Mutex<HashMap<NetworkId, mpsc::UnboundedSender<PeerId>>>,
+
+    // The score of each peer (which is removed when the peer disconnects), and
+    // the scoring config of each network (see `report_peer_event()`).
+    peer_scores: RwLock<HashMap<PeerNetworkId, PeerScore>>,
+    peer_scoring_configs: RwLock<HashMap<NetworkId, PeerScoringConfig>>,
 }
 
 impl PeersAndMetadata {
     pub fn new(network_ids: &[NetworkId]) -> Arc<PeersAndMetadata> {
+        Self::new_with_time_service(network_ids, TimeService::real())
+    }
+
+    /// Creates the container with the given time service (used to expire peer bans)
+    pub fn new_with_time_service(
+        network_ids: &[NetworkId],
+        time_service: TimeService,
+    ) -> Arc<PeersAndMetadata> {
         // Create the container
         let mut peers_and_metadata = PeersAndMetadata {
+            network_ids: network_ids.to_vec(),
             peers_and_metadata: RwLock::new(HashMap::new()),
             trusted_peers: HashMap::new(),
             cached_peers_and_metadata: Arc::new(ArcSwap::from(Arc::new(HashMap::new()))),
             subscribers: Mutex::new(vec![]),
+            time_service,
+            banned_peers: RwLock::new(HashMap::new()),
+            ban_subscribers: Mutex::new(HashMap::new()),
+            peer_scores: RwLock::new(HashMap::new()),
+            peer_scoring_configs: RwLock::new(HashMap::new()),
         };
 
         // Initialize each network mapping and trusted peer set
@@ -148,6 +177,34 @@ impl PeersAndMetadata {
         Ok(connected_supported_peers)
     }
 
+    /// Returns all connected peers on the given network, along with the protocols
+    /// negotiated with each (in ascending wire order), in a single pass over the
+    /// peers (e.g., to build routing tables). Peers are sorted by peer id.
+    pub fn connected_peer_protocols(
+        &self,
+        network_id: NetworkId,
+    ) -> Result<Vec<(PeerNetworkId, Vec<ProtocolId>)>, Error> {
+        // Get the cached peers and metadata for the network
+        let cached_peers_and_metadata = self.cached_peers_and_metadata.load();
+        let peers_and_metadata = cached_peers_and_metadata
+            .get(&network_id)
+            .ok_or_else(|| missing_network_metadata_error(&network_id))?;
+
+        // Collect the protocols of all connected peers
+        let mut connected_peer_protocols: Vec<_> = peers_and_metadata
+            .iter()
+            .filter(|(_, peer_metadata)| peer_metadata.is_connected())
+            .map(|(peer_id, peer_metadata)| {
+                (
+                    PeerNetworkId::new(network_id, *peer_id),
+                    peer_metadata.get_supported_protocols().iter().collect(),
+                )
+            })
+            .collect();
+        connected_peer_protocols.sort_by_key(|(peer_network_id, _)| peer_network_id.peer_id());
+        Ok(connected_peer_protocols)
+    }
+
     /// Returns the metadata for the specified peer
     pub fn get_metadata_for_peer(
         &self,
@@ -169,6 +226,12 @@ impl PeersAndMetadata {
             .ok_or_else(|| missing_peer_metadata_error(&peer_network_id))
     }
 
+    /// Returns the networks the container was built for (in the order they
+    /// were given to `new()`), e.g., to enumerate the configured networks.
+    pub fn network_ids(&self) -> Vec<NetworkId> {
+        self.network_ids.clone()
+    }
+
     /// Returns the networks currently held in the container
     pub fn get_registered_networks(&self) -> impl Iterator<Item = NetworkId> + '_ {
         // Get the cached peers and metadata
@@ -196,10 +259,14 @@ impl PeersAndMetadata {
         let peer_metadata_for_network =
             get_peer_metadata_for_network(&peer_network_id, &mut peers_and_metadata)?;
 
-        // Update the metadata for the peer or insert a new entry
+        // Update the metadata for the peer or insert a new entry (and
+        // identify if the supported protocols of an existing peer changed).
+        let mut protocols_updated = false;
         peer_metadata_for_network
             .entry(peer_network_id.peer_id())
             .and_modify(|peer_metadata| {
+                protocols_updated = peer_metadata.connection_metadata.application_protocols
+                    != connection_metadata.application_protocols;
                 peer_metadata.connection_metadata = connection_metadata.clone()
             })
             .or_insert_with(|| PeerMetadata::new(connection_metadata.clone()));
@@ -207,9 +274,16 @@ impl PeersAndMetadata {
         // Update the cached peers and metadata
         self.set_cached_peers_and_metadata(peers_and_metadata.clone());
 
+        // Notify the subscribers of the new peer (and any protocol updates)
+        let updated_protocols = connection_metadata.application_protocols.clone();
         let event =
             ConnectionNotification::NewPeer(connection_metadata, peer_network_id.network_id());
         self.broadcast(event);
+        if protocols_updated {
+            let event =
+                ConnectionNotification::UpdatedPeerProtocols(peer_network_id, updated_protocols);
+            self.broadcast(event);
+        }
 
         Ok(())
     }
@@ -239,6 +313,10 @@ impl PeersAndMetadata {
             let active_connection_id = entry.get().connection_metadata.connection_id;
             if active_connection_id == connection_id {
                 let peer_metadata = entry.remove();
+
+                // Remove the peer's score (the peer may never reconnect)
+                self.peer_scores.write().remove(&peer_network_id);
+
                 let event = ConnectionNotification::LostPeer(
                     peer_metadata.connection_metadata.clone(),
                     peer_network_id.network_id(),
@@ -395,7 +473,7 @@ impl PeersAndMetadata {
         }
     }
 
-    /// subscribe() returns a channel for receiving NewPeer/LostPeer events.
+    /// subscribe() returns a channel for receiving NewPeer/LostPeer/UpdatedPeerProtocols events.
     /// subscribe() immediately sends all* current connections as NewPeer events.
     /// (* capped at NOTIFICATION_BACKLOG, currently 1000, use get_connected_peers() to be sure)
     pub fn subscribe(&self) -> tokio::sync::mpsc::Receiver<ConnectionNotification> {
@@ -419,6 +497,110 @@ impl PeersAndMetadata {
         receiver
     }
 
+    /// Bans the given peer for the given duration, e.g., because an application detected
+    /// that the peer misbehaves. The peer is disconnected (if it is connected), and new
+    /// connections with the peer are refused until the ban expires. Banning a peer that
+    /// is already banned replaces the expiry time of the existing ban.
+    pub fn ban_peer(&self, peer_network_id: PeerNetworkId, duration: Duration) {
+        let expiry_time = self.time_service.now() + duration;
+        {
+            let mut banned_peers = self.banned_peers.write();
+            banned_peers.insert(peer_network_id, expiry_time);
+            self.remove_expired_bans(&mut banned_peers);
+        }
+
+        // Notify the subscriber of the network, so that the peer is disconnected
+        let network_id = peer_network_id.network_id();
+        if let Some(ban_subscriber) = self.ban_subscribers.lock().get(&network_id) {
+            if let Err(error) = ban_subscriber.unbounded_send(peer_network_id.peer_id()) {
+                warn!(
+                    "Failed to notify the ban subscriber of network {} of banned peer {:?}: {:?}",
+                    network_id, peer_network_id, error
+                );
+            }
+        }
+    }
+
+    /// Returns true iff the given peer is currently banned (see `ban_peer()`)
+    pub fn is_peer_banned(&self, peer_network_id: &PeerNetworkId) -> bool {
+        let mut banned_peers = self.banned_peers.write();
+        self.remove_expired_bans(&mut banned_peers);
+        banned_peers.contains_key(peer_network_id)
+    }
+
+    /// Returns a channel for receiving the peers that are banned on the given network (e.g.,
+    /// so that the network can disconnect them). Only the latest subscriber is notified.
+    pub fn subscribe_to_peer_bans(&self, network_id: NetworkId) -> mpsc::UnboundedReceiver<PeerId> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.ban_subscribers.lock().insert(network_id, sender);
+        receiver
+    }
+
+    /// Sets the config used to score the peers of the given network. Networks
+    /// without a config use the default config (i.e., peers are never banned).
+    pub fn set_peer_scoring_config(&self, network_id: NetworkId, config: PeerScoringConfig) {
+        self.peer_scoring_configs.write().insert(network_id, config);
+    }
+
+    /// Reports an event that affects the score of the given peer (e.g., an application
+    /// received a malformed message from the peer). If the peer's score drops to (or
+    /// below) the ban threshold, and bans are enabled for the network, the peer is
+    /// banned (see `ban_peer()`), and its score is reset (for when the ban expires).
+    pub fn report_peer_event(&self, peer_network_id: PeerNetworkId, event: PeerScoreEvent) {
+        let scoring_config = self
+            .peer_scoring_configs
+            .read()
+            .get(&peer_network_id.network_id())
+            .copied()
+            .unwrap_or_default();
+
+        // Update the peer's score
+        let banned_score = {
+            let mut peer_scores = self.peer_scores.write();
+            let peer_score = peer_scores
+                .entry(peer_network_id)
+                .or_insert_with(|| PeerScore::new(&scoring_config));
+            peer_score.record_event(event, &scoring_config);
+            if peer_score.should_ban(&scoring_config) {
+                let banned_score = *peer_score;
+                *peer_score = PeerScore::new(&scoring_config);
+                Some(banned_score)
+            } else {
+                None
+            }
+        };
+
+        // Ban the peer if its score is too low
+        if let Some(banned_score) = banned_score {
+            warn!(
+                "Banning peer {:?} for {} secs, as its score is too low: {:?}",
+                peer_network_id, scoring_config.ban_duration_secs, banned_score
+            );
+            self.ban_peer(
+                peer_network_id,
+                Duration::from_secs(scoring_config.ban_duration_secs),
+            );
+        }
+    }
+
+    /// Returns the score of the given peer, or None if no events were reported for it
+    pub fn get_peer_score(&self, peer_network_id: &PeerNetworkId) -> Option<PeerScore> {
+        self.peer_scores.read().get(peer_network_id).copied()
+    }
+
+    /// Removes all expired bans, and updates the active ban metrics
+    fn remove_expired_bans(&self, banned_peers: &mut HashMap<PeerNetworkId, Instant>) {
+        let now = self.time_service.now();
+        banned_peers.retain(|_, expiry_time| *expiry_time > now);
+        for network_id in &self.network_ids {
+            let num_active_bans = banned_peers
+                .keys()
+                .filter(|peer_network_id| peer_network_id.network_id() == *network_id)
+                .count();
+            counters::active_peer_bans(*network_id).set(num_active_bans as i64);
+        }
+    }
+
     #[cfg(test)]
     pub fn close_subscribers(&self) {
         let mut listeners = self.subscribers.lock();
@@ -442,6 +624,16 @@ impl PeersAndMetadata {
         (peers_and_metadata, trusted_peers, cached_peers_and_metadata)
     }
 
+    /// Returns a cheap handle whose queries are implicitly scoped to the
+    /// given network. The view reads from the same cached state as the
+    /// container, so it always reflects the latest peer updates.
+    pub fn view(&self, network_id: NetworkId) -> NetworkPeersView {
+        NetworkPeersView {
+            network_id,
+            cached_peers_and_metadata: self.cached_peers_and_metadata.clone(),
+        }
+    }
+
     /// Sorts the give peer slice in the order of decreasing latency.
     pub fn sort_peers_by_latency(&self, network_id: NetworkId, peers: &mut [PeerId]) {
         let _timer = counters::OP_MEASURE
@@ -471,6 +663,90 @@ impl PeersAndMetadata {
     }
 }
 
+/// A view of `PeersAndMetadata` that is limited to a single network.
+/// This is useful for applications that only operate on one network,
+/// and avoids accidentally operating on peers from other networks.
+#[derive(Clone, Debug)]
+pub struct NetworkPeersView {
+    network_id: NetworkId,
+    cached_peers_and_metadata: Arc<ArcSwap<HashMap<NetworkId, HashMap<PeerId, PeerMetadata>>>>,
+}
+
+impl NetworkPeersView {
+    /// Returns the network this view is scoped to
+    pub fn network_id(&self) -> NetworkId {
+        self.network_id
+    }
+
+    /// Returns all peers on the network. Note: this will return disconnected
+    /// and unhealthy peers (see `PeersAndMetadata::get_all_peers()`).
+    pub fn get_all_peers(&self) -> Result<Vec<PeerNetworkId>, Error> {
+        self.collect_peers(|_| true)
+    }
+
+    /// Returns metadata for all peers currently connected on the network
+    pub fn get_connected_peers_and_metadata(
+        &self,
+    ) -> Result<HashMap<PeerNetworkId, PeerMetadata>, Error> {
+        // Get the cached peers and metadata
+        let cached_peers_and_metadata = self.cached_peers_and_metadata.load();
+        let peers_and_metadata = cached_peers_and_metadata
+            .get(&self.network_id)
+            .ok_or_else(|| missing_network_metadata_error(&self.network_id))?;
+
+        // Collect all connected peers
+        Ok(peers_and_metadata
+            .iter()
+            .filter(|(_, peer_metadata)| peer_metadata.is_connected())
+            .map(|(peer_id, peer_metadata)| {
+                (
+                    PeerNetworkId::new(self.network_id, *peer_id),
+                    peer_metadata.clone(),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns all connected peers on the network that support
+    /// at least one of the given protocols.
+    pub fn get_connected_supported_peers(
+        &self,
+        protocol_ids: &[ProtocolId],
+    ) -> Result<Vec<PeerNetworkId>, Error> {
+        self.collect_peers(|peer_metadata| {
+            peer_metadata.is_connected() && peer_metadata.supports_any_protocol(protocol_ids)
+        })
+    }
+
+    /// Returns the metadata for the specified peer on the network
+    pub fn get_metadata_for_peer(&self, peer_id: PeerId) -> Result<PeerMetadata, Error> {
+        let peer_network_id = PeerNetworkId::new(self.network_id, peer_id);
+        let cached_peers_and_metadata = self.cached_peers_and_metadata.load();
+        cached_peers_and_metadata
+            .get(&self.network_id)
+            .ok_or_else(|| missing_network_metadata_error(&self.network_id))?
+            .get(&peer_id)
+            .cloned()
+            .ok_or_else(|| missing_peer_metadata_error(&peer_network_id))
+    }
+
+    /// Returns all peers on the network whose metadata matches the given filter
+    fn collect_peers(
+        &self,
+        filter: impl Fn(&PeerMetadata) -> bool,
+    ) -> Result<Vec<PeerNetworkId>, Error> {
+        let cached_peers_and_metadata = self.cached_peers_and_metadata.load();
+        let peers_and_metadata = cached_peers_and_metadata
+            .get(&self.network_id)
+            .ok_or_else(|| missing_network_metadata_error(&self.network_id))?;
+        Ok(peers_and_metadata
+            .iter()
+            .filter(|(_, peer_metadata)| filter(peer_metadata))
+            .map(|(peer_id, _)| PeerNetworkId::new(self.network_id, *peer_id))
+            .collect())
+    }
+}
+
 /// Returns the peer metadata for the given network
 fn get_peer_metadata_for_network<'a>(
     peer_network_id: &'a PeerNetworkId,
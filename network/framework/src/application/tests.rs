@@ -7,6 +7,8 @@ use crate::{
         error::Error,
         interface::{NetworkClient, NetworkClientInterface, NetworkServiceEvents},
         metadata::{ConnectionState, PeerMetadata},
+        peer_set,
+        scoring::{PeerScore, PeerScoreEvent},
         storage::PeersAndMetadata,
     },
     peer_manager::{
@@ -18,21 +20,23 @@ use crate::{
             Event, NetworkEvents, NetworkSender, NewNetworkEvents, NewNetworkSender,
             ReceivedMessage,
         },
+        rpc::error::RpcError,
         wire::{
             handshake::v1::{ProtocolId, ProtocolIdSet},
             messaging::v1::{DirectSendMsg, NetworkMessage, RpcRequest},
         },
     },
+    testutils::recording_client::RecordingNetworkClient,
     transport::ConnectionMetadata,
 };
 use aptos_channels::{aptos_channel, message_queues::QueueStyle};
 use aptos_config::{
-    config::{Peer, PeerRole, PeerSet},
+    config::{Peer, PeerRole, PeerScoringConfig, PeerSet},
     network_id::{NetworkId, PeerNetworkId},
 };
 use aptos_peer_monitoring_service_types::PeerMonitoringMetadata;
 use aptos_types::{account_address::AccountAddress, PeerId};
-use futures_util::StreamExt;
+use futures_util::{FutureExt, StreamExt};
 use maplit::hashmap;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -182,6 +186,141 @@ fn test_peers_and_metadata_simple_interface() {
     check_connected_supported_peers(&peers_and_metadata, &[ProtocolId::ConsensusRpcBcs], vec![]);
 }
 
+#[test]
+fn test_peers_and_metadata_network_view() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create a view for each network, and one for an unregistered network
+    let validator_view = peers_and_metadata.view(NetworkId::Validator);
+    let vfn_view = peers_and_metadata.view(NetworkId::Vfn);
+    let public_view = peers_and_metadata.view(NetworkId::Public);
+
+    // Create peers on both networks (after the views were created)
+    let (peer_network_id_1, connection_1) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_2, _) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Verify each view only reports the peers on its network
+    assert_eq!(validator_view.network_id(), NetworkId::Validator);
+    assert_eq!(validator_view.get_all_peers().unwrap(), vec![
+        peer_network_id_1
+    ]);
+    assert_eq!(vfn_view.get_all_peers().unwrap(), vec![peer_network_id_2]);
+    assert_eq!(
+        validator_view.get_connected_peers_and_metadata().unwrap(),
+        hashmap! {peer_network_id_1 => PeerMetadata::new(connection_1)}
+    );
+    assert_eq!(
+        vfn_view
+            .get_connected_supported_peers(&[ProtocolId::MempoolDirectSend])
+            .unwrap(),
+        vec![peer_network_id_2]
+    );
+    validator_view
+        .get_metadata_for_peer(peer_network_id_1.peer_id())
+        .unwrap();
+    validator_view
+        .get_metadata_for_peer(peer_network_id_2.peer_id())
+        .unwrap_err();
+
+    // Disconnect the validator peer and verify the view is updated
+    disconnect_peer(&peers_and_metadata, peer_network_id_1);
+    assert!(validator_view
+        .get_connected_peers_and_metadata()
+        .unwrap()
+        .is_empty());
+    assert_eq!(validator_view.get_all_peers().unwrap(), vec![
+        peer_network_id_1
+    ]);
+
+    // Verify queries on an unregistered network fail
+    public_view.get_all_peers().unwrap_err();
+    public_view.get_connected_peers_and_metadata().unwrap_err();
+}
+
+#[test]
+fn test_peers_and_metadata_connected_peer_protocols() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create several peers with differing protocols
+    let (peer_network_id_1, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::MempoolDirectSend, ProtocolId::ConsensusRpcBcs],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_2, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::StorageServiceRpc],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_3, _) =
+        create_peer_and_connection(NetworkId::Validator, vec![], peers_and_metadata.clone());
+    let (peer_network_id_4, _) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Verify the connected peers and their protocols are returned (sorted by peer id)
+    let mut expected_peer_protocols = vec![
+        (peer_network_id_1, vec![
+            ProtocolId::ConsensusRpcBcs,
+            ProtocolId::MempoolDirectSend,
+        ]),
+        (peer_network_id_2, vec![ProtocolId::StorageServiceRpc]),
+        (peer_network_id_3, vec![]),
+    ];
+    expected_peer_protocols.sort_by_key(|(peer_network_id, _)| peer_network_id.peer_id());
+    assert_eq!(
+        peers_and_metadata
+            .connected_peer_protocols(NetworkId::Validator)
+            .unwrap(),
+        expected_peer_protocols
+    );
+    assert_eq!(
+        peers_and_metadata
+            .connected_peer_protocols(NetworkId::Vfn)
+            .unwrap(),
+        vec![(peer_network_id_4, vec![ProtocolId::MempoolDirectSend])]
+    );
+
+    // Disconnect a peer and verify it is no longer returned
+    disconnect_peer(&peers_and_metadata, peer_network_id_2);
+    expected_peer_protocols.retain(|(peer_network_id, _)| *peer_network_id != peer_network_id_2);
+    assert_eq!(
+        peers_and_metadata
+            .connected_peer_protocols(NetworkId::Validator)
+            .unwrap(),
+        expected_peer_protocols
+    );
+
+    // Verify queries on an unregistered network fail
+    peers_and_metadata
+        .connected_peer_protocols(NetworkId::Public)
+        .unwrap_err();
+}
+
+#[test]
+fn test_peers_and_metadata_network_ids() {
+    // Create the peers and metadata container with two networks
+    let network_ids = [NetworkId::Validator, NetworkId::Public];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Verify both networks are returned (in order)
+    assert_eq!(peers_and_metadata.network_ids(), network_ids.to_vec());
+}
+
 #[test]
 fn test_peers_and_metadata_simple_errors() {
     // Create the peers and metadata container
@@ -479,6 +618,9 @@ async fn test_peers_and_metadata_subscriptions() {
                 ConnectionNotification::LostPeer(_, _) => {
                     panic!("should get connect but got lost")
                 },
+                ConnectionNotification::UpdatedPeerProtocols(_, _) => {
+                    panic!("should get connect but got protocol update")
+                },
             },
         },
         Err(te) => {
@@ -497,6 +639,9 @@ async fn test_peers_and_metadata_subscriptions() {
             ConnectionNotification::LostPeer(_, _) => {
                 panic!("should get connect but got lost");
             },
+            ConnectionNotification::UpdatedPeerProtocols(_, _) => {
+                panic!("should get connect but got protocol update");
+            },
         },
         Err(_) => {
             panic!("should have pending NewPeer");
@@ -533,6 +678,9 @@ async fn test_peers_and_metadata_subscriptions() {
                 assert_eq!(network_id, NetworkId::Validator);
                 assert_eq!(conn_meta, connection_1);
             },
+            ConnectionNotification::UpdatedPeerProtocols(_, _) => {
+                panic!("expecting lost but got protocol update")
+            },
         },
         Err(_tre) => {
             panic!("no pending connection event")
@@ -540,6 +688,70 @@ async fn test_peers_and_metadata_subscriptions() {
     }
 }
 
+#[tokio::test]
+async fn test_peers_and_metadata_protocol_update_subscriptions() {
+    // Create the peers and metadata container
+    let network_ids = vec![NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create a peer that only supports mempool
+    let (peer_network_id, connection) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Subscribe to connection events and verify the initial NewPeer notification
+    let mut connection_events = peers_and_metadata.subscribe();
+    match connection_events.try_recv() {
+        Ok(ConnectionNotification::NewPeer(conn_meta, network_id)) => {
+            assert_eq!(network_id, NetworkId::Vfn);
+            assert_eq!(conn_meta, connection);
+        },
+        notif => panic!("should have pending NewPeer but got {:?}", notif),
+    }
+
+    // Re-insert the same metadata and verify no protocol update is sent
+    peers_and_metadata
+        .insert_connection_metadata(peer_network_id, connection.clone())
+        .unwrap();
+    match connection_events.try_recv() {
+        Ok(ConnectionNotification::NewPeer(conn_meta, _)) => assert_eq!(conn_meta, connection),
+        notif => panic!("should have pending NewPeer but got {:?}", notif),
+    }
+    assert_eq!(connection_events.try_recv(), Err(TryRecvError::Empty));
+
+    // Update the peer's protocols to also support the storage service
+    let updated_protocols =
+        ProtocolIdSet::from_iter([ProtocolId::MempoolDirectSend, ProtocolId::StorageServiceRpc]);
+    let mut updated_connection = connection.clone();
+    updated_connection.application_protocols = updated_protocols.clone();
+    peers_and_metadata
+        .insert_connection_metadata(peer_network_id, updated_connection.clone())
+        .unwrap();
+
+    // Verify the subscriber is notified of the updated protocol set
+    match connection_events.try_recv() {
+        Ok(ConnectionNotification::NewPeer(conn_meta, _)) => {
+            assert_eq!(conn_meta, updated_connection)
+        },
+        notif => panic!("should have pending NewPeer but got {:?}", notif),
+    }
+    match connection_events.try_recv() {
+        Ok(ConnectionNotification::UpdatedPeerProtocols(updated_peer, protocols)) => {
+            assert_eq!(updated_peer, peer_network_id);
+            assert_eq!(protocols, updated_protocols);
+        },
+        notif => panic!("should have pending protocol update but got {:?}", notif),
+    }
+
+    // Verify the peer is now a supported storage service peer
+    let connected_peers = peers_and_metadata
+        .get_connected_supported_peers(&[ProtocolId::StorageServiceRpc])
+        .unwrap();
+    assert_eq!(connected_peers, vec![peer_network_id]);
+}
+
 #[test]
 fn test_network_client_available_peers() {
     // Create the peers and metadata container
@@ -678,22 +890,28 @@ async fn test_network_client_missing_network_sender() {
 
     // Verify that sending a message to a peer without a network sender fails
     let bad_peer_network_id = PeerNetworkId::new(NetworkId::Vfn, PeerId::random());
-    network_client
-        .send_to_peer(DummyMessage::new_empty(), bad_peer_network_id)
-        .unwrap_err();
-    network_client
-        .send_to_peer_rpc(
-            DummyMessage::new_empty(),
-            Duration::from_secs(MAX_MESSAGE_TIMEOUT_SECS),
-            bad_peer_network_id,
-        )
-        .await
-        .unwrap_err();
+    let expected_error = Error::UnknownNetwork(NetworkId::Vfn);
+    assert_eq!(
+        network_client.send_to_peer(DummyMessage::new_empty(), bad_peer_network_id),
+        Err(expected_error.clone())
+    );
+    assert_eq!(
+        network_client
+            .send_to_peer_rpc(
+                DummyMessage::new_empty(),
+                Duration::from_secs(MAX_MESSAGE_TIMEOUT_SECS),
+                bad_peer_network_id,
+            )
+            .await
+            .unwrap_err(),
+        expected_error
+    );
 
-    // Verify that sending a message to all peers without a network simply logs the errors
-    network_client
-        .send_to_peers(DummyMessage::new_empty(), vec![bad_peer_network_id])
-        .unwrap();
+    // Verify that sending a message to multiple peers also fails if any peer is without a network
+    assert_eq!(
+        network_client.send_to_peers(DummyMessage::new_empty(), vec![bad_peer_network_id]),
+        Err(expected_error)
+    );
 }
 
 #[tokio::test]
@@ -853,6 +1071,525 @@ async fn test_network_client_network_senders_direct_send() {
     .await;
 }
 
+#[tokio::test]
+async fn test_network_client_clones_share_senders() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create a peer and initialize the connection metadata
+    let (peer_network_id, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Create a network client with network senders, and clone it
+    let (
+        network_senders,
+        network_events,
+        mut outbound_request_receivers,
+        mut inbound_request_senders,
+    ) = create_network_sender_and_events(&network_ids);
+    let network_client: NetworkClient<DummyMessage> = NetworkClient::new(
+        vec![ProtocolId::MempoolDirectSend],
+        vec![],
+        network_senders,
+        peers_and_metadata.clone(),
+    );
+    let cloned_network_client = network_client.clone();
+    assert!(Arc::ptr_eq(
+        &network_client.get_peers_and_metadata(),
+        &cloned_network_client.get_peers_and_metadata()
+    ));
+
+    // Extract the network and events
+    let mut network_and_events = network_events.into_network_and_events();
+    let mut validator_network_events = network_and_events.remove(&NetworkId::Validator).unwrap();
+
+    // Send a message from each client (in separate tasks) to the same peer
+    let dummy_message_1 = DummyMessage::new(1);
+    let dummy_message_2 = DummyMessage::new(2);
+    for (client, message) in [
+        (network_client, dummy_message_1.clone()),
+        (cloned_network_client, dummy_message_2.clone()),
+    ] {
+        tokio::spawn(async move { client.send_to_peer(message, peer_network_id).unwrap() })
+            .await
+            .unwrap();
+    }
+
+    // Verify that both messages are sent over the same network sender
+    for dummy_message in [dummy_message_1, dummy_message_2] {
+        wait_for_network_event(
+            peer_network_id,
+            &mut outbound_request_receivers,
+            &mut inbound_request_senders,
+            &mut validator_network_events,
+            false,
+            Some(ProtocolId::MempoolDirectSend),
+            None,
+            dummy_message,
+        )
+        .await;
+    }
+
+    // Verify that no other messages were sent (i.e., nothing was sent twice)
+    let outbound_request_receiver = outbound_request_receivers
+        .get_mut(&NetworkId::Validator)
+        .unwrap();
+    assert!(outbound_request_receiver
+        .select_next_some()
+        .now_or_never()
+        .is_none());
+}
+
+#[test]
+fn test_recording_network_client_broadcast() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create three peers (one of which doesn't support any of the client protocols)
+    let (peer_network_id_1, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::ConsensusDirectSendBcs],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_2, _) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![
+            ProtocolId::ConsensusDirectSendJson,
+            ProtocolId::ConsensusDirectSendBcs,
+        ],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_3, _) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Create a recording network client
+    let network_client: RecordingNetworkClient<DummyMessage> = RecordingNetworkClient::new(
+        vec![
+            ProtocolId::ConsensusDirectSendJson,
+            ProtocolId::ConsensusDirectSendBcs,
+        ],
+        vec![],
+        peers_and_metadata.clone(),
+    );
+    assert!(network_client.recorded_messages().is_empty());
+
+    // Broadcast a message to the available peers
+    let dummy_message = DummyMessage::new(999);
+    broadcast_to_available_peers(&network_client, dummy_message.clone()).unwrap();
+
+    // Verify the broadcast was recorded for each available peer, using its preferred protocol
+    let mut recorded_messages = network_client.take_recorded_messages();
+    recorded_messages.sort_by_key(|(peer_network_id, _, _)| *peer_network_id);
+    let mut expected_messages = vec![
+        (
+            peer_network_id_1,
+            ProtocolId::ConsensusDirectSendBcs,
+            dummy_message.clone(),
+        ),
+        (
+            peer_network_id_2,
+            ProtocolId::ConsensusDirectSendJson,
+            dummy_message.clone(),
+        ),
+    ];
+    expected_messages.sort_by_key(|(peer_network_id, _, _)| *peer_network_id);
+    assert_eq!(recorded_messages, expected_messages);
+    assert!(network_client.recorded_messages().is_empty());
+
+    // Verify that sending to a peer without a common protocol fails (and nothing is recorded)
+    network_client
+        .send_to_peer(dummy_message, peer_network_id_3)
+        .unwrap_err();
+    assert!(network_client.recorded_messages().is_empty());
+}
+
+#[tokio::test]
+async fn test_network_client_send_to_account() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Connect the same account on both the validator and vfn networks
+    let account = AccountAddress::random();
+    let protocol_id = ProtocolId::ConsensusDirectSendBcs;
+    for network_id in network_ids {
+        let mut connection = ConnectionMetadata::mock(account);
+        connection.application_protocols = ProtocolIdSet::from_iter([protocol_id]);
+        peers_and_metadata
+            .insert_connection_metadata(PeerNetworkId::new(network_id, account), connection)
+            .unwrap();
+    }
+
+    // Create a network client with network senders
+    let (
+        network_senders,
+        network_events,
+        mut outbound_request_receivers,
+        mut inbound_request_senders,
+    ) = create_network_sender_and_events(&network_ids);
+    let network_client: NetworkClient<DummyMessage> = NetworkClient::new(
+        vec![protocol_id],
+        vec![],
+        network_senders,
+        peers_and_metadata.clone(),
+    );
+    let mut network_and_events = network_events.into_network_and_events();
+    let mut validator_network_events = network_and_events.remove(&NetworkId::Validator).unwrap();
+    let mut vfn_network_events = network_and_events.remove(&NetworkId::Vfn).unwrap();
+
+    // Verify the account resolves to the validator network, and the message is sent there
+    let validator_peer = PeerNetworkId::new(NetworkId::Validator, account);
+    assert_eq!(
+        network_client.resolve_account(account, protocol_id),
+        Ok(validator_peer)
+    );
+    let dummy_message = DummyMessage::new(4455);
+    network_client
+        .send_to_account(account, protocol_id, dummy_message.clone())
+        .unwrap();
+    wait_for_network_event(
+        validator_peer,
+        &mut outbound_request_receivers,
+        &mut inbound_request_senders,
+        &mut validator_network_events,
+        false,
+        Some(protocol_id),
+        None,
+        dummy_message,
+    )
+    .await;
+
+    // Disconnect the account on the validator network and verify the vfn network is used
+    disconnect_peer(&peers_and_metadata, validator_peer);
+    let vfn_peer = PeerNetworkId::new(NetworkId::Vfn, account);
+    let dummy_message = DummyMessage::new(6677);
+    network_client
+        .send_to_account(account, protocol_id, dummy_message.clone())
+        .unwrap();
+    wait_for_network_event(
+        vfn_peer,
+        &mut outbound_request_receivers,
+        &mut inbound_request_senders,
+        &mut vfn_network_events,
+        false,
+        Some(protocol_id),
+        None,
+        dummy_message,
+    )
+    .await;
+
+    // Verify that sending to an account that is not connected fails
+    disconnect_peer(&peers_and_metadata, vfn_peer);
+    assert_eq!(
+        network_client.send_to_account(account, protocol_id, DummyMessage::new(0)),
+        Err(Error::AccountNotConnected(account))
+    );
+
+    // Verify that sending with a protocol the account doesn't support fails
+    connect_peer(&peers_and_metadata, validator_peer);
+    assert_eq!(
+        network_client.send_to_account(
+            account,
+            ProtocolId::MempoolDirectSend,
+            DummyMessage::new(0)
+        ),
+        Err(Error::AccountNotConnected(account))
+    );
+}
+
+#[test]
+fn test_peer_set_operations() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create several peers across both networks
+    let (peer_network_id_1, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_2, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::ConsensusRpcBcs],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_3, _) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Verify the constructors only return the expected peers
+    let validator_peers =
+        peer_set::PeerSet::all_connected(&peers_and_metadata, NetworkId::Validator).unwrap();
+    let mempool_peers =
+        peer_set::PeerSet::supporting(&peers_and_metadata, ProtocolId::MempoolDirectSend).unwrap();
+    assert_eq!(
+        validator_peers,
+        peer_set::PeerSet::from_iter([peer_network_id_1, peer_network_id_2])
+    );
+    assert_eq!(
+        mempool_peers,
+        peer_set::PeerSet::from_iter([peer_network_id_1, peer_network_id_3])
+    );
+
+    // Verify the set operations
+    assert_eq!(
+        validator_peers.union(&mempool_peers),
+        peer_set::PeerSet::from_iter([peer_network_id_1, peer_network_id_2, peer_network_id_3])
+    );
+    assert_eq!(
+        validator_peers.intersection(&mempool_peers),
+        peer_set::PeerSet::from_iter([peer_network_id_1])
+    );
+    assert_eq!(
+        validator_peers.difference(&mempool_peers),
+        peer_set::PeerSet::from_iter([peer_network_id_2])
+    );
+    assert_eq!(
+        mempool_peers.filter(|peer| peer.network_id() == NetworkId::Vfn),
+        peer_set::PeerSet::from_iter([peer_network_id_3])
+    );
+    assert!(validator_peers
+        .intersection(&peer_set::PeerSet::new())
+        .is_empty());
+
+    // Disconnect a peer and verify it is no longer included
+    disconnect_peer(&peers_and_metadata, peer_network_id_1);
+    let validator_peers =
+        peer_set::PeerSet::all_connected(&peers_and_metadata, NetworkId::Validator).unwrap();
+    assert!(!validator_peers.contains(&peer_network_id_1));
+    assert_eq!(validator_peers.len(), 1);
+}
+
+#[test]
+fn test_peer_scoring() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Public];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Enable peer bans on the validator network
+    let scoring_config = PeerScoringConfig {
+        enable_peer_bans: true,
+        ..Default::default()
+    };
+    peers_and_metadata.set_peer_scoring_config(NetworkId::Validator, scoring_config);
+
+    // Verify peers without reported events have no score
+    let validator_peer = PeerNetworkId::new(NetworkId::Validator, PeerId::random());
+    let public_peer = PeerNetworkId::new(NetworkId::Public, PeerId::random());
+    assert!(peers_and_metadata.get_peer_score(&validator_peer).is_none());
+
+    // Report several successful RPCs and verify the score is capped
+    peers_and_metadata.report_peer_event(
+        validator_peer,
+        PeerScoreEvent::RpcSuccess(Duration::from_secs(1)),
+    );
+    peers_and_metadata.report_peer_event(
+        validator_peer,
+        PeerScoreEvent::RpcSuccess(Duration::from_secs(9)),
+    );
+    let peer_score = peers_and_metadata.get_peer_score(&validator_peer).unwrap();
+    assert_eq!(peer_score.score, scoring_config.max_score);
+    assert_eq!(peer_score.num_rpc_successes, 2);
+    assert_eq!(peer_score.average_rpc_latency_secs, Some(2.0));
+
+    // Report several failures and verify the score drops (without a ban)
+    peers_and_metadata.report_peer_event(validator_peer, PeerScoreEvent::RpcFailure);
+    peers_and_metadata.report_peer_event(validator_peer, PeerScoreEvent::MalformedMessage);
+    peers_and_metadata.report_peer_event(validator_peer, PeerScoreEvent::HandshakeError);
+    let peer_score = peers_and_metadata.get_peer_score(&validator_peer).unwrap();
+    let expected_score = scoring_config.max_score
+        - scoring_config.rpc_failure_penalty
+        - scoring_config.malformed_message_penalty
+        - scoring_config.handshake_error_penalty;
+    assert_eq!(peer_score.score, expected_score);
+    assert_eq!(peer_score.num_rpc_failures, 1);
+    assert_eq!(peer_score.num_malformed_messages, 1);
+    assert_eq!(peer_score.num_handshake_errors, 1);
+    assert!(!peers_and_metadata.is_peer_banned(&validator_peer));
+
+    // Report malformed messages until the peer is banned, and verify the score is reset
+    while !peers_and_metadata.is_peer_banned(&validator_peer) {
+        peers_and_metadata.report_peer_event(validator_peer, PeerScoreEvent::MalformedMessage);
+    }
+    assert_eq!(
+        peers_and_metadata.get_peer_score(&validator_peer),
+        Some(PeerScore::new(&scoring_config))
+    );
+
+    // Verify peers on networks without a scoring config are never banned
+    for _ in 0..100 {
+        peers_and_metadata.report_peer_event(public_peer, PeerScoreEvent::HandshakeError);
+    }
+    let peer_score = peers_and_metadata.get_peer_score(&public_peer).unwrap();
+    assert_eq!(peer_score.score, 0);
+    assert!(!peers_and_metadata.is_peer_banned(&public_peer));
+}
+
+#[test]
+fn test_peer_scores_removed_on_disconnect() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create a peer and report an event for it
+    let (peer_network_id, connection) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::StorageServiceRpc],
+        peers_and_metadata.clone(),
+    );
+    peers_and_metadata.report_peer_event(peer_network_id, PeerScoreEvent::RpcFailure);
+    assert!(peers_and_metadata
+        .get_peer_score(&peer_network_id)
+        .is_some());
+
+    // Remove the peer and verify its score is also removed
+    peers_and_metadata
+        .remove_peer_metadata(peer_network_id, connection.connection_id)
+        .unwrap();
+    assert!(peers_and_metadata
+        .get_peer_score(&peer_network_id)
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_network_client_rpc_scoring() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create a network client with network senders
+    let (
+        network_senders,
+        _network_events,
+        mut outbound_request_receivers,
+        _inbound_request_senders,
+    ) = create_network_sender_and_events(&network_ids);
+    let network_client: NetworkClient<DummyMessage> = NetworkClient::new(
+        vec![],
+        vec![ProtocolId::StorageServiceRpc],
+        network_senders,
+        peers_and_metadata.clone(),
+    );
+
+    // Create a peer that supports the rpc protocol
+    let (peer_network_id, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::StorageServiceRpc],
+        peers_and_metadata.clone(),
+    );
+
+    // Send an rpc that times out, and verify the peer is penalized
+    let rpc_timeout = Duration::from_secs(MAX_MESSAGE_TIMEOUT_SECS);
+    let network_client_clone = network_client.clone();
+    let rpc_task = tokio::spawn(async move {
+        network_client_clone
+            .send_to_peer_rpc(DummyMessage::new_empty(), rpc_timeout, peer_network_id)
+            .await
+    });
+    let mut outbound_request_receiver = outbound_request_receivers
+        .remove(&NetworkId::Validator)
+        .unwrap();
+    match outbound_request_receiver.select_next_some().await {
+        PeerManagerRequest::SendRpc(_, outbound_rpc_request) => outbound_rpc_request
+            .res_tx
+            .send(Err(RpcError::TimedOut))
+            .unwrap(),
+        request => panic!("Unexpected peer manager request: {:?}", request),
+    }
+    rpc_task.await.unwrap().unwrap_err();
+    let peer_score = peers_and_metadata.get_peer_score(&peer_network_id).unwrap();
+    assert_eq!(peer_score.num_rpc_failures, 1);
+
+    // Send an rpc that fails locally (the outbound queue is closed), and
+    // verify the peer is not penalized.
+    drop(outbound_request_receiver);
+    network_client
+        .send_to_peer_rpc(DummyMessage::new_empty(), rpc_timeout, peer_network_id)
+        .await
+        .unwrap_err();
+    let peer_score = peers_and_metadata.get_peer_score(&peer_network_id).unwrap();
+    assert_eq!(peer_score.num_rpc_failures, 1);
+}
+
+#[tokio::test]
+async fn test_network_client_peer_set_broadcast() {
+    // Create the peers and metadata container
+    let network_ids = [NetworkId::Validator, NetworkId::Vfn];
+    let peers_and_metadata = PeersAndMetadata::new(&network_ids);
+
+    // Create a peer on each network
+    let (peer_network_id_1, _) = create_peer_and_connection(
+        NetworkId::Validator,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+    let (peer_network_id_2, _) = create_peer_and_connection(
+        NetworkId::Vfn,
+        vec![ProtocolId::MempoolDirectSend],
+        peers_and_metadata.clone(),
+    );
+
+    // Create a network client with network senders
+    let (
+        network_senders,
+        network_events,
+        mut outbound_request_receivers,
+        mut inbound_request_senders,
+    ) = create_network_sender_and_events(&network_ids);
+    let network_client: NetworkClient<DummyMessage> = NetworkClient::new(
+        vec![ProtocolId::MempoolDirectSend],
+        vec![],
+        network_senders,
+        peers_and_metadata.clone(),
+    );
+
+    // Extract the network and events
+    let mut network_and_events = network_events.into_network_and_events();
+    let mut validator_network_events = network_and_events.remove(&NetworkId::Validator).unwrap();
+    let mut vfn_network_events = network_and_events.remove(&NetworkId::Vfn).unwrap();
+
+    // Broadcast to all peers supporting mempool and verify both peers receive the message
+    let dummy_message = DummyMessage::new(999);
+    let peer_set =
+        peer_set::PeerSet::supporting(&peers_and_metadata, ProtocolId::MempoolDirectSend).unwrap();
+    network_client
+        .send_to_peers(dummy_message.clone(), peer_set.into())
+        .unwrap();
+    wait_for_network_event(
+        peer_network_id_1,
+        &mut outbound_request_receivers,
+        &mut inbound_request_senders,
+        &mut validator_network_events,
+        false,
+        Some(ProtocolId::MempoolDirectSend),
+        None,
+        dummy_message.clone(),
+    )
+    .await;
+    wait_for_network_event(
+        peer_network_id_2,
+        &mut outbound_request_receivers,
+        &mut inbound_request_senders,
+        &mut vfn_network_events,
+        false,
+        Some(ProtocolId::MempoolDirectSend),
+        None,
+        dummy_message,
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn test_network_client_network_senders_rpc() {
     // Create the peers and metadata container
@@ -1033,7 +1770,7 @@ fn create_network_sender_and_events(
             PeerManagerRequestSender::new(outbound_request_sender),
             ConnectionRequestSender::new(connection_outbound_sender),
         );
-        let network_events = NetworkEvents::new(inbound_request_receiver, None, true);
+        let network_events = NetworkEvents::new(inbound_request_receiver, None, true, None, None);
 
         // Save the sender, events and receivers
         network_senders.insert(*network_id, network_sender);
@@ -1053,6 +1790,16 @@ fn create_network_sender_and_events(
     )
 }
 
+/// A simple application component that broadcasts the
+/// given message to all available peers.
+fn broadcast_to_available_peers<Client: NetworkClientInterface<DummyMessage>>(
+    network_client: &Client,
+    message: DummyMessage,
+) -> Result<(), Error> {
+    let available_peers = network_client.get_available_peers()?;
+    network_client.send_to_peers(message, available_peers)
+}
+
 /// Creates a new peer and connection metadata using the
 /// given network and protocols.
 fn create_peer_and_connection(
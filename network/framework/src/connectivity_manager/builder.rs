@@ -8,17 +8,51 @@ use crate::{
     counters,
     peer_manager::{conn_notifs_channel, ConnectionRequestSender},
 };
-use aptos_config::{config::PeerSet, network_id::NetworkContext};
+use aptos_config::{
+    config::{PeerSet, ReconnectBackoff},
+    network_id::NetworkContext,
+};
 use aptos_time_service::TimeService;
-use std::{sync::Arc, time::Duration};
-use tokio::runtime::Handle;
-use tokio_retry::strategy::ExponentialBackoff;
+use std::{cmp::min, sync::Arc, time::Duration};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+pub type ConnectivityManagerService = ConnectivityManager<ReconnectBackoffStrategy>;
+
+/// The backoff strategy used to redial peers, as configured by a `ReconnectBackoff`
+/// policy. Yields exponentially growing delays, starting at the initial delay and
+/// capped at the maximum delay. Note: jitter is added separately for each dial.
+#[derive(Clone, Debug)]
+pub struct ReconnectBackoffStrategy {
+    current_delay: Duration,
+    multiplier: u32,
+    max_delay: Duration,
+}
 
-pub type ConnectivityManagerService = ConnectivityManager<ExponentialBackoff>;
+impl ReconnectBackoffStrategy {
+    pub fn new(reconnect_backoff: &ReconnectBackoff) -> Self {
+        Self {
+            current_delay: reconnect_backoff.initial_delay(),
+            multiplier: reconnect_backoff.multiplier,
+            max_delay: reconnect_backoff.max_delay(),
+        }
+    }
+}
+
+impl Iterator for ReconnectBackoffStrategy {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = min(self.current_delay, self.max_delay);
+        self.current_delay = self.current_delay.saturating_mul(self.multiplier);
+        Some(delay)
+    }
+}
 
 pub struct ConnectivityManagerBuilder {
     connectivity_manager: Option<ConnectivityManagerService>,
     conn_mgr_reqs_tx: aptos_channels::Sender<ConnectivityRequest>,
+    // Set once the ConnectivityManager has been started
+    connectivity_manager_handle: Option<JoinHandle<()>>,
 }
 
 impl ConnectivityManagerBuilder {
@@ -28,8 +62,7 @@ impl ConnectivityManagerBuilder {
         peers_and_metadata: Arc<PeersAndMetadata>,
         seeds: PeerSet,
         connectivity_check_interval_ms: u64,
-        backoff_base: u64,
-        max_connection_delay_ms: u64,
+        reconnect_backoff: ReconnectBackoff,
         channel_size: usize,
         connection_reqs_tx: ConnectionRequestSender,
         connection_notifs_rx: conn_notifs_channel::Receiver,
@@ -44,6 +77,7 @@ impl ConnectivityManagerBuilder {
 
         Self {
             conn_mgr_reqs_tx,
+            connectivity_manager_handle: None,
             connectivity_manager: Some(ConnectivityManager::new(
                 network_context,
                 time_service,
@@ -53,8 +87,9 @@ impl ConnectivityManagerBuilder {
                 connection_notifs_rx,
                 conn_mgr_reqs_rx,
                 Duration::from_millis(connectivity_check_interval_ms),
-                ExponentialBackoff::from_millis(backoff_base).factor(1000),
-                Duration::from_millis(max_connection_delay_ms),
+                ReconnectBackoffStrategy::new(&reconnect_backoff),
+                reconnect_backoff.max_delay(),
+                reconnect_backoff.max_jitter(),
                 outbound_connection_limit,
                 mutual_authentication,
                 enable_latency_aware_dialing,
@@ -71,6 +106,14 @@ impl ConnectivityManagerBuilder {
             .connectivity_manager
             .take()
             .expect("Service Must be present");
-        executor.spawn(conn_mgr.start());
+        self.connectivity_manager_handle = Some(executor.spawn(conn_mgr.start()));
+    }
+
+    /// Stops the ConnectivityManager (if it was started), i.e., no new peers are
+    /// dialed. Existing connections are kept open.
+    pub fn stop(&self) {
+        if let Some(connectivity_manager_handle) = &self.connectivity_manager_handle {
+            connectivity_manager_handle.abort();
+        }
     }
 }
@@ -72,14 +72,6 @@ mod selection;
 #[cfg(test)]
 mod test;
 
-/// In addition to the backoff strategy, we also add some small random jitter to
-/// the delay before each dial. This jitter helps reduce the probability of
-/// simultaneous dials, especially in non-production environments where most nodes
-/// are spun up around the same time. Similarly, it smears the dials out in time
-/// to avoid spiky load / thundering herd issues where all dial requests happen
-/// around the same time at startup.
-const MAX_CONNECTION_DELAY_JITTER: Duration = Duration::from_millis(100);
-
 /// The maximum amount of time to wait before timing out a connection attempt.
 /// This should be relatively small to avoid blocking dials for too long.
 const MAX_CONNECTION_TIMEOUT_SECS: u64 = 2;
@@ -121,6 +113,9 @@ pub struct ConnectivityManager<TBackoff> {
     backoff_strategy: TBackoff,
     /// Maximum delay b/w 2 consecutive attempts to connect with a disconnected peer.
     max_delay: Duration,
+    /// Maximum random jitter added to the delay before each dial.
+    /// See `MAX_CONNECTION_DELAY_JITTER_MS` for more details.
+    max_dial_jitter: Duration,
     /// A local counter incremented on receiving an incoming message. Printing this in debugging
     /// allows for easy debugging.
     event_id: u32,
@@ -356,6 +351,7 @@ where
         connectivity_check_interval: Duration,
         backoff_strategy: TBackoff,
         max_delay: Duration,
+        max_dial_jitter: Duration,
         outbound_connection_limit: Option<usize>,
         mutual_authentication: bool,
         enable_latency_aware_dialing: bool,
@@ -391,6 +387,7 @@ where
             connectivity_check_interval,
             backoff_strategy,
             max_delay,
+            max_dial_jitter,
             event_id: 0,
             outbound_connection_limit,
             mutual_authentication,
@@ -754,9 +751,15 @@ where
             },
         };
 
+        // If we've already dialed this peer before (without success), this is
+        // a reconnect attempt.
+        if dial_state.is_reconnect() {
+            counters::reconnect_attempts(&self.network_context, &peer_id).inc();
+        }
+
         // Using the DialState's backoff strategy, compute the delay until
         // the next dial attempt for this peer.
-        let dial_delay = dial_state.next_backoff_delay(self.max_delay);
+        let dial_delay = dial_state.next_backoff_delay(self.max_delay, self.max_dial_jitter);
         let f_delay = self.time_service.sleep(dial_delay);
 
         let (cancel_tx, cancel_rx) = oneshot::channel();
@@ -1046,6 +1049,9 @@ where
                     );
                 }
             },
+            peer_manager::ConnectionNotification::UpdatedPeerProtocols(_, _) => {
+                // Protocol updates don't affect connectivity
+            },
         }
     }
 
@@ -1372,8 +1378,13 @@ where
         self.get_addr_at_index(addr_index, addrs)
     }
 
-    fn next_backoff_delay(&mut self, max_delay: Duration) -> Duration {
-        let jitter = jitter(MAX_CONNECTION_DELAY_JITTER);
+    /// Returns true iff this peer has already been dialed with this dial state
+    fn is_reconnect(&self) -> bool {
+        self.addr_idx > 0
+    }
+
+    fn next_backoff_delay(&mut self, max_delay: Duration, max_jitter: Duration) -> Duration {
+        let jitter = jitter(max_jitter);
 
         min(max_delay, self.backoff.next().unwrap_or(max_delay)) + jitter
     }
@@ -4,12 +4,16 @@
 
 use super::*;
 use crate::{
+    connectivity_manager::builder::ReconnectBackoffStrategy,
     peer_manager::{conn_notifs_channel, ConnectionNotification, ConnectionRequest},
     transport::ConnectionMetadata,
 };
 use aptos_channels::{aptos_channel, message_queues::QueueStyle};
 use aptos_config::{
-    config::{Peer, PeerRole, PeerSet, HANDSHAKE_VERSION},
+    config::{
+        Peer, PeerRole, PeerSet, ReconnectBackoff, HANDSHAKE_VERSION,
+        MAX_CONNECTION_DELAY_JITTER_MS,
+    },
     network_id::NetworkId,
 };
 use aptos_crypto::{test_utils::TEST_SEED, x25519, Uniform};
@@ -27,6 +31,7 @@ const CONNECTIVITY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 const CONNECTION_DELAY: Duration = Duration::from_millis(100);
 const MAX_CONNECTION_DELAY: Duration = Duration::from_secs(60);
 const DEFAULT_BASE_ADDR: &str = "/ip4/127.0.0.1/tcp/9090";
+const MAX_CONNECTION_DELAY_JITTER: Duration = Duration::from_millis(MAX_CONNECTION_DELAY_JITTER_MS);
 
 // TODO: the test code could use a lot of love.
 
@@ -84,6 +89,20 @@ struct TestHarness {
 
 impl TestHarness {
     fn new(seeds: PeerSet) -> (Self, ConnectivityManager<FixedInterval>) {
+        Self::new_with_backoff(
+            seeds,
+            FixedInterval::new(CONNECTION_DELAY),
+            MAX_CONNECTION_DELAY,
+            MAX_CONNECTION_DELAY_JITTER,
+        )
+    }
+
+    fn new_with_backoff<TBackoff: Iterator<Item = Duration> + Clone>(
+        seeds: PeerSet,
+        backoff_strategy: TBackoff,
+        max_delay: Duration,
+        max_dial_jitter: Duration,
+    ) -> (Self, ConnectivityManager<TBackoff>) {
         let network_context = NetworkContext::mock();
         let time_service = TimeService::mock();
         let (connection_reqs_tx, connection_reqs_rx) =
@@ -101,8 +120,9 @@ impl TestHarness {
             connection_notifs_rx,
             conn_mgr_reqs_rx,
             CONNECTIVITY_CHECK_INTERVAL,
-            FixedInterval::new(CONNECTION_DELAY),
-            MAX_CONNECTION_DELAY,
+            backoff_strategy,
+            max_delay,
+            max_dial_jitter,
             Some(MAX_TEST_CONNECTIONS),
             true, /* mutual_authentication */
             true, /* enable_latency_aware_dialing */
@@ -602,6 +622,50 @@ fn backoff_on_failure() {
     block_on(future::join(conn_mgr.start(), test));
 }
 
+#[test]
+fn reconnect_backoff_policy() {
+    // Create a backoff policy (without jitter) that caps after three attempts
+    let reconnect_backoff = ReconnectBackoff {
+        initial_ms: 10_000,
+        max_ms: 40_000,
+        multiplier: 2,
+        jitter_ms: 0,
+    };
+    let (mut mock, conn_mgr) = TestHarness::new_with_backoff(
+        HashMap::new(),
+        ReconnectBackoffStrategy::new(&reconnect_backoff),
+        reconnect_backoff.max_delay(),
+        reconnect_backoff.max_jitter(),
+    );
+
+    let test = async move {
+        let (peer_id, peer, _, peer_addr) = test_peer(AccountAddress::ONE);
+        let peers = hashmap! {peer_id => peer};
+        mock.send_update_discovered_peers(DiscoverySource::OnChainValidatorSet, peers)
+            .await;
+
+        // The delay should grow per the policy, up to the cap
+        for delay_secs in [10, 20, 40, 40] {
+            // Trigger a connectivity check and wait for the dial to be queued
+            mock.trigger_connectivity_check().await;
+            while mock.get_dial_queue_size().await == 0 {}
+
+            // Verify the dial is still pending just before the delay elapses
+            let delay = Duration::from_secs(delay_secs);
+            mock.mock_time
+                .advance_async(delay - Duration::from_millis(1))
+                .await;
+            assert_eq!(mock.get_dial_queue_size().await, 1);
+            assert!(mock.connection_reqs_rx.next().now_or_never().is_none());
+
+            // Verify the dial happens once the delay elapses
+            mock.mock_time.advance_async(Duration::from_millis(1)).await;
+            mock.expect_one_dial_fail(peer_id, peer_addr.clone()).await;
+        }
+    };
+    block_on(future::join(conn_mgr.start(), test));
+}
+
 // Test that connectivity manager will still connect to a peer if it advertises
 // multiple listen addresses and some of them don't work.
 #[test]
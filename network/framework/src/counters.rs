@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::protocols::wire::handshake::v1::ProtocolId;
-use aptos_config::network_id::NetworkContext;
+use aptos_config::network_id::{NetworkContext, NetworkId};
 use aptos_metrics_core::{
     exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge,
     register_int_gauge_vec, Histogram, HistogramTimer, HistogramVec, IntCounter, IntCounterVec,
@@ -13,6 +13,10 @@ use aptos_netcore::transport::ConnectionOrigin;
 use aptos_short_hex_str::AsShortHexStr;
 use aptos_types::PeerId;
 use once_cell::sync::Lazy;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
 
 // some type labels
 pub const REQUEST_LABEL: &str = "request";
@@ -21,12 +25,23 @@ pub const RESPONSE_LABEL: &str = "response";
 // some state labels
 pub const CANCELED_LABEL: &str = "canceled";
 pub const DECLINED_LABEL: &str = "declined";
+pub const DELIVERED_LABEL: &str = "delivered";
 pub const EXPIRED_LABEL: &str = "expired";
 pub const RECEIVED_LABEL: &str = "received";
 pub const SENT_LABEL: &str = "sent";
 pub const SUCCEEDED_LABEL: &str = "succeeded";
 pub const FAILED_LABEL: &str = "failed";
 pub const UNKNOWN_LABEL: &str = "unknown";
+pub const OVER_MEMORY_BUDGET_LABEL: &str = "over_memory_budget";
+
+// Handshake failure cause labels
+pub const HANDSHAKE_UNKNOWN_PEER_LABEL: &str = "unknown_peer";
+pub const HANDSHAKE_AUTH_REJECTED_LABEL: &str = "auth_rejected";
+pub const HANDSHAKE_CHAIN_ID_MISMATCH_LABEL: &str = "chain_id_mismatch";
+pub const HANDSHAKE_NETWORK_ID_MISMATCH_LABEL: &str = "network_id_mismatch";
+pub const HANDSHAKE_PROTOCOL_MISMATCH_LABEL: &str = "protocol_mismatch";
+pub const HANDSHAKE_TIMEOUT_LABEL: &str = "timeout";
+pub const HANDSHAKE_OTHER_LABEL: &str = "other";
 
 // Direction labels
 pub const INBOUND_LABEL: &str = "inbound";
@@ -36,6 +51,10 @@ pub const OUTBOUND_LABEL: &str = "outbound";
 const CONNECTED_LABEL: &str = "connected";
 const PRE_DIAL_LABEL: &str = "pre_dial";
 
+// Traffic type labels
+pub const BYTES_LABEL: &str = "bytes";
+pub const MESSAGES_LABEL: &str = "messages";
+
 // Serialization labels
 pub const SERIALIZATION_LABEL: &str = "serialization";
 pub const DESERIALIZATION_LABEL: &str = "deserialization";
@@ -79,6 +98,77 @@ pub fn connections_rejected(
     ])
 }
 
+pub static APTOS_NETWORK_ACTIVE_PEER_BANS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_network_active_peer_bans",
+        "Number of peers that are currently banned",
+        &["network_id"]
+    )
+    .unwrap()
+});
+
+pub fn active_peer_bans(network_id: NetworkId) -> IntGauge {
+    APTOS_NETWORK_ACTIVE_PEER_BANS.with_label_values(&[network_id.as_str()])
+}
+
+pub static APTOS_IDLE_CONNECTIONS_CLOSED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_idle_connections_closed",
+        "Number of connections closed due to inactivity",
+        &["role_type", "network_id", "peer_id"]
+    )
+    .unwrap()
+});
+
+pub fn idle_connections_closed(network_context: &NetworkContext) -> IntCounter {
+    APTOS_IDLE_CONNECTIONS_CLOSED.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        network_context.peer_id().short_str().as_str(),
+    ])
+}
+
+pub static APTOS_NETWORK_DUPLICATE_CONNECTIONS_RESOLVED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_duplicate_connections_resolved",
+        "Number of duplicate connections to the same peer resolved by dropping one of them",
+        &["role_type", "network_id", "peer_id", "dropped"]
+    )
+    .unwrap()
+});
+
+/// Returns the duplicate connection counter, labelled by the connection that was
+/// dropped (i.e., the "existing" or the "new" connection).
+pub fn duplicate_connections_resolved(
+    network_context: &NetworkContext,
+    dropped: &str,
+) -> IntCounter {
+    APTOS_NETWORK_DUPLICATE_CONNECTIONS_RESOLVED.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        network_context.peer_id().short_str().as_str(),
+        dropped,
+    ])
+}
+
+pub static APTOS_NETWORK_RECONNECT_ATTEMPTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_reconnect_attempts",
+        "Number of attempts to redial a peer after a failed or cancelled dial",
+        &["role_type", "network_id", "peer_id", "remote_peer_id"]
+    )
+    .unwrap()
+});
+
+pub fn reconnect_attempts(network_context: &NetworkContext, remote_peer_id: &PeerId) -> IntCounter {
+    APTOS_NETWORK_RECONNECT_ATTEMPTS.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        network_context.peer_id().short_str().as_str(),
+        remote_peer_id.short_str().as_str(),
+    ])
+}
+
 pub static APTOS_NETWORK_PEER_CONNECTED: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
         "aptos_network_peer_connected",
@@ -118,6 +208,28 @@ pub fn inc_by_with_context(
         .inc_by(val)
 }
 
+pub static APTOS_NETWORK_HANDSHAKE_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_handshake_failures",
+        "Number of failed connection handshakes, by cause",
+        &["role_type", "network_id", "direction", "cause"]
+    )
+    .unwrap()
+});
+
+pub fn handshake_failures(
+    network_context: &NetworkContext,
+    origin: ConnectionOrigin,
+    cause: &str,
+) -> IntCounter {
+    APTOS_NETWORK_HANDSHAKE_FAILURES.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        origin.as_str(),
+        cause,
+    ])
+}
+
 pub static APTOS_NETWORK_PENDING_CONNECTION_UPGRADES: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
         "aptos_network_pending_connection_upgrades",
@@ -162,6 +274,19 @@ pub fn connection_upgrade_time(
     ])
 }
 
+pub static APTOS_NETWORK_OUTBOUND_CONNECTION_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_network_outbound_connection_latency_seconds",
+        "Time from dialing a peer to successfully completing the connection handshake",
+        &["network_id"]
+    )
+    .unwrap()
+});
+
+pub fn outbound_connection_latency(network_id: NetworkId) -> Histogram {
+    APTOS_NETWORK_OUTBOUND_CONNECTION_LATENCY.with_label_values(&[network_id.as_str()])
+}
+
 pub static APTOS_NETWORK_DISCOVERY_NOTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
         "aptos_network_discovery_notes",
@@ -312,6 +437,60 @@ pub fn direct_send_messages(
     ])
 }
 
+pub static APTOS_NETWORK_OUTBOUND_MESSAGES_DROPPED_ON_DISCONNECT: Lazy<IntCounterVec> = Lazy::new(
+    || {
+        register_int_counter_vec!(
+            "aptos_network_outbound_messages_dropped_on_disconnect",
+            "Number of outbound messages (or stream fragments) dropped on disconnect before being written",
+            &["role_type", "network_id", "peer_id"]
+        )
+        .unwrap()
+    },
+);
+
+pub fn outbound_messages_dropped_on_disconnect(network_context: &NetworkContext) -> IntCounter {
+    APTOS_NETWORK_OUTBOUND_MESSAGES_DROPPED_ON_DISCONNECT.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        network_context.peer_id().short_str().as_str(),
+    ])
+}
+
+pub static APTOS_NETWORK_OUTBOUND_MESSAGE_WRITE_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_outbound_message_write_errors",
+        "Number of outbound messages (or stream fragments) that failed to be written to an open connection",
+        &["role_type", "network_id", "peer_id"]
+    )
+    .unwrap()
+});
+
+pub fn outbound_message_write_errors(network_context: &NetworkContext) -> IntCounter {
+    APTOS_NETWORK_OUTBOUND_MESSAGE_WRITE_ERRORS.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        network_context.peer_id().short_str().as_str(),
+    ])
+}
+
+pub static APTOS_NETWORK_KEEP_ALIVE_PINGS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_keep_alive_pings",
+        "Number of keep-alive pings sent on idle connections",
+        &["role_type", "network_id", "peer_id", "state"]
+    )
+    .unwrap()
+});
+
+pub fn keep_alive_pings(network_context: &NetworkContext, state_label: &'static str) -> IntCounter {
+    APTOS_NETWORK_KEEP_ALIVE_PINGS.with_label_values(&[
+        network_context.role().as_str(),
+        network_context.network_id().as_str(),
+        network_context.peer_id().short_str().as_str(),
+        state_label,
+    ])
+}
+
 pub static APTOS_NETWORK_DIRECT_SEND_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
         "aptos_network_direct_send_bytes",
@@ -655,3 +834,92 @@ pub fn inbound_queue_delay_observe(protocol_id: ProtocolId, seconds: f64) {
         .with_label_values(&[protocol_id.as_str()])
         .observe(seconds)
 }
+
+/// Counts the inbound messages (per protocol) that were received by the peer actors
+/// and handed to the application queues, and those that were delivered (i.e.,
+/// dequeued, not dropped as duplicates, and deserialized) to the applications. A
+/// growing gap indicates drops, backpressure or malformed messages.
+pub static INBOUND_APPLICATION_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_inbound_application_messages",
+        "Number of inbound messages received from peers and delivered to applications",
+        &["protocol_id", "state"]
+    )
+    .unwrap()
+});
+
+pub fn inbound_application_message_received(protocol_id: ProtocolId) {
+    INBOUND_APPLICATION_MESSAGES
+        .with_label_values(&[protocol_id.as_str(), RECEIVED_LABEL])
+        .inc()
+}
+
+pub fn inbound_application_message_delivered(protocol_id: ProtocolId) {
+    INBOUND_APPLICATION_MESSAGES
+        .with_label_values(&[protocol_id.as_str(), DELIVERED_LABEL])
+        .inc()
+}
+
+pub static INBOUND_DUPLICATE_MESSAGES_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_inbound_duplicate_messages_dropped",
+        "Number of inbound direct send messages dropped as recently seen duplicates",
+        &["protocol_id"]
+    )
+    .unwrap()
+});
+
+pub fn inbound_duplicate_message_dropped(protocol_id: ProtocolId) {
+    INBOUND_DUPLICATE_MESSAGES_DROPPED
+        .with_label_values(&[protocol_id.as_str()])
+        .inc()
+}
+
+/// The number of buckets that recipients are hashed into for the outbound peer traffic
+/// metrics. This bounds the label cardinality, regardless of the number of peers.
+pub const NUM_OUTBOUND_PEER_BUCKETS: u64 = 16;
+
+/// Counts the outbound application messages and bytes (per protocol), labeled by
+/// a hash bucket of the recipient (to help spot peers or protocols hogging bandwidth).
+pub static OUTBOUND_PEER_TRAFFIC: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_outbound_peer_traffic",
+        "Outbound application messages and bytes sent, by protocol and peer bucket",
+        &["protocol_id", "peer_bucket", "type"]
+    )
+    .unwrap()
+});
+
+/// Returns the bucket (used as a metric label) for the given peer
+pub fn outbound_peer_bucket(peer_id: &PeerId) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    peer_id.hash(&mut hasher);
+    hasher.finish() % NUM_OUTBOUND_PEER_BUCKETS
+}
+
+pub fn outbound_peer_message_sent(protocol_id: ProtocolId, peer_id: &PeerId, num_bytes: u64) {
+    let peer_bucket = outbound_peer_bucket(peer_id).to_string();
+    OUTBOUND_PEER_TRAFFIC
+        .with_label_values(&[protocol_id.as_str(), &peer_bucket, MESSAGES_LABEL])
+        .inc();
+    OUTBOUND_PEER_TRAFFIC
+        .with_label_values(&[protocol_id.as_str(), &peer_bucket, BYTES_LABEL])
+        .inc_by(num_bytes);
+}
+
+/// Counts the outbound direct-send messages that were dropped because the rate limit
+/// of their protocol (for the recipient) was exceeded (see `NetworkSender`)
+pub static OUTBOUND_MESSAGES_RATE_LIMITED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_network_outbound_messages_rate_limited",
+        "Number of outbound direct-send messages dropped by the outbound rate limits",
+        &["protocol_id"]
+    )
+    .unwrap()
+});
+
+pub fn outbound_message_rate_limited(protocol_id: ProtocolId) {
+    OUTBOUND_MESSAGES_RATE_LIMITED
+        .with_label_values(&[protocol_id.as_str()])
+        .inc();
+}
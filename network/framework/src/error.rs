@@ -24,6 +24,9 @@ pub enum NetworkErrorKind {
 
     #[error("Peer not connected")]
     NotConnected,
+
+    #[error("Outbound rate limit exceeded")]
+    RateLimited,
 }
 
 impl From<NetworkErrorKind> for NetworkError {
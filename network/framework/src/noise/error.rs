@@ -2,7 +2,7 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::application;
+use crate::{application, counters};
 use aptos_crypto::noise::NoiseError;
 use aptos_short_hex_str::ShortHexStr;
 use aptos_types::PeerId;
@@ -92,6 +92,22 @@ impl NoiseHandshakeError {
         use NoiseHandshakeError::*;
         matches!(self, ServerReplayDetected(_, _))
     }
+
+    /// Returns the cause of the handshake failure, as a metrics label
+    pub fn failure_cause(&self) -> &'static str {
+        use NoiseHandshakeError::*;
+        match self {
+            UnauthenticatedClient(_, _) => counters::HANDSHAKE_UNKNOWN_PEER_LABEL,
+            // The server closes the connection when it rejects our handshake message
+            ClientReadFailed(_)
+            | ClientExpectingDifferentPubkey(_, _, _)
+            | UnauthenticatedClientPubkey(_, _)
+            | ClientPeerIdMismatch(_, _, _)
+            | MissingAntiReplayTimestamp(_)
+            | ServerReplayDetected(_, _) => counters::HANDSHAKE_AUTH_REJECTED_LABEL,
+            _ => counters::HANDSHAKE_OTHER_LABEL,
+        }
+    }
 }
 
 impl From<application::error::Error> for NoiseHandshakeError {
@@ -121,6 +121,16 @@ impl HandshakeAuthMode {
         HandshakeAuthMode::maybe_mutual(peers_and_metadata)
     }
 
+    /// Returns the peers and metadata container used to authenticate peers
+    pub fn peers_and_metadata(&self) -> &Arc<PeersAndMetadata> {
+        match self {
+            HandshakeAuthMode::Mutual {
+                peers_and_metadata, ..
+            } => peers_and_metadata,
+            HandshakeAuthMode::MaybeMutual(peers_and_metadata) => peers_and_metadata,
+        }
+    }
+
     fn anti_replay_timestamps(&self) -> Option<&RwLock<AntiReplayTimestamps>> {
         match &self {
             HandshakeAuthMode::Mutual {
@@ -174,6 +184,11 @@ impl NoiseUpgrader {
         }
     }
 
+    /// Returns the peers and metadata container used to authenticate peers
+    pub fn peers_and_metadata(&self) -> &Arc<PeersAndMetadata> {
+        self.auth_mode.peers_and_metadata()
+    }
+
     /// Perform an outbound protocol upgrade on this connection.
     ///
     /// This runs the "client" side of the Noise IK handshake to establish a
@@ -268,12 +283,7 @@ impl NoiseUpgrader {
     /// the remote peer, an error is logged and we return an unknown peer role.
     fn extract_peer_role_from_trusted_peers(&self, remote_peer_id: PeerId) -> PeerRole {
         // Get the peers and metadata struct
-        let peers_and_metadata = match &self.auth_mode {
-            HandshakeAuthMode::Mutual {
-                peers_and_metadata, ..
-            } => peers_and_metadata.clone(),
-            HandshakeAuthMode::MaybeMutual(peers_and_metadata) => peers_and_metadata.clone(),
-        };
+        let peers_and_metadata = self.auth_mode.peers_and_metadata();
 
         // Determine the peer role
         match peers_and_metadata.get_trusted_peers(&self.network_context.network_id()) {
@@ -13,7 +13,10 @@ use crate::{
     transport::{Connection, ConnectionId, ConnectionMetadata},
 };
 use aptos_channels::{aptos_channel, message_queues::QueueStyle};
-use aptos_config::{config::PeerRole, network_id::NetworkContext};
+use aptos_config::{
+    config::{PeerRole, UnknownProtocolPolicy},
+    network_id::NetworkContext,
+};
 use aptos_memsocket::MemorySocket;
 use aptos_netcore::transport::ConnectionOrigin;
 use aptos_proptest_helpers::ValueGenerator;
@@ -108,6 +111,12 @@ pub fn fuzz(data: &[u8]) {
         constants::MAX_CONCURRENT_OUTBOUND_RPCS,
         constants::MAX_FRAME_SIZE,
         constants::MAX_MESSAGE_SIZE,
+        None,
+        None,
+        None,
+        UnknownProtocolPolicy::default(),
+        None,
+        None,
     );
     executor.spawn(peer.start());
 
@@ -0,0 +1,162 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! `InboundMemoryBudget` bounds the total number of bytes of inbound messages that
+//! are buffered in the upstream handler queues (i.e., received from remote peers, but
+//! not yet dequeued by the applications). Each upstream queue is bounded on its own,
+//! but with many protocols and peers, the sum across all queues can still be large.
+//!
+//! The budget is shared by all [`Peer`](crate::peer::Peer) actors of a network. The
+//! bytes of each message are reserved before the message is pushed into its upstream
+//! queue, and are released once the message is dequeued or dropped by the queue (this
+//! is observed lazily, through the queue's feedback channel). Messages that would
+//! exceed the budget are dropped by the caller.
+
+use crate::protocols::network::ReceivedMessage;
+use aptos_channels::aptos_channel::ElementStatus;
+use aptos_infallible::Mutex;
+use futures::channel::oneshot;
+
+/// The minimum number of pending reservations before released reservations are reaped
+const MIN_REAP_THRESHOLD: usize = 1024;
+
+/// The feedback channel to pass along with a message that reserved bytes in the budget
+pub type BudgetFeedbackSender = oneshot::Sender<ElementStatus<ReceivedMessage>>;
+
+#[derive(Debug)]
+pub struct InboundMemoryBudget {
+    max_buffered_bytes: usize,
+    state: Mutex<BudgetState>,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    buffered_bytes: usize,
+    reservations: Vec<(oneshot::Receiver<ElementStatus<ReceivedMessage>>, usize)>,
+    reap_threshold: usize,
+}
+
+impl InboundMemoryBudget {
+    pub fn new(max_buffered_bytes: usize) -> Self {
+        Self {
+            max_buffered_bytes,
+            state: Mutex::new(BudgetState {
+                buffered_bytes: 0,
+                reservations: vec![],
+                reap_threshold: MIN_REAP_THRESHOLD,
+            }),
+        }
+    }
+
+    /// Returns the maximum number of bytes that may be buffered
+    pub fn max_buffered_bytes(&self) -> usize {
+        self.max_buffered_bytes
+    }
+
+    /// Returns the number of bytes currently buffered in the upstream queues
+    pub fn buffered_bytes(&self) -> usize {
+        let mut state = self.state.lock();
+        state.reap();
+        state.buffered_bytes
+    }
+
+    /// Reserves `num_bytes` in the budget for an inbound message, returning the feedback
+    /// channel to push the message with (see `aptos_channel::Sender::push_with_feedback()`),
+    /// so that the bytes are released once the message leaves the queue. Returns `None` if
+    /// the reservation would exceed the budget, in which case the message should be dropped.
+    pub fn try_reserve(&self, num_bytes: usize) -> Option<BudgetFeedbackSender> {
+        let mut state = self.state.lock();
+        if state.reservations.len() >= state.reap_threshold
+            || state.buffered_bytes + num_bytes > self.max_buffered_bytes
+        {
+            state.reap();
+        }
+        if state.buffered_bytes + num_bytes > self.max_buffered_bytes {
+            return None;
+        }
+
+        let (feedback_tx, feedback_rx) = oneshot::channel();
+        state.buffered_bytes += num_bytes;
+        state.reservations.push((feedback_rx, num_bytes));
+        Some(feedback_tx)
+    }
+}
+
+impl BudgetState {
+    /// Releases the bytes of all messages that were dequeued or dropped
+    fn reap(&mut self) {
+        let mut released_bytes = 0;
+        self.reservations
+            .retain_mut(|(feedback_rx, num_bytes)| match feedback_rx.try_recv() {
+                Ok(None) => true, // The message is still queued
+                Ok(Some(_)) | Err(oneshot::Canceled) => {
+                    released_bytes += *num_bytes;
+                    false
+                },
+            });
+        self.buffered_bytes -= released_bytes;
+        self.reap_threshold = MIN_REAP_THRESHOLD.max(2 * self.reservations.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::wire::messaging::v1::{DirectSendMsg, NetworkMessage};
+    use aptos_channels::{aptos_channel, message_queues::QueueStyle};
+    use aptos_config::network_id::{NetworkId, PeerNetworkId};
+    use aptos_types::PeerId;
+    use futures::{FutureExt, StreamExt};
+
+    fn received_message(num_bytes: usize) -> ReceivedMessage {
+        ReceivedMessage::new(
+            NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: crate::ProtocolId::ConsensusDirectSendBcs,
+                priority: 0,
+                raw_msg: vec![0; num_bytes],
+            }),
+            PeerNetworkId::new(NetworkId::Validator, PeerId::random()),
+        )
+    }
+
+    #[test]
+    fn test_budget_released_on_dequeue_and_drop() {
+        let budget = InboundMemoryBudget::new(100);
+        let (sender, mut receiver) = aptos_channel::new(QueueStyle::FIFO, 1, None);
+
+        // Fill the budget, and verify that further reservations are rejected
+        let feedback_tx = budget.try_reserve(60).unwrap();
+        sender
+            .push_with_feedback(0, received_message(60), Some(feedback_tx))
+            .unwrap();
+        assert!(budget.try_reserve(50).is_none());
+        assert_eq!(budget.buffered_bytes(), 60);
+
+        // Dequeue the message, and verify the bytes are released
+        receiver.next().now_or_never().unwrap().unwrap();
+        assert_eq!(budget.buffered_bytes(), 0);
+
+        // Overflow the queue (dropping the newest message), and verify the bytes are released
+        for _ in 0..2 {
+            let feedback_tx = budget.try_reserve(40).unwrap();
+            sender
+                .push_with_feedback(0, received_message(40), Some(feedback_tx))
+                .unwrap();
+        }
+        assert_eq!(budget.buffered_bytes(), 40);
+
+        // Drop the channel, and verify the bytes of the queued message are released
+        drop(sender);
+        drop(receiver);
+        assert_eq!(budget.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_budget_released_on_unused_reservation() {
+        let budget = InboundMemoryBudget::new(100);
+        let feedback_tx = budget.try_reserve(100).unwrap();
+        assert!(budget.try_reserve(1).is_none());
+        drop(feedback_tx);
+        assert!(budget.try_reserve(100).is_some());
+    }
+}
@@ -18,25 +18,35 @@
 use crate::{
     counters::{
         self, network_application_inbound_traffic, network_application_outbound_traffic,
-        DECLINED_LABEL, FAILED_LABEL, RECEIVED_LABEL, SENT_LABEL, UNKNOWN_LABEL,
+        DECLINED_LABEL, FAILED_LABEL, INBOUND_LABEL, OVER_MEMORY_BUDGET_LABEL, RECEIVED_LABEL,
+        REQUEST_LABEL, SENT_LABEL, UNKNOWN_LABEL,
     },
     logging::NetworkSchema,
+    peer::memory_budget::InboundMemoryBudget,
     peer_manager::{PeerManagerError, TransportNotification},
     protocols::{
         direct_send::Message,
         network::ReceivedMessage,
         rpc::{error::RpcError, InboundRpcs, OutboundRpcRequest, OutboundRpcs},
         stream::{InboundStreamBuffer, OutboundStream, StreamMessage},
-        wire::messaging::v1::{
-            DirectSendMsg, ErrorCode, MultiplexMessage, MultiplexMessageSink,
-            MultiplexMessageStream, NetworkMessage, Priority, ReadError, WriteError,
+        wire::{
+            handshake::v1::MessagingFeature,
+            messaging::v1::{
+                CompressedMsg, DirectSendMsg, ErrorCode, MultiplexMessage, MultiplexMessageSink,
+                MultiplexMessageStream, NetworkMessage, Priority, ReadError, RpcRequest,
+                TracedDirectSendMsg, WriteError,
+            },
         },
     },
     transport::{self, Connection, ConnectionMetadata},
     ProtocolId,
 };
 use aptos_channels::{aptos_channel, message_queues::QueueStyle};
-use aptos_config::network_id::{NetworkContext, PeerNetworkId};
+use aptos_compression::client::CompressionClient;
+use aptos_config::{
+    config::{CompressionAlgo, UnknownProtocolPolicy},
+    network_id::{NetworkContext, PeerNetworkId},
+};
 use aptos_logger::prelude::*;
 use aptos_short_hex_str::AsShortHexStr;
 use aptos_time_service::{TimeService, TimeServiceTrait};
@@ -44,24 +54,68 @@ use aptos_types::PeerId;
 use futures::{
     self,
     channel::oneshot,
+    future::FutureExt,
     io::{AsyncRead, AsyncWrite},
-    stream::StreamExt,
+    stream::{select_with_strategy, PollNext, Stream, StreamExt},
     SinkExt,
 };
 use futures_util::stream::select;
 use serde::Serialize;
-use std::{collections::HashMap, fmt, panic, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt, panic,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{runtime::Handle, time::timeout};
 use tokio_util::compat::{
     FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt,
 };
 
+pub mod memory_budget;
 #[cfg(test)]
 mod test;
 
 #[cfg(any(test, feature = "fuzzing"))]
 pub mod fuzzing;
 
+/// The number of times the idle timeout is checked per timeout period (i.e., idle
+/// connections are closed at most a quarter of the timeout after it expires).
+const IDLE_CHECKS_PER_TIMEOUT: u32 = 4;
+
+/// The minimum data length of an outbound message for it to be compressed (smaller
+/// messages don't shrink enough to be worth the cost of compressing them).
+const MIN_COMPRESSION_DATA_LEN: usize = 1024;
+
+/// Returns true iff traffic on the given protocol counts as activity for the idle
+/// timeout. Health checker pings are periodic (on every connection), so they don't.
+fn is_application_protocol(protocol_id: ProtocolId) -> bool {
+    protocol_id != ProtocolId::HealthCheckerRpc
+}
+
+/// Returns true iff the inbound message counts as activity for the idle timeout (see
+/// `is_application_protocol()`). Keep-alive pings and errors never count, and neither
+/// do RPC responses (as the request was already counted).
+fn is_application_message(message: &MultiplexMessage) -> bool {
+    match message {
+        MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+            protocol_id,
+            ..
+        }))
+        | MultiplexMessage::Message(NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg {
+            message: DirectSendMsg { protocol_id, .. },
+            ..
+        }))
+        | MultiplexMessage::Message(NetworkMessage::RpcRequest(RpcRequest {
+            protocol_id, ..
+        })) => is_application_protocol(*protocol_id),
+        // Only large (i.e., application) messages are compressed or streamed
+        MultiplexMessage::Message(NetworkMessage::CompressedMsg(_)) => true,
+        MultiplexMessage::Message(_) => false,
+        MultiplexMessage::Stream(_) => true,
+    }
+}
+
 /// Requests [`Peer`] receives from the [`PeerManager`](crate::peer_manager::PeerManager).
 #[derive(Debug)]
 pub enum PeerRequest {
@@ -81,6 +135,8 @@ pub enum PeerRequest {
 pub enum DisconnectReason {
     Requested,
     ConnectionLost,
+    IdleTimeout,
+    UnknownProtocol,
 }
 
 impl fmt::Display for DisconnectReason {
@@ -88,6 +144,8 @@ impl fmt::Display for DisconnectReason {
         let s = match self {
             DisconnectReason::Requested => "Requested",
             DisconnectReason::ConnectionLost => "ConnectionLost",
+            DisconnectReason::IdleTimeout => "IdleTimeout",
+            DisconnectReason::UnknownProtocol => "UnknownProtocol",
         };
         write!(f, "{}", s)
     }
@@ -130,6 +188,24 @@ pub struct Peer<TSocket> {
     max_message_size: usize,
     /// Inbound stream buffer
     inbound_stream: InboundStreamBuffer,
+    /// Timeout after which the connection is closed if there is no traffic (if any)
+    idle_timeout: Option<Duration>,
+    /// The time of the last inbound or outbound application traffic on this connection
+    /// (see `is_application_protocol()`)
+    last_activity_time: Instant,
+    /// Interval at which keep-alive pings are sent if there is no outbound traffic (if any)
+    keep_alive_interval: Option<Duration>,
+    /// The time of the last outbound traffic (excluding keep-alive pings) on this connection
+    last_outbound_activity_time: Instant,
+    /// Timeout for flushing pending outbound messages when the connection is closed (if any)
+    drain_timeout: Option<Duration>,
+    /// How to handle inbound messages for unregistered protocols
+    unknown_protocol_policy: UnknownProtocolPolicy,
+    /// The budget for inbound messages buffered across all upstream handlers (if any)
+    inbound_memory_budget: Option<Arc<InboundMemoryBudget>>,
+    /// The algorithm used to compress outbound messages, if the remote peer can
+    /// decompress them (if any)
+    compression: Option<CompressionAlgo>,
 }
 
 impl<TSocket> Peer<TSocket>
@@ -152,6 +228,12 @@ where
         max_concurrent_outbound_rpcs: u32,
         max_frame_size: usize,
         max_message_size: usize,
+        idle_timeout: Option<Duration>,
+        keep_alive_interval: Option<Duration>,
+        drain_timeout: Option<Duration>,
+        unknown_protocol_policy: UnknownProtocolPolicy,
+        inbound_memory_budget: Option<Arc<InboundMemoryBudget>>,
+        compression: Option<CompressionAlgo>,
     ) -> Self {
         let Connection {
             metadata: connection_metadata,
@@ -177,7 +259,7 @@ where
             ),
             outbound_rpcs: OutboundRpcs::new(
                 network_context,
-                time_service,
+                time_service.clone(),
                 remote_peer_id,
                 max_concurrent_outbound_rpcs,
             ),
@@ -185,6 +267,14 @@ where
             max_frame_size,
             max_message_size,
             inbound_stream: InboundStreamBuffer::new(max_fragments),
+            idle_timeout,
+            last_activity_time: time_service.now(),
+            keep_alive_interval,
+            last_outbound_activity_time: time_service.now(),
+            drain_timeout,
+            unknown_protocol_policy,
+            inbound_memory_budget,
+            compression,
         }
     }
 
@@ -192,6 +282,13 @@ where
         self.connection_metadata.remote_peer_id
     }
 
+    /// Returns true iff both peers advertised the messaging feature in the handshake
+    fn supports_feature(&self, feature: MessagingFeature) -> bool {
+        self.connection_metadata
+            .application_protocols
+            .supports_feature(feature)
+    }
+
     pub async fn start(mut self) {
         let remote_peer_id = self.remote_peer_id();
         trace!(
@@ -210,19 +307,56 @@ where
             MultiplexMessageStream::new(read_socket.compat(), self.max_frame_size).fuse();
         let writer = MultiplexMessageSink::new(write_socket.compat_write(), self.max_frame_size);
 
-        // Start writer "process" as a separate task. We receive two handles to
+        // Only compress outbound messages if the remote peer can decompress them
+        let compression = self
+            .compression
+            .filter(|_| self.supports_feature(MessagingFeature::Compression));
+
+        // Start writer "process" as a separate task. We receive three handles to
         // communicate with the task:
         //   1. `write_reqs_tx`: Queue of pending NetworkMessages to write.
-        //   2. `close_tx`: Handle to close the task and underlying connection.
-        let (mut write_reqs_tx, writer_close_tx) = Self::start_writer_task(
-            &self.executor,
-            self.time_service.clone(),
-            self.connection_metadata.clone(),
-            self.network_context,
-            writer,
-            self.max_frame_size,
-            self.max_message_size,
-        );
+        //   2. `high_priority_write_reqs_tx`: Queue of pending high-priority
+        //      NetworkMessages to write (ahead of those in `write_reqs_tx`).
+        //   3. `close_tx`: Handle to close the task and underlying connection.
+        let (mut write_reqs_tx, mut high_priority_write_reqs_tx, writer_close_tx) =
+            Self::start_writer_task(
+                &self.executor,
+                self.time_service.clone(),
+                self.connection_metadata.clone(),
+                self.network_context,
+                writer,
+                self.max_frame_size,
+                self.max_message_size,
+                compression,
+            );
+
+        // Create the timer for checking if the connection is idle (if enabled)
+        let mut idle_check_timer = match self.idle_timeout {
+            Some(idle_timeout) => {
+                let idle_check_interval = idle_timeout / IDLE_CHECKS_PER_TIMEOUT;
+                self.time_service
+                    .interval_at(
+                        self.time_service.now() + idle_check_interval,
+                        idle_check_interval,
+                    )
+                    .boxed()
+                    .fuse()
+            },
+            None => futures::stream::pending().boxed().fuse(),
+        };
+
+        // Create the timer for sending keep-alive pings on idle connections (if enabled)
+        let mut keep_alive_timer = match self.keep_alive_interval {
+            Some(keep_alive_interval) => self
+                .time_service
+                .interval_at(
+                    self.time_service.now() + keep_alive_interval,
+                    keep_alive_interval,
+                )
+                .boxed()
+                .fuse(),
+            None => futures::stream::pending().boxed().fuse(),
+        };
 
         // Start main Peer event loop.
         let reason = loop {
@@ -234,7 +368,11 @@ where
                 // Handle a new outbound request from the PeerManager.
                 maybe_request = self.peer_reqs_rx.next() => {
                     match maybe_request {
-                        Some(request) => self.handle_outbound_request(request, &mut write_reqs_tx),
+                        Some(request) => self.handle_outbound_request(
+                            request,
+                            &mut write_reqs_tx,
+                            &mut high_priority_write_reqs_tx,
+                        ),
                         // The PeerManager is requesting this connection to close
                         // by dropping the corresponding peer_reqs_tx handle.
                         None => self.shutdown(DisconnectReason::Requested),
@@ -271,6 +409,7 @@ where
                     };
 
                     // Send the response to the remote peer
+                    self.last_outbound_activity_time = self.time_service.now();
                     if let Err(error) = self.inbound_rpcs.send_outbound_response(&mut write_reqs_tx, maybe_response) {
                         // It's quite common for applications to drop an RPC request.
                         // If this happens, we want to avoid logging a warning/error
@@ -300,23 +439,41 @@ where
                 // successfully or unsuccessfully completed request.
                 (request_id, maybe_completed_request) = self.outbound_rpcs.next_completed_request() => {
                     self.outbound_rpcs.handle_completed_request(request_id, maybe_completed_request);
+                },
+                // Periodically check if the connection has been idle for too long
+                _ = idle_check_timer.select_next_some() => {
+                    self.close_if_idle();
+                },
+                // Periodically send a keep-alive ping if there is no outbound traffic
+                _ = keep_alive_timer.select_next_some() => {
+                    self.send_keep_alive_if_idle(&mut write_reqs_tx);
                 }
             }
         };
 
         // Finish shutting down the connection. Close the writer task and notify
         // PeerManager that this connection has shutdown.
-        self.do_shutdown(write_reqs_tx, writer_close_tx, reason)
-            .await;
+        self.do_shutdown(
+            write_reqs_tx,
+            high_priority_write_reqs_tx,
+            writer_close_tx,
+            reason,
+        )
+        .await;
     }
 
     // Start a new task on the given executor which is responsible for writing outbound messages on
-    // the wire. The function returns two channels which can be used to send instructions to the
+    // the wire. The function returns three channels which can be used to send instructions to the
     // task:
     // 1. The first channel is used to send outbound NetworkMessages to the task
-    // 2. The second channel is used to instruct the task to close the connection and terminate.
-    // If outbound messages are queued when the task receives a close instruction, it discards
-    // them and immediately closes the connection.
+    // 2. The second channel is used to send high-priority outbound NetworkMessages to the task.
+    //    These are always written before any messages pending in the first channel.
+    // 3. The third channel is used to instruct the task to close the connection and terminate.
+    // If outbound messages are queued when the task receives a close instruction, it first
+    // attempts to write them for up to the drain timeout carried by the instruction (if any).
+    // Any messages that could not be written are discarded (and counted as dropped) before
+    // the connection is closed. If a compression algorithm is given, outbound messages are
+    // compressed before they are written (see `compress_message()`).
     fn start_writer_task(
         executor: &Handle,
         time_service: TimeService,
@@ -325,17 +482,25 @@ where
         mut writer: MultiplexMessageSink<impl AsyncWrite + Unpin + Send + 'static>,
         max_frame_size: usize,
         max_message_size: usize,
+        compression: Option<CompressionAlgo>,
     ) -> (
         aptos_channel::Sender<(), NetworkMessage>,
-        oneshot::Sender<()>,
+        aptos_channel::Sender<(), NetworkMessage>,
+        oneshot::Sender<Option<Duration>>,
     ) {
         let remote_peer_id = connection_metadata.remote_peer_id;
-        let (write_reqs_tx, mut write_reqs_rx): (aptos_channel::Sender<(), NetworkMessage>, _) =
+        let (write_reqs_tx, write_reqs_rx): (aptos_channel::Sender<(), NetworkMessage>, _) =
             aptos_channel::new(
                 QueueStyle::KLAST,
                 1024,
                 Some(&counters::PENDING_WIRE_MESSAGES),
             );
+        // The high-priority queue is kept small, as it should only hold latency sensitive messages
+        let (high_priority_write_reqs_tx, high_priority_write_reqs_rx) = aptos_channel::new(
+            QueueStyle::KLAST,
+            256,
+            Some(&counters::PENDING_WIRE_MESSAGES),
+        );
         let (close_tx, mut close_rx) = oneshot::channel();
 
         let (mut msg_tx, msg_rx) = aptos_channels::new(1024, &counters::PENDING_MULTIPLEX_MESSAGE);
@@ -350,7 +515,7 @@ where
             loop {
                 futures::select! {
                     message = stream.select_next_some() => {
-                        if let Err(err) = timeout(transport::TRANSPORT_TIMEOUT,writer.send(&message)).await {
+                        if let Err(err) = write_message(&mut writer, &message).await {
                             warn!(
                                 log_context,
                                 error = %err,
@@ -358,13 +523,37 @@ where
                                 network_context,
                                 remote_peer_id.short_str(),
                             );
+                            counters::outbound_message_write_errors(&network_context).inc();
                         }
                     }
-                    _ = close_rx => {
+                    drain_timeout = close_rx => {
+                        // Attempt to write the pending messages (if draining is enabled)
+                        if let Ok(Some(drain_timeout)) = drain_timeout {
+                            let drain = async {
+                                while let Some(message) = stream.next().await {
+                                    if write_message(&mut writer, &message).await.is_err() {
+                                        counters::outbound_messages_dropped_on_disconnect(&network_context).inc();
+                                    }
+                                }
+                            };
+                            if time_service.timeout(drain_timeout, drain).await.is_err() {
+                                info!(
+                                    log_context,
+                                    "{} Timeout in draining outbound messages to peer: {}",
+                                    network_context,
+                                    remote_peer_id.short_str()
+                                );
+                            }
+                        }
                         break;
                     }
                 }
             }
+
+            // Discard (and count) any messages that were not written
+            let num_dropped_messages = stream.count().await;
+            counters::outbound_messages_dropped_on_disconnect(&network_context)
+                .inc_by(num_dropped_messages as u64);
             info!(
                 log_context,
                 "{} Closing connection to peer: {}",
@@ -408,11 +597,17 @@ where
                 },
             }
         };
-        // the task ends when the write_reqs_tx is dropped
+        // the task ends when both the write_reqs_tx and high_priority_write_reqs_tx are dropped
         let multiplex_task = async move {
+            let mut write_reqs_rx =
+                prioritize_write_requests(high_priority_write_reqs_rx, write_reqs_rx);
             let mut outbound_stream =
                 OutboundStream::new(max_frame_size, max_message_size, stream_msg_tx);
             while let Some(message) = write_reqs_rx.next().await {
+                let message = match compression {
+                    Some(compression) => compress_message(message, compression, max_message_size),
+                    None => message,
+                };
                 // either channel full would block the other one
                 let result = if outbound_stream.should_stream(&message) {
                     outbound_stream.stream_message(message).await
@@ -434,7 +629,7 @@ where
         };
         executor.spawn(writer_task);
         executor.spawn(multiplex_task);
-        (write_reqs_tx, close_tx)
+        (write_reqs_tx, high_priority_write_reqs_tx, close_tx)
     }
 
     fn handle_inbound_network_message(
@@ -442,7 +637,10 @@ where
         message: NetworkMessage,
     ) -> Result<(), PeerManagerError> {
         match &message {
-            NetworkMessage::DirectSendMsg(direct) => {
+            NetworkMessage::DirectSendMsg(direct)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg {
+                message: direct, ..
+            }) => {
                 let data_len = direct.raw_msg.len();
                 network_application_inbound_traffic(
                     self.network_context,
@@ -450,17 +648,38 @@ where
                     data_len as u64,
                 );
                 match self.upstream_handlers.get(&direct.protocol_id) {
-                    None => {
-                        counters::direct_send_messages(&self.network_context, UNKNOWN_LABEL).inc();
-                        counters::direct_send_bytes(&self.network_context, UNKNOWN_LABEL)
-                            .inc_by(data_len as u64);
-                    },
+                    None => self.handle_unknown_protocol(direct.protocol_id, data_len as u64),
                     Some(handler) => {
+                        // Drop the message if it would exceed the inbound memory budget
+                        let feedback_tx = match &self.inbound_memory_budget {
+                            Some(budget) => match budget.try_reserve(data_len) {
+                                Some(feedback_tx) => Some(feedback_tx),
+                                None => {
+                                    counters::direct_send_messages(
+                                        &self.network_context,
+                                        OVER_MEMORY_BUDGET_LABEL,
+                                    )
+                                    .inc();
+                                    counters::direct_send_bytes(
+                                        &self.network_context,
+                                        OVER_MEMORY_BUDGET_LABEL,
+                                    )
+                                    .inc_by(data_len as u64);
+                                    return Ok(());
+                                },
+                            },
+                            None => None,
+                        };
+
                         let key = (self.connection_metadata.remote_peer_id, direct.protocol_id);
                         let sender = self.connection_metadata.remote_peer_id;
                         let network_id = self.network_context.network_id();
                         let sender = PeerNetworkId::new(network_id, sender);
-                        match handler.push(key, ReceivedMessage::new(message, sender)) {
+                        match handler.push_with_feedback(
+                            key,
+                            ReceivedMessage::new(message, sender),
+                            feedback_tx,
+                        ) {
                             Err(_err) => {
                                 // NOTE: aptos_channel never returns other than Ok(()), but we might switch to tokio::sync::mpsc and then this would work
                                 counters::direct_send_messages(
@@ -472,6 +691,7 @@ where
                                     .inc_by(data_len as u64);
                             },
                             Ok(_) => {
+                                counters::inbound_application_message_received(key.1);
                                 counters::direct_send_messages(
                                     &self.network_context,
                                     RECEIVED_LABEL,
@@ -497,31 +717,56 @@ where
             },
             NetworkMessage::RpcRequest(request) => {
                 match self.upstream_handlers.get(&request.protocol_id) {
-                    None => {
-                        counters::direct_send_messages(&self.network_context, UNKNOWN_LABEL).inc();
-                        counters::direct_send_bytes(&self.network_context, UNKNOWN_LABEL)
-                            .inc_by(request.raw_request.len() as u64);
-                    },
+                    None => self.handle_unknown_protocol(
+                        request.protocol_id,
+                        request.raw_request.len() as u64,
+                    ),
                     Some(handler) => {
+                        // Drop the request if it would exceed the inbound memory budget
+                        let feedback_tx = match &self.inbound_memory_budget {
+                            Some(budget) => match budget.try_reserve(request.raw_request.len()) {
+                                Some(feedback_tx) => Some(feedback_tx),
+                                None => {
+                                    counters::rpc_messages(
+                                        &self.network_context,
+                                        REQUEST_LABEL,
+                                        INBOUND_LABEL,
+                                        OVER_MEMORY_BUDGET_LABEL,
+                                    )
+                                    .inc();
+                                    return Ok(());
+                                },
+                            },
+                            None => None,
+                        };
+
+                        let protocol_id = request.protocol_id;
                         let sender = self.connection_metadata.remote_peer_id;
                         let network_id = self.network_context.network_id();
                         let sender = PeerNetworkId::new(network_id, sender);
-                        if let Err(err) = self
-                            .inbound_rpcs
-                            .handle_inbound_request(handler, ReceivedMessage::new(message, sender))
-                        {
-                            warn!(
-                                NetworkSchema::new(&self.network_context)
-                                    .connection_metadata(&self.connection_metadata),
-                                error = %err,
-                                "{} Error handling inbound rpc request: {}",
-                                self.network_context,
-                                err
-                            );
+                        match self.inbound_rpcs.handle_inbound_request(
+                            handler,
+                            ReceivedMessage::new(message, sender),
+                            feedback_tx,
+                        ) {
+                            Ok(()) => counters::inbound_application_message_received(protocol_id),
+                            Err(err) => {
+                                warn!(
+                                    NetworkSchema::new(&self.network_context)
+                                        .connection_metadata(&self.connection_metadata),
+                                    error = %err,
+                                    "{} Error handling inbound rpc request: {}",
+                                    self.network_context,
+                                    err
+                                );
+                            },
                         }
                     },
                 }
             },
+            NetworkMessage::KeepAlive => {
+                // Keep-alive pings only exist to generate traffic, so there is nothing to do
+            },
             NetworkMessage::RpcResponse(_) => {
                 // non-reference cast identical to this match case
                 let NetworkMessage::RpcResponse(response) = message else {
@@ -529,10 +774,62 @@ where
                 };
                 self.outbound_rpcs.handle_inbound_response(response)
             },
+            NetworkMessage::CompressedMsg(_) => {
+                // non-reference cast identical to this match case
+                let NetworkMessage::CompressedMsg(message) = message else {
+                    unreachable!("NetworkMessage type changed between match and let")
+                };
+                let message = self.decompress_message(message)?;
+                return self.handle_inbound_network_message(message);
+            },
         };
         Ok(())
     }
 
+    /// Decompresses an inbound compressed message (see `NetworkMessage::CompressedMsg`).
+    /// Note: compressed messages may not be nested, to bound the decompression work.
+    fn decompress_message(
+        &self,
+        message: CompressedMsg,
+    ) -> Result<NetworkMessage, PeerManagerError> {
+        let raw_msg = aptos_compression::decompress(
+            &message.compressed_msg,
+            CompressionClient::Network,
+            self.max_message_size,
+        )
+        .map_err(|error| anyhow::anyhow!("Failed to decompress message: {}", error))?;
+        match bcs::from_bytes(&raw_msg)? {
+            NetworkMessage::CompressedMsg(_) => Err(anyhow::anyhow!(
+                "Compressed message contains another compressed message"
+            )
+            .into()),
+            message => Ok(message),
+        }
+    }
+
+    /// Handles an inbound message for a protocol that has no upstream
+    /// handler, according to the configured unknown protocol policy.
+    fn handle_unknown_protocol(&mut self, protocol_id: ProtocolId, data_len: u64) {
+        if self.unknown_protocol_policy == UnknownProtocolPolicy::Ignore {
+            return;
+        }
+
+        counters::direct_send_messages(&self.network_context, UNKNOWN_LABEL).inc();
+        counters::direct_send_bytes(&self.network_context, UNKNOWN_LABEL).inc_by(data_len);
+
+        if self.unknown_protocol_policy == UnknownProtocolPolicy::Disconnect {
+            warn!(
+                NetworkSchema::new(&self.network_context)
+                    .connection_metadata(&self.connection_metadata),
+                "{} Disconnecting from peer {} after receiving a message for unknown protocol: {:?}",
+                self.network_context,
+                self.remote_peer_id().short_str(),
+                protocol_id
+            );
+            self.shutdown(DisconnectReason::UnknownProtocol);
+        }
+    }
+
     fn handle_inbound_stream_message(
         &mut self,
         message: StreamMessage,
@@ -563,6 +860,10 @@ where
             self.remote_peer_id().short_str()
         );
 
+        if matches!(&message, Ok(message) if is_application_message(message)) {
+            self.last_activity_time = self.time_service.now();
+        }
+
         let message = match message {
             Ok(message) => message,
             Err(err) => match err {
@@ -596,12 +897,21 @@ where
         &mut self,
         request: PeerRequest,
         write_reqs_tx: &mut aptos_channel::Sender<(), NetworkMessage>,
+        high_priority_write_reqs_tx: &mut aptos_channel::Sender<(), NetworkMessage>,
     ) {
         trace!(
             "Peer {} PeerRequest::{:?}",
             self.remote_peer_id().short_str(),
             request
         );
+        self.last_outbound_activity_time = self.time_service.now();
+        let protocol_id = match &request {
+            PeerRequest::SendRpc(request) => request.protocol_id,
+            PeerRequest::SendDirectSend(message) => message.protocol_id,
+        };
+        if is_application_protocol(protocol_id) {
+            self.last_activity_time = self.last_outbound_activity_time;
+        }
         match request {
             // To send an outbound DirectSendMsg, we just bump some counters and
             // push it onto our outbound writer queue.
@@ -609,12 +919,30 @@ where
                 // Create the direct send message
                 let message_len = message.mdata.len();
                 let protocol_id = message.protocol_id;
-                let message = NetworkMessage::DirectSendMsg(DirectSendMsg {
+                let direct_send_msg = DirectSendMsg {
                     protocol_id,
                     priority: Priority::default(),
                     raw_msg: Vec::from(message.mdata.as_ref()),
-                });
+                };
+                // Only attach the trace id if one was specified (to avoid any wire overhead),
+                // and the remote peer can parse traced messages (otherwise, the trace id is
+                // dropped).
+                let message = match message.trace_id {
+                    Some(trace_id) if self.supports_feature(MessagingFeature::TracedDirectSend) => {
+                        NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg {
+                            trace_id,
+                            message: direct_send_msg,
+                        })
+                    },
+                    _ => NetworkMessage::DirectSendMsg(direct_send_msg),
+                };
 
+                // Messages for high-priority protocols skip ahead of normal traffic
+                let write_reqs_tx = if protocol_id.is_high_priority() {
+                    high_priority_write_reqs_tx
+                } else {
+                    write_reqs_tx
+                };
                 match write_reqs_tx.push((), message) {
                     Ok(_) => {
                         self.update_outbound_direct_send_metrics(protocol_id, message_len as u64);
@@ -635,6 +963,11 @@ where
             },
             PeerRequest::SendRpc(request) => {
                 let protocol_id = request.protocol_id;
+                let write_reqs_tx = if protocol_id.is_high_priority() {
+                    high_priority_write_reqs_tx
+                } else {
+                    write_reqs_tx
+                };
                 if let Err(e) = self
                     .outbound_rpcs
                     .handle_outbound_request(request, write_reqs_tx)
@@ -663,6 +996,68 @@ where
         network_application_outbound_traffic(self.network_context, protocol_id, data_len);
     }
 
+    /// Shuts down the connection if there has been no inbound or outbound
+    /// application traffic for longer than the idle timeout.
+    fn close_if_idle(&mut self) {
+        if let Some(idle_timeout) = self.idle_timeout {
+            let idle_duration = self
+                .time_service
+                .now()
+                .saturating_duration_since(self.last_activity_time);
+            if idle_duration >= idle_timeout {
+                info!(
+                    NetworkSchema::new(&self.network_context)
+                        .connection_metadata(&self.connection_metadata),
+                    "{} Closing idle connection to peer: {} (idle for {:?})",
+                    self.network_context,
+                    self.remote_peer_id().short_str(),
+                    idle_duration
+                );
+                counters::idle_connections_closed(&self.network_context).inc();
+                self.shutdown(DisconnectReason::IdleTimeout);
+            }
+        }
+    }
+
+    /// Sends a keep-alive ping to the remote peer if there has been no outbound
+    /// traffic on the connection for the keep-alive interval. Note: keep-alive
+    /// pings are not counted as activity for the idle timeout of this connection,
+    /// and are only sent to peers that can parse them.
+    fn send_keep_alive_if_idle(
+        &mut self,
+        write_reqs_tx: &mut aptos_channel::Sender<(), NetworkMessage>,
+    ) {
+        if !self.supports_feature(MessagingFeature::KeepAlive) {
+            return;
+        }
+
+        if let Some(keep_alive_interval) = self.keep_alive_interval {
+            let idle_duration = self
+                .time_service
+                .now()
+                .saturating_duration_since(self.last_outbound_activity_time);
+            if idle_duration < keep_alive_interval {
+                return;
+            }
+
+            match write_reqs_tx.push((), NetworkMessage::KeepAlive) {
+                Ok(()) => counters::keep_alive_pings(&self.network_context, SENT_LABEL).inc(),
+                Err(err) => {
+                    warn!(
+                        NetworkSchema::new(&self.network_context)
+                            .connection_metadata(&self.connection_metadata),
+                        error = %err,
+                        "{} Failed to send keep-alive ping to peer: {}, error: {}",
+                        self.network_context,
+                        self.remote_peer_id().short_str(),
+                        err
+                    );
+                    counters::keep_alive_pings(&self.network_context, FAILED_LABEL).inc();
+                },
+            }
+        }
+    }
+
     fn shutdown(&mut self, reason: DisconnectReason) {
         // Set the state of the actor to `State::ShuttingDown` to true ensures that the peer actor
         // will terminate and close the connection.
@@ -671,17 +1066,40 @@ where
 
     async fn do_shutdown(
         mut self,
-        write_req_tx: aptos_channel::Sender<(), NetworkMessage>,
-        writer_close_tx: oneshot::Sender<()>,
+        mut write_req_tx: aptos_channel::Sender<(), NetworkMessage>,
+        mut high_priority_write_req_tx: aptos_channel::Sender<(), NetworkMessage>,
+        writer_close_tx: oneshot::Sender<Option<Duration>>,
         reason: DisconnectReason,
     ) {
-        // Drop the sender to shut down multiplex task.
+        // Pending messages can only be drained if the connection is still usable
+        let drain_timeout = match reason {
+            DisconnectReason::ConnectionLost => None,
+            _ => self.drain_timeout,
+        };
+
+        // Handle the requests that are still pending from the PeerManager. If draining
+        // is enabled, direct send messages are queued for writing. Otherwise (and for
+        // rpcs, as their responses can no longer be received), the requests are dropped.
+        while let Some(Some(request)) = self.peer_reqs_rx.next().now_or_never() {
+            match request {
+                PeerRequest::SendDirectSend(_) if drain_timeout.is_some() => self
+                    .handle_outbound_request(
+                        request,
+                        &mut write_req_tx,
+                        &mut high_priority_write_req_tx,
+                    ),
+                _ => counters::outbound_messages_dropped_on_disconnect(&self.network_context).inc(),
+            }
+        }
+
+        // Drop the senders to shut down multiplex task.
         drop(write_req_tx);
+        drop(high_priority_write_req_tx);
 
         // Send a close instruction to the writer task. On receipt of this
-        // instruction, the writer task drops all pending outbound messages and
-        // closes the connection.
-        if let Err(e) = writer_close_tx.send(()) {
+        // instruction, the writer task drains the pending outbound messages
+        // (if enabled), drops the rest and closes the connection.
+        if let Err(e) = writer_close_tx.send(drain_timeout) {
             info!(
                 NetworkSchema::new(&self.network_context)
                     .connection_metadata(&self.connection_metadata),
@@ -722,3 +1140,48 @@ where
         );
     }
 }
+
+/// Writes the given message to the connection, failing if the write fails or times out
+async fn write_message(
+    writer: &mut MultiplexMessageSink<impl AsyncWrite + Unpin>,
+    message: &MultiplexMessage,
+) -> anyhow::Result<()> {
+    timeout(transport::TRANSPORT_TIMEOUT, writer.send(message)).await??;
+    Ok(())
+}
+
+/// Compresses the outbound message with the given algorithm. Messages that are too
+/// small, that don't shrink, or that can't be compressed are returned unchanged.
+fn compress_message(
+    message: NetworkMessage,
+    compression: CompressionAlgo,
+    max_message_size: usize,
+) -> NetworkMessage {
+    let data_len = message.data_len();
+    if data_len < MIN_COMPRESSION_DATA_LEN {
+        return message;
+    }
+
+    let compressed_msg = match compression {
+        CompressionAlgo::Lz4 => bcs::to_bytes(&message).ok().and_then(|raw_msg| {
+            aptos_compression::compress(raw_msg, CompressionClient::Network, max_message_size).ok()
+        }),
+    };
+    match compressed_msg {
+        Some(compressed_msg) if compressed_msg.len() < data_len => {
+            NetworkMessage::CompressedMsg(CompressedMsg { compressed_msg })
+        },
+        _ => message,
+    }
+}
+
+/// Merges the queues of pending outbound messages into a single stream of messages
+/// to write, where messages in the high-priority queue are always written first.
+fn prioritize_write_requests(
+    high_priority_write_reqs_rx: aptos_channel::Receiver<(), NetworkMessage>,
+    write_reqs_rx: aptos_channel::Receiver<(), NetworkMessage>,
+) -> impl Stream<Item = NetworkMessage> + Unpin {
+    select_with_strategy(high_priority_write_reqs_rx, write_reqs_rx, |_: &mut ()| {
+        PollNext::Left
+    })
+}
@@ -7,17 +7,24 @@ use crate::{
         INBOUND_RPC_TIMEOUT_MS, MAX_CONCURRENT_INBOUND_RPCS, MAX_CONCURRENT_OUTBOUND_RPCS,
         MAX_FRAME_SIZE, MAX_MESSAGE_SIZE, NETWORK_CHANNEL_SIZE,
     },
-    peer::{DisconnectReason, Peer, PeerRequest},
+    counters::{
+        self, DELIVERED_LABEL, EXPIRED_LABEL, INBOUND_LABEL, OVER_MEMORY_BUDGET_LABEL,
+        RECEIVED_LABEL, REQUEST_LABEL, SENT_LABEL, UNKNOWN_LABEL,
+    },
+    peer::{
+        memory_budget::InboundMemoryBudget, prioritize_write_requests, DisconnectReason, Peer,
+        PeerRequest,
+    },
     peer_manager::TransportNotification,
     protocols::{
         direct_send::Message,
-        network::ReceivedMessage,
+        network::{Event, NetworkEvents, NewNetworkEvents, ReceivedMessage, RpcResponder},
         rpc::{error::RpcError, OutboundRpcRequest},
         wire::{
-            handshake::v1::{MessagingProtocolVersion, ProtocolIdSet},
+            handshake::v1::{MessagingFeature, MessagingProtocolVersion, ProtocolIdSet},
             messaging::v1::{
-                DirectSendMsg, MultiplexMessage, MultiplexMessageSink, MultiplexMessageStream,
-                NetworkMessage, RpcRequest, RpcResponse,
+                CompressedMsg, DirectSendMsg, MultiplexMessage, MultiplexMessageSink,
+                MultiplexMessageStream, NetworkMessage, RpcRequest, RpcResponse,
             },
         },
     },
@@ -25,7 +32,11 @@ use crate::{
     ProtocolId,
 };
 use aptos_channels::{self, aptos_channel, message_queues::QueueStyle};
-use aptos_config::{config::PeerRole, network_id::NetworkContext};
+use aptos_compression::client::CompressionClient;
+use aptos_config::{
+    config::{CompressionAlgo, PeerRole, UnknownProtocolPolicy},
+    network_id::NetworkContext,
+};
 use aptos_logger::info;
 use aptos_memsocket::MemorySocket;
 use aptos_netcore::transport::ConnectionOrigin;
@@ -34,6 +45,7 @@ use aptos_types::{network_address::NetworkAddress, PeerId};
 use bytes::Bytes;
 use futures::{
     channel::oneshot,
+    executor::block_on,
     future::{self, FutureExt},
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
     stream::{StreamExt, TryStreamExt},
@@ -64,6 +76,40 @@ fn build_test_peer(
     PeerHandle,
     MemorySocket,
     aptos_channels::Receiver<TransportNotification<MemorySocket>>,
+) {
+    build_test_peer_with_options(
+        executor,
+        time_service,
+        origin,
+        upstream_handlers,
+        None,
+        None,
+        None,
+        UnknownProtocolPolicy::default(),
+        None,
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_test_peer_with_options(
+    executor: Handle,
+    time_service: TimeService,
+    origin: ConnectionOrigin,
+    upstream_handlers: Arc<
+        HashMap<ProtocolId, aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>>,
+    >,
+    idle_timeout: Option<Duration>,
+    keep_alive_interval: Option<Duration>,
+    drain_timeout: Option<Duration>,
+    unknown_protocol_policy: UnknownProtocolPolicy,
+    inbound_memory_budget: Option<Arc<InboundMemoryBudget>>,
+    compression: Option<CompressionAlgo>,
+) -> (
+    Peer<MemorySocket>,
+    PeerHandle,
+    MemorySocket,
+    aptos_channels::Receiver<TransportNotification<MemorySocket>>,
 ) {
     let (a, b) = MemorySocket::new_pair();
     let peer_id = PeerId::random();
@@ -97,6 +143,12 @@ fn build_test_peer(
         MAX_CONCURRENT_OUTBOUND_RPCS,
         MAX_FRAME_SIZE,
         MAX_MESSAGE_SIZE,
+        idle_timeout,
+        keep_alive_interval,
+        drain_timeout,
+        unknown_protocol_policy,
+        inbound_memory_budget,
+        compression,
     );
     let peer_handle = PeerHandle(peer_reqs_tx);
 
@@ -217,6 +269,7 @@ fn peer_send_message() {
     let send_msg = Message {
         protocol_id: PROTOCOL,
         mdata: Bytes::from("hello world"),
+        trace_id: None,
     };
     let recv_msg = MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
         protocol_id: PROTOCOL,
@@ -306,6 +359,71 @@ fn peer_recv_message() {
     info!("done");
 }
 
+// Inbound messages that the Peer hands to the application queue should be counted as
+// received, but only those that the application dequeues and deserializes should be
+// counted as delivered.
+#[test]
+fn peer_recv_message_fan_in_gap() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+
+    // Create network events with a small inbound queue
+    let protocol_id = ProtocolId::NetbenchDirectSend;
+    let (sender, receiver) = aptos_channel::new(QueueStyle::FIFO, 2, None);
+    let upstream_handlers = Arc::new(HashMap::from([(protocol_id, sender)]));
+    let mut network_events: NetworkEvents<u64> =
+        NetworkEvents::new(receiver, None, false, None, None);
+    let (peer, _peer_handle, connection, _connection_notifs_rx) = build_test_peer(
+        rt.handle().clone(),
+        TimeService::mock(),
+        ConnectionOrigin::Inbound,
+        upstream_handlers,
+    );
+
+    let get_count = |state| {
+        counters::INBOUND_APPLICATION_MESSAGES
+            .with_label_values(&[protocol_id.as_str(), state])
+            .get()
+    };
+    let initial_received = get_count(RECEIVED_LABEL);
+    let initial_delivered = get_count(DELIVERED_LABEL);
+
+    // Send a malformed message, followed by more valid messages than fit in the queue
+    let malformed_message = vec![0xFF];
+    let valid_messages = (0..4u64).map(|i| protocol_id.to_bytes(&i).unwrap());
+    let raw_messages: Vec<_> = std::iter::once(malformed_message)
+        .chain(valid_messages)
+        .collect();
+    let num_messages = raw_messages.len() as u64;
+    let client = async move {
+        let mut connection = MultiplexMessageSink::new(connection, MAX_FRAME_SIZE);
+        for raw_msg in raw_messages {
+            let message = MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id,
+                priority: 0,
+                raw_msg,
+            }));
+            connection.send(&message).await.unwrap();
+        }
+        connection.close().await.unwrap();
+    };
+    rt.block_on(future::join(peer.start(), client));
+
+    // Only the messages that fit in the queue are dequeued, and the malformed one is
+    // dropped, so only the first valid message is delivered.
+    rt.block_on(async {
+        match network_events.next().await {
+            Some(Event::Message(_, message)) => assert_eq!(message, 0),
+            event => panic!("Unexpected network event: {:?}", event),
+        }
+        assert!(network_events.next().await.is_none());
+    });
+
+    // Verify the gap between received and delivered messages
+    assert_eq!(get_count(RECEIVED_LABEL) - initial_received, num_messages);
+    assert_eq!(get_count(DELIVERED_LABEL) - initial_delivered, 1);
+}
+
 // Two connected Peer actors should be able to send/recv a DirectSend from each
 // other and then shutdown gracefully.
 #[test]
@@ -331,10 +449,12 @@ fn peers_send_message_concurrent() {
         let msg_a = Message {
             protocol_id: PROTOCOL,
             mdata: Bytes::from("hello world"),
+            trace_id: None,
         };
         let msg_b = Message {
             protocol_id: PROTOCOL,
             mdata: Bytes::from("namaste"),
+            trace_id: None,
         };
 
         // Peer A -> msg_a -> Peer B
@@ -383,6 +503,114 @@ fn peers_send_message_concurrent() {
     rt.block_on(future::join3(peer_a.start(), peer_b.start(), test));
 }
 
+#[test]
+fn peers_send_message_with_trace_id() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let (upstream_handlers_a, _prot_a_rx) = test_upstream_handlers();
+    let (upstream_handlers_b, mut prot_b_rx) = test_upstream_handlers();
+    let (
+        (mut peer_a, mut peer_handle_a, _connection_notifs_rx_a),
+        (peer_b, peer_handle_b, _connection_notifs_rx_b),
+    ) = build_test_connected_peers(
+        rt.handle().clone(),
+        TimeService::mock(),
+        upstream_handlers_a,
+        upstream_handlers_b,
+    );
+
+    // Peer B advertised support for traced direct sends during the handshake
+    peer_a
+        .connection_metadata
+        .application_protocols
+        .insert_feature(MessagingFeature::TracedDirectSend);
+
+    let test = async move {
+        // Peer A -> traced message -> Peer B
+        let msg = Message {
+            protocol_id: PROTOCOL,
+            mdata: Bytes::from("hello world"),
+            trace_id: Some(1234),
+        };
+        peer_handle_a.send_direct_send(msg.clone());
+
+        // Check that peer B received the message along with the trace id
+        let notif = prot_b_rx.next().await.unwrap();
+        assert_eq!(notif.trace_id(), Some(1234));
+        assert_eq!(notif.protocol_id(), Some(PROTOCOL));
+        assert_eq!(notif.message.data_len(), msg.mdata.len());
+
+        // Peer A -> untraced message -> Peer B
+        let msg = Message {
+            protocol_id: PROTOCOL,
+            mdata: Bytes::from("namaste"),
+            trace_id: None,
+        };
+        peer_handle_a.send_direct_send(msg.clone());
+        let notif = prot_b_rx.next().await.unwrap();
+        assert_eq!(notif.trace_id(), None);
+        assert_eq!(
+            notif.message,
+            NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: PROTOCOL,
+                priority: 0,
+                raw_msg: msg.mdata.into(),
+            })
+        );
+
+        // Shutdown the peers
+        drop(peer_handle_a);
+        drop(peer_handle_b);
+    };
+
+    rt.block_on(future::join3(peer_a.start(), peer_b.start(), test));
+}
+
+#[test]
+fn peers_send_message_with_trace_id_unsupported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let (upstream_handlers_a, _prot_a_rx) = test_upstream_handlers();
+    let (upstream_handlers_b, mut prot_b_rx) = test_upstream_handlers();
+    let (
+        (peer_a, mut peer_handle_a, _connection_notifs_rx_a),
+        (peer_b, peer_handle_b, _connection_notifs_rx_b),
+    ) = build_test_connected_peers(
+        rt.handle().clone(),
+        TimeService::mock(),
+        upstream_handlers_a,
+        upstream_handlers_b,
+    );
+
+    let test = async move {
+        // Peer A -> traced message -> Peer B (which didn't advertise traced direct sends)
+        let msg = Message {
+            protocol_id: PROTOCOL,
+            mdata: Bytes::from("hello world"),
+            trace_id: Some(1234),
+        };
+        peer_handle_a.send_direct_send(msg.clone());
+
+        // Check that peer B received a plain direct send without the trace id
+        let notif = prot_b_rx.next().await.unwrap();
+        assert_eq!(notif.trace_id(), None);
+        assert_eq!(
+            notif.message,
+            NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: PROTOCOL,
+                priority: 0,
+                raw_msg: msg.mdata.into(),
+            })
+        );
+
+        // Shutdown the peers
+        drop(peer_handle_a);
+        drop(peer_handle_b);
+    };
+
+    rt.block_on(future::join3(peer_a.start(), peer_b.start(), test));
+}
+
 #[test]
 fn peer_recv_rpc() {
     ::aptos_logger::Logger::init_for_testing();
@@ -578,6 +806,67 @@ fn peer_recv_rpc_timeout() {
     rt.block_on(future::join(peer.start(), test));
 }
 
+#[test]
+fn peer_recv_rpc_responder_expired() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let mock_time = MockTimeService::new();
+    let (upstream_handlers, mut prot_rx) = test_upstream_handlers();
+    let (peer, _peer_handle, mut connection, _connection_notifs_rx) = build_test_peer(
+        rt.handle().clone(),
+        mock_time.clone().into(),
+        ConnectionOrigin::Inbound,
+        upstream_handlers,
+    );
+    let (mut client_sink, client_stream) = build_network_sink_stream(&mut connection);
+    let expired_requests = counters::rpc_messages(
+        &peer.network_context,
+        REQUEST_LABEL,
+        INBOUND_LABEL,
+        EXPIRED_LABEL,
+    );
+
+    let send_msg = MultiplexMessage::Message(NetworkMessage::RpcRequest(RpcRequest {
+        request_id: 123,
+        protocol_id: PROTOCOL,
+        priority: 0,
+        raw_request: Vec::from("hello world"),
+    }));
+
+    let test = async move {
+        // Client sends the rpc request.
+        client_sink.send(&send_msg).await.unwrap();
+
+        // Server receives the rpc request from client, and wraps the completion handle.
+        let received = prot_rx.next().await.unwrap();
+        let responder = RpcResponder::new(
+            PROTOCOL,
+            Arc::into_inner(received.rpc_replier.unwrap()).unwrap(),
+        );
+        assert!(!responder.is_expired());
+        assert_eq!(expired_requests.get(), 0);
+
+        // Advancing time should expire the responder (without a reply).
+        mock_time.advance_ms_async(INBOUND_RPC_TIMEOUT_MS).await;
+        assert!(responder.is_expired());
+        assert_eq!(expired_requests.get(), 1);
+
+        // A late reply should fail with a typed error.
+        assert!(matches!(
+            responder.send_raw(Ok(Bytes::from("goodbye world"))),
+            Err(RpcError::ResponderExpired)
+        ));
+
+        // Client then half-closes write side.
+        client_sink.close().await.unwrap();
+
+        // Client shouldn't have received any messages.
+        let messages = client_stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(messages, vec![]);
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
 #[test]
 fn peer_recv_rpc_cancel() {
     ::aptos_logger::Logger::init_for_testing();
@@ -940,6 +1229,607 @@ fn peer_disconnect_connection_lost() {
     rt.block_on(future::join(peer.start(), test));
 }
 
+// Peer will shutdown if the connection has no traffic for the idle timeout.
+#[test]
+fn peer_disconnect_idle_timeout() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let mock_time = MockTimeService::new();
+    let idle_timeout = Duration::from_secs(60);
+    let upstream_handlers = Arc::new(HashMap::new());
+    let (peer, _peer_handle, _connection, mut connection_notifs_rx) = build_test_peer_with_options(
+        rt.handle().clone(),
+        mock_time.clone().into(),
+        ConnectionOrigin::Inbound,
+        upstream_handlers,
+        Some(idle_timeout),
+        None,
+        None,
+        UnknownProtocolPolicy::default(),
+        None,
+        None,
+    );
+    let remote_peer_id = peer.remote_peer_id();
+
+    let test = async move {
+        // Advance time past the idle timeout and verify the connection is closed
+        mock_time.advance_async(idle_timeout).await;
+        assert_disconnected_event(
+            remote_peer_id,
+            DisconnectReason::IdleTimeout,
+            &mut connection_notifs_rx,
+        )
+        .await;
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
+// Health checker and keep-alive pings don't count as traffic for the idle timeout.
+#[test]
+fn peer_disconnect_idle_timeout_ignores_pings() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let mock_time = MockTimeService::new();
+    let idle_timeout = Duration::from_secs(60);
+    let (health_checker_tx, mut health_checker_rx) =
+        aptos_channel::new(QueueStyle::FIFO, 100, None);
+    let upstream_handlers = Arc::new(HashMap::from([(
+        ProtocolId::HealthCheckerRpc,
+        health_checker_tx,
+    )]));
+    let (peer, _peer_handle, mut connection, mut connection_notifs_rx) =
+        build_test_peer_with_options(
+            rt.handle().clone(),
+            mock_time.clone().into(),
+            ConnectionOrigin::Inbound,
+            upstream_handlers,
+            Some(idle_timeout),
+            None,
+            None,
+            UnknownProtocolPolicy::default(),
+            None,
+            None,
+        );
+    let remote_peer_id = peer.remote_peer_id();
+
+    let test = async move {
+        let (mut client_sink, _client_stream) = build_network_sink_stream(&mut connection);
+
+        // Send keep-alive and health checker pings throughout the idle timeout
+        for request_id in 0..3 {
+            client_sink
+                .send(&MultiplexMessage::Message(NetworkMessage::KeepAlive))
+                .await
+                .unwrap();
+            let ping = MultiplexMessage::Message(NetworkMessage::RpcRequest(RpcRequest {
+                protocol_id: ProtocolId::HealthCheckerRpc,
+                request_id,
+                priority: 0,
+                raw_request: vec![],
+            }));
+            client_sink.send(&ping).await.unwrap();
+
+            // Wait for the ping to be handled, and advance time
+            health_checker_rx.next().await.unwrap();
+            mock_time.advance_async(idle_timeout / 3).await;
+        }
+
+        // Verify the connection is closed once the idle timeout has elapsed
+        assert_disconnected_event(
+            remote_peer_id,
+            DisconnectReason::IdleTimeout,
+            &mut connection_notifs_rx,
+        )
+        .await;
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
+// Peer will send keep-alive pings at the configured interval if the connection is idle.
+#[test]
+fn peer_send_keep_alive_pings() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let mock_time = MockTimeService::new();
+    let keep_alive_interval = Duration::from_secs(30);
+    let upstream_handlers = Arc::new(HashMap::new());
+    let (mut peer, peer_handle, mut connection, mut connection_notifs_rx) =
+        build_test_peer_with_options(
+            rt.handle().clone(),
+            mock_time.clone().into(),
+            ConnectionOrigin::Inbound,
+            upstream_handlers,
+            None,
+            Some(keep_alive_interval),
+            None,
+            UnknownProtocolPolicy::default(),
+            None,
+            None,
+        );
+    let remote_peer_id = peer.remote_peer_id();
+    let network_context = peer.network_context;
+
+    // The remote peer advertised support for keep-alive pings during the handshake
+    peer.connection_metadata
+        .application_protocols
+        .insert_feature(MessagingFeature::KeepAlive);
+
+    let test = async move {
+        let (_sink, mut stream) = build_network_sink_stream(&mut connection);
+
+        // Advance time by the keep-alive interval (twice) and verify a ping is sent each time
+        for _ in 0..2 {
+            mock_time.advance_async(keep_alive_interval).await;
+            assert_eq!(
+                stream.next().await.unwrap().unwrap(),
+                MultiplexMessage::Message(NetworkMessage::KeepAlive)
+            );
+        }
+
+        // Close the connection
+        drop(peer_handle);
+        assert_disconnected_event(
+            remote_peer_id,
+            DisconnectReason::Requested,
+            &mut connection_notifs_rx,
+        )
+        .await;
+    };
+    rt.block_on(future::join(peer.start(), test));
+
+    // Verify the keep-alive metrics
+    assert_eq!(
+        counters::keep_alive_pings(&network_context, SENT_LABEL).get(),
+        2
+    );
+}
+
+// Keep-alive pings are not sent to peers that didn't advertise support for them.
+#[test]
+fn peer_skip_keep_alive_pings_if_unsupported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let mock_time = MockTimeService::new();
+    let keep_alive_interval = Duration::from_secs(30);
+    let upstream_handlers = Arc::new(HashMap::new());
+    let (peer, peer_handle, mut connection, mut connection_notifs_rx) =
+        build_test_peer_with_options(
+            rt.handle().clone(),
+            mock_time.clone().into(),
+            ConnectionOrigin::Inbound,
+            upstream_handlers,
+            None,
+            Some(keep_alive_interval),
+            None,
+            UnknownProtocolPolicy::default(),
+            None,
+            None,
+        );
+    let remote_peer_id = peer.remote_peer_id();
+
+    let test = async move {
+        let (_sink, mut stream) = build_network_sink_stream(&mut connection);
+
+        // Advance time by the keep-alive interval (twice)
+        for _ in 0..2 {
+            mock_time.advance_async(keep_alive_interval).await;
+        }
+
+        // Close the connection
+        drop(peer_handle);
+        assert_disconnected_event(
+            remote_peer_id,
+            DisconnectReason::Requested,
+            &mut connection_notifs_rx,
+        )
+        .await;
+
+        // Verify that no pings were sent before the connection was closed
+        assert!(stream.next().await.is_none());
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
+// Large outbound messages are compressed if the remote peer can decompress them.
+#[test]
+fn peer_send_compressed_message() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let upstream_handlers = Arc::new(HashMap::new());
+    let (mut peer, mut peer_handle, mut connection, _connection_notifs_rx) =
+        build_test_peer_with_options(
+            rt.handle().clone(),
+            TimeService::mock(),
+            ConnectionOrigin::Inbound,
+            upstream_handlers,
+            None,
+            None,
+            None,
+            UnknownProtocolPolicy::default(),
+            None,
+            Some(CompressionAlgo::Lz4),
+        );
+
+    // The remote peer advertised support for compression during the handshake
+    peer.connection_metadata
+        .application_protocols
+        .insert_feature(MessagingFeature::Compression);
+
+    let test = async move {
+        let (mut sink, mut stream) = build_network_sink_stream(&mut connection);
+
+        // Send a small and a large message to the remote peer
+        let small_msg = Bytes::from("hello world");
+        let large_msg = Bytes::from(vec![7u8; 16 * 1024]);
+        for mdata in [small_msg.clone(), large_msg.clone()] {
+            peer_handle.send_direct_send(Message {
+                protocol_id: PROTOCOL,
+                mdata,
+                trace_id: None,
+            });
+        }
+
+        // Verify that only the large message was compressed
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: PROTOCOL,
+                priority: 0,
+                raw_msg: small_msg.into(),
+            }))
+        );
+        let compressed_msg = match stream.next().await.unwrap().unwrap() {
+            MultiplexMessage::Message(NetworkMessage::CompressedMsg(message)) => {
+                message.compressed_msg
+            },
+            message => panic!("Expected a compressed message, got: {:?}", message),
+        };
+        assert!(compressed_msg.len() < large_msg.len());
+
+        // Verify that the compressed message decompresses to the original message
+        let raw_msg = aptos_compression::decompress(
+            &compressed_msg,
+            CompressionClient::Network,
+            MAX_MESSAGE_SIZE,
+        )
+        .unwrap();
+        assert_eq!(
+            bcs::from_bytes::<NetworkMessage>(&raw_msg).unwrap(),
+            NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: PROTOCOL,
+                priority: 0,
+                raw_msg: large_msg.into(),
+            })
+        );
+
+        // Close the connection
+        sink.close().await.unwrap();
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
+// Outbound messages are not compressed for peers that didn't advertise support for it.
+#[test]
+fn peer_skip_compression_if_unsupported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let upstream_handlers = Arc::new(HashMap::new());
+    let (peer, mut peer_handle, mut connection, _connection_notifs_rx) =
+        build_test_peer_with_options(
+            rt.handle().clone(),
+            TimeService::mock(),
+            ConnectionOrigin::Inbound,
+            upstream_handlers,
+            None,
+            None,
+            None,
+            UnknownProtocolPolicy::default(),
+            None,
+            Some(CompressionAlgo::Lz4),
+        );
+
+    let test = async move {
+        let (mut sink, mut stream) = build_network_sink_stream(&mut connection);
+
+        // Send a large message to the remote peer and verify it wasn't compressed
+        let large_msg = Bytes::from(vec![7u8; 16 * 1024]);
+        peer_handle.send_direct_send(Message {
+            protocol_id: PROTOCOL,
+            mdata: large_msg.clone(),
+            trace_id: None,
+        });
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: PROTOCOL,
+                priority: 0,
+                raw_msg: large_msg.into(),
+            }))
+        );
+
+        // Close the connection
+        sink.close().await.unwrap();
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
+// Inbound compressed messages are decompressed before they are delivered upstream.
+#[test]
+fn peer_recv_compressed_message() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let (upstream_handlers, mut prot_rx) = test_upstream_handlers();
+    let (peer, _peer_handle, mut connection, _connection_notifs_rx) = build_test_peer(
+        rt.handle().clone(),
+        TimeService::mock(),
+        ConnectionOrigin::Inbound,
+        upstream_handlers,
+    );
+
+    let direct_send_msg = NetworkMessage::DirectSendMsg(DirectSendMsg {
+        protocol_id: PROTOCOL,
+        priority: 0,
+        raw_msg: vec![7u8; 16 * 1024],
+    });
+    let compressed_msg = aptos_compression::compress(
+        bcs::to_bytes(&direct_send_msg).unwrap(),
+        CompressionClient::Network,
+        MAX_MESSAGE_SIZE,
+    )
+    .unwrap();
+
+    let test = async move {
+        let (mut sink, _stream) = build_network_sink_stream(&mut connection);
+
+        // Send a compressed message to the peer
+        sink.send(&MultiplexMessage::Message(NetworkMessage::CompressedMsg(
+            CompressedMsg { compressed_msg },
+        )))
+        .await
+        .unwrap();
+
+        // Verify that the decompressed message was delivered upstream
+        let received = prot_rx.next().await.unwrap();
+        assert_eq!(received.message, direct_send_msg);
+
+        // Close the connection
+        sink.close().await.unwrap();
+    };
+    rt.block_on(future::join(peer.start(), test));
+}
+
+// Outbound messages that could not be written before the connection was closed are
+// counted as dropped (or as write errors, if writing them failed before the close).
+#[test]
+fn peer_drop_outbound_messages_on_disconnect() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let upstream_handlers = Arc::new(HashMap::new());
+    let (peer, mut peer_handle, connection, mut connection_notifs_rx) =
+        build_test_peer_with_options(
+            rt.handle().clone(),
+            TimeService::mock(),
+            ConnectionOrigin::Inbound,
+            upstream_handlers,
+            None,
+            None,
+            Some(Duration::from_secs(1)),
+            UnknownProtocolPolicy::default(),
+            None,
+            None,
+        );
+    let remote_peer_id = peer.remote_peer_id();
+    let network_context = peer.network_context;
+
+    // Buffer outbound messages, and close the remote end of the connection (so that
+    // none of the messages can be written).
+    let num_messages = 10;
+    for _ in 0..num_messages {
+        peer_handle.send_direct_send(Message {
+            protocol_id: PROTOCOL,
+            mdata: Bytes::from("hello world"),
+            trace_id: None,
+        });
+    }
+    drop(connection);
+
+    let test = async move {
+        assert_disconnected_event(
+            remote_peer_id,
+            DisconnectReason::ConnectionLost,
+            &mut connection_notifs_rx,
+        )
+        .await;
+        drop(peer_handle);
+    };
+    rt.block_on(future::join(peer.start(), test));
+
+    // Wait for the writer task to discard the messages, and verify each was counted exactly
+    // once: either as a write error (if the writer tried to write it before the disconnect
+    // was detected), or as dropped on disconnect.
+    let dropped_messages = counters::outbound_messages_dropped_on_disconnect(&network_context);
+    let write_errors = counters::outbound_message_write_errors(&network_context);
+    let num_counted_messages = || dropped_messages.get() + write_errors.get();
+    rt.block_on(async {
+        for _ in 0..100 {
+            if num_counted_messages() >= num_messages {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+    assert_eq!(num_counted_messages(), num_messages);
+}
+
+// Inbound messages for unregistered protocols are handled according to the policy.
+#[test]
+fn peer_recv_unknown_protocol() {
+    ::aptos_logger::Logger::init_for_testing();
+    for (unknown_protocol_policy, expected_unknown_count) in [
+        (UnknownProtocolPolicy::Ignore, 0),
+        (UnknownProtocolPolicy::Count, 1),
+        (UnknownProtocolPolicy::Disconnect, 1),
+    ] {
+        let rt = Runtime::new().unwrap();
+        let (upstream_handlers, mut receiver) = test_upstream_handlers();
+        let (peer, _peer_handle, connection, mut connection_notifs_rx) =
+            build_test_peer_with_options(
+                rt.handle().clone(),
+                TimeService::mock(),
+                ConnectionOrigin::Inbound,
+                upstream_handlers,
+                None,
+                None,
+                None,
+                unknown_protocol_policy,
+                None,
+                None,
+            );
+        let remote_peer_id = peer.remote_peer_id();
+        let network_context = peer.network_context;
+
+        let unknown_msg = MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+            protocol_id: ProtocolId::ConsensusDirectSendBcs,
+            priority: 0,
+            raw_msg: Vec::from("unknown"),
+        }));
+        let known_msg = MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+            protocol_id: PROTOCOL,
+            priority: 0,
+            raw_msg: Vec::from("hello world"),
+        }));
+
+        let test = async move {
+            let mut connection = MultiplexMessageSink::new(connection, MAX_FRAME_SIZE);
+            connection.send(&unknown_msg).await.unwrap();
+            if unknown_protocol_policy == UnknownProtocolPolicy::Disconnect {
+                // The peer should disconnect after the unknown protocol message
+                assert_disconnected_event(
+                    remote_peer_id,
+                    DisconnectReason::UnknownProtocol,
+                    &mut connection_notifs_rx,
+                )
+                .await;
+            } else {
+                // The connection should remain open and deliver subsequent messages
+                connection.send(&known_msg).await.unwrap();
+                let received = receiver.next().await.unwrap();
+                assert_eq!(received.protocol_id(), Some(PROTOCOL));
+                connection.close().await.unwrap();
+                assert_disconnected_event(
+                    remote_peer_id,
+                    DisconnectReason::ConnectionLost,
+                    &mut connection_notifs_rx,
+                )
+                .await;
+            }
+        };
+        rt.block_on(future::join(peer.start(), test));
+
+        // Verify the unknown protocol metrics
+        assert_eq!(
+            counters::direct_send_messages(&network_context, UNKNOWN_LABEL).get(),
+            expected_unknown_count
+        );
+    }
+}
+
+// Inbound messages that would exceed the inbound memory budget are dropped.
+#[test]
+fn peer_recv_over_memory_budget() {
+    ::aptos_logger::Logger::init_for_testing();
+    let rt = Runtime::new().unwrap();
+    let (upstream_handlers, receiver) = test_upstream_handlers();
+    let inbound_memory_budget = Arc::new(InboundMemoryBudget::new(20));
+    let (peer, _peer_handle, connection, mut connection_notifs_rx) = build_test_peer_with_options(
+        rt.handle().clone(),
+        TimeService::mock(),
+        ConnectionOrigin::Inbound,
+        upstream_handlers,
+        None,
+        None,
+        None,
+        UnknownProtocolPolicy::default(),
+        Some(inbound_memory_budget.clone()),
+        None,
+    );
+    let remote_peer_id = peer.remote_peer_id();
+    let network_context = peer.network_context;
+
+    let test = async move {
+        // Send more bytes than the budget allows (without the application dequeuing any)
+        let mut connection = MultiplexMessageSink::new(connection, MAX_FRAME_SIZE);
+        for _ in 0..3 {
+            let message = MultiplexMessage::Message(NetworkMessage::DirectSendMsg(DirectSendMsg {
+                protocol_id: PROTOCOL,
+                priority: 0,
+                raw_msg: vec![0; 10],
+            }));
+            connection.send(&message).await.unwrap();
+        }
+
+        // Close the connection (so that all messages are handled by the peer)
+        connection.close().await.unwrap();
+        assert_disconnected_event(
+            remote_peer_id,
+            DisconnectReason::ConnectionLost,
+            &mut connection_notifs_rx,
+        )
+        .await;
+    };
+    rt.block_on(future::join(peer.start(), test));
+
+    // Verify that only the messages within the budget were delivered
+    assert_eq!(inbound_memory_budget.buffered_bytes(), 20);
+    assert_eq!(
+        counters::direct_send_messages(&network_context, OVER_MEMORY_BUDGET_LABEL).get(),
+        1
+    );
+    let received_messages: Vec<_> = block_on(receiver.collect());
+    assert_eq!(received_messages.len(), 2);
+
+    // Verify the budget is released once the messages are dequeued
+    assert_eq!(inbound_memory_budget.buffered_bytes(), 0);
+}
+
+#[test]
+fn peer_writes_high_priority_messages_first() {
+    // Create the normal and high-priority write queues
+    let (write_reqs_tx, write_reqs_rx) = aptos_channel::new(QueueStyle::KLAST, 1024, None);
+    let (high_priority_write_reqs_tx, high_priority_write_reqs_rx) =
+        aptos_channel::new(QueueStyle::KLAST, 256, None);
+    let create_message = |protocol_id: ProtocolId, index: u8| {
+        NetworkMessage::DirectSendMsg(DirectSendMsg {
+            protocol_id,
+            priority: 0,
+            raw_msg: vec![index],
+        })
+    };
+
+    // Enqueue many normal messages, followed by a single high-priority message
+    assert!(!PROTOCOL.is_high_priority());
+    for index in 0..100 {
+        write_reqs_tx
+            .push((), create_message(PROTOCOL, index))
+            .unwrap();
+    }
+    assert!(ProtocolId::ConsensusDirectSendBcs.is_high_priority());
+    let high_priority_message = create_message(ProtocolId::ConsensusDirectSendBcs, 0);
+    high_priority_write_reqs_tx
+        .push((), high_priority_message.clone())
+        .unwrap();
+
+    // Verify the high-priority message is written first, followed by the normal messages (in order)
+    drop(write_reqs_tx);
+    drop(high_priority_write_reqs_tx);
+    let written_messages = block_on(
+        prioritize_write_requests(high_priority_write_reqs_rx, write_reqs_rx).collect::<Vec<_>>(),
+    );
+    let mut expected_messages = vec![high_priority_message];
+    expected_messages.extend((0..100).map(|index| create_message(PROTOCOL, index)));
+    assert_eq!(written_messages, expected_messages);
+}
+
 #[test]
 fn peer_terminates_when_request_tx_has_dropped() {
     ::aptos_logger::Logger::init_for_testing();
@@ -982,10 +1872,12 @@ fn peers_send_multiplex() {
         let msg_a = Message {
             protocol_id: PROTOCOL,
             mdata: Bytes::from(vec![0; MAX_MESSAGE_SIZE]), // stream message
+            trace_id: None,
         };
         let msg_b = Message {
             protocol_id: PROTOCOL,
             mdata: Bytes::from(vec![1; 1024]), // normal message
+            trace_id: None,
         };
 
         // Peer A -> msg_a -> Peer B
@@ -5,21 +5,29 @@
 use crate::{
     application::storage::PeersAndMetadata,
     counters,
+    logging::NetworkSchema,
     noise::{stream::NoiseStream, HandshakeAuthMode},
     peer_manager::{
-        conn_notifs_channel, ConnectionRequest, ConnectionRequestSender, PeerManager,
-        PeerManagerRequest, PeerManagerRequestSender,
+        conn_notifs_channel, ConnectionRequest, ConnectionRequestSender, InflightRpcs, PeerManager,
+        PeerManagerError, PeerManagerRequest, PeerManagerRequestSender,
     },
     protocols::{
         network::{NetworkClientConfig, NetworkServiceConfig, ReceivedMessage},
-        wire::handshake::v1::ProtocolIdSet,
+        wire::{
+            handshake::v1::ProtocolIdSet,
+            messaging::v1::{DirectSendMsg, NetworkMessage},
+        },
     },
     transport::{self, AptosNetTransport, Connection, APTOS_TCP_TRANSPORT},
     ProtocolId,
 };
 use aptos_channels::{self, aptos_channel, message_queues::QueueStyle};
-use aptos_config::{config::HANDSHAKE_VERSION, network_id::NetworkContext};
+use aptos_config::{
+    config::{CompressionAlgo, UnknownProtocolPolicy, HANDSHAKE_VERSION},
+    network_id::{NetworkContext, PeerNetworkId},
+};
 use aptos_crypto::x25519;
+use aptos_infallible::RwLock;
 use aptos_logger::prelude::*;
 #[cfg(any(test, feature = "testing", feature = "fuzzing"))]
 use aptos_netcore::transport::memory::MemoryTransport;
@@ -29,8 +37,18 @@ use aptos_netcore::transport::{
 };
 use aptos_time_service::TimeService;
 use aptos_types::{chain_id::ChainId, network_address::NetworkAddress, PeerId};
-use std::{clone::Clone, collections::HashMap, fmt::Debug, sync::Arc};
-use tokio::runtime::Handle;
+use futures::{FutureExt, StreamExt};
+use std::{
+    clone::Clone,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{runtime::Handle, task::JoinHandle};
 
 /// Inbound and Outbound connections are always secured with NoiseIK.  The dialer
 /// will always verify the listener.
@@ -78,6 +96,12 @@ struct PeerManagerContext {
     max_message_size: usize,
     inbound_connection_limit: usize,
     tcp_buffer_cfg: TCPBufferCfg,
+    idle_timeout: Option<Duration>,
+    keep_alive_interval: Option<Duration>,
+    drain_timeout: Option<Duration>,
+    unknown_protocol_policy: UnknownProtocolPolicy,
+    max_inbound_buffered_bytes: Option<usize>,
+    compression: Option<CompressionAlgo>,
 }
 
 impl PeerManagerContext {
@@ -100,6 +124,12 @@ impl PeerManagerContext {
         max_message_size: usize,
         inbound_connection_limit: usize,
         tcp_buffer_cfg: TCPBufferCfg,
+        idle_timeout: Option<Duration>,
+        keep_alive_interval: Option<Duration>,
+        drain_timeout: Option<Duration>,
+        unknown_protocol_policy: UnknownProtocolPolicy,
+        max_inbound_buffered_bytes: Option<usize>,
+        compression: Option<CompressionAlgo>,
     ) -> Self {
         Self {
             pm_reqs_tx,
@@ -116,6 +146,12 @@ impl PeerManagerContext {
             max_message_size,
             inbound_connection_limit,
             tcp_buffer_cfg,
+            idle_timeout,
+            keep_alive_interval,
+            drain_timeout,
+            unknown_protocol_policy,
+            max_inbound_buffered_bytes,
+            compression,
         }
     }
 
@@ -135,6 +171,23 @@ impl PeerManagerContext {
     }
 }
 
+/// The context for registering clients and services once the PeerManager has
+/// been built (i.e., at runtime).
+struct RuntimeContext {
+    pm_reqs_tx: aptos_channel::Sender<(PeerId, ProtocolId), PeerManagerRequest>,
+    connection_reqs_tx: aptos_channel::Sender<PeerId, ConnectionRequest>,
+    // The protocols with an upstream handler registered before the PeerManager was built
+    service_protocols: HashSet<ProtocolId>,
+    // The protocols advertised before the PeerManager was built
+    startup_protocols: ProtocolIdSet,
+    // The maximum number of protocols that may be advertised (including runtime protocols)
+    max_registered_protocols: usize,
+    runtime_protocols: Arc<RwLock<ProtocolIdSet>>,
+    runtime_upstream_handlers: Arc<
+        RwLock<HashMap<ProtocolId, aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>>>,
+    >,
+}
+
 #[cfg(any(test, feature = "testing", feature = "fuzzing"))]
 type MemoryPeerManager =
     PeerManager<AptosNetTransport<MemoryTransport>, NoiseStream<aptos_memsocket::MemorySocket>>;
@@ -151,10 +204,20 @@ pub struct PeerManagerBuilder {
     time_service: TimeService,
     transport_context: Option<TransportContext>,
     peer_manager_context: Option<PeerManagerContext>,
+    // Set once the PeerManager has been built
+    runtime_context: Option<RuntimeContext>,
     // TODO(philiphayes): better support multiple listening addrs
     peer_manager: Option<TransportPeerManager>,
     // ListenAddress will be updated when the PeerManager is built
     listen_address: NetworkAddress,
+    // Shared with all request senders. Once set, new outbound sends are rejected.
+    quiesced: Arc<AtomicBool>,
+    // Shared with all request senders, to track their outbound RPCs
+    inflight_rpcs: Arc<InflightRpcs>,
+    // Set once the PeerManager has been started
+    listener_handle: Option<JoinHandle<()>>,
+    // The maximum number of protocols that may be registered at runtime (in total)
+    max_registered_protocols: usize,
 }
 
 impl PeerManagerBuilder {
@@ -173,6 +236,12 @@ impl PeerManagerBuilder {
         enable_proxy_protocol: bool,
         inbound_connection_limit: usize,
         tcp_buffer_cfg: TCPBufferCfg,
+        idle_timeout: Option<Duration>,
+        keep_alive_interval: Option<Duration>,
+        drain_timeout: Option<Duration>,
+        unknown_protocol_policy: UnknownProtocolPolicy,
+        max_inbound_buffered_bytes: Option<usize>,
+        compression: Option<CompressionAlgo>,
     ) -> Self {
         // Setup channel to send requests to peer manager.
         let (pm_reqs_tx, pm_reqs_rx) = aptos_channel::new(
@@ -207,16 +276,84 @@ impl PeerManagerBuilder {
                 max_message_size,
                 inbound_connection_limit,
                 tcp_buffer_cfg,
+                idle_timeout,
+                keep_alive_interval,
+                drain_timeout,
+                unknown_protocol_policy,
+                max_inbound_buffered_bytes,
+                compression,
             )),
+            runtime_context: None,
             peer_manager: None,
             listen_address,
+            quiesced: Arc::new(AtomicBool::new(false)),
+            inflight_rpcs: Arc::new(InflightRpcs::default()),
+            listener_handle: None,
+            max_registered_protocols: usize::MAX,
         }
     }
 
+    /// Sets the maximum number of protocols that may be advertised once clients and
+    /// services are registered at runtime (see `add_runtime_client()`). Registrations
+    /// that would exceed the maximum are rejected. By default, there is no maximum.
+    pub fn set_max_registered_protocols(&mut self, max_registered_protocols: usize) {
+        self.max_registered_protocols = max_registered_protocols;
+    }
+
     pub fn listen_address(&self) -> NetworkAddress {
         self.listen_address.clone()
     }
 
+    /// Returns the set of protocols advertised to remote peers during the
+    /// handshake, i.e., the union of all protocols registered by clients and
+    /// services so far.
+    pub fn supported_protocols(&self) -> ProtocolIdSet {
+        self.transport_context
+            .as_ref()
+            .expect("Cannot access supported protocols once PeerManager has been built")
+            .supported_protocols
+            .clone()
+    }
+
+    /// Returns the protocols that have a registered upstream handler (i.e.,
+    /// those for which inbound messages are delivered to a service), in
+    /// ascending wire order.
+    pub fn service_protocols(&self) -> Vec<ProtocolId> {
+        let mut protocols: Vec<ProtocolId> = self
+            .peer_manager_context
+            .as_ref()
+            .expect("Cannot access service protocols once PeerManager has been built")
+            .upstream_handlers
+            .keys()
+            .copied()
+            .collect();
+        protocols.sort_by_key(|protocol_id| *protocol_id as u8);
+        protocols
+    }
+
+    /// Rejects all new outbound sends on the request senders handed out by
+    /// this builder. Inbound messages continue to be delivered.
+    pub fn quiesce(&self) {
+        self.quiesced.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_quiesced(&self) -> bool {
+        self.quiesced.load(Ordering::Relaxed)
+    }
+
+    /// Stops accepting new connections (and dialing new peers). Existing
+    /// connections are kept open.
+    pub fn stop_listener(&self) {
+        if let Some(listener_handle) = &self.listener_handle {
+            listener_handle.abort();
+        }
+    }
+
+    /// Returns the tracker of the outbound RPCs sent by this builder's request senders
+    pub fn inflight_rpcs(&self) -> Arc<InflightRpcs> {
+        self.inflight_rpcs.clone()
+    }
+
     pub fn connection_reqs_tx(&self) -> aptos_channel::Sender<PeerId, ConnectionRequest> {
         self.peer_manager_context
             .as_ref()
@@ -248,6 +385,7 @@ impl PeerManagerBuilder {
             .expect("PeerManager can only be built once");
 
         let protos = transport_context.supported_protocols;
+        let startup_protocols = protos.clone();
         let chain_id = transport_context.chain_id;
         let enable_proxy_protocol = transport_context.enable_proxy_protocol;
 
@@ -268,24 +406,28 @@ impl PeerManagerBuilder {
 
         self.peer_manager = match self.listen_address.as_slice() {
             [Ip4(_), Tcp(_)] | [Ip6(_), Tcp(_)] => {
+                let transport = AptosNetTransport::new(
+                    aptos_tcp_transport,
+                    self.network_context,
+                    self.time_service.clone(),
+                    key,
+                    auth_mode,
+                    HANDSHAKE_VERSION,
+                    chain_id,
+                    protos,
+                    enable_proxy_protocol,
+                );
+                let runtime_protocols = transport.runtime_protocols();
                 Some(TransportPeerManager::Tcp(self.build_with_transport(
-                    AptosNetTransport::new(
-                        aptos_tcp_transport,
-                        self.network_context,
-                        self.time_service.clone(),
-                        key,
-                        auth_mode,
-                        HANDSHAKE_VERSION,
-                        chain_id,
-                        protos,
-                        enable_proxy_protocol,
-                    ),
+                    transport,
+                    startup_protocols,
+                    runtime_protocols,
                     executor,
                 )))
             },
             #[cfg(any(test, feature = "testing", feature = "fuzzing"))]
-            [Memory(_)] => Some(TransportPeerManager::Memory(self.build_with_transport(
-                AptosNetTransport::new(
+            [Memory(_)] => {
+                let transport = AptosNetTransport::new(
                     MemoryTransport,
                     self.network_context,
                     self.time_service.clone(),
@@ -295,9 +437,15 @@ impl PeerManagerBuilder {
                     chain_id,
                     protos,
                     enable_proxy_protocol,
-                ),
-                executor,
-            ))),
+                );
+                let runtime_protocols = transport.runtime_protocols();
+                Some(TransportPeerManager::Memory(self.build_with_transport(
+                    transport,
+                    startup_protocols,
+                    runtime_protocols,
+                    executor,
+                )))
+            },
             _ => panic!(
                 "{} Unsupported listen_address: '{}', expected '/memory/<port>', \
                  '/ip4/<addr>/tcp/<port>', or '/ip6/<addr>/tcp/<port>'.",
@@ -313,6 +461,8 @@ impl PeerManagerBuilder {
     fn build_with_transport<TTransport, TSocket>(
         &mut self,
         transport: TTransport,
+        startup_protocols: ProtocolIdSet,
+        runtime_protocols: Arc<RwLock<ProtocolIdSet>>,
         executor: &Handle,
     ) -> PeerManager<TTransport, TSocket>
     where
@@ -323,6 +473,9 @@ impl PeerManagerBuilder {
             .peer_manager_context
             .take()
             .expect("PeerManager can only be built once");
+        let pm_reqs_tx = pm_context.pm_reqs_tx.clone();
+        let connection_reqs_tx = pm_context.connection_reqs_tx.clone();
+        let service_protocols = pm_context.upstream_handlers.keys().copied().collect();
         let peer_mgr = PeerManager::new(
             executor.clone(),
             self.time_service.clone(),
@@ -340,22 +493,39 @@ impl PeerManagerBuilder {
             pm_context.max_frame_size,
             pm_context.max_message_size,
             pm_context.inbound_connection_limit,
+            pm_context.idle_timeout,
+            pm_context.keep_alive_interval,
+            pm_context.drain_timeout,
+            pm_context.unknown_protocol_policy,
+            pm_context.max_inbound_buffered_bytes,
+            pm_context.compression,
         );
 
         // PeerManager constructor appends a public key to the listen_address.
         self.listen_address = peer_mgr.listen_addr().clone();
 
+        self.runtime_context = Some(RuntimeContext {
+            pm_reqs_tx,
+            connection_reqs_tx,
+            service_protocols,
+            startup_protocols,
+            max_registered_protocols: self.max_registered_protocols,
+            runtime_protocols,
+            runtime_upstream_handlers: peer_mgr.runtime_upstream_handlers(),
+        });
+
         peer_mgr
     }
 
     fn start_peer_manager<TTransport, TSocket>(
         &mut self,
-        peer_manager: PeerManager<TTransport, TSocket>,
+        mut peer_manager: PeerManager<TTransport, TSocket>,
         executor: &Handle,
     ) where
         TTransport: Transport<Output = Connection<TSocket>> + Send + 'static,
         TSocket: transport::TSocket,
     {
+        self.listener_handle = peer_manager.start_connection_listener();
         executor.spawn(peer_manager.start());
         debug!("{} Started peer manager", self.network_context);
     }
@@ -400,9 +570,17 @@ impl PeerManagerBuilder {
             .add_protocols(&config.rpc_protocols_and_preferences);
 
         // Create the context and return the request senders
+        let time_service = self.time_service.clone();
+        let quiesced = self.quiesced.clone();
+        let inflight_rpcs = self.inflight_rpcs.clone();
         let pm_context = self.peer_manager_context();
         (
-            PeerManagerRequestSender::new(pm_context.pm_reqs_tx.clone()),
+            PeerManagerRequestSender::new_with_quiesce_state(
+                pm_context.pm_reqs_tx.clone(),
+                time_service,
+                quiesced,
+                inflight_rpcs,
+            ),
             ConnectionRequestSender::new(pm_context.connection_reqs_tx.clone()),
         )
     }
@@ -419,16 +597,307 @@ impl PeerManagerBuilder {
             .add_protocols(&config.rpc_protocols_and_preferences);
 
         // Create the context and register the protocols
-        let (network_notifs_tx, network_notifs_rx) = config.inbound_queue_config.build();
+        let (network_notifs_tx, mut network_notifs_rx) = config.build_inbound_queue();
+        let network_context = self.network_context;
         let pm_context = self.peer_manager_context();
-        for protocol in config
+        let protocols: Vec<ProtocolId> = config
             .direct_send_protocols_and_preferences
             .iter()
             .chain(&config.rpc_protocols_and_preferences)
-        {
+            .copied()
+            .collect();
+        for protocol in &protocols {
             pm_context.add_upstream_handler(*protocol, network_notifs_tx.clone());
         }
 
+        // In debug builds, verify the protocols are wired to the service's receiver
+        // (before the receiver is handed to the application).
+        if cfg!(debug_assertions) {
+            self_test_protocol_receiver(
+                &network_context,
+                &protocols,
+                &pm_context.upstream_handlers,
+                &mut network_notifs_rx,
+            );
+        }
+
         network_notifs_rx
     }
+
+    /// Register a service for handling some protocols, with a separate inbound
+    /// queue (and receiver) for each protocol. Unlike `add_service()`, messages on
+    /// different protocols are never interleaved in a single queue, so the service
+    /// can process each protocol with its own priority and concurrency (e.g., RPCs
+    /// separately from bulk direct-send traffic).
+    pub fn add_service_by_protocol(
+        &mut self,
+        config: &NetworkServiceConfig,
+    ) -> BTreeMap<ProtocolId, aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>> {
+        // Register the direct send and rpc protocols
+        self.transport_context()
+            .add_protocols(&config.direct_send_protocols_and_preferences);
+        self.transport_context()
+            .add_protocols(&config.rpc_protocols_and_preferences);
+
+        // Create the context and register each protocol with its own queue
+        let network_context = self.network_context;
+        let pm_context = self.peer_manager_context();
+        let mut network_notifs_rxs = BTreeMap::new();
+        for protocol in config
+            .direct_send_protocols_and_preferences
+            .iter()
+            .chain(&config.rpc_protocols_and_preferences)
+        {
+            let (network_notifs_tx, mut network_notifs_rx) = config.build_inbound_queue();
+            pm_context.add_upstream_handler(*protocol, network_notifs_tx);
+
+            // In debug builds, verify the protocol is wired to its receiver
+            if cfg!(debug_assertions) {
+                self_test_protocol_receiver(
+                    &network_context,
+                    &[*protocol],
+                    &pm_context.upstream_handlers,
+                    &mut network_notifs_rx,
+                );
+            }
+            network_notifs_rxs.insert(*protocol, network_notifs_rx);
+        }
+
+        network_notifs_rxs
+    }
+
+    fn runtime_context(&self) -> &RuntimeContext {
+        self.runtime_context
+            .as_ref()
+            .expect("Cannot register at runtime before PeerManager has been built")
+    }
+
+    /// Register a client once the PeerManager has been built (see `add_client()`).
+    /// The client's protocols are advertised on all subsequent handshakes. Note:
+    /// peers that are already connected only learn of the protocols when they
+    /// reconnect, so messages can't be sent to them on these protocols until then.
+    ///
+    /// Returns an error if the protocols would exceed the max registered protocols.
+    pub fn add_runtime_client(
+        &self,
+        config: &NetworkClientConfig,
+    ) -> Result<(PeerManagerRequestSender, ConnectionRequestSender), PeerManagerError> {
+        let runtime_context = self.runtime_context();
+        let protocols: Vec<ProtocolId> = config
+            .direct_send_protocols_and_preferences
+            .iter()
+            .chain(&config.rpc_protocols_and_preferences)
+            .copied()
+            .collect();
+        runtime_context.verify_protocol_capacity(&protocols)?;
+        runtime_context.add_runtime_protocols(&protocols);
+
+        Ok((
+            PeerManagerRequestSender::new_with_quiesce_state(
+                runtime_context.pm_reqs_tx.clone(),
+                self.time_service.clone(),
+                self.quiesced.clone(),
+                self.inflight_rpcs.clone(),
+            ),
+            ConnectionRequestSender::new(runtime_context.connection_reqs_tx.clone()),
+        ))
+    }
+
+    /// Verifies that the given protocols can be registered at runtime (i.e., that they
+    /// would not exceed the max registered protocols), without registering them.
+    pub fn verify_runtime_protocols(
+        &self,
+        protocols: &[ProtocolId],
+    ) -> Result<(), PeerManagerError> {
+        self.runtime_context().verify_protocol_capacity(protocols)
+    }
+
+    /// Register a service once the PeerManager has been built (see `add_service()`).
+    /// Inbound messages for the service's protocols are only delivered on connections
+    /// established after the service was registered (i.e., peers that are already
+    /// connected must reconnect to use the protocols).
+    ///
+    /// Returns an error if the protocols would exceed the max registered protocols.
+    /// Panics if any of the protocols already has a registered service.
+    pub fn add_runtime_service(
+        &self,
+        config: &NetworkServiceConfig,
+    ) -> Result<aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>, PeerManagerError>
+    {
+        let runtime_context = self.runtime_context();
+        let protocols: Vec<ProtocolId> = config
+            .direct_send_protocols_and_preferences
+            .iter()
+            .chain(&config.rpc_protocols_and_preferences)
+            .copied()
+            .collect();
+        runtime_context.verify_protocol_capacity(&protocols)?;
+        let (network_notifs_tx, network_notifs_rx) = config.build_inbound_queue();
+
+        // Register the upstream handlers before advertising the protocols, so that
+        // a peer never negotiates a protocol that has no handler yet.
+        {
+            let mut runtime_upstream_handlers = runtime_context.runtime_upstream_handlers.write();
+            for protocol in &protocols {
+                assert!(
+                    !runtime_context.service_protocols.contains(protocol)
+                        && !runtime_upstream_handlers.contains_key(protocol),
+                    "{} A service is already registered for protocol: {}",
+                    self.network_context,
+                    protocol
+                );
+            }
+            for protocol in &protocols {
+                runtime_upstream_handlers.insert(*protocol, network_notifs_tx.clone());
+            }
+        }
+        runtime_context.add_runtime_protocols(&protocols);
+
+        info!(
+            NetworkSchema::new(&self.network_context),
+            "{} Registered service at runtime for protocols: {:?}", self.network_context, protocols
+        );
+        Ok(network_notifs_rx)
+    }
+
+    /// Deregister protocols that were registered at runtime (see `add_runtime_client()`
+    /// and `add_runtime_service()`). The protocols are no longer advertised on subsequent
+    /// handshakes, and their services' upstream handlers are dropped. Note: connections
+    /// that are already established keep the protocols until they reconnect, so a
+    /// service's receiver only terminates once those peers have disconnected.
+    ///
+    /// Protocols that were registered before the PeerManager was built can't be
+    /// deregistered (and are ignored). Returns the deregistered protocols.
+    pub fn remove_runtime_protocols(&self, protocols: &[ProtocolId]) -> Vec<ProtocolId> {
+        let runtime_context = self.runtime_context();
+
+        // Stop advertising the protocols before dropping their upstream handlers, so
+        // that a peer never negotiates a protocol that has no handler.
+        let mut removed_protocols: Vec<ProtocolId> = {
+            let mut runtime_protocols = runtime_context.runtime_protocols.write();
+            let removed_protocols = protocols
+                .iter()
+                .copied()
+                .filter(|protocol| runtime_protocols.contains(*protocol))
+                .collect();
+            *runtime_protocols = runtime_protocols
+                .iter()
+                .filter(|protocol| !protocols.contains(protocol))
+                .collect();
+            removed_protocols
+        };
+        {
+            let mut runtime_upstream_handlers = runtime_context.runtime_upstream_handlers.write();
+            for protocol in protocols {
+                if runtime_upstream_handlers.remove(protocol).is_some()
+                    && !removed_protocols.contains(protocol)
+                {
+                    removed_protocols.push(*protocol);
+                }
+            }
+        }
+
+        let ignored_protocols: Vec<_> = protocols
+            .iter()
+            .filter(|protocol| !removed_protocols.contains(protocol))
+            .collect();
+        if !ignored_protocols.is_empty() {
+            warn!(
+                NetworkSchema::new(&self.network_context),
+                "{} Ignoring protocols that were not registered at runtime: {:?}",
+                self.network_context,
+                ignored_protocols
+            );
+        }
+        info!(
+            NetworkSchema::new(&self.network_context),
+            "{} Deregistered runtime protocols: {:?}", self.network_context, removed_protocols
+        );
+        removed_protocols
+    }
+}
+
+impl RuntimeContext {
+    /// Verifies that registering the given protocols (in addition to the protocols
+    /// that are already advertised) would not exceed the max registered protocols.
+    fn verify_protocol_capacity(&self, protocols: &[ProtocolId]) -> Result<(), PeerManagerError> {
+        let num_registered_protocols = self
+            .startup_protocols
+            .union(&self.runtime_protocols.read())
+            .union(&ProtocolIdSet::from_iter(protocols))
+            .iter()
+            .count();
+        if num_registered_protocols > self.max_registered_protocols {
+            return Err(PeerManagerError::TooManyProtocols(
+                num_registered_protocols,
+                self.max_registered_protocols,
+            ));
+        }
+        Ok(())
+    }
+
+    fn add_runtime_protocols(&self, protocols: &[ProtocolId]) {
+        let protocol_id_set = ProtocolIdSet::from_iter(protocols);
+        let mut runtime_protocols = self.runtime_protocols.write();
+        *runtime_protocols = runtime_protocols.union(&protocol_id_set);
+    }
+}
+
+/// Verifies that each protocol is correctly wired to the given receiver by pushing
+/// a synthetic message through the protocol's upstream handler, and checking that
+/// it is delivered. The synthetic messages are drained from the receiver, so this
+/// must only be called before the receiver is handed to the application.
+///
+/// The result is logged for each protocol, and the protocols that failed the
+/// self-test are returned.
+pub fn self_test_protocol_receiver(
+    network_context: &NetworkContext,
+    protocols: &[ProtocolId],
+    upstream_handlers: &HashMap<
+        ProtocolId,
+        aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>,
+    >,
+    receiver: &mut aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>,
+) -> Vec<ProtocolId> {
+    let mut failed_protocols = vec![];
+    for protocol_id in protocols {
+        let delivered = match upstream_handlers.get(protocol_id) {
+            Some(handler) => {
+                let message = ReceivedMessage::new(
+                    NetworkMessage::DirectSendMsg(DirectSendMsg {
+                        protocol_id: *protocol_id,
+                        priority: 0,
+                        raw_msg: vec![],
+                    }),
+                    PeerNetworkId::new(network_context.network_id(), PeerId::ZERO),
+                );
+                handler.push((PeerId::ZERO, *protocol_id), message).is_ok()
+                    && matches!(
+                        receiver.next().now_or_never(),
+                        Some(Some(message)) if message.protocol_id() == Some(*protocol_id)
+                    )
+            },
+            None => false,
+        };
+
+        if delivered {
+            info!(
+                NetworkSchema::new(network_context),
+                protocol_id = %protocol_id,
+                "{} Protocol self-test passed for: {}",
+                network_context,
+                protocol_id
+            );
+        } else {
+            error!(
+                NetworkSchema::new(network_context),
+                protocol_id = %protocol_id,
+                "{} Protocol self-test failed for: {}! The protocol is not wired to its service.",
+                network_context,
+                protocol_id
+            );
+            failed_protocols.push(*protocol_id);
+        }
+    }
+    failed_protocols
 }
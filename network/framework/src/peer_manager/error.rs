@@ -7,6 +7,7 @@
 use crate::protocols::wire::messaging::v1 as wire;
 use aptos_types::{network_address::NetworkAddress, PeerId};
 use futures::channel::{mpsc, oneshot};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -29,6 +30,9 @@ pub enum PeerManagerError {
     #[error("Already connected at {0}")]
     AlreadyConnected(NetworkAddress),
 
+    #[error("Peer {0} is banned")]
+    PeerBanned(PeerId),
+
     #[error("Sending end of oneshot dropped")]
     OneshotSenderDropped,
 
@@ -43,6 +47,15 @@ pub enum PeerManagerError {
 
     #[error("Error writing to wire: {0}")]
     WireWriteError(#[from] wire::WriteError),
+
+    #[error("Network is quiesced, new outbound messages are rejected")]
+    Quiesced,
+
+    #[error("Timed out after {1:?} waiting to enqueue message for Peer {0}")]
+    SendTimeout(PeerId, Duration),
+
+    #[error("Too many protocols would be registered: {0} (the maximum is {1})")]
+    TooManyProtocols(usize, usize),
 }
 
 impl PeerManagerError {
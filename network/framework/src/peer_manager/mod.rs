@@ -15,7 +15,7 @@ use crate::{
     constants,
     counters::{self},
     logging::*,
-    peer::{Peer, PeerRequest},
+    peer::{memory_budget::InboundMemoryBudget, Peer, PeerRequest},
     transport::{
         Connection, ConnectionId, ConnectionMetadata, TSocket as TransportTSocket,
         TRANSPORT_TIMEOUT,
@@ -24,13 +24,14 @@ use crate::{
 };
 use aptos_channels::{self, aptos_channel, message_queues::QueueStyle};
 use aptos_config::network_id::{NetworkContext, PeerNetworkId};
+use aptos_infallible::RwLock;
 use aptos_logger::prelude::*;
 use aptos_netcore::transport::{ConnectionOrigin, Transport};
 use aptos_short_hex_str::AsShortHexStr;
 use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{network_address::NetworkAddress, PeerId};
 use futures::{
-    channel::oneshot,
+    channel::{mpsc, oneshot},
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
     sink::SinkExt,
     stream::StreamExt,
@@ -41,7 +42,7 @@ use std::{
     sync::Arc,
     time::Duration,
 };
-use tokio::runtime::Handle;
+use tokio::{runtime::Handle, task::JoinHandle};
 
 pub mod builder;
 pub mod conn_notifs_channel;
@@ -58,7 +59,7 @@ use crate::{
     peer_manager::transport::{TransportHandler, TransportRequest},
     protocols::network::{ReceivedMessage, SerializedRequest},
 };
-use aptos_config::config::PeerRole;
+use aptos_config::config::{CompressionAlgo, PeerRole, UnknownProtocolPolicy};
 use aptos_types::account_address::AccountAddress;
 pub use senders::*;
 pub use types::*;
@@ -94,6 +95,11 @@ where
     /// of messages across (PeerId, ProtocolId).
     upstream_handlers:
         Arc<HashMap<ProtocolId, aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>>>,
+    /// Upstream handlers for protocols registered after the PeerManager was created. These
+    /// are only used by connections established after the protocols were registered.
+    runtime_upstream_handlers: Arc<
+        RwLock<HashMap<ProtocolId, aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>>>,
+    >,
     /// Channels to send NewPeer/LostPeer notifications to.
     connection_event_handlers: Vec<conn_notifs_channel::Sender>,
     /// Channel used to send Dial requests to the ConnectionHandler actor
@@ -104,6 +110,8 @@ where
     connection_reqs_rx: aptos_channel::Receiver<PeerId, ConnectionRequest>,
     /// Receiver for connection events.
     transport_notifs_rx: aptos_channels::Receiver<TransportNotification<TSocket>>,
+    /// Receiver for peers banned by applications (see `PeersAndMetadata::ban_peer()`).
+    banned_peers_rx: mpsc::UnboundedReceiver<PeerId>,
     /// A map of outstanding disconnect requests.
     outstanding_disconnect_requests:
         HashMap<ConnectionId, oneshot::Sender<Result<(), PeerManagerError>>>,
@@ -117,6 +125,18 @@ where
     max_message_size: usize,
     /// Inbound connection limit separate of outbound connections
     inbound_connection_limit: usize,
+    /// Timeout after which connections without any traffic are closed (if any)
+    idle_timeout: Option<Duration>,
+    /// Interval at which keep-alive pings are sent on connections without outbound traffic (if any)
+    keep_alive_interval: Option<Duration>,
+    /// Timeout for flushing pending outbound messages when a connection is closed (if any)
+    drain_timeout: Option<Duration>,
+    /// How to handle inbound messages for unregistered protocols
+    unknown_protocol_policy: UnknownProtocolPolicy,
+    /// The budget for inbound messages buffered across all peers and protocols (if any)
+    inbound_memory_budget: Option<Arc<InboundMemoryBudget>>,
+    /// The algorithm used to compress outbound messages (if any)
+    compression: Option<CompressionAlgo>,
 }
 
 impl<TTransport, TSocket> PeerManager<TTransport, TSocket>
@@ -144,6 +164,12 @@ where
         max_frame_size: usize,
         max_message_size: usize,
         inbound_connection_limit: usize,
+        idle_timeout: Option<Duration>,
+        keep_alive_interval: Option<Duration>,
+        drain_timeout: Option<Duration>,
+        unknown_protocol_policy: UnknownProtocolPolicy,
+        max_inbound_buffered_bytes: Option<usize>,
+        compression: Option<CompressionAlgo>,
     ) -> Self {
         let (transport_notifs_tx, transport_notifs_rx) = aptos_channels::new(
             channel_size,
@@ -163,6 +189,8 @@ where
             transport_reqs_rx,
             transport_notifs_tx_clone,
         );
+        let banned_peers_rx =
+            peers_and_metadata.subscribe_to_peer_bans(network_context.network_id());
 
         Self {
             network_context,
@@ -177,14 +205,23 @@ where
             transport_reqs_tx,
             transport_notifs_tx,
             transport_notifs_rx,
+            banned_peers_rx,
             outstanding_disconnect_requests: HashMap::new(),
             phantom_transport: PhantomData,
             upstream_handlers: Arc::new(upstream_handlers),
+            runtime_upstream_handlers: Arc::new(RwLock::new(HashMap::new())),
             connection_event_handlers,
             channel_size,
             max_frame_size,
             max_message_size,
             inbound_connection_limit,
+            idle_timeout,
+            keep_alive_interval,
+            drain_timeout,
+            unknown_protocol_policy,
+            inbound_memory_budget: max_inbound_buffered_bytes
+                .map(|max_bytes| Arc::new(InboundMemoryBudget::new(max_bytes))),
+            compression,
         }
     }
 
@@ -229,6 +266,37 @@ where
         &self.listen_addr
     }
 
+    /// Returns the upstream handlers for protocols registered at runtime (i.e., after
+    /// the PeerManager was created). Handlers added here are used by all subsequent
+    /// connections (existing connections are unaffected).
+    pub fn runtime_upstream_handlers(
+        &self,
+    ) -> Arc<
+        RwLock<HashMap<ProtocolId, aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>>>,
+    > {
+        self.runtime_upstream_handlers.clone()
+    }
+
+    /// Returns the upstream handlers for a new connection, i.e., the handlers
+    /// registered at creation along with those registered at runtime.
+    fn upstream_handlers_for_new_peer(
+        &self,
+    ) -> Arc<HashMap<ProtocolId, aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>>>
+    {
+        let runtime_upstream_handlers = self.runtime_upstream_handlers.read();
+        if runtime_upstream_handlers.is_empty() {
+            return self.upstream_handlers.clone();
+        }
+
+        let mut upstream_handlers = (*self.upstream_handlers).clone();
+        for (protocol_id, handler) in runtime_upstream_handlers.iter() {
+            upstream_handlers
+                .entry(*protocol_id)
+                .or_insert_with(|| handler.clone());
+        }
+        Arc::new(upstream_handlers)
+    }
+
     /// Start listening on the set address and return a future which runs PeerManager
     pub async fn start(mut self) {
         // Start listening for connections.
@@ -236,6 +304,7 @@ where
             NetworkSchema::new(&self.network_context),
             "Start listening for incoming connections on {}", self.listen_addr
         );
+        // Note: the listener may have already been started (see `start_connection_listener()`)
         self.start_connection_listener();
         loop {
             ::futures::select! {
@@ -248,6 +317,9 @@ where
                 request = self.requests_rx.select_next_some() => {
                     self.handle_outbound_request(request).await;
                 }
+                banned_peer_id = self.banned_peers_rx.select_next_some() => {
+                    self.disconnect_banned_peer(banned_peer_id);
+                }
                 complete => {
                     break;
                 }
@@ -329,8 +401,43 @@ where
         }
     }
 
+    /// Disconnects the given peer (if it is connected), as it was banned
+    fn disconnect_banned_peer(&mut self, peer_id: PeerId) {
+        if let Some((conn_metadata, sender)) = self.active_peers.remove(&peer_id) {
+            info!(
+                NetworkSchema::new(&self.network_context)
+                    .connection_metadata_with_address(&conn_metadata),
+                "{} Disconnecting banned peer: {}",
+                self.network_context,
+                peer_id.short_str()
+            );
+            self.remove_peer_from_metadata(peer_id, conn_metadata.connection_id);
+
+            // This triggers a disconnect.
+            drop(sender);
+        }
+    }
+
     /// Handles a new connection event
     fn handle_new_connection_event(&mut self, conn: Connection<TSocket>) {
+        // Refuse connections with banned peers
+        let peer_network_id = PeerNetworkId::new(
+            self.network_context.network_id(),
+            conn.metadata.remote_peer_id,
+        );
+        if self.peers_and_metadata.is_peer_banned(&peer_network_id) {
+            info!(
+                NetworkSchema::new(&self.network_context)
+                    .connection_metadata_with_address(&conn.metadata),
+                "{} Connection rejected as the peer is banned: {}",
+                self.network_context,
+                conn.metadata
+            );
+            counters::connections_rejected(&self.network_context, conn.metadata.origin).inc();
+            self.disconnect(conn);
+            return;
+        }
+
         // Get the trusted peers
         let trusted_peers = match self
             .peers_and_metadata
@@ -431,6 +538,24 @@ where
         self.sample_connected_peers();
         match request {
             ConnectionRequest::DialPeer(requested_peer_id, addr, response_tx) => {
+                // Don't dial banned peers
+                let peer_network_id =
+                    PeerNetworkId::new(self.network_context.network_id(), requested_peer_id);
+                if self.peers_and_metadata.is_peer_banned(&peer_network_id) {
+                    let error = PeerManagerError::PeerBanned(requested_peer_id);
+                    if let Err(send_err) = response_tx.send(Err(error)) {
+                        info!(
+                            NetworkSchema::new(&self.network_context)
+                                .remote_peer(&requested_peer_id),
+                            "{} Failed to notify that peer is banned for Peer {}: {:?}",
+                            self.network_context,
+                            requested_peer_id.short_str(),
+                            send_err
+                        );
+                    }
+                    return;
+                }
+
                 // Only dial peers which we aren't already connected with
                 if let Some((curr_connection, _)) = self.active_peers.get(&requested_peer_id) {
                     let error = PeerManagerError::AlreadyConnected(curr_connection.addr.clone());
@@ -532,12 +657,12 @@ where
         }
     }
 
-    fn start_connection_listener(&mut self) {
-        let transport_handler = self
-            .transport_handler
+    /// Spawns the connection listener (if it isn't running yet), and returns its
+    /// handle, e.g., to stop accepting new connections ahead of a shutdown.
+    pub fn start_connection_listener(&mut self) -> Option<JoinHandle<()>> {
+        self.transport_handler
             .take()
-            .expect("Transport handler already taken");
-        self.executor.spawn(transport_handler.listen());
+            .map(|transport_handler| self.executor.spawn(transport_handler.listen()))
     }
 
     /// In the event two peers simultaneously dial each other we need to be able to do
@@ -620,6 +745,7 @@ where
                 let (_, peer_handle) = active_entry.remove();
                 // Drop the existing connection and replace it with the new connection
                 drop(peer_handle);
+                counters::duplicate_connections_resolved(&self.network_context, "existing").inc();
                 info!(
                     NetworkSchema::new(&self.network_context).remote_peer(&peer_id),
                     "{} Closing existing connection with Peer {} to mitigate simultaneous dial",
@@ -635,6 +761,7 @@ where
                     peer_id.short_str()
                 );
                 // Drop the new connection and keep the one already stored in active_peers
+                counters::duplicate_connections_resolved(&self.network_context, "new").inc();
                 self.disconnect(connection);
                 return Ok(());
             }
@@ -655,12 +782,18 @@ where
             connection,
             self.transport_notifs_tx.clone(),
             peer_reqs_rx,
-            self.upstream_handlers.clone(),
+            self.upstream_handlers_for_new_peer(),
             Duration::from_millis(constants::INBOUND_RPC_TIMEOUT_MS),
             constants::MAX_CONCURRENT_INBOUND_RPCS,
             constants::MAX_CONCURRENT_OUTBOUND_RPCS,
             self.max_frame_size,
             self.max_message_size,
+            self.idle_timeout,
+            self.keep_alive_interval,
+            self.drain_timeout,
+            self.unknown_protocol_policy,
+            self.inbound_memory_budget.clone(),
+            self.compression,
         );
         self.executor.spawn(peer.start());
 
@@ -7,20 +7,81 @@ use crate::{
     protocols::{
         direct_send::Message,
         rpc::{error::RpcError, OutboundRpcRequest},
+        wire::messaging::v1::TraceId,
     },
     ProtocolId,
 };
 use aptos_channels::{self, aptos_channel};
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{network_address::NetworkAddress, PeerId};
 use bytes::Bytes;
 use futures::channel::oneshot;
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::Notify;
 
 /// Convenience wrapper which makes it easy to issue communication requests and await the responses
 /// from PeerManager.
 #[derive(Clone, Debug)]
 pub struct PeerManagerRequestSender {
     inner: aptos_channel::Sender<(PeerId, ProtocolId), PeerManagerRequest>,
+    // The time service used to time out sends (e.g., while the outbound queue is full)
+    time_service: TimeService,
+    // Set when the network is quiesced, at which point all new outbound sends are rejected
+    quiesced: Arc<AtomicBool>,
+    // The outbound RPCs awaiting a response (shared by all senders of the network)
+    inflight_rpcs: Arc<InflightRpcs>,
+}
+
+/// Tracks the outbound RPCs that are awaiting a response, so that a network
+/// shutdown can wait for them to complete (instead of a fixed grace period).
+#[derive(Debug, Default)]
+pub struct InflightRpcs {
+    num_inflight_rpcs: AtomicUsize,
+    all_completed: Notify,
+}
+
+impl InflightRpcs {
+    /// Returns the number of outbound RPCs that are awaiting a response
+    pub fn num_inflight_rpcs(&self) -> usize {
+        self.num_inflight_rpcs.load(Ordering::Acquire)
+    }
+
+    /// Waits until no outbound RPCs are awaiting a response
+    pub async fn wait_for_completion(&self) {
+        loop {
+            // Register for the notification before checking the count, so
+            // that a completion in between the two can't be missed.
+            let all_completed = self.all_completed.notified();
+            if self.num_inflight_rpcs() == 0 {
+                return;
+            }
+            all_completed.await;
+        }
+    }
+
+    /// Tracks a new outbound RPC until the returned guard is dropped
+    fn track_rpc(self: &Arc<Self>) -> InflightRpcGuard {
+        self.num_inflight_rpcs.fetch_add(1, Ordering::AcqRel);
+        InflightRpcGuard(self.clone())
+    }
+}
+
+/// Marks an outbound RPC as completed when dropped (i.e., once the RPC
+/// returns, fails, or is cancelled by the caller).
+struct InflightRpcGuard(Arc<InflightRpcs>);
+
+impl Drop for InflightRpcGuard {
+    fn drop(&mut self) {
+        if self.0.num_inflight_rpcs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.all_completed.notify_waiters();
+        }
+    }
 }
 
 /// Convenience wrapper which makes it easy to issue connection requests and await the responses
@@ -33,27 +94,102 @@ pub struct ConnectionRequestSender {
 impl PeerManagerRequestSender {
     /// Construct a new PeerManagerRequestSender with a raw channel::Sender
     pub fn new(inner: aptos_channel::Sender<(PeerId, ProtocolId), PeerManagerRequest>) -> Self {
-        Self { inner }
+        Self::new_with_quiesce_state(
+            inner,
+            TimeService::real(),
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(InflightRpcs::default()),
+        )
+    }
+
+    /// Construct a new PeerManagerRequestSender that uses the given time service,
+    /// and shares the given quiesce flag and in-flight RPC tracker (e.g., with the
+    /// other senders of a network)
+    pub fn new_with_quiesce_state(
+        inner: aptos_channel::Sender<(PeerId, ProtocolId), PeerManagerRequest>,
+        time_service: TimeService,
+        quiesced: Arc<AtomicBool>,
+        inflight_rpcs: Arc<InflightRpcs>,
+    ) -> Self {
+        Self {
+            inner,
+            time_service,
+            quiesced,
+            inflight_rpcs,
+        }
+    }
+
+    /// Returns an error if the network has been quiesced and new sends must be rejected
+    fn ensure_not_quiesced(&self) -> Result<(), PeerManagerError> {
+        if self.quiesced.load(Ordering::Relaxed) {
+            Err(PeerManagerError::Quiesced)
+        } else {
+            Ok(())
+        }
     }
 
     /// Send a fire-and-forget direct-send message to remote peer.
     ///
     /// The function returns when the message has been enqueued on the network actor's event queue.
     /// It therefore makes no reliable delivery guarantees. An error is returned if the event queue
-    /// is unexpectedly shutdown.
+    /// is unexpectedly shutdown, or if the network has been quiesced.
     pub fn send_to(
         &self,
         peer_id: PeerId,
         protocol_id: ProtocolId,
         mdata: Bytes,
     ) -> Result<(), PeerManagerError> {
+        self.send_to_with_trace_id(peer_id, protocol_id, mdata, None)
+    }
+
+    /// Send a fire-and-forget direct-send message to remote peer, with an
+    /// optional trace id that is surfaced on the remote peer's received message.
+    /// See `send_to()` for more details.
+    pub fn send_to_with_trace_id(
+        &self,
+        peer_id: PeerId,
+        protocol_id: ProtocolId,
+        mdata: Bytes,
+        trace_id: Option<TraceId>,
+    ) -> Result<(), PeerManagerError> {
+        self.ensure_not_quiesced()?;
         self.inner.push(
             (peer_id, protocol_id),
-            PeerManagerRequest::SendDirectSend(peer_id, Message { protocol_id, mdata }),
+            PeerManagerRequest::SendDirectSend(peer_id, Message {
+                protocol_id,
+                mdata,
+                trace_id,
+            }),
         )?;
         Ok(())
     }
 
+    /// Send a direct-send message to remote peer, waiting for up to `send_timeout`
+    /// for space in the peer's outbound queue (if the queue is full). If the message
+    /// still can't be enqueued by the deadline, a `SendTimeout` error is returned
+    /// (instead of silently dropping the message). See `send_to()` for more details.
+    pub async fn send_to_with_timeout(
+        &self,
+        peer_id: PeerId,
+        protocol_id: ProtocolId,
+        mdata: Bytes,
+        send_timeout: Duration,
+    ) -> Result<(), PeerManagerError> {
+        self.ensure_not_quiesced()?;
+        let request = PeerManagerRequest::SendDirectSend(peer_id, Message {
+            protocol_id,
+            mdata,
+            trace_id: None,
+        });
+
+        // Wait for space in the queue (if it's full), without dropping any messages
+        let push = self.inner.push_when_ready((peer_id, protocol_id), request);
+        match self.time_service.timeout(send_timeout, push).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(PeerManagerError::SendTimeout(peer_id, send_timeout)),
+        }
+    }
+
     /// Send the _same_ message to many recipients using the direct-send protocol.
     ///
     /// This method is an optimization so that we can avoid serializing and
@@ -71,7 +207,12 @@ impl PeerManagerRequestSender {
         protocol_id: ProtocolId,
         mdata: Bytes,
     ) -> Result<(), PeerManagerError> {
-        let msg = Message { protocol_id, mdata };
+        self.ensure_not_quiesced()?;
+        let msg = Message {
+            protocol_id,
+            mdata,
+            trace_id: None,
+        };
         for recipient in recipients {
             // We return `Err` early here if the send fails. Since sending will
             // only fail if the queue is unexpectedly shutdown (i.e., receiver
@@ -93,6 +234,8 @@ impl PeerManagerRequestSender {
         req: Bytes,
         timeout: Duration,
     ) -> Result<Bytes, RpcError> {
+        self.ensure_not_quiesced()?;
+        let _inflight_rpc_guard = self.inflight_rpcs.track_rpc();
         let (res_tx, res_rx) = oneshot::channel();
         let request = OutboundRpcRequest {
             protocol_id,
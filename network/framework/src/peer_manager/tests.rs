@@ -4,11 +4,15 @@
 
 use crate::{
     application::storage::PeersAndMetadata,
-    constants,
+    constants, counters,
     peer::DisconnectReason,
     peer_manager::{
-        conn_notifs_channel, error::PeerManagerError, ConnectionNotification, ConnectionRequest,
-        PeerManager, PeerManagerRequest, TransportNotification,
+        builder::self_test_protocol_receiver,
+        conn_notifs_channel,
+        error::PeerManagerError,
+        transport::{TransportHandler, TransportRequest},
+        ConnectionNotification, ConnectionRequest, PeerManager, PeerManagerRequest,
+        TransportNotification,
     },
     protocols::wire::{
         handshake::v1::{MessagingProtocolVersion, ProtocolIdSet},
@@ -24,18 +28,18 @@ use crate::{
 use anyhow::anyhow;
 use aptos_channels::{aptos_channel, message_queues::QueueStyle};
 use aptos_config::{
-    config::{PeerRole, MAX_INBOUND_CONNECTIONS},
-    network_id::{NetworkContext, NetworkId},
+    config::{PeerRole, RoleType, UnknownProtocolPolicy, MAX_INBOUND_CONNECTIONS},
+    network_id::{NetworkContext, NetworkId, PeerNetworkId},
 };
 use aptos_memsocket::MemorySocket;
 use aptos_netcore::transport::{
-    boxed::BoxedTransport, memory::MemoryTransport, ConnectionOrigin, TransportExt,
+    boxed::BoxedTransport, memory::MemoryTransport, ConnectionOrigin, Transport, TransportExt,
 };
-use aptos_time_service::TimeService;
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{network_address::NetworkAddress, PeerId};
 use bytes::Bytes;
-use futures::{channel::oneshot, io::AsyncWriteExt, stream::StreamExt};
-use std::error::Error;
+use futures::{channel::oneshot, io::AsyncWriteExt, stream::StreamExt, FutureExt, SinkExt};
+use std::{collections::HashMap, error::Error, time::Duration};
 use tokio::runtime::Handle;
 use tokio_util::compat::{
     FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt,
@@ -98,13 +102,14 @@ fn build_test_peer_manager(
     let (conn_status_tx, conn_status_rx) = conn_notifs_channel::new();
 
     let network_id = NetworkId::Validator;
+    let time_service = TimeService::mock();
     let peer_manager = PeerManager::new(
         executor,
-        TimeService::mock(),
+        time_service.clone(),
         build_test_transport(),
         NetworkContext::mock_with_peer_id(peer_id),
         "/memory/0".parse().unwrap(),
-        PeersAndMetadata::new(&[network_id]),
+        PeersAndMetadata::new_with_time_service(&[network_id], time_service),
         peer_manager_request_rx,
         connection_reqs_rx,
         [(ProtocolId::DiscoveryDirectSend, hello_tx)]
@@ -116,6 +121,12 @@ fn build_test_peer_manager(
         constants::MAX_FRAME_SIZE,
         constants::MAX_MESSAGE_SIZE,
         MAX_INBOUND_CONNECTIONS,
+        None,
+        None,
+        None,
+        UnknownProtocolPolicy::default(),
+        None, /* Disable inbound memory budget */
+        None, /* Disable compression */
     );
 
     (
@@ -590,6 +601,91 @@ fn peer_manager_simultaneous_dial_disconnect_event() {
     runtime.block_on(test);
 }
 
+#[test]
+fn peer_manager_simultaneous_dial_resolves_to_one_connection() {
+    let runtime = ::tokio::runtime::Runtime::new().unwrap();
+
+    // Create a peer manager for each side of the simultaneous dial
+    let ids = ordered_peer_ids(2);
+    let (mut lower_peer_manager, _request_tx, _connection_reqs_tx, _conn_status_rx) =
+        build_test_peer_manager(runtime.handle().clone(), ids[0]);
+    let (mut higher_peer_manager, _request_tx, _connection_reqs_tx, _conn_status_rx) =
+        build_test_peer_manager(runtime.handle().clone(), ids[1]);
+
+    let test = async move {
+        // Both peers dial each other at the same time, so each side sees its
+        // outbound connection followed by the remote's inbound connection.
+        let (lower_dialed, higher_accepted) = build_test_connection();
+        let (higher_dialed, lower_accepted) = build_test_connection();
+        add_peer_to_manager(
+            &mut lower_peer_manager,
+            lower_dialed,
+            ids[1],
+            None,
+            ConnectionOrigin::Outbound,
+            0,
+        );
+        add_peer_to_manager(
+            &mut lower_peer_manager,
+            lower_accepted,
+            ids[1],
+            None,
+            ConnectionOrigin::Inbound,
+            1,
+        );
+        add_peer_to_manager(
+            &mut higher_peer_manager,
+            higher_dialed,
+            ids[0],
+            None,
+            ConnectionOrigin::Outbound,
+            0,
+        );
+        add_peer_to_manager(
+            &mut higher_peer_manager,
+            higher_accepted,
+            ids[0],
+            None,
+            ConnectionOrigin::Inbound,
+            1,
+        );
+
+        // Verify exactly one connection survives on each side, and that both sides
+        // keep the same connection (i.e., the one dialed by the greater peer id).
+        for (peer_manager, remote_peer_id, expected_origin) in [
+            (&lower_peer_manager, ids[1], ConnectionOrigin::Inbound),
+            (&higher_peer_manager, ids[0], ConnectionOrigin::Outbound),
+        ] {
+            assert_eq!(peer_manager.active_peers.len(), 1);
+            let (connection_metadata, _) = peer_manager.active_peers.get(&remote_peer_id).unwrap();
+            assert_eq!(connection_metadata.origin, expected_origin);
+
+            let connected_peers = peer_manager
+                .peers_and_metadata
+                .get_connected_peers_and_metadata()
+                .unwrap();
+            assert_eq!(connected_peers.len(), 1);
+            let peer_metadata = connected_peers.values().next().unwrap();
+            assert_eq!(
+                peer_metadata.get_connection_metadata().origin,
+                expected_origin
+            );
+        }
+
+        // Verify the resolved duplicates were counted on both sides
+        for (peer_manager, dropped) in [
+            (&lower_peer_manager, "existing"),
+            (&higher_peer_manager, "new"),
+        ] {
+            let counter =
+                counters::duplicate_connections_resolved(&peer_manager.network_context, dropped);
+            assert_eq!(counter.get(), 1);
+        }
+    };
+
+    runtime.block_on(test);
+}
+
 #[test]
 fn test_dial_disconnect() {
     ::aptos_logger::Logger::init_for_testing();
@@ -651,6 +747,223 @@ fn test_dial_disconnect() {
     runtime.block_on(test);
 }
 
+#[test]
+fn test_banned_peer() {
+    ::aptos_logger::Logger::init_for_testing();
+    let runtime = ::tokio::runtime::Runtime::new().unwrap();
+
+    // Create a list of ordered PeerIds so we can ensure how PeerIds will be compared.
+    let ids = ordered_peer_ids(2);
+    let (mut peer_manager, _request_tx, _connection_reqs_tx, mut conn_status_rx) =
+        build_test_peer_manager(runtime.handle().clone(), ids[1]);
+    let mock_time_service = peer_manager.time_service.clone().into_mock();
+    let peers_and_metadata = peer_manager.peers_and_metadata.clone();
+    let peer_network_id = PeerNetworkId::new(NetworkId::Validator, ids[0]);
+
+    let test = async move {
+        // Connect to the peer
+        let (outbound, _inbound) = build_test_connection();
+        add_peer_to_manager(
+            &mut peer_manager,
+            outbound,
+            ids[0],
+            None,
+            ConnectionOrigin::Outbound,
+            0,
+        );
+        let conn_notif = conn_status_rx.next().await.unwrap();
+        assert!(matches!(conn_notif, ConnectionNotification::NewPeer(_, _)));
+
+        // Ban the peer, and verify the peer manager disconnects it
+        peers_and_metadata.ban_peer(peer_network_id, Duration::from_secs(60));
+        assert!(peers_and_metadata.is_peer_banned(&peer_network_id));
+        let banned_peer_id = peer_manager.banned_peers_rx.next().await.unwrap();
+        assert_eq!(banned_peer_id, ids[0]);
+        peer_manager.disconnect_banned_peer(banned_peer_id);
+        assert!(!peer_manager.active_peers.contains_key(&ids[0]));
+
+        // Verify new connections and dials are refused while the peer is banned
+        let (inbound, _outbound) = build_test_connection();
+        peer_manager.handle_connection_event(TransportNotification::NewConnection(
+            create_connection(
+                inbound,
+                ids[0],
+                NetworkAddress::mock(),
+                ConnectionOrigin::Inbound,
+                ConnectionId::from(1),
+            ),
+        ));
+        assert!(!peer_manager.active_peers.contains_key(&ids[0]));
+        let (response_tx, response_rx) = oneshot::channel();
+        peer_manager
+            .handle_outbound_connection_request(ConnectionRequest::DialPeer(
+                ids[0],
+                NetworkAddress::mock(),
+                response_tx,
+            ))
+            .await;
+        assert!(matches!(
+            response_rx.await.unwrap(),
+            Err(PeerManagerError::PeerBanned(_))
+        ));
+
+        // Verify new connections are accepted once the ban expires
+        mock_time_service.advance(Duration::from_secs(60));
+        assert!(!peers_and_metadata.is_peer_banned(&peer_network_id));
+        let (inbound, _outbound) = build_test_connection();
+        peer_manager.handle_connection_event(TransportNotification::NewConnection(
+            create_connection(
+                inbound,
+                ids[0],
+                NetworkAddress::mock(),
+                ConnectionOrigin::Inbound,
+                ConnectionId::from(2),
+            ),
+        ));
+        assert!(peer_manager.active_peers.contains_key(&ids[0]));
+    };
+
+    runtime.block_on(test);
+}
+
+#[test]
+fn test_outbound_connection_latency() {
+    ::aptos_logger::Logger::init_for_testing();
+    let runtime = ::tokio::runtime::Runtime::new().unwrap();
+    let time_service = TimeService::mock();
+    let mock_time_service = time_service.clone().into_mock();
+
+    // Create a transport where each connection handshake takes 250ms (on the mock clock)
+    let remote_peer_id = PeerId::random();
+    let handshake_time_service = time_service.clone();
+    let transport = MemoryTransport
+        .and_then(move |socket, addr, origin| {
+            let handshake_time_service = handshake_time_service.clone();
+            async move {
+                handshake_time_service
+                    .sleep(Duration::from_millis(250))
+                    .await;
+                Ok::<_, std::io::Error>(create_connection(
+                    socket,
+                    remote_peer_id,
+                    addr,
+                    origin,
+                    ConnectionId::default(),
+                ))
+            }
+        })
+        .boxed();
+
+    // Create a transport handler for the public network, and a remote listener to dial
+    let network_id = NetworkId::Public;
+    let (mut transport_reqs_tx, transport_reqs_rx) = aptos_channels::new_test(1);
+    let (transport_notifs_tx, mut transport_notifs_rx) = aptos_channels::new_test(1);
+    let (_remote_listener, remote_addr) = MemoryTransport
+        .listen_on("/memory/0".parse().unwrap())
+        .unwrap();
+    let latency_histogram = counters::outbound_connection_latency(network_id);
+    let initial_sample_count = latency_histogram.get_sample_count();
+    let initial_sample_sum = latency_histogram.get_sample_sum();
+
+    let test = async move {
+        let (transport_handler, _) = TransportHandler::new(
+            NetworkContext::new(RoleType::FullNode, network_id, PeerId::random()),
+            time_service,
+            transport,
+            "/memory/0".parse().unwrap(),
+            transport_reqs_rx,
+            transport_notifs_tx,
+        );
+        tokio::spawn(transport_handler.listen());
+
+        // Dial the remote peer, and wait for the handshake to start
+        let (response_tx, response_rx) = oneshot::channel();
+        transport_reqs_tx
+            .send(TransportRequest::DialPeer(
+                remote_peer_id,
+                remote_addr,
+                response_tx,
+            ))
+            .await
+            .unwrap();
+        while mock_time_service.num_waiters() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Complete the handshake, and verify the connection is established
+        mock_time_service
+            .advance_async(Duration::from_millis(250))
+            .await;
+        response_rx.await.unwrap().unwrap();
+        let notification = transport_notifs_rx.next().await.unwrap();
+        assert!(matches!(
+            notification,
+            TransportNotification::NewConnection(_)
+        ));
+
+        // Verify the histogram recorded the simulated connection duration
+        assert_eq!(
+            latency_histogram.get_sample_count() - initial_sample_count,
+            1
+        );
+        let recorded_latency = latency_histogram.get_sample_sum() - initial_sample_sum;
+        assert!((recorded_latency - 0.25).abs() < f64::EPSILON);
+    };
+
+    runtime.block_on(test);
+}
+
+#[test]
+fn test_protocol_self_test() {
+    let network_context = NetworkContext::mock();
+    let (sender, mut receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (other_sender, mut other_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let protocols = [
+        ProtocolId::ConsensusDirectSendBcs,
+        ProtocolId::ConsensusRpcBcs,
+    ];
+
+    // Verify the self-test passes for correctly wired protocols
+    let mut upstream_handlers = HashMap::new();
+    for protocol_id in protocols {
+        upstream_handlers.insert(protocol_id, sender.clone());
+    }
+    let failed_protocols = self_test_protocol_receiver(
+        &network_context,
+        &protocols,
+        &upstream_handlers,
+        &mut receiver,
+    );
+    assert!(failed_protocols.is_empty());
+
+    // Verify the self-test drained all synthetic messages
+    assert!(receiver.next().now_or_never().is_none());
+
+    // Wire one protocol to the wrong receiver and verify the self-test fails for it
+    upstream_handlers.insert(ProtocolId::ConsensusRpcBcs, other_sender);
+    let failed_protocols = self_test_protocol_receiver(
+        &network_context,
+        &protocols,
+        &upstream_handlers,
+        &mut receiver,
+    );
+    assert_eq!(failed_protocols, vec![ProtocolId::ConsensusRpcBcs]);
+    assert!(matches!(
+        other_receiver.next().now_or_never(),
+        Some(Some(_))
+    ));
+
+    // Remove the handler for a protocol and verify the self-test fails for it
+    upstream_handlers.remove(&ProtocolId::ConsensusDirectSendBcs);
+    let failed_protocols = self_test_protocol_receiver(
+        &network_context,
+        &protocols,
+        &upstream_handlers,
+        &mut receiver,
+    );
+    assert_eq!(failed_protocols, protocols.to_vec());
+}
+
 fn add_peer_to_manager<TSocket: transport::TSocket>(
     peer_manager: &mut PeerManager<
         BoxedTransport<Connection<TSocket>, impl Error + Sync + Send + 'static>,
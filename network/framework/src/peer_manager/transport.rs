@@ -252,6 +252,8 @@ where
 
         let response = match upgrade {
             Ok(connection) => {
+                counters::outbound_connection_latency(self.network_context.network_id())
+                    .observe(elapsed_time);
                 self.send_connection_to_peer_manager(connection, &addr, elapsed_time)
                     .await;
                 Ok(())
@@ -4,10 +4,12 @@
 use crate::{
     peer::DisconnectReason,
     peer_manager::PeerManagerError,
-    protocols::{direct_send::Message, rpc::OutboundRpcRequest},
+    protocols::{
+        direct_send::Message, rpc::OutboundRpcRequest, wire::handshake::v1::ProtocolIdSet,
+    },
     transport::{Connection, ConnectionMetadata},
 };
-use aptos_config::network_id::NetworkId;
+use aptos_config::network_id::{NetworkId, PeerNetworkId};
 use aptos_types::{network_address::NetworkAddress, PeerId};
 use futures::channel::oneshot;
 use serde::Serialize;
@@ -41,6 +43,9 @@ pub enum ConnectionNotification {
     NewPeer(ConnectionMetadata, NetworkId),
     /// Connection to a peer has been terminated. This could have been triggered from either end.
     LostPeer(ConnectionMetadata, NetworkId),
+    /// The set of application protocols supported by a connected peer has changed
+    /// (e.g., the peer renegotiated its handshake). Carries the updated protocol set.
+    UpdatedPeerProtocols(PeerNetworkId, ProtocolIdSet),
 }
 
 impl fmt::Debug for ConnectionNotification {
@@ -58,6 +63,9 @@ impl fmt::Display for ConnectionNotification {
             ConnectionNotification::LostPeer(metadata, network_id) => {
                 write!(f, "[{},{}]", metadata, network_id)
             },
+            ConnectionNotification::UpdatedPeerProtocols(peer_network_id, protocols) => {
+                write!(f, "[{},{:?}]", peer_network_id, protocols)
+            },
         }
     }
 }
@@ -2,7 +2,10 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{protocols::network::SerializedRequest, ProtocolId};
+use crate::{
+    protocols::{network::SerializedRequest, wire::messaging::v1::TraceId},
+    ProtocolId,
+};
 use bytes::Bytes;
 use serde::Serialize;
 use std::fmt::Debug;
@@ -20,6 +23,9 @@ pub struct Message {
     /// deserialized later in the handling application module.
     #[serde(skip)]
     pub mdata: Bytes,
+    /// An optional trace id that is sent alongside the message (e.g., to
+    /// correlate the message across peers in logs).
+    pub trace_id: Option<TraceId>,
 }
 
 impl Debug for Message {
@@ -31,8 +37,8 @@ impl Debug for Message {
         };
         write!(
             f,
-            "Message {{ protocol: {:?}, mdata: {} }}",
-            self.protocol_id, mdata_str
+            "Message {{ protocol: {:?}, mdata: {}, trace_id: {:?} }}",
+            self.protocol_id, mdata_str, self.trace_id
         )
     }
 }
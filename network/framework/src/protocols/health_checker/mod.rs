@@ -224,6 +224,9 @@ impl<NetworkClient: NetworkClientInterface<HealthCheckerMsg> + Unpin> HealthChec
                                 );
                             }
                         }
+                        ConnectionNotification::UpdatedPeerProtocols(_, _) => {
+                            // Health checks are unaffected by the protocols a peer supports
+                        }
                     }
                 }
                 _ = ticker.select_next_some() => {
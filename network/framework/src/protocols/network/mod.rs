@@ -6,26 +6,46 @@
 
 pub use crate::protocols::rpc::error::RpcError;
 use crate::{
-    error::NetworkError,
+    application::{scoring::PeerScoreEvent, storage::PeersAndMetadata},
+    counters,
+    error::{NetworkError, NetworkErrorKind},
     peer_manager::{ConnectionRequestSender, PeerManagerRequestSender},
-    protocols::wire::messaging::v1::{IncomingRequest, NetworkMessage},
+    protocols::wire::{
+        handshake::v1::ProtocolIdSet,
+        messaging::v1::{IncomingRequest, NetworkMessage, TraceId, TracedDirectSendMsg},
+    },
     ProtocolId,
 };
 use aptos_channels::aptos_channel;
-use aptos_config::network_id::PeerNetworkId;
+use aptos_config::{config::OutboundProtocolRateLimit, network_id::PeerNetworkId};
+use aptos_crypto::HashValue;
+use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
+use aptos_rate_limiter::rate_limit::Bucket;
 use aptos_short_hex_str::AsShortHexStr;
 use aptos_types::{network_address::NetworkAddress, PeerId};
 use bytes::Bytes;
 use futures::{
     channel::oneshot,
+    future::{Either, FutureExt},
     stream::{FusedStream, Stream, StreamExt},
     task::{Context, Poll},
 };
 use futures_util::ready;
+use lru::LruCache;
 use pin_project::pin_project;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{cmp::min, fmt::Debug, future, marker::PhantomData, pin::Pin, sync::Arc, time::Duration};
+use std::{
+    cmp::{min, Reverse},
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
 
 pub trait Message: DeserializeOwned + Serialize {}
 impl<T: DeserializeOwned + Serialize> Message for T {}
@@ -66,6 +86,76 @@ impl<TMessage: PartialEq> PartialEq for Event<TMessage> {
     }
 }
 
+impl<TMessage> Event<TMessage> {
+    /// Splits the event into the sending peer, the message and the kind of the
+    /// inbound message. For rpc requests, the kind carries the responder that
+    /// must be used to reply to the request.
+    pub fn into_inbound(self) -> (PeerId, TMessage, InboundKind) {
+        match self {
+            Event::Message(peer_id, message) => (peer_id, message, InboundKind::DirectSend),
+            Event::RpcRequest(peer_id, message, protocol_id, response_sender) => (
+                peer_id,
+                message,
+                InboundKind::RpcRequest(RpcResponder::new(protocol_id, response_sender)),
+            ),
+        }
+    }
+}
+
+/// The kind of an inbound message delivered to the application
+#[derive(Debug)]
+pub enum InboundKind {
+    /// A fire-and-forget direct-send message (no reply is expected)
+    DirectSend,
+    /// An rpc request that expects a reply via the given responder
+    RpcRequest(RpcResponder),
+}
+
+/// A handle for replying to an inbound rpc request. The response is sent
+/// over-the-wire by the network layer.
+#[derive(Debug)]
+pub struct RpcResponder {
+    protocol_id: ProtocolId,
+    response_sender: oneshot::Sender<Result<Bytes, RpcError>>,
+}
+
+impl RpcResponder {
+    pub fn new(
+        protocol_id: ProtocolId,
+        response_sender: oneshot::Sender<Result<Bytes, RpcError>>,
+    ) -> Self {
+        Self {
+            protocol_id,
+            response_sender,
+        }
+    }
+
+    /// Returns the protocol the rpc request was received on
+    pub fn protocol_id(&self) -> ProtocolId {
+        self.protocol_id
+    }
+
+    /// Serializes the response message (using the request's protocol) and sends it
+    pub fn send<TMessage: Message>(self, response: &TMessage) -> Result<(), RpcError> {
+        let response_bytes = self.protocol_id.to_bytes(response)?;
+        self.send_raw(Ok(response_bytes.into()))
+    }
+
+    /// Returns true iff the responder has expired, i.e., the network is no longer
+    /// waiting for the response (e.g., because the inbound rpc timeout elapsed).
+    pub fn is_expired(&self) -> bool {
+        self.response_sender.is_canceled()
+    }
+
+    /// Sends the raw response (or error) for the rpc request. Fails with
+    /// `RpcError::ResponderExpired` if the responder has expired.
+    pub fn send_raw(self, response: Result<Bytes, RpcError>) -> Result<(), RpcError> {
+        self.response_sender
+            .send(response)
+            .map_err(|_| RpcError::ResponderExpired)
+    }
+}
+
 /// Configuration needed for the client side of AptosNet applications
 #[derive(Clone)]
 pub struct NetworkClientConfig {
@@ -87,6 +177,9 @@ impl NetworkClientConfig {
     }
 }
 
+/// The default number of inbound RPC requests drained per turn (see `NetworkServiceConfig`)
+pub const DEFAULT_RPC_DRAIN_WEIGHT: usize = 4;
+
 /// Configuration needed for the service side of AptosNet applications
 #[derive(Clone)]
 pub struct NetworkServiceConfig {
@@ -96,6 +189,17 @@ pub struct NetworkServiceConfig {
     pub rpc_protocols_and_preferences: Vec<ProtocolId>,
     /// The inbound queue config (from the network to the application)
     pub inbound_queue_config: aptos_channel::Config,
+    /// The number of recently seen direct send messages remembered (per protocol)
+    /// to drop duplicates before delivery. If None, deduplication is disabled.
+    pub inbound_dedup_window: Option<usize>,
+    /// The protocols whose inbound messages are deserialized on the blocking thread
+    /// pool (to avoid blocking the runtime with large payloads). Messages for all
+    /// other protocols are deserialized inline. If None, all protocols are offloaded.
+    pub offload_deserialization_protocols: Option<Vec<ProtocolId>>,
+    /// The number of inbound messages drained per turn from each (peer, rpc protocol)
+    /// queue, relative to one for each (peer, direct send protocol) queue. This favors
+    /// RPC requests (which have a waiting caller) over bulk direct send traffic.
+    pub rpc_drain_weight: usize,
 }
 
 impl NetworkServiceConfig {
@@ -108,8 +212,80 @@ impl NetworkServiceConfig {
             direct_send_protocols_and_preferences,
             rpc_protocols_and_preferences,
             inbound_queue_config,
+            inbound_dedup_window: None,
+            offload_deserialization_protocols: None,
+            rpc_drain_weight: DEFAULT_RPC_DRAIN_WEIGHT,
         }
     }
+
+    /// Enables deduplication of inbound direct send messages, remembering
+    /// the last `window` messages seen for each protocol.
+    pub fn with_inbound_dedup_window(mut self, window: usize) -> Self {
+        self.inbound_dedup_window = Some(window);
+        self
+    }
+
+    /// Only offloads the deserialization of inbound messages for the given
+    /// protocols (e.g., those with large payloads) to the blocking thread pool.
+    /// Messages for all other (small) protocols are deserialized inline.
+    pub fn with_offloaded_deserialization(mut self, protocols: Vec<ProtocolId>) -> Self {
+        self.offload_deserialization_protocols = Some(protocols);
+        self
+    }
+
+    /// Sets the weight used to favor RPC requests when draining inbound messages
+    /// (see `rpc_drain_weight`). A weight of one drains all protocols equally.
+    pub fn with_rpc_drain_weight(mut self, rpc_drain_weight: usize) -> Self {
+        self.rpc_drain_weight = rpc_drain_weight;
+        self
+    }
+
+    /// Builds the inbound queue (from the network to the application), which
+    /// drains RPC requests ahead of direct send messages (see `rpc_drain_weight`).
+    pub fn build_inbound_queue(
+        &self,
+    ) -> (
+        aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>,
+        aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>,
+    ) {
+        let (sender, receiver) = self.inbound_queue_config.build();
+        let rpc_protocols = ProtocolIdSet::from_iter(&self.rpc_protocols_and_preferences);
+        let rpc_drain_weight = self.rpc_drain_weight;
+        receiver.set_key_weight(move |(_, protocol_id)| {
+            if rpc_protocols.contains(*protocol_id) {
+                rpc_drain_weight
+            } else {
+                1
+            }
+        });
+        (sender, receiver)
+    }
+}
+
+/// The role an application plays for a protocol, i.e., whether the protocol
+/// is registered on the client side (for sending), the service side (for
+/// receiving), or both.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProtocolRole {
+    /// The application only sends on the protocol (e.g., it issues RPCs but never serves them)
+    ClientOnly,
+    /// The application only receives on the protocol (e.g., it serves RPCs but never issues them)
+    ServerOnly,
+    /// The application both sends and receives on the protocol
+    #[default]
+    Both,
+}
+
+impl ProtocolRole {
+    /// Returns true iff the protocol should be registered on the client side
+    pub fn is_client(&self) -> bool {
+        matches!(self, ProtocolRole::ClientOnly | ProtocolRole::Both)
+    }
+
+    /// Returns true iff the protocol should be registered on the service side
+    pub fn is_server(&self) -> bool {
+        matches!(self, ProtocolRole::ServerOnly | ProtocolRole::Both)
+    }
 }
 
 /// Configuration needed for AptosNet applications to register with the network
@@ -118,6 +294,8 @@ impl NetworkServiceConfig {
 pub struct NetworkApplicationConfig {
     pub network_client_config: NetworkClientConfig,
     pub network_service_config: NetworkServiceConfig,
+    /// The role of each protocol. Protocols without a role default to `ProtocolRole::Both`.
+    pub protocol_roles: HashMap<ProtocolId, ProtocolRole>,
 }
 
 impl NetworkApplicationConfig {
@@ -128,8 +306,66 @@ impl NetworkApplicationConfig {
         Self {
             network_client_config,
             network_service_config,
+            protocol_roles: HashMap::new(),
         }
     }
+
+    /// Sets the role of the given protocol (e.g., to avoid registering a
+    /// receiver for a protocol that the application never serves).
+    pub fn with_protocol_role(mut self, protocol_id: ProtocolId, role: ProtocolRole) -> Self {
+        self.protocol_roles.insert(protocol_id, role);
+        self
+    }
+
+    /// Returns the role of the given protocol
+    pub fn protocol_role(&self, protocol_id: ProtocolId) -> ProtocolRole {
+        self.protocol_roles
+            .get(&protocol_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the client config, containing only the protocols that are sent on
+    pub fn client_config(&self) -> NetworkClientConfig {
+        let config = &self.network_client_config;
+        NetworkClientConfig::new(
+            self.filter_protocols(
+                &config.direct_send_protocols_and_preferences,
+                ProtocolRole::is_client,
+            ),
+            self.filter_protocols(
+                &config.rpc_protocols_and_preferences,
+                ProtocolRole::is_client,
+            ),
+        )
+    }
+
+    /// Returns the service config, containing only the protocols that are received on
+    pub fn service_config(&self) -> NetworkServiceConfig {
+        let mut config = self.network_service_config.clone();
+        config.direct_send_protocols_and_preferences = self.filter_protocols(
+            &config.direct_send_protocols_and_preferences,
+            ProtocolRole::is_server,
+        );
+        config.rpc_protocols_and_preferences = self.filter_protocols(
+            &config.rpc_protocols_and_preferences,
+            ProtocolRole::is_server,
+        );
+        config
+    }
+
+    /// Returns the given protocols (in order) whose role satisfies the predicate
+    fn filter_protocols(
+        &self,
+        protocols: &[ProtocolId],
+        predicate: impl Fn(&ProtocolRole) -> bool,
+    ) -> Vec<ProtocolId> {
+        protocols
+            .iter()
+            .copied()
+            .filter(|protocol_id| predicate(&self.protocol_role(*protocol_id)))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -156,22 +392,37 @@ impl ReceivedMessage {
 
     pub fn protocol_id(&self) -> Option<ProtocolId> {
         match &self.message {
-            NetworkMessage::Error(_e) => None,
+            NetworkMessage::Error(_e)
+            | NetworkMessage::KeepAlive
+            | NetworkMessage::CompressedMsg(_) => None,
             NetworkMessage::RpcRequest(req) => Some(req.protocol_id),
             NetworkMessage::RpcResponse(_response) => {
                 // design of RpcResponse lacking ProtocolId requires global rpc counter (or at least per-peer) and requires reply matching globally or per-peer
                 None
             },
-            NetworkMessage::DirectSendMsg(msg) => Some(msg.protocol_id),
+            NetworkMessage::DirectSendMsg(msg)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg { message: msg, .. }) => {
+                Some(msg.protocol_id)
+            },
         }
     }
 
+    /// Returns the trace id the sender attached to the message (if any)
+    pub fn trace_id(&self) -> Option<TraceId> {
+        self.message.trace_id()
+    }
+
     pub fn protocol_id_as_str(&self) -> &'static str {
         match &self.message {
             NetworkMessage::Error(_) => "error",
+            NetworkMessage::KeepAlive => "keep alive",
+            NetworkMessage::CompressedMsg(_) => "compressed",
             NetworkMessage::RpcRequest(rr) => rr.protocol_id.as_str(),
             NetworkMessage::RpcResponse(_) => "rpc response",
-            NetworkMessage::DirectSendMsg(dm) => dm.protocol_id.as_str(),
+            NetworkMessage::DirectSendMsg(dm)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg { message: dm, .. }) => {
+                dm.protocol_id.as_str()
+            },
         }
     }
 }
@@ -184,24 +435,41 @@ impl PartialEq for ReceivedMessage {
     }
 }
 
+/// An inbound message once deserialized: either the application event, or
+/// the sender of a message that failed to deserialize (i.e., was malformed)
+type DeserializedEvent<TMessage> = Result<Event<TMessage>, PeerNetworkId>;
+
 /// A `Stream` of `Event<TMessage>` from the lower network layer to an upper
 /// network application that deserializes inbound network direct-send and rpc
 /// messages into `TMessage`. Inbound messages that fail to deserialize are logged
-/// and dropped.
+/// and dropped (and reported to the peer scoring, if enabled).
 #[pin_project]
 pub struct NetworkEvents<TMessage> {
     #[pin]
-    event_stream: Pin<Box<dyn Stream<Item = Event<TMessage>> + Send + Sync + 'static>>,
+    event_stream: Pin<Box<dyn Stream<Item = DeserializedEvent<TMessage>> + Send + Sync + 'static>>,
     done: bool,
+    // The protocols that events are received on (reported once the source closes)
+    source_protocols: Vec<ProtocolId>,
+    // The container that the senders of malformed messages are reported to (if any)
+    peers_and_metadata: Option<Arc<PeersAndMetadata>>,
     _marker: PhantomData<TMessage>,
 }
 
+/// The terminal event of a `NetworkEvents` stream (see `NetworkEvents::next_or_closed()`),
+/// returned once the senders for all of the source's protocols were dropped, i.e., no more
+/// events will ever be received. Applications can use this to trigger recovery.
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("The network event source is closed! Protocols: {0:?}")]
+pub struct SourceClosed(pub Vec<ProtocolId>);
+
 /// Trait specifying the signature for `new()` `NetworkEvents`
 pub trait NewNetworkEvents {
     fn new(
         peer_mgr_notifs_rx: aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>,
         max_parallel_deserialization_tasks: Option<usize>,
         allow_out_of_order_delivery: bool,
+        inbound_dedup_window: Option<usize>,
+        offload_deserialization_protocols: Option<Vec<ProtocolId>>,
     ) -> Self;
 }
 
@@ -210,16 +478,48 @@ impl<TMessage: Message + Send + Sync + 'static> NewNetworkEvents for NetworkEven
         peer_mgr_notifs_rx: aptos_channel::Receiver<(PeerId, ProtocolId), ReceivedMessage>,
         max_parallel_deserialization_tasks: Option<usize>,
         allow_out_of_order_delivery: bool,
+        inbound_dedup_window: Option<usize>,
+        offload_deserialization_protocols: Option<Vec<ProtocolId>>,
     ) -> Self {
         // Determine the number of parallel deserialization tasks to use
         let max_parallel_deserialization_tasks = max_parallel_deserialization_tasks.unwrap_or(1);
 
-        let data_event_stream = peer_mgr_notifs_rx.map(|notification| {
-            tokio::task::spawn_blocking(move || received_message_to_event(notification))
-        });
+        // Identify the protocols to offload deserialization for (if specified)
+        let offload_deserialization_protocols =
+            offload_deserialization_protocols.map(ProtocolIdSet::from_iter);
+
+        // Create the inbound dedup filter (if enabled)
+        let mut inbound_dedup_filter = inbound_dedup_window
+            .filter(|window| *window > 0)
+            .map(InboundDedupFilter::new);
+
+        let data_event_stream = peer_mgr_notifs_rx
+            .filter(move |notification| {
+                let should_deliver = inbound_dedup_filter
+                    .as_mut()
+                    .map_or(true, |filter| filter.should_deliver(notification));
+                future::ready(should_deliver)
+            })
+            .map(move |notification| {
+                // Deserialize the message on the blocking thread pool (if required),
+                // otherwise, deserialize it inline.
+                let offload_deserialization = match &offload_deserialization_protocols {
+                    Some(protocols) => notification
+                        .protocol_id()
+                        .map_or(false, |protocol_id| protocols.contains(protocol_id)),
+                    None => true,
+                };
+                if offload_deserialization {
+                    Either::Left(tokio::task::spawn_blocking(move || {
+                        received_message_to_event(notification)
+                    }))
+                } else {
+                    Either::Right(future::ready(Ok(received_message_to_event(notification))))
+                }
+            });
 
         let data_event_stream: Pin<
-            Box<dyn Stream<Item = Event<TMessage>> + Send + Sync + 'static>,
+            Box<dyn Stream<Item = DeserializedEvent<TMessage>> + Send + Sync + 'static>,
         > = if allow_out_of_order_delivery {
             Box::pin(
                 data_event_stream
@@ -237,28 +537,140 @@ impl<TMessage: Message + Send + Sync + 'static> NewNetworkEvents for NetworkEven
         Self {
             event_stream: data_event_stream,
             done: false,
+            source_protocols: vec![],
+            peers_and_metadata: None,
             _marker: PhantomData,
         }
     }
 }
 
+impl<TMessage> NetworkEvents<TMessage> {
+    /// Returns the next event if one is immediately available, or `None` otherwise
+    /// (including once the stream has terminated). This is useful for applications
+    /// that run their own polling loop and can't await the next event.
+    ///
+    /// Note: inbound messages are deserialized on blocking tasks, so a message that
+    /// has only just been received may not be returned until a subsequent call. No
+    /// messages are lost if `None` is returned, and calls can be freely interleaved
+    /// with the async `next()`.
+    pub fn try_next(&mut self) -> Option<Event<TMessage>> {
+        self.next().now_or_never().flatten()
+    }
+
+    /// Sets the protocols that events are received on (e.g., the protocols
+    /// of the application's service config), so that they can be reported
+    /// once the source closes (see `next_or_closed()`).
+    pub fn with_source_protocols(mut self, source_protocols: Vec<ProtocolId>) -> Self {
+        self.source_protocols = source_protocols;
+        self
+    }
+
+    /// Reports the senders of inbound messages that fail to deserialize to the peer
+    /// scoring of the given container (see `PeerScoreEvent::MalformedMessage`), so
+    /// that peers that keep sending malformed messages are eventually banned.
+    pub fn with_peer_scoring(mut self, peers_and_metadata: Arc<PeersAndMetadata>) -> Self {
+        self.peers_and_metadata = Some(peers_and_metadata);
+        self
+    }
+
+    /// Returns the next event, or `SourceClosed` once the stream has terminated (i.e.,
+    /// all senders were dropped and all pending events were delivered). Unlike `next()`,
+    /// which only yields `None`, this makes the closure explicit, e.g., for applications
+    /// that merge several event streams (where a closed stream is otherwise dropped
+    /// silently) and would stop receiving events without noticing.
+    pub async fn next_or_closed(&mut self) -> Result<Event<TMessage>, SourceClosed> {
+        self.next()
+            .await
+            .ok_or_else(|| SourceClosed(self.source_protocols.clone()))
+    }
+
+    /// Converts these events into a stream of event batches. Each poll waits for
+    /// the next event, and then drains up to `max_batch` of the events that are
+    /// already available, in the same order they would have been received one at
+    /// a time (see `aptos_channel::Receiver::batched()`). This is useful for
+    /// high-throughput applications that can process events in bulk.
+    ///
+    /// Panics if `max_batch` is zero.
+    pub fn batched(self, max_batch: usize) -> BatchedNetworkEvents<TMessage> {
+        assert!(max_batch > 0, "The maximum batch size must be non-zero!");
+        BatchedNetworkEvents {
+            network_events: self,
+            max_batch,
+        }
+    }
+}
+
 impl<TMessage> Stream for NetworkEvents<TMessage> {
     type Item = Event<TMessage>;
 
     fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<Self::Item>> {
-        let this = self.project();
+        let mut this = self.project();
         if *this.done {
             return Poll::Ready(None);
         }
-        let item = ready!(this.event_stream.poll_next(context));
-        if item.is_none() {
-            *this.done = true;
+        loop {
+            match ready!(this.event_stream.as_mut().poll_next(context)) {
+                Some(Ok(event)) => return Poll::Ready(Some(event)),
+                Some(Err(sender)) => {
+                    if let Some(peers_and_metadata) = this.peers_and_metadata {
+                        peers_and_metadata
+                            .report_peer_event(sender, PeerScoreEvent::MalformedMessage);
+                    }
+                },
+                None => {
+                    *this.done = true;
+                    return Poll::Ready(None);
+                },
+            }
         }
-        Poll::Ready(item)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.event_stream.size_hint()
+        // Malformed messages are dropped, so there may be fewer events than messages
+        (0, self.event_stream.size_hint().1)
+    }
+}
+
+/// Drops inbound direct send messages that were recently seen on the same
+/// protocol (e.g., the same mempool transactions gossiped by multiple peers).
+/// RPC requests are never deduplicated, as each one expects a response.
+struct InboundDedupFilter {
+    window: usize,
+    recently_seen: HashMap<ProtocolId, LruCache<HashValue, ()>>,
+}
+
+impl InboundDedupFilter {
+    fn new(window: usize) -> Self {
+        Self {
+            window,
+            recently_seen: HashMap::new(),
+        }
+    }
+
+    /// Returns true iff the message should be delivered to the application
+    fn should_deliver(&mut self, notification: &ReceivedMessage) -> bool {
+        let direct_send_msg = match &notification.message {
+            NetworkMessage::DirectSendMsg(direct_send_msg)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg {
+                message: direct_send_msg,
+                ..
+            }) => direct_send_msg,
+            _ => return true,
+        };
+
+        let protocol_id = direct_send_msg.protocol_id;
+        let window = self.window;
+        let recently_seen = self
+            .recently_seen
+            .entry(protocol_id)
+            .or_insert_with(|| LruCache::new(window));
+        let message_hash = HashValue::sha3_256_of(&direct_send_msg.raw_msg);
+        if recently_seen.put(message_hash, ()).is_some() {
+            crate::counters::inbound_duplicate_message_dropped(protocol_id);
+            false
+        } else {
+            true
+        }
     }
 }
 
@@ -270,14 +682,14 @@ fn unix_micros() -> u64 {
 }
 
 /// Deserialize inbound direct send and rpc messages into the application `TMessage`
-/// type, logging and dropping messages that fail to deserialize.
+/// type, logging messages that fail to deserialize (and returning their sender).
 fn received_message_to_event<TMessage: Message>(
     message: ReceivedMessage,
-) -> Option<Event<TMessage>> {
+) -> Option<DeserializedEvent<TMessage>> {
     let peer_id = message.sender.peer_id();
     let ReceivedMessage {
         message,
-        sender: _sender,
+        sender,
         receive_timestamp_micros: rx_at,
         rpc_replier,
     } = message;
@@ -288,12 +700,22 @@ fn received_message_to_event<TMessage: Message>(
         NetworkMessage::RpcRequest(rpc_req) => {
             crate::counters::inbound_queue_delay_observe(rpc_req.protocol_id, dt_seconds);
             let rpc_replier = Arc::into_inner(rpc_replier.unwrap()).unwrap();
-            request_to_network_event(peer_id, &rpc_req)
-                .map(|msg| Event::RpcRequest(peer_id, msg, rpc_req.protocol_id, rpc_replier))
+            Some(
+                request_to_network_event(peer_id, &rpc_req)
+                    .map(|msg| Event::RpcRequest(peer_id, msg, rpc_req.protocol_id, rpc_replier))
+                    .ok_or(sender),
+            )
         },
-        NetworkMessage::DirectSendMsg(request) => {
+        NetworkMessage::DirectSendMsg(request)
+        | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg {
+            message: request, ..
+        }) => {
             crate::counters::inbound_queue_delay_observe(request.protocol_id, dt_seconds);
-            request_to_network_event(peer_id, &request).map(|msg| Event::Message(peer_id, msg))
+            Some(
+                request_to_network_event(peer_id, &request)
+                    .map(|msg| Event::Message(peer_id, msg))
+                    .ok_or(sender),
+            )
         },
         _ => None,
     }
@@ -305,7 +727,10 @@ fn request_to_network_event<TMessage: Message, Request: IncomingRequest>(
     request: &Request,
 ) -> Option<TMessage> {
     match request.to_message() {
-        Ok(msg) => Some(msg),
+        Ok(msg) => {
+            crate::counters::inbound_application_message_delivered(request.protocol_id());
+            Some(msg)
+        },
         Err(err) => {
             let data = request.data();
             warn!(
@@ -326,6 +751,126 @@ impl<TMessage> FusedStream for NetworkEvents<TMessage> {
     }
 }
 
+/// A `Stream` of `Event<TMessage>` batches (see `NetworkEvents::batched()`)
+#[pin_project]
+pub struct BatchedNetworkEvents<TMessage> {
+    #[pin]
+    network_events: NetworkEvents<TMessage>,
+    max_batch: usize,
+}
+
+impl<TMessage> Stream for BatchedNetworkEvents<TMessage> {
+    type Item = Vec<Event<TMessage>>;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let mut batch = Vec::new();
+        while batch.len() < *this.max_batch {
+            match this.network_events.as_mut().poll_next(context) {
+                Poll::Ready(Some(event)) => batch.push(event),
+                Poll::Ready(None) if batch.is_empty() => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => break,
+            }
+        }
+
+        if batch.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(Some(batch))
+        }
+    }
+}
+
+impl<TMessage> FusedStream for BatchedNetworkEvents<TMessage> {
+    fn is_terminated(&self) -> bool {
+        self.network_events.is_terminated()
+    }
+}
+
+/// Merges the given streams (e.g., the per-protocol `NetworkEvents` of a service)
+/// into a single stream that polls them by weight (unlike `select_all()`, which
+/// gives no ordering guarantees). The streams are polled in descending order of
+/// weight, and each stream yields at most `weight` items per round. So, when several
+/// streams have items queued, the highest weighted streams are drained first (e.g.,
+/// consensus RPCs before bulk direct-sends), but the lower weighted streams are never
+/// starved. A weight of 0 is treated as 1.
+pub fn select_weighted<S: Stream + Unpin>(sources: Vec<(S, usize)>) -> WeightedSelect<S> {
+    let mut sources: Vec<_> = sources
+        .into_iter()
+        .map(|(stream, weight)| {
+            let weight = weight.max(1);
+            WeightedSource {
+                stream,
+                weight,
+                credits: weight,
+                done: false,
+            }
+        })
+        .collect();
+    sources.sort_by_key(|source| Reverse(source.weight));
+    WeightedSelect { sources }
+}
+
+/// The stream returned by `select_weighted()`
+pub struct WeightedSelect<S> {
+    // The streams, sorted by descending weight
+    sources: Vec<WeightedSource<S>>,
+}
+
+struct WeightedSource<S> {
+    stream: S,
+    weight: usize,
+    // The number of items the stream can still yield in the current round
+    credits: usize,
+    done: bool,
+}
+
+impl<S: Stream + Unpin> Stream for WeightedSelect<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<Self::Item>> {
+        // Poll the streams that have credits left, and start a new round (i.e.,
+        // refill the credits) if those are all pending but others are not.
+        for _ in 0..2 {
+            for source in self.sources.iter_mut() {
+                if source.done || source.credits == 0 {
+                    continue;
+                }
+                match source.stream.poll_next_unpin(context) {
+                    Poll::Ready(Some(item)) => {
+                        source.credits -= 1;
+                        return Poll::Ready(Some(item));
+                    },
+                    Poll::Ready(None) => source.done = true,
+                    Poll::Pending => {},
+                }
+            }
+
+            if self.sources.iter().all(|source| source.done) {
+                return Poll::Ready(None);
+            }
+            if self
+                .sources
+                .iter()
+                .all(|source| source.done || source.credits > 0)
+            {
+                // All live streams were polled (and are pending)
+                break;
+            }
+            for source in self.sources.iter_mut() {
+                source.credits = source.weight;
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<S: Stream + Unpin> FusedStream for WeightedSelect<S> {
+    fn is_terminated(&self) -> bool {
+        self.sources.iter().all(|source| source.done)
+    }
+}
+
 /// `NetworkSender` is the generic interface from upper network applications to
 /// the lower network layer. It provides the full API for network applications,
 /// including sending direct-send messages, sending rpc requests, as well as
@@ -343,9 +888,198 @@ impl<TMessage> FusedStream for NetworkEvents<TMessage> {
 pub struct NetworkSender<TMessage> {
     peer_mgr_reqs_tx: PeerManagerRequestSender,
     connection_reqs_tx: ConnectionRequestSender,
+    // An optional buffer of recently sent direct-send messages (shared across clones)
+    resend_buffer: Option<Arc<Mutex<ResendBuffer>>>,
+    // Optional per (peer, protocol) accounting of outbound traffic (shared across clones)
+    outbound_traffic: Option<Arc<Mutex<HashMap<(PeerId, ProtocolId), OutboundTraffic>>>>,
+    // The send timeouts that override the protocol defaults (see `send_to_with_timeout()`)
+    send_timeouts: HashMap<ProtocolId, Duration>,
+    // An optional cache of recently serialized direct-send messages (shared across clones)
+    message_coalescer: Option<Arc<Mutex<MessageCoalescer>>>,
+    // Optional per (peer, protocol) rate limits on direct-send messages (shared across clones)
+    outbound_rate_limiter: Option<Arc<OutboundRateLimiter>>,
     _marker: PhantomData<TMessage>,
 }
 
+/// The outbound traffic (i.e., messages and serialized bytes) sent to a peer on a protocol
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OutboundTraffic {
+    pub num_messages: u64,
+    pub num_bytes: u64,
+}
+
+/// A bounded buffer that retains the last `capacity` direct-send messages
+/// sent to each (peer, protocol) pair, so they can be resent on reconnect.
+#[derive(Debug)]
+struct ResendBuffer {
+    capacity: usize,
+    recent_messages: HashMap<(PeerId, ProtocolId), VecDeque<Bytes>>,
+}
+
+impl ResendBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            recent_messages: HashMap::new(),
+        }
+    }
+
+    /// Records the message, evicting the oldest message for the pair if the buffer is full
+    fn record(&mut self, peer_id: PeerId, protocol_id: ProtocolId, message: Bytes) {
+        let recent_messages = self
+            .recent_messages
+            .entry((peer_id, protocol_id))
+            .or_default();
+        if recent_messages.len() == self.capacity {
+            recent_messages.pop_front();
+        }
+        recent_messages.push_back(message);
+    }
+
+    /// Returns the recent messages for the pair (oldest first)
+    fn get_recent(&self, peer_id: PeerId, protocol_id: ProtocolId) -> Vec<Bytes> {
+        self.recent_messages
+            .get(&(peer_id, protocol_id))
+            .map(|recent_messages| recent_messages.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The serializations performed (and avoided) by the message coalescer of a sender
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CoalescingStats {
+    pub num_serializations: u64,
+    pub num_coalesced: u64,
+}
+
+/// A cache of recently serialized direct-send messages, keyed by protocol and a
+/// coalescing key supplied by the sender (e.g., a hash of the message contents).
+/// Messages sent with the same key within the coalescing window (e.g., the same
+/// broadcast sent to many peers one at a time) share a single serialized buffer.
+#[derive(Debug)]
+struct MessageCoalescer {
+    window: Duration,
+    serialized_messages: HashMap<(ProtocolId, HashValue), (Instant, Bytes)>,
+    // The cache keys in insertion (and thus expiry) order
+    expiry_queue: VecDeque<(Instant, (ProtocolId, HashValue))>,
+    stats: CoalescingStats,
+}
+
+impl MessageCoalescer {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            serialized_messages: HashMap::new(),
+            expiry_queue: VecDeque::new(),
+            stats: CoalescingStats::default(),
+        }
+    }
+
+    /// Returns the serialized message for the given protocol and coalescing key,
+    /// serializing the message (using the given function) only if no message with
+    /// the same key was serialized within the coalescing window.
+    fn get_or_serialize(
+        &mut self,
+        protocol_id: ProtocolId,
+        coalescing_key: HashValue,
+        serialize: impl FnOnce() -> Result<Bytes, NetworkError>,
+    ) -> Result<Bytes, NetworkError> {
+        let now = Instant::now();
+        self.evict_expired(now);
+
+        let key = (protocol_id, coalescing_key);
+        if let Some((_, bytes)) = self.serialized_messages.get(&key) {
+            self.stats.num_coalesced += 1;
+            return Ok(bytes.clone());
+        }
+
+        let bytes = serialize()?;
+        self.stats.num_serializations += 1;
+        self.serialized_messages.insert(key, (now, bytes.clone()));
+        self.expiry_queue.push_back((now, key));
+        Ok(bytes)
+    }
+
+    /// Evicts the entries that were serialized outside the coalescing window. Entries
+    /// expire in insertion order, so only the expired prefix of the queue is visited.
+    fn evict_expired(&mut self, now: Instant) {
+        while let Some((serialized_at, key)) = self.expiry_queue.front().copied() {
+            if now.duration_since(serialized_at) < self.window {
+                break;
+            }
+            self.expiry_queue.pop_front();
+            self.serialized_messages.remove(&key);
+        }
+    }
+}
+
+/// Token bucket rate limits on the direct-send messages sent to each
+/// (peer, protocol) pair. Buckets are created lazily (full) on first use, and
+/// idle buckets are evicted (see `prune_idle_buckets()`), so the number of
+/// buckets is bounded by the number of recently active (peer, protocol) pairs.
+#[derive(Debug)]
+struct OutboundRateLimiter {
+    rate_limits: HashMap<ProtocolId, OutboundProtocolRateLimit>,
+    buckets: Mutex<HashMap<(PeerId, ProtocolId), Bucket>>,
+}
+
+impl OutboundRateLimiter {
+    fn new(rate_limits: HashMap<ProtocolId, OutboundProtocolRateLimit>) -> Self {
+        Self {
+            rate_limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the bucket size for the given rate limit
+    fn bucket_size(rate_limit: &OutboundProtocolRateLimit) -> usize {
+        rate_limit.burst_size.max(rate_limit.messages_per_second)
+    }
+
+    /// Acquires a token for a message sent to the peer on the protocol. Returns
+    /// false iff the protocol is rate limited and the peer's bucket is empty.
+    fn try_acquire(&self, peer_id: PeerId, protocol_id: ProtocolId) -> bool {
+        let rate_limit = match self.rate_limits.get(&protocol_id) {
+            Some(rate_limit) => rate_limit,
+            None => return true,
+        };
+        let mut buckets = self.buckets.lock();
+        if !buckets.contains_key(&(peer_id, protocol_id)) {
+            self.prune_idle_buckets(&mut buckets);
+        }
+        let bucket = buckets.entry((peer_id, protocol_id)).or_insert_with(|| {
+            let size = Self::bucket_size(rate_limit);
+            Bucket::new(
+                "outbound_protocol_rate_limit".into(),
+                protocol_id.as_str().into(),
+                peer_id.short_str().to_string(),
+                size,
+                size,
+                rate_limit.messages_per_second,
+                None,
+            )
+        });
+        bucket.acquire_all_tokens(1).is_ok()
+    }
+
+    /// Evicts the buckets that have refilled completely. These are equivalent to
+    /// new (full) buckets, so evicting them doesn't change the rate limits, but
+    /// it ensures buckets are not retained for disconnected (or idle) peers.
+    fn prune_idle_buckets(&self, buckets: &mut HashMap<(PeerId, ProtocolId), Bucket>) {
+        let now = Instant::now();
+        buckets.retain(|(_, protocol_id), bucket| {
+            let size = self
+                .rate_limits
+                .get(protocol_id)
+                .map(Self::bucket_size)
+                .unwrap_or_default();
+            bucket
+                .time_of_tokens_needed(size)
+                .map_or(true, |refill_time| refill_time > now)
+        });
+    }
+}
+
 /// Trait specifying the signature for `new()` `NetworkSender`s
 pub trait NewNetworkSender {
     fn new(
@@ -362,12 +1096,143 @@ impl<TMessage> NewNetworkSender for NetworkSender<TMessage> {
         Self {
             peer_mgr_reqs_tx,
             connection_reqs_tx,
+            resend_buffer: None,
+            outbound_traffic: None,
+            send_timeouts: HashMap::new(),
+            message_coalescer: None,
+            outbound_rate_limiter: None,
             _marker: PhantomData,
         }
     }
 }
 
 impl<TMessage> NetworkSender<TMessage> {
+    /// Enables retention of the last `capacity` direct-send messages sent to
+    /// each (peer, protocol) pair, so that they can be replayed using
+    /// `resend_recent()` (e.g., when a peer reconnects after a transient drop).
+    /// This is disabled by default.
+    pub fn with_resend_buffer(mut self, capacity: usize) -> Self {
+        self.resend_buffer =
+            (capacity > 0).then(|| Arc::new(Mutex::new(ResendBuffer::new(capacity))));
+        self
+    }
+
+    /// Resends the recently buffered direct-send messages for the given peer
+    /// and protocol (in the order they were originally sent). Returns the
+    /// number of messages that were resent. If the resend buffer is disabled,
+    /// nothing is sent.
+    pub fn resend_recent(&self, peer: PeerId, protocol: ProtocolId) -> Result<usize, NetworkError> {
+        let recent_messages = match &self.resend_buffer {
+            Some(resend_buffer) => resend_buffer.lock().get_recent(peer, protocol),
+            None => return Ok(0),
+        };
+        for message in &recent_messages {
+            self.peer_mgr_reqs_tx
+                .send_to(peer, protocol, message.clone())?;
+        }
+        Ok(recent_messages.len())
+    }
+
+    /// Records the direct-send message in the resend buffer (if enabled)
+    fn record_for_resend(&self, recipient: PeerId, protocol: ProtocolId, message: &Bytes) {
+        if let Some(resend_buffer) = &self.resend_buffer {
+            resend_buffer
+                .lock()
+                .record(recipient, protocol, message.clone());
+        }
+    }
+
+    /// Enables accounting of the outbound traffic sent to each (peer, protocol)
+    /// pair, which can be read using `outbound_traffic()`. This is disabled by
+    /// default. Note: the aggregated outbound traffic metrics are always updated.
+    pub fn with_outbound_accounting(mut self) -> Self {
+        self.outbound_traffic = Some(Arc::new(Mutex::new(HashMap::new())));
+        self
+    }
+
+    /// Returns the outbound traffic sent to the given peer on the given protocol.
+    /// If outbound accounting is disabled, no traffic is reported.
+    pub fn outbound_traffic(&self, peer: PeerId, protocol: ProtocolId) -> OutboundTraffic {
+        self.outbound_traffic
+            .as_ref()
+            .and_then(|outbound_traffic| outbound_traffic.lock().get(&(peer, protocol)).copied())
+            .unwrap_or_default()
+    }
+
+    /// Accounts for a message (of the given serialized size) sent to the recipient
+    fn account_outbound_message(&self, recipient: PeerId, protocol: ProtocolId, num_bytes: usize) {
+        counters::outbound_peer_message_sent(protocol, &recipient, num_bytes as u64);
+        if let Some(outbound_traffic) = &self.outbound_traffic {
+            let mut outbound_traffic = outbound_traffic.lock();
+            let traffic = outbound_traffic.entry((recipient, protocol)).or_default();
+            traffic.num_messages += 1;
+            traffic.num_bytes += num_bytes as u64;
+        }
+    }
+
+    /// Sets the send timeout for the given protocol, overriding the protocol's
+    /// default (see `ProtocolId::default_send_timeout()`).
+    pub fn with_send_timeout(mut self, protocol: ProtocolId, send_timeout: Duration) -> Self {
+        self.send_timeouts.insert(protocol, send_timeout);
+        self
+    }
+
+    /// Returns the send timeout for the given protocol
+    pub fn send_timeout(&self, protocol: ProtocolId) -> Duration {
+        self.send_timeouts
+            .get(&protocol)
+            .copied()
+            .unwrap_or_else(|| protocol.default_send_timeout())
+    }
+
+    /// Enables coalescing of direct-send messages sent via `send_to_coalesced()`: a
+    /// message that is sent (on the same protocol) within `window` of a message with
+    /// the same coalescing key reuses the previously serialized bytes, instead of
+    /// being serialized again. This is disabled by default.
+    pub fn with_message_coalescing(mut self, window: Duration) -> Self {
+        self.message_coalescer =
+            (!window.is_zero()).then(|| Arc::new(Mutex::new(MessageCoalescer::new(window))));
+        self
+    }
+
+    /// Returns the serializations performed (and avoided) by the message coalescer.
+    /// If coalescing is disabled, no serializations are reported.
+    pub fn coalescing_stats(&self) -> CoalescingStats {
+        self.message_coalescer
+            .as_ref()
+            .map(|message_coalescer| message_coalescer.lock().stats)
+            .unwrap_or_default()
+    }
+
+    /// Enables the given per-protocol rate limits on the direct-send messages sent
+    /// to each peer (e.g., so that a mempool broadcast storm can't starve consensus).
+    /// Messages that exceed the rate limit are dropped and a `RateLimited` error is
+    /// returned. Protocols without a rate limit (and RPCs) are never throttled.
+    pub fn with_outbound_rate_limits(
+        mut self,
+        rate_limits: HashMap<ProtocolId, OutboundProtocolRateLimit>,
+    ) -> Self {
+        self.outbound_rate_limiter =
+            (!rate_limits.is_empty()).then(|| Arc::new(OutboundRateLimiter::new(rate_limits)));
+        self
+    }
+
+    /// Acquires a rate limit token for a direct-send message to the recipient. Returns
+    /// a `RateLimited` error (and updates the metrics) if the rate limit is exceeded.
+    fn check_outbound_rate_limit(
+        &self,
+        recipient: PeerId,
+        protocol: ProtocolId,
+    ) -> Result<(), NetworkError> {
+        match &self.outbound_rate_limiter {
+            Some(rate_limiter) if !rate_limiter.try_acquire(recipient, protocol) => {
+                counters::outbound_message_rate_limited(protocol);
+                Err(NetworkErrorKind::RateLimited.into())
+            },
+            _ => Ok(()),
+        }
+    }
+
     /// Request that a given Peer be dialed at the provided `NetworkAddress` and
     /// synchronously wait for the request to be performed.
     pub async fn dial_peer(&self, peer: PeerId, addr: NetworkAddress) -> Result<(), NetworkError> {
@@ -396,6 +1261,30 @@ impl<TMessage: Message + Send + 'static> NetworkSender<TMessage> {
         self.send_to_raw(recipient, protocol, mdata)
     }
 
+    /// Send a message to a single recipient, reusing the serialized bytes of a recent
+    /// message sent with the same coalescing key (if coalescing is enabled). The caller
+    /// must ensure that messages with the same key are identical (e.g., by deriving the
+    /// key from the message contents); the message is not inspected by the coalescer.
+    pub fn send_to_coalesced(
+        &self,
+        recipient: PeerId,
+        protocol: ProtocolId,
+        message: TMessage,
+        coalescing_key: HashValue,
+    ) -> Result<(), NetworkError> {
+        let serialize =
+            || -> Result<Bytes, NetworkError> { Ok(protocol.to_bytes(&message)?.into()) };
+        let mdata = match &self.message_coalescer {
+            Some(message_coalescer) => {
+                message_coalescer
+                    .lock()
+                    .get_or_serialize(protocol, coalescing_key, serialize)?
+            },
+            None => serialize()?,
+        };
+        self.send_to_raw(recipient, protocol, mdata)
+    }
+
     /// Sends a raw message to a single recipient
     pub fn send_to_raw(
         &self,
@@ -403,13 +1292,57 @@ impl<TMessage: Message + Send + 'static> NetworkSender<TMessage> {
         protocol: ProtocolId,
         message: Bytes,
     ) -> Result<(), NetworkError> {
+        self.check_outbound_rate_limit(recipient, protocol)?;
+        self.record_for_resend(recipient, protocol, &message);
+        let num_bytes = message.len();
         self.peer_mgr_reqs_tx
             .send_to(recipient, protocol, message)?;
+        self.account_outbound_message(recipient, protocol, num_bytes);
+        Ok(())
+    }
+
+    /// Send a message to a single recipient, waiting for up to the protocol's send
+    /// timeout for the message to be enqueued (if the outbound queue is full). This
+    /// is separate from any RPC timeout. Returns an error if the send times out.
+    pub async fn send_to_with_timeout(
+        &self,
+        recipient: PeerId,
+        protocol: ProtocolId,
+        message: TMessage,
+    ) -> Result<(), NetworkError> {
+        self.check_outbound_rate_limit(recipient, protocol)?;
+        let mdata: Bytes = protocol.to_bytes(&message)?.into();
+        self.record_for_resend(recipient, protocol, &mdata);
+        let num_bytes = mdata.len();
+        self.peer_mgr_reqs_tx
+            .send_to_with_timeout(recipient, protocol, mdata, self.send_timeout(protocol))
+            .await?;
+        self.account_outbound_message(recipient, protocol, num_bytes);
+        Ok(())
+    }
+
+    /// Send a message to a single recipient, attaching the given trace id.
+    /// The trace id is surfaced on the recipient's `ReceivedMessage`.
+    pub fn send_to_with_trace_id(
+        &self,
+        recipient: PeerId,
+        protocol: ProtocolId,
+        message: TMessage,
+        trace_id: TraceId,
+    ) -> Result<(), NetworkError> {
+        self.check_outbound_rate_limit(recipient, protocol)?;
+        let mdata: Bytes = protocol.to_bytes(&message)?.into();
+        self.record_for_resend(recipient, protocol, &mdata);
+        let num_bytes = mdata.len();
+        self.peer_mgr_reqs_tx
+            .send_to_with_trace_id(recipient, protocol, mdata, Some(trace_id))?;
+        self.account_outbound_message(recipient, protocol, num_bytes);
         Ok(())
     }
 
     /// Send a protobuf message to a many recipients. Provides a wrapper over
-    /// `[peer_manager::PeerManagerRequestSender::send_to_many]`.
+    /// `[peer_manager::PeerManagerRequestSender::send_to_many]`. Recipients that
+    /// exceed the protocol's outbound rate limit are skipped (and don't error).
     pub fn send_to_many(
         &self,
         recipients: impl Iterator<Item = PeerId>,
@@ -417,9 +1350,19 @@ impl<TMessage: Message + Send + 'static> NetworkSender<TMessage> {
         message: TMessage,
     ) -> Result<(), NetworkError> {
         // Serialize message.
-        let mdata = protocol.to_bytes(&message)?.into();
+        let mdata: Bytes = protocol.to_bytes(&message)?.into();
+        let num_bytes = mdata.len();
+        let recipients: Vec<_> = recipients
+            .filter(|recipient| self.check_outbound_rate_limit(*recipient, protocol).is_ok())
+            .collect();
+        for recipient in &recipients {
+            self.record_for_resend(*recipient, protocol, &mdata);
+        }
         self.peer_mgr_reqs_tx
-            .send_to_many(recipients, protocol, mdata)?;
+            .send_to_many(recipients.iter().copied(), protocol, mdata)?;
+        for recipient in recipients {
+            self.account_outbound_message(recipient, protocol, num_bytes);
+        }
         Ok(())
     }
 
@@ -454,6 +1397,7 @@ impl<TMessage: Message + Send + 'static> NetworkSender<TMessage> {
         timeout: Duration,
     ) -> Result<TMessage, RpcError> {
         // Send the request and wait for the response
+        self.account_outbound_message(recipient, protocol, req_msg.len());
         let res_data = self
             .peer_mgr_reqs_tx
             .send_rpc(recipient, protocol, req_msg, timeout)
@@ -476,3 +1420,6 @@ pub trait SerializedRequest {
         self.protocol_id().from_bytes(self.data())
     }
 }
+
+#[cfg(test)]
+mod test;
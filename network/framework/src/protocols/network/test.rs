@@ -0,0 +1,894 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    peer_manager::{PeerManagerRequest, PeerManagerRequestSender},
+    protocols::wire::messaging::v1::{DirectSendMsg, RpcRequest},
+};
+use aptos_channels::message_queues::QueueStyle;
+use aptos_config::network_id::NetworkId;
+use serde::Deserialize;
+use std::time::Instant;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct DummyMessage(u64);
+
+#[tokio::test]
+async fn test_inbound_dedup_window() {
+    // Create network events with a dedup window
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, Some(10), None);
+
+    // Send the same message twice (from different peers), followed by a new message
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let num_dropped_duplicates = || {
+        crate::counters::INBOUND_DUPLICATE_MESSAGES_DROPPED
+            .with_label_values(&[protocol_id.as_str()])
+            .get()
+    };
+    let initial_dropped_duplicates = num_dropped_duplicates();
+    for (peer_id, message) in [
+        (PeerId::random(), DummyMessage(1)),
+        (PeerId::random(), DummyMessage(1)),
+        (PeerId::random(), DummyMessage(2)),
+    ] {
+        let received_message = create_direct_send_message(peer_id, protocol_id, &message);
+        inbound_sender
+            .push((peer_id, protocol_id), received_message)
+            .unwrap();
+    }
+
+    // Verify the duplicate was dropped before delivery
+    for expected_message in [DummyMessage(1), DummyMessage(2)] {
+        match network_events.next().await.unwrap() {
+            Event::Message(_, message) => assert_eq!(message, expected_message),
+            event => panic!("Unexpected network event: {:?}", event),
+        }
+    }
+    assert_eq!(num_dropped_duplicates() - initial_dropped_duplicates, 1);
+}
+
+#[tokio::test]
+async fn test_inbound_dedup_disabled() {
+    // Create network events without a dedup window
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, None, None);
+
+    // Send the same message twice
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    for _ in 0..2 {
+        let peer_id = PeerId::random();
+        let received_message = create_direct_send_message(peer_id, protocol_id, &DummyMessage(1));
+        inbound_sender
+            .push((peer_id, protocol_id), received_message)
+            .unwrap();
+    }
+
+    // Verify both messages are delivered
+    for _ in 0..2 {
+        match network_events.next().await.unwrap() {
+            Event::Message(_, message) => assert_eq!(message, DummyMessage(1)),
+            event => panic!("Unexpected network event: {:?}", event),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_message_scoring() {
+    // Create network events that report malformed messages to the peer scoring
+    let peers_and_metadata = PeersAndMetadata::new(&[NetworkId::Validator]);
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, None, None)
+            .with_peer_scoring(peers_and_metadata.clone());
+
+    // Send a message that fails to deserialize, followed by a valid message
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    let malformed_message = ReceivedMessage::new(
+        NetworkMessage::DirectSendMsg(DirectSendMsg {
+            protocol_id,
+            priority: 0,
+            raw_msg: vec![0xFF],
+        }),
+        PeerNetworkId::new(NetworkId::Validator, peer_id),
+    );
+    let valid_message = create_direct_send_message(peer_id, protocol_id, &DummyMessage(1));
+    for received_message in [malformed_message, valid_message] {
+        inbound_sender
+            .push((peer_id, protocol_id), received_message)
+            .unwrap();
+    }
+
+    // Verify the malformed message is dropped and the valid message is delivered
+    match network_events.next().await.unwrap() {
+        Event::Message(_, message) => assert_eq!(message, DummyMessage(1)),
+        event => panic!("Unexpected network event: {:?}", event),
+    }
+
+    // Verify the sender of the malformed message was reported
+    let peer_score = peers_and_metadata
+        .get_peer_score(&PeerNetworkId::new(NetworkId::Validator, peer_id))
+        .unwrap();
+    assert_eq!(peer_score.num_malformed_messages, 1);
+}
+
+#[tokio::test]
+async fn test_batched_events() {
+    // Create batched network events (deserializing inline, so that events are ready immediately)
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut batched_events =
+        NetworkEvents::<DummyMessage>::new(inbound_receiver, None, false, None, Some(vec![]))
+            .batched(2);
+
+    // Send several messages
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    for message_id in 0..3 {
+        let received_message =
+            create_direct_send_message(peer_id, protocol_id, &DummyMessage(message_id));
+        inbound_sender
+            .push((peer_id, protocol_id), received_message)
+            .unwrap();
+    }
+
+    // Verify the messages are delivered in order, in batches of at most 2
+    let mut received_batches = vec![];
+    for _ in 0..2 {
+        let batch = batched_events.next().await.unwrap();
+        let messages: Vec<_> = batch
+            .into_iter()
+            .map(|event| match event {
+                Event::Message(_, message) => message,
+                event => panic!("Unexpected network event: {:?}", event),
+            })
+            .collect();
+        received_batches.push(messages);
+    }
+    assert_eq!(received_batches, vec![
+        vec![DummyMessage(0), DummyMessage(1)],
+        vec![DummyMessage(2)]
+    ]);
+
+    // Verify the stream terminates once the sender is dropped
+    drop(inbound_sender);
+    assert!(batched_events.next().await.is_none());
+    assert!(batched_events.is_terminated());
+}
+
+#[tokio::test]
+async fn test_source_closed() {
+    // Create network events for a source with a single protocol
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, None, None)
+            .with_source_protocols(vec![protocol_id]);
+
+    // Send a message and drop the sender (e.g., as if the protocol handler was dropped)
+    let peer_id = PeerId::random();
+    let received_message = create_direct_send_message(peer_id, protocol_id, &DummyMessage(0));
+    inbound_sender
+        .push((peer_id, protocol_id), received_message)
+        .unwrap();
+    drop(inbound_sender);
+
+    // Verify the pending message is still delivered
+    match network_events.next_or_closed().await {
+        Ok(Event::Message(_, message)) => assert_eq!(message, DummyMessage(0)),
+        result => panic!("Unexpected network event: {:?}", result),
+    }
+
+    // Verify the source then reports its closure (instead of hanging)
+    assert_eq!(
+        network_events.next_or_closed().await.unwrap_err(),
+        SourceClosed(vec![protocol_id])
+    );
+    assert_eq!(
+        network_events.next_or_closed().await.unwrap_err(),
+        SourceClosed(vec![protocol_id])
+    );
+}
+
+#[tokio::test]
+async fn test_offloaded_deserialization() {
+    // Create network events that only offload deserialization for consensus messages
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> = NetworkEvents::new(
+        inbound_receiver,
+        None,
+        false,
+        None,
+        Some(vec![ProtocolId::ConsensusDirectSendBcs]),
+    );
+
+    // Send a message for an offloaded protocol, followed by one for an inline protocol
+    for (protocol_id, message) in [
+        (ProtocolId::ConsensusDirectSendBcs, DummyMessage(1)),
+        (ProtocolId::MempoolDirectSend, DummyMessage(2)),
+    ] {
+        let peer_id = PeerId::random();
+        let received_message = create_direct_send_message(peer_id, protocol_id, &message);
+        inbound_sender
+            .push((peer_id, protocol_id), received_message)
+            .unwrap();
+    }
+
+    // Verify both messages are deserialized and delivered in order
+    for expected_message in [DummyMessage(1), DummyMessage(2)] {
+        match network_events.next().await.unwrap() {
+            Event::Message(_, message) => assert_eq!(message, expected_message),
+            event => panic!("Unexpected network event: {:?}", event),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_rpc_drain_weight_under_load() {
+    // Create network events with an inbound queue that favors rpc requests
+    let direct_send_protocol_id = ProtocolId::MempoolDirectSend;
+    let rpc_protocol_id = ProtocolId::ConsensusRpcBcs;
+    let service_config = NetworkServiceConfig::new(
+        vec![direct_send_protocol_id],
+        vec![rpc_protocol_id],
+        aptos_channel::Config::new(100),
+    );
+    let (inbound_sender, inbound_receiver) = service_config.build_inbound_queue();
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, None, None);
+
+    // Load the inbound queue with direct send messages from several peers,
+    // interleaved with rpc requests from another peer.
+    let direct_send_peer_ids: Vec<_> = (0..3).map(|_| PeerId::random()).collect();
+    let rpc_peer_id = PeerId::random();
+    let num_messages_per_peer = 10;
+    for i in 0..num_messages_per_peer {
+        for peer_id in &direct_send_peer_ids {
+            let received_message =
+                create_direct_send_message(*peer_id, direct_send_protocol_id, &DummyMessage(i));
+            inbound_sender
+                .push((*peer_id, direct_send_protocol_id), received_message)
+                .unwrap();
+        }
+        let received_message = create_rpc_request_message(rpc_peer_id, rpc_protocol_id, i);
+        inbound_sender
+            .push((rpc_peer_id, rpc_protocol_id), received_message)
+            .unwrap();
+    }
+
+    // Drain all events, using the delivery order as a proxy for latency
+    let (mut rpc_positions, mut direct_send_positions) = (vec![], vec![]);
+    let num_messages = num_messages_per_peer * 4;
+    for position in 0..num_messages {
+        match network_events.next().await.unwrap() {
+            Event::RpcRequest(..) => rpc_positions.push(position),
+            Event::Message(..) => direct_send_positions.push(position),
+            event => panic!("Unexpected network event: {:?}", event),
+        }
+    }
+
+    // Verify all messages were delivered, and rpc requests were delivered sooner on average
+    let average = |positions: &[u64]| positions.iter().sum::<u64>() as f64 / positions.len() as f64;
+    assert_eq!(rpc_positions.len() as u64, num_messages_per_peer);
+    assert_eq!(
+        direct_send_positions.len() as u64,
+        num_messages_per_peer * 3
+    );
+    assert!(average(&rpc_positions) < average(&direct_send_positions));
+}
+
+#[tokio::test]
+async fn test_try_next() {
+    // Create network events
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, None, None);
+
+    // Verify nothing is returned when there are no messages
+    assert!(network_events.try_next().is_none());
+
+    // Send a message and verify it is eventually returned (once deserialized)
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    let received_message = create_direct_send_message(peer_id, protocol_id, &DummyMessage(1));
+    inbound_sender
+        .push((peer_id, protocol_id), received_message)
+        .unwrap();
+    let event = loop {
+        if let Some(event) = network_events.try_next() {
+            break event;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    match event {
+        Event::Message(sender, message) => {
+            assert_eq!(sender, peer_id);
+            assert_eq!(message, DummyMessage(1));
+        },
+        event => panic!("Unexpected network event: {:?}", event),
+    }
+    assert!(network_events.try_next().is_none());
+
+    // Verify the async path is unaffected
+    let received_message = create_direct_send_message(peer_id, protocol_id, &DummyMessage(2));
+    inbound_sender
+        .push((peer_id, protocol_id), received_message)
+        .unwrap();
+    match network_events.next().await.unwrap() {
+        Event::Message(_, message) => assert_eq!(message, DummyMessage(2)),
+        event => panic!("Unexpected network event: {:?}", event),
+    }
+}
+
+#[tokio::test]
+async fn test_resend_recent_after_reconnect() {
+    // Create a network sender with a resend buffer of size 2
+    let (outbound_sender, mut outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_resend_buffer(2);
+
+    // Send several messages to the peer (which are lost when the peer disconnects)
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    for i in 0..3 {
+        network_sender
+            .send_to(peer_id, protocol_id, DummyMessage(i))
+            .unwrap();
+    }
+    for _ in 0..3 {
+        outbound_receiver.next().await.unwrap();
+    }
+
+    // Simulate a reconnect and verify only the last two messages are resent (in order)
+    assert_eq!(
+        network_sender.resend_recent(peer_id, protocol_id).unwrap(),
+        2
+    );
+    for expected_message in [DummyMessage(1), DummyMessage(2)] {
+        match outbound_receiver.next().await.unwrap() {
+            PeerManagerRequest::SendDirectSend(recipient, message) => {
+                assert_eq!(recipient, peer_id);
+                assert_eq!(message.protocol_id, protocol_id);
+                let message: DummyMessage = protocol_id.from_bytes(&message.mdata).unwrap();
+                assert_eq!(message, expected_message);
+            },
+            request => panic!("Unexpected peer manager request: {:?}", request),
+        }
+    }
+
+    // Verify nothing is resent for other peers or protocols
+    assert_eq!(
+        network_sender
+            .resend_recent(PeerId::random(), protocol_id)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        network_sender
+            .resend_recent(peer_id, ProtocolId::ConsensusDirectSendBcs)
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_resend_recent_disabled() {
+    // Create a network sender without a resend buffer
+    let (outbound_sender, _outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    );
+
+    // Send a message and verify nothing is resent
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    network_sender
+        .send_to(peer_id, protocol_id, DummyMessage(0))
+        .unwrap();
+    assert_eq!(
+        network_sender.resend_recent(peer_id, protocol_id).unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_inbound_kind_rpc_responder() {
+    // Create network events
+    let (inbound_sender, inbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let mut network_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(inbound_receiver, None, false, None, None);
+
+    // Send an rpc request and a direct send message to the application
+    let peer_id = PeerId::random();
+    let rpc_protocol_id = ProtocolId::ConsensusRpcBcs;
+    let rpc_request = RpcRequest {
+        protocol_id: rpc_protocol_id,
+        request_id: 0,
+        priority: 0,
+        raw_request: rpc_protocol_id.to_bytes(&DummyMessage(1)).unwrap(),
+    };
+    let (response_sender, response_receiver) = oneshot::channel();
+    let mut received_message = ReceivedMessage::new(
+        NetworkMessage::RpcRequest(rpc_request),
+        PeerNetworkId::new(NetworkId::Validator, peer_id),
+    );
+    received_message.rpc_replier = Some(Arc::new(response_sender));
+    inbound_sender
+        .push((peer_id, rpc_protocol_id), received_message)
+        .unwrap();
+    let direct_send_protocol_id = ProtocolId::ConsensusDirectSendBcs;
+    let received_message =
+        create_direct_send_message(peer_id, direct_send_protocol_id, &DummyMessage(2));
+    inbound_sender
+        .push((peer_id, direct_send_protocol_id), received_message)
+        .unwrap();
+
+    // Verify the rpc request carries a working responder
+    let (sender, message, inbound_kind) = network_events.next().await.unwrap().into_inbound();
+    assert_eq!(sender, peer_id);
+    assert_eq!(message, DummyMessage(1));
+    match inbound_kind {
+        InboundKind::RpcRequest(responder) => {
+            assert_eq!(responder.protocol_id(), rpc_protocol_id);
+            responder.send(&DummyMessage(3)).unwrap();
+        },
+        InboundKind::DirectSend => panic!("Expected an rpc request!"),
+    }
+    let response_bytes = response_receiver.await.unwrap().unwrap();
+    let response: DummyMessage = rpc_protocol_id.from_bytes(&response_bytes).unwrap();
+    assert_eq!(response, DummyMessage(3));
+
+    // Verify the direct send message is identified as such
+    let (_, message, inbound_kind) = network_events.next().await.unwrap().into_inbound();
+    assert_eq!(message, DummyMessage(2));
+    assert!(matches!(inbound_kind, InboundKind::DirectSend));
+}
+
+#[tokio::test]
+async fn test_send_timeout_with_full_queue() {
+    // Create a network sender with an outbound queue of size 1
+    let (outbound_sender, mut outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 1, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let send_timeout = Duration::from_millis(100);
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_send_timeout(protocol_id, send_timeout);
+
+    // Verify the first message is enqueued without waiting
+    let peer_id = PeerId::random();
+    network_sender
+        .send_to_with_timeout(peer_id, protocol_id, DummyMessage(0))
+        .await
+        .unwrap();
+
+    // Verify the next message times out (after the configured duration) as the queue is full
+    let start_time = Instant::now();
+    let error = network_sender
+        .send_to_with_timeout(peer_id, protocol_id, DummyMessage(1))
+        .await
+        .unwrap_err();
+    assert!(start_time.elapsed() >= send_timeout);
+    assert!(format!("{:?}", error).contains("Timed out"));
+
+    // Verify a waiting message is enqueued as soon as the queue is drained
+    let send = network_sender.send_to_with_timeout(peer_id, protocol_id, DummyMessage(2));
+    let drain = outbound_receiver.next();
+    let (result, _) = futures::future::join(send, drain).await;
+    result.unwrap();
+    assert!(outbound_receiver.next().now_or_never().is_some());
+
+    // Verify other protocols still use their default send timeout
+    let other_protocol_id = ProtocolId::ConsensusDirectSendBcs;
+    assert_eq!(
+        network_sender.send_timeout(other_protocol_id),
+        other_protocol_id.default_send_timeout()
+    );
+}
+
+#[test]
+fn test_outbound_traffic_accounting() {
+    // Create a network sender with outbound accounting enabled
+    let (outbound_sender, _outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_outbound_accounting();
+
+    // Send several messages to a peer, and broadcast a message to two peers
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    let other_peer_id = PeerId::random();
+    let mut expected_num_bytes = 0;
+    for i in 0..3 {
+        let message = DummyMessage(i);
+        expected_num_bytes += protocol_id.to_bytes(&message).unwrap().len() as u64;
+        network_sender
+            .send_to(peer_id, protocol_id, message)
+            .unwrap();
+    }
+    let message = DummyMessage(3);
+    let broadcast_num_bytes = protocol_id.to_bytes(&message).unwrap().len() as u64;
+    network_sender
+        .send_to_many([peer_id, other_peer_id].into_iter(), protocol_id, message)
+        .unwrap();
+
+    // Verify the accounted traffic matches the serialized message sizes
+    assert_eq!(
+        network_sender.outbound_traffic(peer_id, protocol_id),
+        OutboundTraffic {
+            num_messages: 4,
+            num_bytes: expected_num_bytes + broadcast_num_bytes,
+        }
+    );
+    assert_eq!(
+        network_sender.outbound_traffic(other_peer_id, protocol_id),
+        OutboundTraffic {
+            num_messages: 1,
+            num_bytes: broadcast_num_bytes,
+        }
+    );
+
+    // Verify no traffic is reported for other protocols
+    assert_eq!(
+        network_sender.outbound_traffic(peer_id, ProtocolId::ConsensusDirectSendBcs),
+        OutboundTraffic::default()
+    );
+}
+
+#[tokio::test]
+async fn test_broadcast_coalescing() {
+    // Create a network sender with message coalescing enabled
+    let (outbound_sender, mut outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_message_coalescing(Duration::from_secs(60));
+
+    // Broadcast the same message to several peers (one at a time)
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let coalescing_key = HashValue::sha3_256_of(b"message_0");
+    let peer_ids: Vec<_> = (0..5).map(|_| PeerId::random()).collect();
+    for peer_id in &peer_ids {
+        network_sender
+            .send_to_coalesced(*peer_id, protocol_id, DummyMessage(0), coalescing_key)
+            .unwrap();
+    }
+
+    // Verify the message was only serialized once (computing the key requires no serialization)
+    assert_eq!(network_sender.coalescing_stats(), CoalescingStats {
+        num_serializations: 1,
+        num_coalesced: 4,
+    });
+
+    // Verify all peers were sent the same (shared) buffer
+    let mut sent_buffers = vec![];
+    for peer_id in &peer_ids {
+        match outbound_receiver.next().await.unwrap() {
+            PeerManagerRequest::SendDirectSend(recipient, message) => {
+                assert_eq!(recipient, *peer_id);
+                let sent_message: DummyMessage = protocol_id.from_bytes(&message.mdata).unwrap();
+                assert_eq!(sent_message, DummyMessage(0));
+                sent_buffers.push(message.mdata);
+            },
+            request => panic!("Unexpected peer manager request: {:?}", request),
+        }
+    }
+    assert!(sent_buffers
+        .iter()
+        .all(|buffer| buffer.as_ptr() == sent_buffers[0].as_ptr()));
+
+    // Verify a different key (or protocol) is serialized separately
+    let other_key = HashValue::sha3_256_of(b"message_1");
+    network_sender
+        .send_to_coalesced(peer_ids[0], protocol_id, DummyMessage(1), other_key)
+        .unwrap();
+    network_sender
+        .send_to_coalesced(
+            peer_ids[0],
+            ProtocolId::ConsensusDirectSendBcs,
+            DummyMessage(1),
+            other_key,
+        )
+        .unwrap();
+    assert_eq!(network_sender.coalescing_stats().num_serializations, 3);
+
+    // Verify messages sent without a coalescing key bypass the coalescer
+    network_sender
+        .send_to(peer_ids[0], protocol_id, DummyMessage(0))
+        .unwrap();
+    assert_eq!(network_sender.coalescing_stats(), CoalescingStats {
+        num_serializations: 3,
+        num_coalesced: 4,
+    });
+}
+
+#[test]
+fn test_broadcast_coalescing_expiry() {
+    // Create a network sender with a short coalescing window
+    let (outbound_sender, _outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let window = Duration::from_millis(50);
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_message_coalescing(window);
+
+    // Send several messages (with different keys) to a peer
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let peer_id = PeerId::random();
+    for index in 0..3u8 {
+        network_sender
+            .send_to_coalesced(
+                peer_id,
+                protocol_id,
+                DummyMessage(index.into()),
+                HashValue::sha3_256_of(&[index]),
+            )
+            .unwrap();
+    }
+
+    // Wait for the window to elapse and send another message
+    std::thread::sleep(window * 2);
+    network_sender
+        .send_to_coalesced(
+            peer_id,
+            protocol_id,
+            DummyMessage(0),
+            HashValue::sha3_256_of(&[0]),
+        )
+        .unwrap();
+
+    // Verify the expired message was serialized again, and the expired entries were evicted
+    assert_eq!(network_sender.coalescing_stats(), CoalescingStats {
+        num_serializations: 4,
+        num_coalesced: 0,
+    });
+    let message_coalescer = network_sender.message_coalescer.as_ref().unwrap().lock();
+    assert_eq!(message_coalescer.serialized_messages.len(), 1);
+    assert_eq!(message_coalescer.expiry_queue.len(), 1);
+}
+
+#[test]
+fn test_outbound_rate_limits() {
+    // Create a network sender with a rate limit on mempool messages
+    let (outbound_sender, _outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 100, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let rate_limit = OutboundProtocolRateLimit {
+        messages_per_second: 1,
+        burst_size: 3,
+    };
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_outbound_accounting()
+    .with_outbound_rate_limits(HashMap::from([(protocol_id, rate_limit)]));
+
+    // Verify a burst of messages is sent to the peer, but the next message is rejected
+    let peer_id = PeerId::random();
+    for i in 0..3 {
+        network_sender
+            .send_to(peer_id, protocol_id, DummyMessage(i))
+            .unwrap();
+    }
+    let error = network_sender
+        .send_to(peer_id, protocol_id, DummyMessage(3))
+        .unwrap_err();
+    assert_eq!(error.to_string(), NetworkErrorKind::RateLimited.to_string());
+    assert_eq!(
+        network_sender
+            .outbound_traffic(peer_id, protocol_id)
+            .num_messages,
+        3
+    );
+
+    // Verify other peers and other protocols are not throttled
+    let other_peer_id = PeerId::random();
+    network_sender
+        .send_to(other_peer_id, protocol_id, DummyMessage(4))
+        .unwrap();
+    for i in 0..10 {
+        network_sender
+            .send_to(peer_id, ProtocolId::ConsensusDirectSendBcs, DummyMessage(i))
+            .unwrap();
+    }
+
+    // Verify broadcasts skip the throttled peer (without failing)
+    network_sender
+        .send_to_many(
+            [peer_id, other_peer_id].into_iter(),
+            protocol_id,
+            DummyMessage(5),
+        )
+        .unwrap();
+    assert_eq!(
+        network_sender
+            .outbound_traffic(peer_id, protocol_id)
+            .num_messages,
+        3
+    );
+    assert_eq!(
+        network_sender
+            .outbound_traffic(other_peer_id, protocol_id)
+            .num_messages,
+        2
+    );
+}
+
+#[test]
+fn test_outbound_rate_limits_prune_idle_buckets() {
+    // Create a network sender with a rate limit on mempool messages
+    let (outbound_sender, _outbound_receiver) = aptos_channel::new(QueueStyle::FIFO, 100, None);
+    let (connection_sender, _connection_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let protocol_id = ProtocolId::MempoolDirectSend;
+    let rate_limit = OutboundProtocolRateLimit {
+        messages_per_second: 10,
+        burst_size: 10,
+    };
+    let network_sender: NetworkSender<DummyMessage> = NetworkSender::new(
+        PeerManagerRequestSender::new(outbound_sender),
+        ConnectionRequestSender::new(connection_sender),
+    )
+    .with_outbound_rate_limits(HashMap::from([(protocol_id, rate_limit)]));
+    let num_buckets = || {
+        network_sender
+            .outbound_rate_limiter
+            .as_ref()
+            .unwrap()
+            .buckets
+            .lock()
+            .len()
+    };
+
+    // Send a message to several peers, and verify a bucket is created for each
+    for i in 0..5 {
+        network_sender
+            .send_to(PeerId::random(), protocol_id, DummyMessage(i))
+            .unwrap();
+    }
+    assert_eq!(num_buckets(), 5);
+
+    // Verify that unthrottled protocols don't create buckets
+    network_sender
+        .send_to(
+            PeerId::random(),
+            ProtocolId::ConsensusDirectSendBcs,
+            DummyMessage(5),
+        )
+        .unwrap();
+    assert_eq!(num_buckets(), 5);
+
+    // Wait for the buckets to refill, and verify the idle buckets are evicted
+    // when a bucket is created for a new peer.
+    std::thread::sleep(Duration::from_millis(1100));
+    network_sender
+        .send_to(PeerId::random(), protocol_id, DummyMessage(6))
+        .unwrap();
+    assert_eq!(num_buckets(), 1);
+}
+
+#[tokio::test]
+async fn test_select_weighted() {
+    // Merge a high weight stream and a low weight stream (both fully queued)
+    let rpc_events = futures::stream::iter(vec!["rpc_1", "rpc_2", "rpc_3", "rpc_4", "rpc_5"]);
+    let direct_send_events = futures::stream::iter(vec!["send_1", "send_2", "send_3"]);
+    let merged_events = select_weighted(vec![(direct_send_events, 1), (rpc_events, 3)]);
+
+    // Verify the high weight stream is drained first, without starving the other stream
+    assert_eq!(merged_events.collect::<Vec<_>>().await, vec![
+        "rpc_1", "rpc_2", "rpc_3", "send_1", "rpc_4", "rpc_5", "send_2", "send_3"
+    ]);
+
+    // Verify a zero weight is treated as a weight of one
+    let first_events = futures::stream::iter(vec![1, 2]);
+    let second_events = futures::stream::iter(vec![3, 4]);
+    let merged_events = select_weighted(vec![(first_events, 0), (second_events, 1)]);
+    assert_eq!(merged_events.collect::<Vec<_>>().await, vec![1, 3, 2, 4]);
+}
+
+#[tokio::test]
+async fn test_select_weighted_pending_sources() {
+    // Merge two network event sources with different weights
+    let rpc_protocol_id = ProtocolId::ConsensusRpcBcs;
+    let direct_send_protocol_id = ProtocolId::ConsensusDirectSendBcs;
+    let (rpc_sender, rpc_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let (direct_send_sender, direct_send_receiver) = aptos_channel::new(QueueStyle::FIFO, 10, None);
+    let rpc_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(rpc_receiver, None, false, None, None);
+    let direct_send_events: NetworkEvents<DummyMessage> =
+        NetworkEvents::new(direct_send_receiver, None, false, None, None);
+    let mut merged_events = select_weighted(vec![(direct_send_events, 1), (rpc_events, 10)]);
+
+    // Verify the merged stream is pending while the sources are empty
+    assert!(merged_events.next().now_or_never().is_none());
+
+    // Queue a direct send message, and then an rpc request
+    let peer_id = PeerId::random();
+    direct_send_sender
+        .push(
+            (peer_id, direct_send_protocol_id),
+            create_direct_send_message(peer_id, direct_send_protocol_id, &DummyMessage(0)),
+        )
+        .unwrap();
+    rpc_sender
+        .push(
+            (peer_id, rpc_protocol_id),
+            create_rpc_request_message(peer_id, rpc_protocol_id, 1),
+        )
+        .unwrap();
+
+    // Verify the rpc request is delivered before the direct send message
+    match merged_events.next().await.unwrap() {
+        Event::RpcRequest(_, message, protocol_id, _) => {
+            assert_eq!(message, DummyMessage(1));
+            assert_eq!(protocol_id, rpc_protocol_id);
+        },
+        event => panic!("Unexpected network event: {:?}", event),
+    }
+    match merged_events.next().await.unwrap() {
+        Event::Message(_, message) => assert_eq!(message, DummyMessage(0)),
+        event => panic!("Unexpected network event: {:?}", event),
+    }
+
+    // Drop the senders, and verify the merged stream terminates
+    drop(rpc_sender);
+    drop(direct_send_sender);
+    assert!(merged_events.next().await.is_none());
+    assert!(merged_events.is_terminated());
+}
+
+/// Creates a received rpc request (with a response channel) for the given peer and protocol
+fn create_rpc_request_message(
+    peer_id: PeerId,
+    protocol_id: ProtocolId,
+    request_id: u64,
+) -> ReceivedMessage {
+    let rpc_request = RpcRequest {
+        protocol_id,
+        request_id: request_id as u32,
+        priority: 0,
+        raw_request: protocol_id.to_bytes(&DummyMessage(request_id)).unwrap(),
+    };
+    let (response_sender, _response_receiver) = oneshot::channel();
+    let mut received_message = ReceivedMessage::new(
+        NetworkMessage::RpcRequest(rpc_request),
+        PeerNetworkId::new(NetworkId::Validator, peer_id),
+    );
+    received_message.rpc_replier = Some(Arc::new(response_sender));
+    received_message
+}
+
+/// Creates a received direct send message for the given peer and protocol
+fn create_direct_send_message(
+    peer_id: PeerId,
+    protocol_id: ProtocolId,
+    message: &DummyMessage,
+) -> ReceivedMessage {
+    let direct_send_msg = DirectSendMsg {
+        protocol_id,
+        priority: 0,
+        raw_msg: protocol_id.to_bytes(message).unwrap(),
+    };
+    ReceivedMessage::new(
+        NetworkMessage::DirectSendMsg(direct_send_msg),
+        PeerNetworkId::new(NetworkId::Validator, peer_id),
+    )
+}
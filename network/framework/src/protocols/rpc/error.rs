@@ -42,6 +42,21 @@ pub enum RpcError {
 
     #[error("Rpc timed out")]
     TimedOut,
+
+    #[error("Rpc responder expired before the response was sent")]
+    ResponderExpired,
+}
+
+impl RpcError {
+    /// Returns true iff the error was caused by the remote peer (e.g., the peer
+    /// didn't respond in time), rather than by the local node (e.g., the peer
+    /// isn't connected or the outbound queue is full).
+    pub fn is_remote_failure(&self) -> bool {
+        matches!(
+            self,
+            RpcError::TimedOut | RpcError::InvalidRpcResponse | RpcError::ApplicationError(_)
+        )
+    }
 }
 
 impl From<PeerManagerError> for RpcError {
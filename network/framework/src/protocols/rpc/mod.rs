@@ -51,6 +51,7 @@ use crate::{
         RECEIVED_LABEL, REQUEST_LABEL, RESPONSE_LABEL, SENT_LABEL,
     },
     logging::NetworkSchema,
+    peer::memory_budget::BudgetFeedbackSender,
     protocols::{
         network::{ReceivedMessage, SerializedRequest},
         wire::messaging::v1::{NetworkMessage, Priority, RequestId, RpcRequest, RpcResponse},
@@ -202,11 +203,13 @@ impl InboundRpcs {
         }
     }
 
-    /// Handle a new inbound `RpcRequest` message off the wire.
+    /// Handle a new inbound `RpcRequest` message off the wire. The feedback channel
+    /// (if any) is notified once the request leaves the upstream queue.
     pub fn handle_inbound_request(
         &mut self,
         peer_notifs_tx: &aptos_channel::Sender<(PeerId, ProtocolId), ReceivedMessage>,
         mut request: ReceivedMessage,
+        feedback_tx: Option<BudgetFeedbackSender>,
     ) -> Result<(), RpcError> {
         let network_context = &self.network_context;
 
@@ -247,13 +250,18 @@ impl InboundRpcs {
         // Forward request to PeerManager for handling.
         let (response_tx, response_rx) = oneshot::channel();
         request.rpc_replier = Some(Arc::new(response_tx));
-        if let Err(err) = peer_notifs_tx.push((peer_id, protocol_id), request) {
+        if let Err(err) =
+            peer_notifs_tx.push_with_feedback((peer_id, protocol_id), request, feedback_tx)
+        {
             counters::rpc_messages(network_context, REQUEST_LABEL, INBOUND_LABEL, FAILED_LABEL)
                 .inc();
             return Err(err.into());
         }
 
         // Create a new task that waits for a response from the upper layer with a timeout.
+        // If the timeout elapses, the responder held by the application expires (i.e.,
+        // the response channel is dropped, and any late response is rejected).
+        let network_context = *network_context;
         let inbound_rpc_task = self
             .time_service
             .timeout(self.inbound_rpc_timeout, response_rx)
@@ -270,7 +278,16 @@ impl InboundRpcs {
                     },
                     Ok(Ok(Err(err))) => Err(err),
                     Ok(Err(oneshot::Canceled)) => Err(RpcError::UnexpectedResponseChannelCancel),
-                    Err(timeout::Elapsed) => Err(RpcError::TimedOut),
+                    Err(timeout::Elapsed) => {
+                        counters::rpc_messages(
+                            &network_context,
+                            REQUEST_LABEL,
+                            INBOUND_LABEL,
+                            EXPIRED_LABEL,
+                        )
+                        .inc();
+                        Err(RpcError::TimedOut)
+                    },
                 };
                 // Only record latency of successful requests
                 match maybe_response {
@@ -1,7 +1,9 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::protocols::wire::messaging::v1::{MultiplexMessage, NetworkMessage};
+use crate::protocols::wire::messaging::v1::{
+    MultiplexMessage, NetworkMessage, TracedDirectSendMsg,
+};
 use anyhow::{bail, ensure};
 use aptos_channels::Sender;
 use aptos_id_generator::{IdGenerator, U32IdGenerator};
@@ -109,8 +111,11 @@ pub struct InboundStream {
 impl InboundStream {
     fn new(header: StreamHeader, max_fragments: usize) -> anyhow::Result<Self> {
         ensure!(
-            !matches!(header.message, NetworkMessage::Error(_)),
-            "Error message is not expected for stream"
+            !matches!(
+                header.message,
+                NetworkMessage::Error(_) | NetworkMessage::KeepAlive
+            ),
+            "Error and keep-alive messages are not expected for stream"
         );
         ensure!(
             header.num_fragments as usize <= max_fragments,
@@ -138,10 +143,16 @@ impl InboundStream {
         self.current_fragment_id += 1;
         let raw_data = &mut fragment.raw_data;
         match &mut self.message {
-            NetworkMessage::Error(_) => panic!("StreamHeader with Error should be rejected"),
+            NetworkMessage::Error(_) | NetworkMessage::KeepAlive => {
+                panic!("StreamHeader with Error or KeepAlive should be rejected")
+            },
             NetworkMessage::RpcRequest(request) => request.raw_request.append(raw_data),
             NetworkMessage::RpcResponse(response) => response.raw_response.append(raw_data),
-            NetworkMessage::DirectSendMsg(message) => message.raw_msg.append(raw_data),
+            NetworkMessage::DirectSendMsg(message)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg { message, .. }) => {
+                message.raw_msg.append(raw_data)
+            },
+            NetworkMessage::CompressedMsg(message) => message.compressed_msg.append(raw_data),
         }
         Ok(self.current_fragment_id == self.num_fragments)
     }
@@ -195,8 +206,10 @@ impl OutboundStream {
         );
         let request_id = self.request_id_gen.next();
         let rest = match &mut message {
-            NetworkMessage::Error(_) => {
-                unreachable!("NetworkMessage::Error should always fit in a single frame")
+            NetworkMessage::Error(_) | NetworkMessage::KeepAlive => {
+                unreachable!(
+                    "NetworkMessage::{Error, KeepAlive} should always fit in a single frame"
+                )
             },
             NetworkMessage::RpcRequest(request) => {
                 request.raw_request.split_off(self.max_frame_size)
@@ -204,9 +217,13 @@ impl OutboundStream {
             NetworkMessage::RpcResponse(response) => {
                 response.raw_response.split_off(self.max_frame_size)
             },
-            NetworkMessage::DirectSendMsg(message) => {
+            NetworkMessage::DirectSendMsg(message)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg { message, .. }) => {
                 message.raw_msg.split_off(self.max_frame_size)
             },
+            NetworkMessage::CompressedMsg(message) => {
+                message.compressed_msg.split_off(self.max_frame_size)
+            },
         };
         let chunks = rest.chunks(self.max_frame_size);
         ensure!(
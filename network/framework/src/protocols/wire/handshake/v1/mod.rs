@@ -13,7 +13,9 @@
 //!
 //! [AptosNet Handshake v1 Specification]: https://github.com/aptos-labs/aptos-core/blob/main/specifications/network/handshake-v1.md
 
-use crate::counters::{start_serialization_timer, DESERIALIZATION_LABEL, SERIALIZATION_LABEL};
+use crate::counters::{
+    self, start_serialization_timer, DESERIALIZATION_LABEL, SERIALIZATION_LABEL,
+};
 use anyhow::anyhow;
 use aptos_compression::client::CompressionClient;
 use aptos_config::{config::MAX_APPLICATION_MESSAGE_SIZE, network_id::NetworkId};
@@ -26,6 +28,7 @@ use std::{
     fmt,
     iter::{FromIterator, Iterator},
     ops::{BitAnd, BitOr},
+    time::Duration,
 };
 use thiserror::Error;
 
@@ -39,9 +42,10 @@ mod test;
 pub const USER_INPUT_RECURSION_LIMIT: usize = 32;
 pub const RECURSION_LIMIT: usize = 64;
 
-/// Unique identifier associated with each application protocol.
+/// Unique identifier associated with each application protocol. Protocols are
+/// ordered by their wire value (i.e., the order in which they are declared).
 #[repr(u8)]
-#[derive(Clone, Copy, Hash, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
 #[cfg_attr(any(test, feature = "fuzzing"), derive(Arbitrary))]
 pub enum ProtocolId {
     ConsensusRpcBcs = 0,
@@ -153,6 +157,45 @@ impl ProtocolId {
         ]
     }
 
+    /// Returns true iff messages for the protocol should be written to the wire
+    /// ahead of normal traffic (e.g., consensus messages on a congested network).
+    pub fn is_high_priority(&self) -> bool {
+        matches!(
+            self,
+            ProtocolId::ConsensusRpcBcs
+                | ProtocolId::ConsensusDirectSendBcs
+                | ProtocolId::ConsensusDirectSendJson
+                | ProtocolId::ConsensusRpcJson
+                | ProtocolId::ConsensusRpcCompressed
+                | ProtocolId::ConsensusDirectSendCompressed
+        )
+    }
+
+    /// Returns the default time to wait for an outbound message of the protocol
+    /// to be enqueued (when the peer's outbound queue is full), before giving up.
+    /// Note: this is distinct from the RPC response timeout.
+    pub fn default_send_timeout(&self) -> Duration {
+        match self {
+            // Consensus messages quickly become stale, so fail fast
+            _ if self.is_high_priority() => Duration::from_millis(500),
+            ProtocolId::ConsensusObserver
+            | ProtocolId::ConsensusObserverRpc
+            | ProtocolId::DKGDirectSendCompressed
+            | ProtocolId::DKGDirectSendBcs
+            | ProtocolId::DKGDirectSendJson
+            | ProtocolId::DKGRpcCompressed
+            | ProtocolId::DKGRpcBcs
+            | ProtocolId::DKGRpcJson
+            | ProtocolId::JWKConsensusDirectSendCompressed
+            | ProtocolId::JWKConsensusDirectSendBcs
+            | ProtocolId::JWKConsensusDirectSendJson
+            | ProtocolId::JWKConsensusRpcCompressed
+            | ProtocolId::JWKConsensusRpcBcs
+            | ProtocolId::JWKConsensusRpcJson => Duration::from_secs(1),
+            _ => Duration::from_secs(5),
+        }
+    }
+
     /// Specifies how to encode messages for a given `ProtocolId`
     fn encoding(self) -> Encoding {
         match self {
@@ -172,6 +215,15 @@ impl ProtocolId {
         }
     }
 
+    /// Returns the preference rank of the protocol's encoding (lower is preferred)
+    fn encoding_preference(self) -> u8 {
+        match self.encoding() {
+            Encoding::CompressedBcs(_) => 0,
+            Encoding::Bcs(_) => 1,
+            Encoding::Json => 2,
+        }
+    }
+
     /// Returns the compression client label based on the current protocol id
     fn get_compression_client(self) -> CompressionClient {
         match self {
@@ -334,6 +386,16 @@ impl ProtocolIdSet {
     pub fn insert(&mut self, protocol: ProtocolId) {
         self.0.set(protocol as u16)
     }
+
+    /// Returns if the messaging feature is set.
+    pub fn supports_feature(&self, feature: MessagingFeature) -> bool {
+        self.0.is_set(feature.bit())
+    }
+
+    /// Insert a messaging feature into the set.
+    pub fn insert_feature(&mut self, feature: MessagingFeature) {
+        self.0.set(feature.bit())
+    }
 }
 
 impl FromIterator<ProtocolId> for ProtocolIdSet {
@@ -348,6 +410,99 @@ impl<'a> FromIterator<&'a ProtocolId> for ProtocolIdSet {
     }
 }
 
+//
+// Protocols
+//
+
+/// A list of [`ProtocolId`]s ordered by preference (from highest to lowest), e.g.,
+/// the protocols an application registers with the network. Unlike a
+/// [`ProtocolIdSet`], the order of the protocols is meaningful.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Protocols(Vec<ProtocolId>);
+
+impl Protocols {
+    pub fn new(protocols: Vec<ProtocolId>) -> Self {
+        Self(protocols)
+    }
+
+    /// Sorts the protocols into their canonical order and removes duplicates, so
+    /// that the same logical protocols always produce the same list (regardless of
+    /// the code path that assembled them). The canonical order keeps the preference
+    /// semantics used across the applications: compressed encodings are preferred
+    /// over BCS, which is preferred over JSON. Ties are broken by the wire value.
+    pub fn canonicalize(&mut self) {
+        self.0
+            .sort_by_key(|protocol| (protocol.encoding_preference(), *protocol as u8));
+        self.0.dedup();
+    }
+
+    pub fn as_slice(&self) -> &[ProtocolId] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<ProtocolId> {
+        self.0
+    }
+}
+
+impl From<Vec<ProtocolId>> for Protocols {
+    fn from(protocols: Vec<ProtocolId>) -> Self {
+        Self::new(protocols)
+    }
+}
+
+impl From<&[ProtocolId]> for Protocols {
+    fn from(protocols: &[ProtocolId]) -> Self {
+        Self::new(protocols.to_vec())
+    }
+}
+
+//
+// MessagingFeature
+//
+
+/// Optional messaging features (e.g., new [`NetworkMessage`] variants) that are
+/// advertised along with the application protocols in the [`HandshakeMsg`]. Peers
+/// that predate a feature cannot parse its messages, so a feature is only used on
+/// a connection if both peers advertise it.
+///
+/// Features are encoded as the highest bits of the [`ProtocolIdSet`] (counting
+/// down from 255), which peers ignore if they don't understand them (see
+/// [`ProtocolIdSet::iter()`]).
+///
+/// [`NetworkMessage`]: crate::protocols::wire::messaging::v1::NetworkMessage
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MessagingFeature {
+    /// Direct send messages that carry a trace id (i.e., `TracedDirectSendMsg`)
+    TracedDirectSend,
+    /// Keep-alive pings on idle connections (i.e., `KeepAlive`)
+    KeepAlive,
+    /// Compressed network messages (i.e., `CompressedMsg`). Note: this only
+    /// signals that the peer can decompress messages, so whether messages are
+    /// actually compressed is up to the sender's config.
+    Compression,
+}
+
+impl MessagingFeature {
+    /// Returns all messaging features supported by this node
+    pub fn all() -> &'static [MessagingFeature] {
+        &[
+            MessagingFeature::TracedDirectSend,
+            MessagingFeature::KeepAlive,
+            MessagingFeature::Compression,
+        ]
+    }
+
+    /// Returns the bit that encodes the feature in a [`ProtocolIdSet`]
+    fn bit(self) -> u16 {
+        match self {
+            MessagingFeature::TracedDirectSend => 255,
+            MessagingFeature::KeepAlive => 254,
+            MessagingFeature::Compression => 253,
+        }
+    }
+}
+
 //
 // MessageProtocolVersion
 //
@@ -398,6 +553,17 @@ pub enum HandshakeError {
     NoCommonProtocols,
 }
 
+impl HandshakeError {
+    /// Returns the cause of the handshake failure, as a metrics label
+    pub fn failure_cause(&self) -> &'static str {
+        match self {
+            HandshakeError::InvalidChainId(_, _) => counters::HANDSHAKE_CHAIN_ID_MISMATCH_LABEL,
+            HandshakeError::InvalidNetworkId(_, _) => counters::HANDSHAKE_NETWORK_ID_MISMATCH_LABEL,
+            HandshakeError::NoCommonProtocols => counters::HANDSHAKE_PROTOCOL_MISMATCH_LABEL,
+        }
+    }
+}
+
 /// The HandshakeMsg contains a mapping from [`MessagingProtocolVersion`]
 /// suppported by the node to a bit-vector specifying application-level protocols
 /// supported over that version.
@@ -455,7 +621,9 @@ impl HandshakeMsg {
             if let Some(their_protocols) = other.supported_protocols.get(our_handshake_version) {
                 let common_protocols = our_protocols.intersect(their_protocols);
 
-                if !common_protocols.is_empty() {
+                // Messaging features alone are not enough (i.e., a common
+                // application protocol is still required).
+                if common_protocols.iter().next().is_some() {
                     return Ok((*our_handshake_version, common_protocols));
                 }
             }
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use rand::seq::SliceRandom;
 use std::iter::FromIterator;
 
 // Ensure serialization of MessagingProtocolVersion enum takes 1 byte.
@@ -183,3 +184,80 @@ fn ignore_unknown_protocols() {
         ProtocolIdSet::empty(),
     );
 }
+
+#[test]
+fn negotiate_messaging_features() {
+    let protocols = ProtocolIdSet::from_iter([ProtocolId::MempoolDirectSend]);
+    let mut protocols_with_features = protocols.clone();
+    for feature in MessagingFeature::all() {
+        protocols_with_features.insert_feature(*feature);
+    }
+    let legacy_hs = HandshakeMsg::from_supported(protocols.clone());
+    let features_hs = HandshakeMsg::from_supported(protocols_with_features.clone());
+
+    // Case 1: both peers advertise the features, so they are negotiated
+    let (_, common_protos) = features_hs.perform_handshake(&features_hs).unwrap();
+    assert_eq!(common_protos, protocols_with_features);
+    for feature in MessagingFeature::all() {
+        assert!(common_protos.supports_feature(*feature));
+    }
+
+    // Case 2: only one peer advertises the features, so they are not negotiated
+    let (_, common_protos) = features_hs.perform_handshake(&legacy_hs).unwrap();
+    assert_eq!(common_protos, protocols);
+    let (_, common_protos) = legacy_hs.perform_handshake(&features_hs).unwrap();
+    assert_eq!(common_protos, protocols);
+    for feature in MessagingFeature::all() {
+        assert!(!common_protos.supports_feature(*feature));
+    }
+
+    // Case 3: features are never reported as application protocols
+    assert_eq!(
+        ProtocolIdSet::from_iter(protocols_with_features.iter()),
+        protocols,
+    );
+
+    // Case 4: common features without a common protocol aren't enough to communicate
+    let mut only_features = ProtocolIdSet::empty();
+    for feature in MessagingFeature::all() {
+        only_features.insert_feature(*feature);
+    }
+    let only_features_hs = HandshakeMsg::from_supported(only_features);
+    assert_eq!(
+        features_hs
+            .perform_handshake(&only_features_hs)
+            .unwrap_err(),
+        HandshakeError::NoCommonProtocols,
+    );
+}
+
+#[test]
+fn canonical_protocol_ordering() {
+    // The preference order used by the applications is already canonical
+    let consensus_protocols = vec![
+        ProtocolId::ConsensusDirectSendCompressed,
+        ProtocolId::ConsensusDirectSendBcs,
+        ProtocolId::ConsensusDirectSendJson,
+    ];
+    let mut protocols = Protocols::from(consensus_protocols.clone());
+    protocols.canonicalize();
+    assert_eq!(protocols.as_slice(), consensus_protocols.as_slice());
+
+    // Any ordering (with duplicates) of the same protocols should canonicalize identically
+    let mut all_protocols = ProtocolId::all().to_vec();
+    all_protocols.extend_from_slice(&consensus_protocols);
+    let mut expected_protocols = Protocols::from(all_protocols.clone());
+    expected_protocols.canonicalize();
+    assert_eq!(expected_protocols.as_slice().len(), ProtocolId::all().len());
+    for _ in 0..10 {
+        all_protocols.shuffle(&mut rand::thread_rng());
+        let mut protocols = Protocols::from(all_protocols.as_slice());
+        protocols.canonicalize();
+        assert_eq!(protocols, expected_protocols);
+    }
+
+    // Canonicalization should be idempotent
+    let mut protocols = expected_protocols.clone();
+    protocols.canonicalize();
+    assert_eq!(protocols, expected_protocols);
+}
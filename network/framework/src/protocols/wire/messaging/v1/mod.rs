@@ -43,6 +43,23 @@ pub enum NetworkMessage {
     RpcRequest(RpcRequest),
     RpcResponse(RpcResponse),
     DirectSendMsg(DirectSendMsg),
+    /// A direct send message that carries a trace id. This is only used when
+    /// the sender specifies a trace id, so untraced messages are unchanged on
+    /// the wire. Note: peers that predate this variant cannot parse it, so it is
+    /// only sent to peers that advertise `MessagingFeature::TracedDirectSend`.
+    TracedDirectSendMsg(TracedDirectSendMsg),
+    /// A tiny heartbeat sent on otherwise idle connections (if keep-alives are
+    /// enabled), so that intermediaries do not drop them. It carries no data and
+    /// is never delivered to applications. Note: peers that predate this variant
+    /// cannot parse it, so it is only sent to peers that advertise
+    /// `MessagingFeature::KeepAlive`.
+    KeepAlive,
+    /// A compressed network message (if compression is enabled). It wraps the
+    /// compressed serialization of another (uncompressed) network message, and
+    /// is unwrapped before the message is handled. Note: peers that predate this
+    /// variant cannot parse it, so it is only sent to peers that advertise
+    /// `MessagingFeature::Compression`.
+    CompressedMsg(CompressedMsg),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
@@ -56,10 +73,22 @@ impl NetworkMessage {
     /// The size of the raw data excluding the headers
     pub fn data_len(&self) -> usize {
         match self {
-            NetworkMessage::Error(_) => 0,
+            NetworkMessage::Error(_) | NetworkMessage::KeepAlive => 0,
             NetworkMessage::RpcRequest(request) => request.raw_request.len(),
             NetworkMessage::RpcResponse(response) => response.raw_response.len(),
-            NetworkMessage::DirectSendMsg(message) => message.raw_msg.len(),
+            NetworkMessage::DirectSendMsg(message)
+            | NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg { message, .. }) => {
+                message.raw_msg.len()
+            },
+            NetworkMessage::CompressedMsg(message) => message.compressed_msg.len(),
+        }
+    }
+
+    /// The trace id carried by the message (if any)
+    pub fn trace_id(&self) -> Option<TraceId> {
+        match self {
+            NetworkMessage::TracedDirectSendMsg(message) => Some(message.trace_id),
+            _ => None,
         }
     }
 }
@@ -103,6 +132,9 @@ pub type RequestId = u32;
 /// Create alias Priority for u8.
 pub type Priority = u8;
 
+/// Create alias TraceId for `u64`.
+pub type TraceId = u64;
+
 pub trait IncomingRequest {
     fn protocol_id(&self) -> crate::ProtocolId;
     fn data(&self) -> &Vec<u8>;
@@ -163,6 +195,23 @@ pub struct DirectSendMsg {
     pub raw_msg: Vec<u8>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(Arbitrary))]
+pub struct TracedDirectSendMsg {
+    /// Trace id used to correlate the message across peers (e.g., in logs).
+    pub trace_id: TraceId,
+    /// The direct send message.
+    pub message: DirectSendMsg,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(Arbitrary))]
+pub struct CompressedMsg {
+    /// The compressed serialization of the original network message.
+    #[serde(with = "serde_bytes")]
+    pub compressed_msg: Vec<u8>,
+}
+
 impl IncomingRequest for DirectSendMsg {
     fn protocol_id(&self) -> crate::ProtocolId {
         self.protocol_id
@@ -51,6 +51,39 @@ fn rpc_request() -> bcs::Result<()> {
     Ok(())
 }
 
+#[test]
+fn traced_direct_send_msg() -> bcs::Result<()> {
+    let direct_send_msg = DirectSendMsg {
+        protocol_id: ProtocolId::ConsensusDirectSendBcs,
+        priority: 0,
+        raw_msg: [0, 1, 2, 3].to_vec(),
+    };
+    let untraced = NetworkMessage::DirectSendMsg(direct_send_msg.clone());
+    let traced = NetworkMessage::TracedDirectSendMsg(TracedDirectSendMsg {
+        trace_id: 7,
+        message: direct_send_msg,
+    });
+    assert_eq!(untraced.trace_id(), None);
+    assert_eq!(traced.trace_id(), Some(7));
+    assert_eq!(
+        bcs::to_bytes(&traced)?,
+        // [4] -> NetworkMessage::TracedDirectSendMsg
+        // [7, 0, 0, 0, 0, 0, 0, 0] -> trace_id
+        // [1] -> protocol_id
+        // [0] -> priority
+        // [4] -> length of raw_msg
+        // [0, 1, 2, 3] -> raw_msg bytes
+        vec![4, 7, 0, 0, 0, 0, 0, 0, 0, 1, 0, 4, 0, 1, 2, 3]
+    );
+    // The trace id only adds the trace id bytes, and untraced messages are unchanged
+    assert_eq!(
+        bcs::to_bytes(&traced)?.len(),
+        bcs::to_bytes(&untraced)?.len() + 8
+    );
+    assert_eq!(bcs::to_bytes(&untraced)?[0], 3);
+    Ok(())
+}
+
 #[test]
 fn stream_message() {
     let message = NetworkMessage::DirectSendMsg(DirectSendMsg {
@@ -202,6 +235,7 @@ fn arb_network_message(max_frame_size: usize) -> impl Strategy<Value = NetworkMe
         arb_rpc_request(max_frame_size).prop_map(NetworkMessage::RpcRequest),
         arb_rpc_response(max_frame_size).prop_map(NetworkMessage::RpcResponse),
         arb_direct_send_msg(max_frame_size).prop_map(NetworkMessage::DirectSendMsg),
+        Just(NetworkMessage::KeepAlive),
     ]
     .prop_filter("larger than max frame size", move |msg| {
         bcs::serialized_size(&msg).unwrap() <= max_frame_size
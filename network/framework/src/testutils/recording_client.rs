@@ -0,0 +1,217 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    application::{
+        error::Error,
+        interface::{get_preferred_protocol_for_peer, NetworkClientInterface, NetworkMessageTrait},
+        storage::PeersAndMetadata,
+    },
+    protocols::wire::handshake::v1::ProtocolId,
+};
+use aptos_config::network_id::{NetworkId, PeerNetworkId};
+use aptos_crypto::HashValue;
+use aptos_infallible::Mutex;
+use aptos_types::{network_address::NetworkAddress, PeerId};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// An outbound message recorded by the `RecordingNetworkClient`, i.e., the
+/// peer, protocol and message that would have been sent over the network.
+pub type RecordedMessage<Message> = (PeerNetworkId, ProtocolId, Message);
+
+/// A network client for tests that records every outbound message instead of
+/// sending it, so that tests can assert what an application component would send
+/// without a transport. Protocols are selected exactly as in `NetworkClient` (i.e.,
+/// using the protocol preferences and the peers and metadata container).
+///
+/// Note: RPCs are recorded like direct sends, but always fail (as no peer responds).
+#[derive(Debug)]
+pub struct RecordingNetworkClient<Message> {
+    direct_send_protocols_and_preferences: Vec<ProtocolId>, // Protocols are sorted by preference (highest to lowest)
+    rpc_protocols_and_preferences: Vec<ProtocolId>, // Protocols are sorted by preference (highest to lowest)
+    peers_and_metadata: Arc<PeersAndMetadata>,
+    recorded_messages: Arc<Mutex<Vec<RecordedMessage<Message>>>>, // Shared across clones
+}
+
+impl<Message> Clone for RecordingNetworkClient<Message> {
+    fn clone(&self) -> Self {
+        Self {
+            direct_send_protocols_and_preferences: self
+                .direct_send_protocols_and_preferences
+                .clone(),
+            rpc_protocols_and_preferences: self.rpc_protocols_and_preferences.clone(),
+            peers_and_metadata: self.peers_and_metadata.clone(),
+            recorded_messages: self.recorded_messages.clone(),
+        }
+    }
+}
+
+impl<Message: NetworkMessageTrait> RecordingNetworkClient<Message> {
+    pub fn new(
+        direct_send_protocols_and_preferences: Vec<ProtocolId>,
+        rpc_protocols_and_preferences: Vec<ProtocolId>,
+        peers_and_metadata: Arc<PeersAndMetadata>,
+    ) -> Self {
+        Self {
+            direct_send_protocols_and_preferences,
+            rpc_protocols_and_preferences,
+            peers_and_metadata,
+            recorded_messages: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns all messages recorded so far (in the order they were sent)
+    pub fn recorded_messages(&self) -> Vec<RecordedMessage<Message>> {
+        self.recorded_messages.lock().clone()
+    }
+
+    /// Returns and clears all messages recorded so far (in the order they were sent)
+    pub fn take_recorded_messages(&self) -> Vec<RecordedMessage<Message>> {
+        std::mem::take(&mut *self.recorded_messages.lock())
+    }
+
+    /// Records the given message for the peer, using the peer's preferred protocol
+    fn record_message(
+        &self,
+        message: Message,
+        peer: PeerNetworkId,
+        preferred_protocols: &[ProtocolId],
+    ) -> Result<(), Error> {
+        let protocol_id =
+            get_preferred_protocol_for_peer(&self.peers_and_metadata, &peer, preferred_protocols)?;
+        self.recorded_messages
+            .lock()
+            .push((peer, protocol_id, message));
+        Ok(())
+    }
+
+    /// Records the given message bytes for the peer (after deserializing
+    /// them using the peer's preferred protocol).
+    fn record_message_bytes(
+        &self,
+        message: Bytes,
+        peer: PeerNetworkId,
+        preferred_protocols: &[ProtocolId],
+    ) -> Result<(), Error> {
+        let protocol_id =
+            get_preferred_protocol_for_peer(&self.peers_and_metadata, &peer, preferred_protocols)?;
+        let message = protocol_id.from_bytes(&message)?;
+        self.recorded_messages
+            .lock()
+            .push((peer, protocol_id, message));
+        Ok(())
+    }
+
+    /// Returns the error for RPCs (as the recording client never receives responses)
+    fn rpc_error(peer: PeerNetworkId) -> Error {
+        Error::RpcError(format!(
+            "The recording network client does not receive RPC responses! Peer: {:?}",
+            peer
+        ))
+    }
+}
+
+#[async_trait]
+impl<Message: NetworkMessageTrait> NetworkClientInterface<Message>
+    for RecordingNetworkClient<Message>
+{
+    async fn add_peers_to_discovery(
+        &self,
+        _peers: &[(PeerNetworkId, NetworkAddress)],
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn disconnect_from_peer(&self, _peer: PeerNetworkId) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get_available_peers(&self) -> Result<Vec<PeerNetworkId>, Error> {
+        let supported_protocol_ids: Vec<ProtocolId> = self
+            .direct_send_protocols_and_preferences
+            .iter()
+            .chain(self.rpc_protocols_and_preferences.iter())
+            .cloned()
+            .collect();
+        self.peers_and_metadata
+            .get_connected_supported_peers(&supported_protocol_ids)
+    }
+
+    fn get_peers_and_metadata(&self) -> Arc<PeersAndMetadata> {
+        self.peers_and_metadata.clone()
+    }
+
+    fn send_to_peer(&self, message: Message, peer: PeerNetworkId) -> Result<(), Error> {
+        self.record_message(message, peer, &self.direct_send_protocols_and_preferences)
+    }
+
+    fn send_to_peer_coalesced(
+        &self,
+        message: Message,
+        peer: PeerNetworkId,
+        _coalescing_key: HashValue,
+    ) -> Result<(), Error> {
+        self.record_message(message, peer, &self.direct_send_protocols_and_preferences)
+    }
+
+    fn send_to_peer_raw(&self, message: Bytes, peer: PeerNetworkId) -> Result<(), Error> {
+        self.record_message_bytes(message, peer, &self.direct_send_protocols_and_preferences)
+    }
+
+    fn send_to_peers(&self, message: Message, peers: Vec<PeerNetworkId>) -> Result<(), Error> {
+        // Peers without a common protocol are skipped (as in `NetworkClient`)
+        for peer in peers {
+            let _ = self.record_message(
+                message.clone(),
+                peer,
+                &self.direct_send_protocols_and_preferences,
+            );
+        }
+        Ok(())
+    }
+
+    async fn send_to_peer_rpc(
+        &self,
+        message: Message,
+        _rpc_timeout: Duration,
+        peer: PeerNetworkId,
+    ) -> Result<Message, Error> {
+        self.record_message(message, peer, &self.rpc_protocols_and_preferences)?;
+        Err(Self::rpc_error(peer))
+    }
+
+    async fn send_to_peer_rpc_raw(
+        &self,
+        message: Bytes,
+        _rpc_timeout: Duration,
+        peer: PeerNetworkId,
+    ) -> Result<Message, Error> {
+        self.record_message_bytes(message, peer, &self.rpc_protocols_and_preferences)?;
+        Err(Self::rpc_error(peer))
+    }
+
+    fn to_bytes_by_protocol(
+        &self,
+        peers: Vec<PeerNetworkId>,
+        message: Message,
+    ) -> anyhow::Result<HashMap<PeerNetworkId, Bytes>> {
+        let mut bytes_per_peer = HashMap::new();
+        for peer in peers {
+            if let Ok(protocol_id) = get_preferred_protocol_for_peer(
+                &self.peers_and_metadata,
+                &peer,
+                &self.direct_send_protocols_and_preferences,
+            ) {
+                bytes_per_peer.insert(peer, protocol_id.to_bytes(&message)?.into());
+            }
+        }
+        Ok(bytes_per_peer)
+    }
+
+    fn sort_peers_by_latency(&self, network_id: NetworkId, peers: &mut [PeerId]) {
+        self.peers_and_metadata
+            .sort_peers_by_latency(network_id, peers)
+    }
+}
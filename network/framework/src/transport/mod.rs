@@ -3,19 +3,24 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    application::scoring::PeerScoreEvent,
+    counters,
     logging::NetworkSchema,
     noise::{stream::NoiseStream, AntiReplayTimestamps, HandshakeAuthMode, NoiseUpgrader},
     protocols::{
         identity::exchange_handshake,
-        wire::handshake::v1::{HandshakeMsg, MessagingProtocolVersion, ProtocolIdSet},
+        wire::handshake::v1::{
+            HandshakeMsg, MessagingFeature, MessagingProtocolVersion, ProtocolIdSet,
+        },
     },
 };
 use aptos_config::{
     config::{PeerRole, HANDSHAKE_VERSION},
-    network_id::{NetworkContext, NetworkId},
+    network_id::{NetworkContext, NetworkId, PeerNetworkId},
 };
 use aptos_crypto::x25519;
 use aptos_id_generator::{IdGenerator, U32IdGenerator};
+use aptos_infallible::RwLock;
 use aptos_logger::prelude::*;
 // Re-exposed for aptos-network-checker
 pub use aptos_netcore::transport::tcp::{resolve_and_connect, TCPBufferCfg, TcpSocket};
@@ -191,6 +196,29 @@ pub struct Connection<TSocket> {
     pub metadata: ConnectionMetadata,
 }
 
+/// Convenience function for adding the transport timeout to a connection upgrade,
+/// counting the upgrades that time out as handshake failures.
+async fn timeout_upgrade<F, T>(
+    ctxt: Arc<UpgradeContext>,
+    origin: ConnectionOrigin,
+    time_service: TimeService,
+    fut: F,
+) -> io::Result<T>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    let result = timeout_io(time_service, TRANSPORT_TIMEOUT, fut).await;
+    if matches!(&result, Err(err) if err.kind() == io::ErrorKind::TimedOut) {
+        counters::handshake_failures(
+            &ctxt.noise.network_context,
+            origin,
+            counters::HANDSHAKE_TIMEOUT_LABEL,
+        )
+        .inc();
+    }
+    result
+}
+
 /// Convenience function for adding a timeout to a Future that returns an `io::Result`.
 async fn timeout_io<F, T>(time_service: TimeService, duration: Duration, fut: F) -> io::Result<T>
 where
@@ -208,6 +236,8 @@ pub struct UpgradeContext {
     noise: NoiseUpgrader,
     handshake_version: u8,
     supported_protocols: BTreeMap<MessagingProtocolVersion, ProtocolIdSet>,
+    // Protocols registered after the transport was created (advertised on subsequent handshakes)
+    runtime_protocols: Arc<RwLock<ProtocolIdSet>>,
     chain_id: ChainId,
     network_id: NetworkId,
 }
@@ -224,10 +254,37 @@ impl UpgradeContext {
             noise,
             handshake_version,
             supported_protocols,
+            runtime_protocols: Arc::new(RwLock::new(ProtocolIdSet::empty())),
             chain_id,
             network_id,
         }
     }
+
+    /// Returns the protocols to advertise in the handshake, i.e., the supported
+    /// protocols along with any protocols registered at runtime, and the messaging
+    /// features supported by this node.
+    fn advertised_protocols(&self) -> BTreeMap<MessagingProtocolVersion, ProtocolIdSet> {
+        let runtime_protocols = self.runtime_protocols.read();
+        self.supported_protocols
+            .iter()
+            .map(|(version, protocols)| {
+                let mut protocols = protocols.union(&runtime_protocols);
+                for feature in MessagingFeature::all() {
+                    protocols.insert_feature(*feature);
+                }
+                (*version, protocols)
+            })
+            .collect()
+    }
+
+    /// Reports a failed handshake negotiation with the given (authenticated) peer,
+    /// so that it affects the peer's score (see `PeerScoreEvent::HandshakeError`).
+    fn report_handshake_error(&self, remote_peer_id: PeerId) {
+        self.noise.peers_and_metadata().report_peer_event(
+            PeerNetworkId::new(self.network_id, remote_peer_id),
+            PeerScoreEvent::HandshakeError,
+        );
+    }
 }
 
 /// If we have proxy protocol enabled, then prepend the un-proxied address to the error.
@@ -289,6 +346,8 @@ async fn upgrade_inbound<T: TSocket>(
                     )
                 );
             }
+            counters::handshake_failures(&ctxt.noise.network_context, origin, err.failure_cause())
+                .inc();
             let err = io::Error::new(io::ErrorKind::Other, err);
             add_pp_addr(proxy_protocol_enabled, err, &addr)
         })?;
@@ -297,7 +356,7 @@ async fn upgrade_inbound<T: TSocket>(
 
     // exchange HandshakeMsg
     let handshake_msg = HandshakeMsg {
-        supported_protocols: ctxt.supported_protocols.clone(),
+        supported_protocols: ctxt.advertised_protocols(),
         chain_id: ctxt.chain_id,
         network_id: ctxt.network_id,
     };
@@ -309,6 +368,9 @@ async fn upgrade_inbound<T: TSocket>(
     let (messaging_protocol, application_protocols) = handshake_msg
         .perform_handshake(&remote_handshake)
         .map_err(|err| {
+            ctxt.report_handshake_error(remote_peer_id);
+            counters::handshake_failures(&ctxt.noise.network_context, origin, err.failure_cause())
+                .inc();
             let err = format!(
                 "handshake negotiation with peer {} failed: {}",
                 remote_peer_id.short_str(),
@@ -371,6 +433,8 @@ pub async fn upgrade_outbound<T: TSocket>(
                     )
                 );
             }
+            counters::handshake_failures(&ctxt.noise.network_context, origin, err.failure_cause())
+                .inc();
             io::Error::new(io::ErrorKind::Other, err)
         })?;
 
@@ -379,7 +443,7 @@ pub async fn upgrade_outbound<T: TSocket>(
 
     // exchange HandshakeMsg
     let handshake_msg = HandshakeMsg {
-        supported_protocols: ctxt.supported_protocols.clone(),
+        supported_protocols: ctxt.advertised_protocols(),
         chain_id: ctxt.chain_id,
         network_id: ctxt.network_id,
     };
@@ -389,6 +453,9 @@ pub async fn upgrade_outbound<T: TSocket>(
     let (messaging_protocol, application_protocols) = handshake_msg
         .perform_handshake(&remote_handshake)
         .map_err(|e| {
+            ctxt.report_handshake_error(remote_peer_id);
+            counters::handshake_failures(&ctxt.noise.network_context, origin, e.failure_cause())
+                .inc();
             let e = format!(
                 "handshake negotiation with peer {} failed: {}",
                 remote_peer_id, e
@@ -473,6 +540,13 @@ where
         }
     }
 
+    /// Returns the set of protocols registered after the transport was created.
+    /// Protocols added to this set are advertised on all subsequent handshakes
+    /// (existing connections are unaffected).
+    pub fn runtime_protocols(&self) -> Arc<RwLock<ProtocolIdSet>> {
+        self.ctxt.runtime_protocols.clone()
+    }
+
     fn parse_dial_addr(
         addr: &NetworkAddress,
     ) -> io::Result<(NetworkAddress, x25519::PublicKey, u8)> {
@@ -566,7 +640,12 @@ where
 
         // outbound dial upgrade task
         let upgrade_fut = upgrade_outbound(self.ctxt.clone(), fut_socket, addr, peer_id, pubkey);
-        let upgrade_fut = timeout_io(self.time_service.clone(), TRANSPORT_TIMEOUT, upgrade_fut);
+        let upgrade_fut = timeout_upgrade(
+            self.ctxt.clone(),
+            ConnectionOrigin::Outbound,
+            self.time_service.clone(),
+            upgrade_fut,
+        );
         Ok(upgrade_fut)
     }
 
@@ -623,7 +702,12 @@ where
                 addr.clone(),
                 enable_proxy_protocol,
             );
-            let fut_upgrade = timeout_io(time_service.clone(), TRANSPORT_TIMEOUT, fut_upgrade);
+            let fut_upgrade = timeout_upgrade(
+                ctxt.clone(),
+                ConnectionOrigin::Inbound,
+                time_service.clone(),
+                fut_upgrade,
+            );
             (fut_upgrade, addr)
         });
 
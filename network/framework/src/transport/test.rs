@@ -4,7 +4,10 @@
 
 use crate::{
     application::storage::PeersAndMetadata,
-    protocols::wire::handshake::v1::{MessagingProtocolVersion, ProtocolId, ProtocolIdSet},
+    counters,
+    protocols::wire::handshake::v1::{
+        MessagingFeature, MessagingProtocolVersion, ProtocolId, ProtocolIdSet,
+    },
     testutils,
     transport::*,
 };
@@ -56,6 +59,29 @@ fn setup<TTransport>(
     Arc<PeersAndMetadata>,
     ProtocolIdSet,
 )
+where
+    TTransport: Transport<Error = io::Error> + Clone,
+    TTransport::Output: TSocket,
+    TTransport::Outbound: Send + 'static,
+    TTransport::Inbound: Send + 'static,
+    TTransport::Listener: Send + 'static,
+{
+    setup_with_chain_ids(base_transport, auth, ChainId::default(), ChainId::default())
+}
+
+fn setup_with_chain_ids<TTransport>(
+    base_transport: TTransport,
+    auth: Auth,
+    listener_chain_id: ChainId,
+    dialer_chain_id: ChainId,
+) -> (
+    Runtime,
+    MockTimeService,
+    (PeerId, AptosNetTransport<TTransport>),
+    (PeerId, AptosNetTransport<TTransport>),
+    Arc<PeersAndMetadata>,
+    ProtocolIdSet,
+)
 where
     TTransport: Transport<Error = io::Error> + Clone,
     TTransport::Output: TSocket,
@@ -159,7 +185,6 @@ where
 
     let supported_protocols =
         ProtocolIdSet::from_iter([ProtocolId::ConsensusRpcBcs, ProtocolId::DiscoveryDirectSend]);
-    let chain_id = ChainId::default();
     let listener_transport = AptosNetTransport::new(
         base_transport.clone(),
         listener_network_context,
@@ -167,7 +192,7 @@ where
         listener_key,
         listener_auth_mode,
         HANDSHAKE_VERSION,
-        chain_id,
+        listener_chain_id,
         supported_protocols.clone(),
         false, /* Disable proxy protocol */
     );
@@ -179,18 +204,24 @@ where
         dialer_key,
         dialer_auth_mode,
         HANDSHAKE_VERSION,
-        chain_id,
+        dialer_chain_id,
         supported_protocols.clone(),
         false, /* Disable proxy protocol */
     );
 
+    // Both sides advertise all messaging features, so they are always negotiated
+    let mut negotiated_protocols = supported_protocols;
+    for feature in MessagingFeature::all() {
+        negotiated_protocols.insert_feature(*feature);
+    }
+
     (
         rt,
         time_service.into_mock(),
         (listener_network_context.peer_id(), listener_transport),
         (dialer_network_context.peer_id(), dialer_transport),
         peers_and_metadata,
-        supported_protocols,
+        negotiated_protocols,
     )
 }
 
@@ -366,6 +397,151 @@ fn test_transport_rejects_unauthed_dialer<TTransport>(
     rt.block_on(future::join(listener_task, dialer_task));
 }
 
+fn test_transport_counts_rejected_handshake<TTransport>(
+    base_transport: TTransport,
+    listen_addr: &str,
+    expect_formatted_addr: fn(&NetworkAddress),
+) where
+    TTransport: Transport<Error = io::Error> + Clone,
+    TTransport::Output: TSocket,
+    TTransport::Outbound: Send + 'static,
+    TTransport::Inbound: Send + 'static,
+    TTransport::Listener: Send + 'static,
+{
+    let (
+        rt,
+        _mock_time,
+        (listener_peer_id, listener_transport),
+        (dialer_peer_id, dialer_transport),
+        peers_and_metadata,
+        _supported_protocols,
+    ) = setup(base_transport, Auth::Mutual);
+
+    // trust the dialer's peer id, but with a different pubkey
+    let network_id = peers_and_metadata.get_registered_networks().next().unwrap();
+    let mut rng = StdRng::from_seed([1u8; 32]);
+    let other_key = x25519::PrivateKey::generate(&mut rng);
+    let mut trusted_peers = peers_and_metadata.get_trusted_peers(&network_id).unwrap();
+    add_trusted_peer(
+        &mut trusted_peers,
+        dialer_peer_id,
+        &other_key,
+        PeerRole::Validator,
+    );
+    peers_and_metadata
+        .set_trusted_peers(&network_id, trusted_peers)
+        .unwrap();
+
+    let auth_rejected_counter = counters::handshake_failures(
+        &NetworkContext::mock(),
+        ConnectionOrigin::Inbound,
+        counters::HANDSHAKE_AUTH_REJECTED_LABEL,
+    );
+    let num_auth_rejected = auth_rejected_counter.get();
+
+    let _guard = rt.enter();
+    let (mut inbounds, listener_addr) = listener_transport
+        .listen_on(listen_addr.parse().unwrap())
+        .unwrap();
+    expect_formatted_addr(&listener_addr);
+
+    // the listener should reject the dialer's handshake because the dialer's
+    // pubkey doesn't match the trusted pubkey for its peer id.
+    let listener_task = async move {
+        let (inbound, _dialer_addr) = inbounds.next().await.unwrap().unwrap();
+        inbound
+            .await
+            .expect_err("should fail because the dialer's pubkey is not trusted");
+    };
+    let dialer_task = async move {
+        let fut_upgrade = dialer_transport
+            .dial(listener_peer_id, listener_addr.clone())
+            .unwrap();
+        fut_upgrade
+            .await
+            .expect_err("should fail because listener rejects our connection");
+    };
+    rt.block_on(future::join(listener_task, dialer_task));
+
+    // verify the rejected handshake was counted
+    assert_eq!(auth_rejected_counter.get(), num_auth_rejected + 1);
+}
+
+fn test_transport_reports_handshake_error<TTransport>(base_transport: TTransport, listen_addr: &str)
+where
+    TTransport: Transport<Error = io::Error> + Clone,
+    TTransport::Output: TSocket,
+    TTransport::Outbound: Send + 'static,
+    TTransport::Inbound: Send + 'static,
+    TTransport::Listener: Send + 'static,
+{
+    // Create a listener and dialer on different chains
+    let (
+        rt,
+        _mock_time,
+        (listener_peer_id, listener_transport),
+        (dialer_peer_id, dialer_transport),
+        peers_and_metadata,
+        _supported_protocols,
+    ) = setup_with_chain_ids(
+        base_transport,
+        Auth::Mutual,
+        ChainId::new(1),
+        ChainId::new(2),
+    );
+    let network_id = peers_and_metadata.get_registered_networks().next().unwrap();
+
+    let chain_id_mismatch_counters =
+        [ConnectionOrigin::Inbound, ConnectionOrigin::Outbound].map(|origin| {
+            counters::handshake_failures(
+                &NetworkContext::mock(),
+                origin,
+                counters::HANDSHAKE_CHAIN_ID_MISMATCH_LABEL,
+            )
+        });
+    let num_chain_id_mismatches = chain_id_mismatch_counters
+        .each_ref()
+        .map(|counter| counter.get());
+
+    let _guard = rt.enter();
+    let (mut inbounds, listener_addr) = listener_transport
+        .listen_on(listen_addr.parse().unwrap())
+        .unwrap();
+
+    // Both sides should fail the handshake negotiation (after authenticating each other)
+    let listener_task = async move {
+        let (inbound, _dialer_addr) = inbounds.next().await.unwrap().unwrap();
+        inbound
+            .await
+            .expect_err("should fail because the chain ids don't match");
+    };
+    let dialer_task = async move {
+        let fut_upgrade = dialer_transport
+            .dial(listener_peer_id, listener_addr.clone())
+            .unwrap();
+        fut_upgrade
+            .await
+            .expect_err("should fail because the chain ids don't match");
+    };
+    rt.block_on(future::join(listener_task, dialer_task));
+
+    // Verify each side reported the handshake error for the remote peer
+    for peer_id in [listener_peer_id, dialer_peer_id] {
+        let peer_score = peers_and_metadata
+            .get_peer_score(&PeerNetworkId::new(network_id, peer_id))
+            .unwrap();
+        assert_eq!(peer_score.num_handshake_errors, 1);
+    }
+
+    // Verify each side counted the failure as a chain id mismatch
+    for (counter, num_mismatches) in chain_id_mismatch_counters
+        .iter()
+        .zip(num_chain_id_mismatches)
+    {
+        assert_eq!(counter.get(), num_mismatches + 1);
+    }
+}
+
 fn test_transport_maybe_mutual<TTransport>(
     base_transport: TTransport,
     listen_addr: &str,
@@ -554,6 +730,20 @@ fn test_memory_transport_maybe_mutual() {
     );
 }
 
+#[test]
+fn test_memory_transport_reports_handshake_error() {
+    test_transport_reports_handshake_error(memory::MemoryTransport, "/memory/0");
+}
+
+#[test]
+fn test_memory_transport_counts_rejected_handshake() {
+    test_transport_counts_rejected_handshake(
+        memory::MemoryTransport,
+        "/memory/0",
+        expect_memory_noise_addr,
+    );
+}
+
 /////////////////////////////////////
 // AptosNetTransport<TcpTransport> //
 /////////////////////////////////////
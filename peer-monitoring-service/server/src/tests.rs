@@ -507,7 +507,8 @@ impl MockClient {
             .counters(&metrics::PENDING_PEER_MONITORING_SERVER_NETWORK_EVENTS);
             let (peer_manager_notifier, peer_manager_notification_receiver) = queue_cfg.build();
 
-            let network_events = NetworkEvents::new(peer_manager_notification_receiver, None, true);
+            let network_events =
+                NetworkEvents::new(peer_manager_notification_receiver, None, true, None, None);
             network_and_events.insert(network_id, network_events);
             peer_manager_notifiers.insert(network_id, peer_manager_notifier);
         }
@@ -0,0 +1,35 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use thiserror::Error;
+
+/// Errors that can occur when parsing JWKs from JSON documents, or encoding them for other libraries.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum JwkError {
+    #[error("Malformed JWKS document: {0}")]
+    MalformedDocument(String),
+    #[error("Malformed JWKS for issuer `{issuer}`: {reason}")]
+    MalformedIssuerEntry { issuer: String, reason: String },
+    #[error("Issuer `{0}` appears more than once in the JWKS document")]
+    DuplicateIssuer(String),
+    #[error("Failed to encode RSA public key: {0}")]
+    RsaKeyEncoding(String),
+    #[error("Invalid RSA key field `{field}`: {detail}")]
+    InvalidRsaField { field: String, detail: String },
+    #[error("Invalid X.509 certificate: {0}")]
+    InvalidCertificate(String),
+    #[error("Invalid OKP key: {0}")]
+    InvalidOkpKey(String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("Malformed JWK: {0}")]
+    MalformedKey(String),
+    #[error("Unsupported JWK: {0}")]
+    UnsupportedKey(String),
+    #[error("JWKS document exceeds the maximum size of {0} bytes")]
+    DocumentTooLarge(usize),
+    #[error("JWKS document has {num_keys} keys, exceeding the maximum of {max_keys}")]
+    TooManyKeys { num_keys: usize, max_keys: usize },
+    #[error("Failed to read JWKS document: {0}")]
+    ReadFailed(String),
+}
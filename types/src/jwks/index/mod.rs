@@ -0,0 +1,74 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::jwks::jwk::JWK;
+use std::collections::HashMap;
+
+/// An index over a set of JWKs, for verifiers that repeatedly select keys by `kid`,
+/// or by RFC 7638 thumbprint (see `JWK::thumbprint()`), instead of scanning the set.
+///
+/// If several keys share a `kid` (or thumbprint), the first one in the set is returned.
+/// Keys without a `kid` (or a defined thumbprint) are only indexed by the other.
+#[derive(Clone, Debug, Default)]
+pub struct JwkIndex {
+    keys: Vec<JWK>,
+    key_indices_by_kid: HashMap<String, usize>,
+    key_indices_by_thumbprint: HashMap<String, usize>,
+}
+
+impl JwkIndex {
+    pub fn new(keys: Vec<JWK>) -> Self {
+        let mut key_indices_by_kid = HashMap::new();
+        let mut key_indices_by_thumbprint = HashMap::new();
+        for (key_index, key) in keys.iter().enumerate() {
+            if let Some(kid) = key.kid() {
+                key_indices_by_kid.entry(kid).or_insert(key_index);
+            }
+            if let Ok(thumbprint) = key.thumbprint() {
+                key_indices_by_thumbprint
+                    .entry(thumbprint)
+                    .or_insert(key_index);
+            }
+        }
+
+        Self {
+            keys,
+            key_indices_by_kid,
+            key_indices_by_thumbprint,
+        }
+    }
+
+    /// Returns the key with the given `kid` (if any)
+    pub fn by_kid(&self, kid: &str) -> Option<&JWK> {
+        self.key_indices_by_kid
+            .get(kid)
+            .map(|key_index| &self.keys[*key_index])
+    }
+
+    /// Returns the key with the given RFC 7638 thumbprint (if any)
+    pub fn by_thumbprint(&self, thumbprint: &str) -> Option<&JWK> {
+        self.key_indices_by_thumbprint
+            .get(thumbprint)
+            .map(|key_index| &self.keys[*key_index])
+    }
+
+    /// Returns the key with the given `kid`, or else the key with the given thumbprint
+    pub fn by_either(&self, kid_or_thumbprint: &str) -> Option<&JWK> {
+        self.by_kid(kid_or_thumbprint)
+            .or_else(|| self.by_thumbprint(kid_or_thumbprint))
+    }
+
+    /// Returns all keys in the index (in their original order)
+    pub fn keys(&self) -> &[JWK] {
+        &self.keys
+    }
+}
+
+impl From<Vec<JWK>> for JwkIndex {
+    fn from(keys: Vec<JWK>) -> Self {
+        Self::new(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests;
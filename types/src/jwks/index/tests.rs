@@ -0,0 +1,45 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::jwks::{index::JwkIndex, jwk::JWK, rsa::RSA_JWK};
+
+#[test]
+fn lookup_by_kid_and_thumbprint() {
+    let rsa_jwk = JWK::RSA(RSA_JWK::new_256_aqab("kid1", "6S7asUuzq5Q_3U9rbs-PkDVIdjgmtgWreG5qWPsC9xXZKiMV1AiV9LXyqQsAYpCqEDM3XbfmZqGb48yLhb_XqZaKgSYaC_h2DjM7lgrIQAp9902Rr8fUmLN2ivr5tnLxUUOnMOc2SQtr9dgzTONYW5Zu3PwyvAWk5D6ueIUhLtYzpcB-etoNdL3Ir2746KIy_VUsDwAM7dhrqSK8U2xFCGlau4ikOTtvzDownAMHMrfE7q1B6WZQDAQlBmxRQsyKln5DIsKv6xauNsHRgBAKctUxZG8M4QJIx3S6Aughd3RZC4Ca5Ae9fd8L8mlNYBCrQhOZ7dS0f4at4arlLcajtw"));
+    let okp_jwk = JWK::from(serde_json::json!({
+        "kid": "kid2",
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+    }));
+    // A key without a `kid`, that can only be found by its thumbprint
+    let anonymous_jwk = JWK::from(serde_json::json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "x": "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+    }));
+    let index = JwkIndex::new(vec![
+        rsa_jwk.clone(),
+        okp_jwk.clone(),
+        anonymous_jwk.clone(),
+    ]);
+
+    // Verify lookups by kid and by thumbprint return the same key
+    for jwk in [&rsa_jwk, &okp_jwk] {
+        let kid = jwk.kid().unwrap();
+        let thumbprint = jwk.thumbprint().unwrap();
+        assert_eq!(index.by_kid(&kid), Some(jwk));
+        assert_eq!(index.by_thumbprint(&thumbprint), Some(jwk));
+        assert_eq!(index.by_either(&kid), Some(jwk));
+        assert_eq!(index.by_either(&thumbprint), Some(jwk));
+    }
+    let thumbprint = anonymous_jwk.thumbprint().unwrap();
+    assert_eq!(index.by_thumbprint(&thumbprint), Some(&anonymous_jwk));
+    assert_eq!(index.by_either(&thumbprint), Some(&anonymous_jwk));
+
+    // Verify unknown kids and thumbprints are not found
+    assert_eq!(index.by_kid("kid3"), None);
+    assert_eq!(index.by_thumbprint("kid1"), None);
+    assert_eq!(index.by_either("unknown"), None);
+    assert_eq!(index.keys().len(), 3);
+}
@@ -0,0 +1,50 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversions between the `jsonwebtoken` crate's JWK representation and `JWK`,
+//! for callers migrating keys from other Rust JWT libraries. Both types follow
+//! the JWK JSON format (RFC 7517), so conversions go through `serde_json::Value`
+//! and apply the same parsing rules as JWKs fetched from a provider.
+
+use crate::jwks::{jwk::JWK, rsa::RSA_JWK};
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+
+impl TryFrom<&jsonwebtoken::jwk::Jwk> for JWK {
+    type Error = anyhow::Error;
+
+    fn try_from(jwk: &jsonwebtoken::jwk::Jwk) -> Result<Self, Self::Error> {
+        let json_value =
+            serde_json::to_value(jwk).context("failed to serialize jsonwebtoken jwk to json")?;
+        Ok(JWK::from(json_value))
+    }
+}
+
+impl TryFrom<&JWK> for jsonwebtoken::jwk::Jwk {
+    type Error = anyhow::Error;
+
+    fn try_from(jwk: &JWK) -> Result<Self, Self::Error> {
+        let json_value = match jwk {
+            JWK::RSA(rsa_jwk) => rsa_jwk_to_json(rsa_jwk),
+            JWK::Unsupported(unsupported_jwk) => {
+                serde_json::from_slice(&unsupported_jwk.payload)
+                    .map_err(|e| anyhow!("unsupported jwk payload is not valid json: {e}"))?
+            },
+        };
+        serde_json::from_value(json_value).context("failed to convert jwk to a jsonwebtoken jwk")
+    }
+}
+
+/// Returns the JSON form of the given RSA JWK, omitting `alg` if it is unspecified.
+fn rsa_jwk_to_json(rsa_jwk: &RSA_JWK) -> Value {
+    let mut json_value = json!({
+        "kid": rsa_jwk.kid,
+        "kty": rsa_jwk.kty,
+        "e": rsa_jwk.e,
+        "n": rsa_jwk.n,
+    });
+    if !rsa_jwk.alg.is_empty() {
+        json_value["alg"] = Value::String(rsa_jwk.alg.clone());
+    }
+    json_value
+}
@@ -4,18 +4,25 @@
 #![allow(clippy::match_result_ok)]
 
 use crate::{
-    jwks::{rsa::RSA_JWK, unsupported::UnsupportedJWK},
+    jwks::{error::JwkError, okp::OKP_JWK, rsa::RSA_JWK, unsupported::UnsupportedJWK},
     move_any::{Any as MoveAny, AsMoveAny},
     move_utils::as_move_value::AsMoveValue,
 };
 use anyhow::anyhow;
+use aptos_crypto::HashValue;
 use aptos_crypto_derive::{BCSCryptoHash, CryptoHasher};
+use base64::URL_SAFE_NO_PAD;
 use move_core_types::value::{MoveStruct, MoveValue};
 use poem_openapi_derive::Union;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{Error as DeError, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use std::{
     cmp::Ordering,
-    fmt::{Debug, Formatter},
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter},
+    sync::Arc,
 };
 
 /// Reflection of Move type `0x1::jwks::JWK`.
@@ -50,6 +57,10 @@ impl From<RSA_JWK> for JWKMoveStruct {
 }
 
 /// The JWK type that can be converted from/to `JWKMoveStruct` but easier to use in rust.
+///
+/// The type can also be BCS-serialized directly (e.g., for off-chain persistence and comparison).
+/// BCS encodes the variant index as the enum discriminant, so new variants must only be
+/// appended; existing variants must never be reordered or removed.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Union)]
 pub enum JWK {
     RSA(RSA_JWK),
@@ -63,6 +74,187 @@ impl JWK {
             JWK::Unsupported(unsupported) => unsupported.id(),
         }
     }
+
+    /// Returns the key as a JSON object (for RSA keys, only their standard members)
+    fn to_json_value(&self) -> serde_json::Value {
+        match self {
+            JWK::RSA(rsa) => serde_json::json!({
+                "kty": rsa.kty,
+                "kid": rsa.kid,
+                "alg": rsa.alg,
+                "e": rsa.e,
+                "n": rsa.n,
+            }),
+            JWK::Unsupported(unsupported) => {
+                serde_json::from_slice::<serde_json::Value>(&unsupported.payload)
+                    .unwrap_or_default()
+            },
+        }
+    }
+
+    /// Returns the `kid` of the key (if it has a non-empty one)
+    pub fn kid(&self) -> Option<String> {
+        let kid = match self {
+            JWK::RSA(rsa) => rsa.kid.clone(),
+            JWK::Unsupported(_) => self.to_json_value()["kid"].as_str()?.to_string(),
+        };
+        (!kid.is_empty()).then_some(kid)
+    }
+
+    /// Returns the RFC 7638 thumbprint of the key (see `thumbprint()`).
+    pub fn thumbprint(&self) -> Result<String, JwkError> {
+        thumbprint(&self.to_json_value())
+    }
+
+    /// Returns a redacted view of the key, e.g., for structured logs and admin responses
+    /// (see `RedactedJwk`).
+    pub fn redacted(&self) -> RedactedJwk {
+        let fields = match self.to_json_value() {
+            serde_json::Value::Object(fields) => fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = redact_field(&name, value);
+                    (name, value)
+                })
+                .collect(),
+            // The payload is not a JSON object, so none of its fields can be kept
+            _ => serde_json::Map::new(),
+        };
+        RedactedJwk { fields }
+    }
+}
+
+/// Returns the RFC 7638 thumbprint of the given JSON key, i.e., the base64url-encoded SHA-256
+/// hash of the JSON object holding only the required members of its `kty` (sorted, without
+/// whitespace). Fails if the `kty` is unknown, or a required member is missing. This also
+/// accepts keys that are not parsed as a `JWK` (e.g., by off-chain services).
+pub fn thumbprint(json_value: &serde_json::Value) -> Result<String, JwkError> {
+    let kty = json_value["kty"]
+        .as_str()
+        .ok_or_else(|| JwkError::UnsupportedKey("missing `kty`".to_string()))?;
+    let required_members: &[&str] = match kty {
+        "RSA" => &["e", "kty", "n"],
+        "EC" => &["crv", "kty", "x", "y"],
+        "OKP" => &["crv", "kty", "x"],
+        "oct" => &["k", "kty"],
+        _ => {
+            return Err(JwkError::UnsupportedKey(format!(
+                "no thumbprint is defined for `kty` {}",
+                kty
+            )))
+        },
+    };
+
+    let members = required_members
+        .iter()
+        .map(|name| match json_value[*name].as_str() {
+            Some(value) => Ok(format!("\"{}\":{}", name, serde_json::Value::from(value))),
+            None => Err(JwkError::UnsupportedKey(format!(
+                "missing `{}` required for the thumbprint",
+                name
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let canonical_json = format!("{{{}}}", members.join(","));
+    let digest = ring::digest::digest(&ring::digest::SHA256, canonical_json.as_bytes());
+    Ok(base64::encode_config(digest, URL_SAFE_NO_PAD))
+}
+
+/// The number of bytes of the key material hash shown when displaying a `JWK`
+const MATERIAL_HASH_DISPLAY_BYTES: usize = 4;
+
+/// Returns a short (truncated) hex-encoded hash of the given key material
+fn short_material_hash(material: &[u8]) -> String {
+    hex::encode(&HashValue::sha3_256_of(material).to_vec()[..MATERIAL_HASH_DISPLAY_BYTES])
+}
+
+/// A concise, single-line summary of the key that is safe to log, e.g., to eyeball-diff
+/// two key sets. Only the `kty`, `kid` and `alg` are shown, along with a short hash of
+/// the key material. The key material itself (e.g., the RSA modulus, or the secret of a
+/// symmetric key in an unsupported JWK) is never displayed.
+impl Display for JWK {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let or_none = |value: &str| {
+            if value.is_empty() {
+                "<none>".to_string()
+            } else {
+                value.to_string()
+            }
+        };
+        let (kty, kid, alg, material_hash) = match self {
+            JWK::RSA(rsa) => (
+                rsa.kty.clone(),
+                or_none(&rsa.kid),
+                or_none(&rsa.alg),
+                short_material_hash(format!("{}.{}", rsa.n, rsa.e).as_bytes()),
+            ),
+            JWK::Unsupported(unsupported) => {
+                let json_value = serde_json::from_slice::<serde_json::Value>(&unsupported.payload)
+                    .unwrap_or_default();
+                let field = |name: &str| or_none(json_value[name].as_str().unwrap_or_default());
+                (
+                    field("kty"),
+                    field("kid"),
+                    field("alg"),
+                    short_material_hash(&unsupported.payload),
+                )
+            },
+        };
+        write!(
+            f,
+            "kty={} kid={} alg={} material={}",
+            kty, kid, alg, material_hash
+        )
+    }
+}
+
+/// The fields of a JWK that hold secret material (e.g., of symmetric or private keys)
+const SECRET_FIELDS: &[&str] = &["k", "d", "p", "q", "dp", "dq", "qi", "oth"];
+
+/// The number of characters of large public fields (e.g., `n`) kept in a `RedactedJwk`
+const TRUNCATED_FIELD_PREFIX_CHARS: usize = 8;
+
+/// A view of a `JWK` that is safe to include in structured logs and admin responses.
+/// The identifying fields (e.g., `kty`, `kid` and `alg`) are kept as is, large public
+/// fields are truncated (e.g., `n` is replaced by its prefix and length, and `x5c` by its
+/// number of certificates), and secret material (e.g., `k` and `d`) is fully redacted.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct RedactedJwk {
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl RedactedJwk {
+    /// Returns the (redacted) value of the given field (if the key has it)
+    pub fn field(&self, name: &str) -> Option<&serde_json::Value> {
+        self.fields.get(name)
+    }
+}
+
+impl Display for RedactedJwk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::Value::Object(self.fields.clone()))
+    }
+}
+
+/// Redacts the value of the given JWK field (see `RedactedJwk`)
+fn redact_field(name: &str, value: serde_json::Value) -> serde_json::Value {
+    if SECRET_FIELDS.contains(&name) {
+        return serde_json::Value::String("<redacted>".to_string());
+    }
+    match (name, value) {
+        ("n", serde_json::Value::String(n)) => serde_json::Value::String(format!(
+            "{}...<{} chars>",
+            n.chars()
+                .take(TRUNCATED_FIELD_PREFIX_CHARS)
+                .collect::<String>(),
+            n.chars().count()
+        )),
+        ("x5c", serde_json::Value::Array(certificates)) => {
+            serde_json::Value::String(format!("<{} certificates>", certificates.len()))
+        },
+        (_, value) => value,
+    }
 }
 
 impl PartialOrd for JWK {
@@ -77,8 +269,157 @@ impl Ord for JWK {
     }
 }
 
+/// Canonicalize a JWS `alg` value to its JOSE form (e.g., `rs256` becomes `RS256`),
+/// also accepting some legacy aliases. Returns `None` if the algorithm is not recognized.
+pub fn normalize_alg(alg: &str) -> Option<String> {
+    let canonical_alg = match alg.to_ascii_uppercase().as_str() {
+        "HS256" => "HS256",
+        "HS384" => "HS384",
+        "HS512" => "HS512",
+        "RS256" | "RSA-SHA256" => "RS256",
+        "RS384" | "RSA-SHA384" => "RS384",
+        "RS512" | "RSA-SHA512" => "RS512",
+        "PS256" => "PS256",
+        "PS384" => "PS384",
+        "PS512" => "PS512",
+        "ES256" | "ECDSA-SHA256" => "ES256",
+        "ES384" | "ECDSA-SHA384" => "ES384",
+        "ES512" | "ECDSA-SHA512" => "ES512",
+        "ES256K" => "ES256K",
+        "EDDSA" => "EdDSA",
+        _ => return None,
+    };
+    Some(canonical_alg.to_string())
+}
+
+/// A JWS signature scheme, i.e., the signature algorithm (and digest) that a JOSE `alg`
+/// stands for (see `alg_to_scheme()`).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SignatureScheme {
+    /// RSASSA-PKCS1-v1_5 with SHA-256 (`RS256`)
+    RsaPkcs1Sha256,
+    /// RSASSA-PKCS1-v1_5 with SHA-384 (`RS384`)
+    RsaPkcs1Sha384,
+    /// RSASSA-PKCS1-v1_5 with SHA-512 (`RS512`)
+    RsaPkcs1Sha512,
+    /// ECDSA on the P-256 curve with SHA-256 (`ES256`)
+    EcdsaP256Sha256,
+    /// ECDSA on the P-384 curve with SHA-384 (`ES384`)
+    EcdsaP384Sha384,
+    /// EdDSA (e.g., on the Ed25519 curve), which hashes the message itself (`EdDSA`)
+    EdDsa,
+}
+
+/// A digest algorithm used by a `SignatureScheme`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl SignatureScheme {
+    /// Returns the JOSE `alg` of the scheme
+    pub fn alg(&self) -> &'static str {
+        match self {
+            Self::RsaPkcs1Sha256 => "RS256",
+            Self::RsaPkcs1Sha384 => "RS384",
+            Self::RsaPkcs1Sha512 => "RS512",
+            Self::EcdsaP256Sha256 => "ES256",
+            Self::EcdsaP384Sha384 => "ES384",
+            Self::EdDsa => OKP_JWK::EDDSA_ALG,
+        }
+    }
+
+    /// Returns the digest applied to the message before signing. EdDSA has none, as
+    /// the hash is an internal part of the signature algorithm.
+    pub fn digest(&self) -> Option<DigestAlgorithm> {
+        match self {
+            Self::RsaPkcs1Sha256 | Self::EcdsaP256Sha256 => Some(DigestAlgorithm::Sha256),
+            Self::RsaPkcs1Sha384 | Self::EcdsaP384Sha384 => Some(DigestAlgorithm::Sha384),
+            Self::RsaPkcs1Sha512 => Some(DigestAlgorithm::Sha512),
+            Self::EdDsa => None,
+        }
+    }
+}
+
+/// Maps a JOSE `alg` to its signature scheme. Returns `None` for `none` and any `alg` that
+/// is unknown or not supported. The match is exact (as `alg` values are case sensitive), so
+/// callers that accept other spellings should canonicalize them first (see `normalize_alg()`).
+pub fn alg_to_scheme(alg: &str) -> Option<SignatureScheme> {
+    match alg {
+        "RS256" => Some(SignatureScheme::RsaPkcs1Sha256),
+        "RS384" => Some(SignatureScheme::RsaPkcs1Sha384),
+        "RS512" => Some(SignatureScheme::RsaPkcs1Sha512),
+        "ES256" => Some(SignatureScheme::EcdsaP256Sha256),
+        "ES384" => Some(SignatureScheme::EcdsaP384Sha384),
+        OKP_JWK::EDDSA_ALG => Some(SignatureScheme::EdDsa),
+        _ => None,
+    }
+}
+
+/// A parser for JWKs with a custom `kty` (see `JwkParserRegistry`).
+pub type JwkParser = Box<dyn Fn(&serde_json::Value) -> Result<JWK, JwkError> + Send + Sync>;
+
+/// A registry of parsers for JWKs with custom key types (e.g., `EC` or `OKP`), keyed by
+/// `kty`. This is only consulted by the parsing functions that take it explicitly (see
+/// `JWK::try_from_value_with_parsers()`), for keys that would otherwise be unsupported.
+///
+/// Note: the JWKs observed by validators must not depend on the parsers registered by
+/// each node, so this is for off-chain tooling only (and is never used by JWK consensus).
+#[derive(Clone, Default)]
+pub struct JwkParserRegistry {
+    parsers: HashMap<String, Arc<JwkParser>>,
+}
+
+impl JwkParserRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a parser for JWKs with the given custom `kty`, replacing any
+    /// parser previously registered for it.
+    pub fn register_jwk_parser(&mut self, kty: &str, parser: JwkParser) {
+        self.parsers.insert(kty.to_string(), Arc::new(parser));
+    }
+
+    /// Returns true iff a parser is registered for the `kty` of the given JSON value
+    fn is_registered(&self, value: &serde_json::Value) -> bool {
+        value
+            .get("kty")
+            .and_then(|kty| kty.as_str())
+            .map_or(false, |kty| self.parsers.contains_key(kty))
+    }
+
+    /// Parses the given JSON value using the parser registered for its `kty` (if any)
+    fn parse(&self, value: &serde_json::Value) -> Option<JWK> {
+        let kty = value.get("kty")?.as_str()?;
+        let parser = self.parsers.get(kty)?;
+        parser(value).ok()
+    }
+}
+
+impl Debug for JwkParserRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwkParserRegistry")
+            .field("ktys", &self.parsers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Converts any JSON value into a `JWK`, without panicking. Values that are not JSON
+/// objects (e.g., arrays, strings or numbers) are never valid keys, so they are always
+/// kept as unsupported keys.
+///
+/// Note: the JWK observers of all validators must agree on the parsed keys, so this
+/// conversion must not change. Revised parsing rules are only applied when enabled by
+/// `FeatureFlag::JWK_PARSING_V2` (see `ParseOptions::parsing_v2`).
 impl From<serde_json::Value> for JWK {
     fn from(value: serde_json::Value) -> Self {
+        if !value.is_object() {
+            return Self::Unsupported(UnsupportedJWK::from(value));
+        }
+
         match RSA_JWK::try_from(&value) {
             Ok(rsa) => Self::RSA(rsa),
             Err(_) => {
@@ -89,6 +430,297 @@ impl From<serde_json::Value> for JWK {
     }
 }
 
+/// Options for parsing JWKs from raw JSON text (see `JWK::try_parse()`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseOptions {
+    /// If set, keys (and JWKS documents) with duplicate members at any depth (e.g.,
+    /// `{"kty":"RSA","kty":"EC",...}`) are rejected. Otherwise, the last occurrence
+    /// of a member wins.
+    pub reject_duplicate_members: bool,
+    /// If set, keys that would be parsed as `JWK::Unsupported` (e.g., unknown key
+    /// types) are rejected, instead of being kept as opaque unsupported keys.
+    pub reject_unsupported: bool,
+    /// If set, the hex strings in the payloads of unsupported keys are lowercased, so
+    /// that keys only differing in hex case get the same id. This is lossy, so it is
+    /// off by default (see `UnsupportedJWK::from_value_with_normalized_hex()`).
+    pub normalize_unsupported_hex: bool,
+    /// If set, keys are parsed with the revised rules (see `JWK::from_value_v2()`).
+    /// These change the observed keys, so JWK consensus only sets this when
+    /// `FeatureFlag::JWK_PARSING_V2` is enabled on chain.
+    pub parsing_v2: bool,
+    /// The maximum number of keys in a JWKS document. Larger documents are
+    /// rejected before any of their keys are parsed (only if `parsing_v2` is set).
+    pub max_keys: usize,
+    /// The maximum size (in bytes) of the JSON text of a JWKS document. Larger
+    /// documents are rejected before they are parsed.
+    pub max_bytes: usize,
+}
+
+impl ParseOptions {
+    /// The default maximum size of a JWKS document (1 MiB)
+    pub const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+    /// The default maximum number of keys in a JWKS document (providers serve a handful)
+    pub const DEFAULT_MAX_KEYS: usize = 1_000;
+
+    /// Options that reject any key that is ambiguous to parse, or not supported
+    pub fn strict() -> Self {
+        Self {
+            reject_duplicate_members: true,
+            reject_unsupported: true,
+            ..Self::default()
+        }
+    }
+
+    /// Options that accept keys the way most JSON parsers would
+    pub fn lenient() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            reject_duplicate_members: false,
+            reject_unsupported: false,
+            normalize_unsupported_hex: false,
+            parsing_v2: false,
+            max_keys: Self::DEFAULT_MAX_KEYS,
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl JWK {
+    /// Parses a single JWK from the given JSON text (e.g., a provider response).
+    ///
+    /// JSON objects may (technically) contain duplicate members, which parsers resolve
+    /// differently. So, the policy for duplicate members is explicit here: they are
+    /// rejected (at any depth) if `options.reject_duplicate_members` is set, otherwise
+    /// the last occurrence of each member wins. The parsed object is then converted into a `JWK`
+    /// (see `From<serde_json::Value>`), which fails if the key is not supported and
+    /// `options.reject_unsupported` is set.
+    pub fn try_parse(json: &str, options: &ParseOptions) -> Result<Self, JwkError> {
+        let members = parse_members(json, options)?;
+        Self::try_from_value(serde_json::Value::Object(members), options)
+    }
+
+    /// Parses a single JWK from the given JSON text (see `try_parse()`), consulting
+    /// the given parsers for keys with a custom `kty` (see `try_from_value_with_parsers()`).
+    pub fn try_parse_with_parsers(
+        json: &str,
+        options: &ParseOptions,
+        parsers: &JwkParserRegistry,
+    ) -> Result<Self, JwkError> {
+        let members = parse_members(json, options)?;
+        Self::try_from_value_with_parsers(serde_json::Value::Object(members), options, parsers)
+    }
+
+    /// Converts the given JSON value into a `JWK` (see `From<serde_json::Value>`, or
+    /// `from_value_v2()` if `options.parsing_v2` is set), rejecting unsupported keys if
+    /// `options.reject_unsupported` is set, and normalizing them if
+    /// `options.normalize_unsupported_hex` is set.
+    pub fn try_from_value(
+        value: serde_json::Value,
+        options: &ParseOptions,
+    ) -> Result<Self, JwkError> {
+        Self::try_from_value_with_parsers(value, options, &JwkParserRegistry::new())
+    }
+
+    /// Converts the given JSON value into a `JWK` (see `try_from_value()`), consulting
+    /// the given parsers for keys with a custom `kty` that would otherwise be unsupported.
+    /// If the registered parser fails, the key is kept as unsupported.
+    pub fn try_from_value_with_parsers(
+        value: serde_json::Value,
+        options: &ParseOptions,
+        parsers: &JwkParserRegistry,
+    ) -> Result<Self, JwkError> {
+        let convert = |value: serde_json::Value| {
+            let registered_value = parsers.is_registered(&value).then(|| value.clone());
+            let jwk = if options.parsing_v2 {
+                Self::from_value_v2(value)
+            } else {
+                Self::from(value)
+            };
+            match (jwk, registered_value) {
+                (JWK::Unsupported(unsupported), Some(value)) => parsers
+                    .parse(&value)
+                    .unwrap_or(JWK::Unsupported(unsupported)),
+                (jwk, _) => jwk,
+            }
+        };
+        let jwk = if options.normalize_unsupported_hex {
+            match convert(value.clone()) {
+                JWK::Unsupported(_) => {
+                    JWK::Unsupported(UnsupportedJWK::from_value_with_normalized_hex(value))
+                },
+                jwk => jwk,
+            }
+        } else {
+            convert(value)
+        };
+        match jwk {
+            JWK::Unsupported(unsupported) if options.reject_unsupported => Err(
+                JwkError::UnsupportedKey(JWK::Unsupported(unsupported).to_string()),
+            ),
+            jwk => Ok(jwk),
+        }
+    }
+
+    /// Parses a single JWK from the given JSON text (see `try_parse()`), also
+    /// capturing parse-time metadata that is not kept in the key itself (e.g., `jku`).
+    pub fn try_parse_with_metadata(
+        json: &str,
+        options: &ParseOptions,
+    ) -> Result<JwkParse, JwkError> {
+        let members = parse_members(json, options)?;
+        let jku = members
+            .get("jku")
+            .and_then(|jku| jku.as_str())
+            .map(|jku| jku.to_string());
+        Ok(JwkParse {
+            jwk: Self::try_from_value(serde_json::Value::Object(members), options)?,
+            jku,
+        })
+    }
+
+    /// Converts the given JSON value into a `JWK` (see `From<serde_json::Value>`), also
+    /// applying the revised parsing rules (see `ParseOptions::parsing_v2`): RSA keys are
+    /// parsed with `RSA_JWK::try_from_json_v2()` (which also canonicalizes the modulus),
+    /// their `alg` is canonicalized (see `normalize_alg()`), and RSA keys with an unknown `alg` (or claiming `EdDSA`, which
+    /// is only valid for OKP keys) are unsupported.
+    fn from_value_v2(value: serde_json::Value) -> Self {
+        if !value.is_object() {
+            return Self::Unsupported(UnsupportedJWK::from(value));
+        }
+
+        match RSA_JWK::try_from_json_v2(&value) {
+            // RSA keys without an `alg` are kept as is (see `RSA_JWK::is_compatible_with_jwt_alg()`).
+            Ok(rsa) if rsa.alg.is_empty() => Self::RSA(rsa),
+            Ok(mut rsa) => match normalize_alg(&rsa.alg) {
+                Some(alg) if alg != OKP_JWK::EDDSA_ALG => {
+                    rsa.alg = alg;
+                    Self::RSA(rsa)
+                },
+                _ => Self::Unsupported(UnsupportedJWK::from(value)),
+            },
+            Err(_) => Self::Unsupported(UnsupportedJWK::from(value)),
+        }
+    }
+}
+
+/// The result of parsing a single JWK (see `JWK::try_parse_with_metadata()`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JwkParse {
+    pub jwk: JWK,
+    jku: Option<String>,
+}
+
+impl JwkParse {
+    /// Returns the `jku` (JWK Set URL) of the key, if any. This is only recorded
+    /// (e.g., so that tooling can validate it against an allowlist): the URL is
+    /// never fetched, to avoid server-side request forgery.
+    pub fn jku(&self) -> Option<&str> {
+        self.jku.as_deref()
+    }
+}
+
+/// Parses the top-level members of a JWK from the given JSON text, applying the
+/// duplicate member policy of the options (see `JWK::try_parse()`).
+fn parse_members(
+    json: &str,
+    options: &ParseOptions,
+) -> Result<serde_json::Map<String, serde_json::Value>, JwkError> {
+    let malformed = |reason: String| JwkError::MalformedDocument(reason);
+    match parse_json_value(json, options).map_err(|e| malformed(e.to_string()))? {
+        serde_json::Value::Object(members) => Ok(members),
+        _ => Err(malformed("JWK is not an object".to_string())),
+    }
+}
+
+/// Parses the given JSON text, rejecting objects (at any depth) with duplicate
+/// members if `options.reject_duplicate_members` is set. Otherwise, the last
+/// occurrence of each member wins.
+pub(crate) fn parse_json_value(
+    json: &str,
+    options: &ParseOptions,
+) -> Result<serde_json::Value, serde_json::Error> {
+    if options.reject_duplicate_members {
+        serde_json::from_str::<UniqueMembers>(json).map(|value| value.0)
+    } else {
+        serde_json::from_str(json)
+    }
+}
+
+/// A JSON value that is rejected (during deserialization) if any of its
+/// objects (at any depth) has a member that appears more than once.
+struct UniqueMembers(serde_json::Value);
+
+impl<'de> Deserialize<'de> for UniqueMembers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UniqueMembersVisitor;
+
+        impl<'de> Visitor<'de> for UniqueMembersVisitor {
+            type Value = UniqueMembers;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a JSON value without duplicate object members")
+            }
+
+            fn visit_bool<E: DeError>(self, value: bool) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Value::Bool(value)))
+            }
+
+            fn visit_i64<E: DeError>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Value::Number(value.into())))
+            }
+
+            fn visit_u64<E: DeError>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Value::Number(value.into())))
+            }
+
+            fn visit_f64<E: DeError>(self, value: f64) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Number::from_f64(value).map_or(
+                    serde_json::Value::Null,
+                    serde_json::Value::Number,
+                )))
+            }
+
+            fn visit_str<E: DeError>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Value::String(value.to_string())))
+            }
+
+            fn visit_string<E: DeError>(self, value: String) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Value::String(value)))
+            }
+
+            fn visit_unit<E: DeError>(self) -> Result<Self::Value, E> {
+                Ok(UniqueMembers(serde_json::Value::Null))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut values = vec![];
+                while let Some(UniqueMembers(value)) = seq.next_element()? {
+                    values.push(value);
+                }
+                Ok(UniqueMembers(serde_json::Value::Array(values)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut members = serde_json::Map::new();
+                while let Some((name, UniqueMembers(value))) = map.next_entry::<String, _>()? {
+                    if members.contains_key(&name) {
+                        return Err(A::Error::custom(format!("duplicate member `{}`", name)));
+                    }
+                    members.insert(name, value);
+                }
+                Ok(UniqueMembers(serde_json::Value::Object(members)))
+            }
+        }
+
+        deserializer.deserialize_any(UniqueMembersVisitor)
+    }
+}
+
 impl From<JWK> for JWKMoveStruct {
     fn from(jwk: JWK) -> Self {
         let variant = match jwk {
@@ -121,5 +753,7 @@ impl TryFrom<&JWKMoveStruct> for JWK {
     }
 }
 
+#[cfg(feature = "jsonwebtoken-jwk")]
+mod jsonwebtoken_compat;
 #[cfg(test)]
 mod tests;
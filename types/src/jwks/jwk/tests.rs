@@ -3,14 +3,25 @@
 
 use crate::{
     jwks::{
-        jwk::{JWKMoveStruct, JWK},
+        error::JwkError,
+        jwk::{
+            alg_to_scheme, normalize_alg, DigestAlgorithm, JWKMoveStruct, JwkParserRegistry,
+            ParseOptions, SignatureScheme, JWK,
+        },
         rsa::RSA_JWK,
         unsupported::UnsupportedJWK,
     },
     move_any::{Any as MoveAny, AsMoveAny},
 };
 use aptos_crypto::HashValue;
-use std::str::FromStr;
+use proptest::prelude::*;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 #[test]
 fn convert_jwk_move_struct_to_jwk() {
@@ -96,3 +107,507 @@ fn convert_json_value_to_jwk() {
     });
     assert_eq!(expected, actual);
 }
+
+#[test]
+fn convert_non_object_json_value_to_jwk() {
+    // Values that are not objects should deterministically become unsupported JWKs
+    let mut nested_array = serde_json::json!([]);
+    let mut nested_object = serde_json::json!({"kty": "RSA"});
+    for _ in 0..100 {
+        nested_array = serde_json::json!([nested_array]);
+        nested_object = serde_json::json!({ "kty": nested_object });
+    }
+    for json in [
+        serde_json::Value::Null,
+        serde_json::json!(true),
+        serde_json::json!(13131),
+        serde_json::json!(u64::MAX),
+        serde_json::json!(i64::MIN),
+        serde_json::json!(f64::MAX),
+        serde_json::json!("RSA"),
+        serde_json::json!([{"kty": "RSA", "kid": "kid1", "e": "AQAB", "n": "13131"}]),
+        nested_array,
+    ] {
+        let expected = JWK::Unsupported(UnsupportedJWK::from(json.clone()));
+        assert_eq!(expected, JWK::from(json.clone()));
+        assert_eq!(expected, JWK::from(json));
+    }
+
+    // Deeply nested objects should also become unsupported JWKs
+    assert!(matches!(JWK::from(nested_object), JWK::Unsupported(_)));
+}
+
+/// Returns a strategy for arbitrary JSON values, biased towards JWK members
+fn arb_json_value() -> impl Strategy<Value = serde_json::Value> {
+    let arb_string = prop_oneof![
+        prop::sample::select(vec![
+            "RSA", "OKP", "EC", "RS256", "rs256", "EdDSA", "sig", "verify", "AQAB", "kid1",
+        ])
+        .prop_map(String::from),
+        ".*",
+    ];
+    let leaf = prop_oneof![
+        Just(serde_json::Value::Null),
+        any::<bool>().prop_map(serde_json::Value::from),
+        any::<i64>().prop_map(serde_json::Value::from),
+        any::<u64>().prop_map(serde_json::Value::from),
+        any::<f64>().prop_map(serde_json::Value::from),
+        arb_string,
+    ];
+    leaf.prop_recursive(8, 256, 10, |inner| {
+        let arb_member = prop_oneof![
+            prop::sample::select(vec![
+                "kty", "kid", "alg", "e", "n", "use", "key_ops", "crv", "x", "d",
+            ])
+            .prop_map(String::from),
+            "[a-z_]{0,8}",
+        ];
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..10).prop_map(serde_json::Value::from),
+            prop::collection::btree_map(arb_member, inner, 0..10)
+                .prop_map(|members| serde_json::Value::Object(members.into_iter().collect())),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn convert_arbitrary_json_value_to_jwk(json in arb_json_value()) {
+        // The conversion should never panic, and should be deterministic
+        let jwk = JWK::from(json.clone());
+        prop_assert_eq!(&jwk, &JWK::from(json.clone()));
+        if !json.is_object() {
+            prop_assert_eq!(jwk, JWK::Unsupported(UnsupportedJWK::from(json)));
+        }
+    }
+}
+
+#[test]
+fn normalize_jwk_alg() {
+    // Mixed-case algorithms should be canonicalized
+    assert_eq!(Some("RS256".to_string()), normalize_alg("RS256"));
+    assert_eq!(Some("RS256".to_string()), normalize_alg("rs256"));
+    assert_eq!(Some("PS384".to_string()), normalize_alg("Ps384"));
+    assert_eq!(Some("EdDSA".to_string()), normalize_alg("eddsa"));
+
+    // Legacy aliases should be canonicalized
+    assert_eq!(Some("RS512".to_string()), normalize_alg("rsa-sha512"));
+
+    // Unknown algorithms should be rejected
+    assert_eq!(None, normalize_alg("RS1024"));
+    assert_eq!(None, normalize_alg("ED25519"));
+    assert_eq!(None, normalize_alg(""));
+}
+
+#[test]
+fn map_jwk_alg_to_scheme() {
+    // Every supported algorithm should map to its scheme (and back)
+    for (alg, scheme, digest) in [
+        (
+            "RS256",
+            SignatureScheme::RsaPkcs1Sha256,
+            Some(DigestAlgorithm::Sha256),
+        ),
+        (
+            "RS384",
+            SignatureScheme::RsaPkcs1Sha384,
+            Some(DigestAlgorithm::Sha384),
+        ),
+        (
+            "RS512",
+            SignatureScheme::RsaPkcs1Sha512,
+            Some(DigestAlgorithm::Sha512),
+        ),
+        (
+            "ES256",
+            SignatureScheme::EcdsaP256Sha256,
+            Some(DigestAlgorithm::Sha256),
+        ),
+        (
+            "ES384",
+            SignatureScheme::EcdsaP384Sha384,
+            Some(DigestAlgorithm::Sha384),
+        ),
+        ("EdDSA", SignatureScheme::EdDsa, None),
+    ] {
+        assert_eq!(Some(scheme), alg_to_scheme(alg));
+        assert_eq!(alg, scheme.alg());
+        assert_eq!(digest, scheme.digest());
+    }
+
+    // Unsigned, unknown, unsupported and non-canonical algorithms should not be mapped
+    for alg in [
+        "none", "", "RS1024", "HS256", "PS256", "ES512", "rs256", "EDDSA",
+    ] {
+        assert_eq!(None, alg_to_scheme(alg));
+    }
+}
+
+#[test]
+fn convert_json_value_to_jwk_normalizes_alg() {
+    let options = ParseOptions {
+        parsing_v2: true,
+        ..ParseOptions::lenient()
+    };
+
+    // A lowercase `alg` should be stored in its canonical form
+    let json_str =
+        r#"{"alg": "rs256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    let expected = JWK::RSA(RSA_JWK::new_from_strs(
+        "kid1", "RSA", "RS256", "AQAB", "13131",
+    ));
+    assert_eq!(
+        expected,
+        JWK::try_from_value(json.clone(), &options).unwrap()
+    );
+
+    // Without the revised parsing rules, the `alg` should be kept as is
+    let expected = JWK::RSA(RSA_JWK::new_from_strs(
+        "kid1", "RSA", "rs256", "AQAB", "13131",
+    ));
+    assert_eq!(expected, JWK::from(json));
+
+    // An RSA key without an `alg` should be kept as an RSA key
+    let json_str = r#"{"kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    let expected = JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "", "AQAB", "13131"));
+    assert_eq!(
+        expected,
+        JWK::try_from_value(json.clone(), &options).unwrap()
+    );
+
+    // Without the revised parsing rules, it should be flagged as unsupported
+    assert!(matches!(JWK::from(json), JWK::Unsupported(_)));
+
+    // An RSA key with an unknown `alg` should be flagged as unsupported
+    let json_str = r#"{"alg": "RS1024", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(matches!(
+        JWK::try_from_value(json.clone(), &options).unwrap(),
+        JWK::Unsupported(_)
+    ));
+
+    // Without the revised parsing rules, it should be kept as an RSA key
+    assert!(matches!(JWK::from(json), JWK::RSA(_)));
+}
+
+#[test]
+fn convert_json_value_with_numeric_kid_to_jwk() {
+    let options = ParseOptions {
+        parsing_v2: true,
+        ..ParseOptions::lenient()
+    };
+
+    // A numeric `kid` should be coerced to its string form
+    let json_str =
+        r#"{"alg": "RS256", "kid": 12345, "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    let expected = JWK::RSA(RSA_JWK::new_from_strs(
+        "12345", "RSA", "RS256", "AQAB", "13131",
+    ));
+    assert_eq!(
+        expected,
+        JWK::try_from_value(json.clone(), &options).unwrap()
+    );
+
+    // Without the revised parsing rules, it should be kept as an unsupported JWK
+    assert!(matches!(JWK::from(json), JWK::Unsupported(_)));
+
+    // Any other non-string `kid` should gracefully fall back to an unsupported JWK
+    let json_str =
+        r#"{"alg": "RS256", "kid": [1], "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(matches!(
+        JWK::try_from_value(json, &options).unwrap(),
+        JWK::Unsupported(_)
+    ));
+}
+
+#[test]
+fn convert_json_value_with_registered_kty_parser_to_jwk() {
+    // Register a parser for a custom key type that counts its invocations
+    let num_invocations = Arc::new(AtomicUsize::new(0));
+    let parser_invocations = num_invocations.clone();
+    let mut parsers = JwkParserRegistry::new();
+    parsers.register_jwk_parser(
+        "DUMMY",
+        Box::new(move |value| {
+            parser_invocations.fetch_add(1, Ordering::SeqCst);
+            match value["kid"].as_str() {
+                Some(kid) => Ok(JWK::RSA(RSA_JWK::new_from_strs(
+                    kid, "DUMMY", "RS256", "AQAB", "dummy",
+                ))),
+                None => Err(JwkError::MalformedDocument("missing kid".to_string())),
+            }
+        }),
+    );
+    let options = ParseOptions::lenient();
+
+    // Verify the parser is invoked for keys of the custom type
+    let json_value = serde_json::json!({"kty": "DUMMY", "kid": "kid1"});
+    assert_eq!(
+        JWK::RSA(RSA_JWK::new_from_strs(
+            "kid1", "DUMMY", "RS256", "AQAB", "dummy"
+        )),
+        JWK::try_from_value_with_parsers(json_value.clone(), &options, &parsers).unwrap()
+    );
+    assert_eq!(num_invocations.load(Ordering::SeqCst), 1);
+
+    // Verify the parser is also invoked when parsing JSON text
+    assert!(matches!(
+        JWK::try_parse_with_parsers(&json_value.to_string(), &options, &parsers).unwrap(),
+        JWK::RSA(_)
+    ));
+    assert_eq!(num_invocations.load(Ordering::SeqCst), 2);
+
+    // Verify the parser is never consulted without the registry (e.g., by JWK consensus)
+    assert!(matches!(JWK::from(json_value.clone()), JWK::Unsupported(_)));
+    assert!(matches!(
+        JWK::try_from_value(json_value, &options).unwrap(),
+        JWK::Unsupported(_)
+    ));
+    assert_eq!(num_invocations.load(Ordering::SeqCst), 2);
+
+    // Verify keys are kept as unsupported if the parser fails
+    let json_value = serde_json::json!({"kty": "DUMMY"});
+    assert_eq!(
+        JWK::Unsupported(UnsupportedJWK::from(json_value.clone())),
+        JWK::try_from_value_with_parsers(json_value, &options, &parsers).unwrap()
+    );
+    assert_eq!(num_invocations.load(Ordering::SeqCst), 3);
+
+    // Verify the parser is not invoked for other key types
+    let json_value = serde_json::json!({"kty": "EC", "kid": "kid2"});
+    assert!(matches!(
+        JWK::try_from_value_with_parsers(json_value, &options, &parsers).unwrap(),
+        JWK::Unsupported(_)
+    ));
+    assert_eq!(num_invocations.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn jwk_bcs_round_trip() {
+    let rsa_jwk = JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "n1"));
+    let unsupported_jwk = JWK::Unsupported(UnsupportedJWK::new_for_testing("id1", "payload1"));
+
+    for (jwk, expected_discriminant) in [(rsa_jwk, 0u8), (unsupported_jwk, 1u8)] {
+        let bytes = bcs::to_bytes(&jwk).unwrap();
+        // The variant discriminants must remain stable so stored bytes stay readable.
+        assert_eq!(expected_discriminant, bytes[0]);
+        assert_eq!(jwk, bcs::from_bytes::<JWK>(&bytes).unwrap());
+    }
+}
+
+#[test]
+fn convert_json_value_with_conflicting_key_ops_to_jwk() {
+    let options = ParseOptions {
+        parsing_v2: true,
+        ..ParseOptions::lenient()
+    };
+
+    // RSA keys with conflicting `use` and `key_ops` are flagged as unsupported.
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "key_ops": ["encrypt"], "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    let expected = JWK::Unsupported(UnsupportedJWK::from(json.clone()));
+    assert_eq!(
+        expected,
+        JWK::try_from_value(json.clone(), &options).unwrap()
+    );
+
+    // Without the revised parsing rules, they are kept as RSA keys.
+    let expected = JWK::RSA(RSA_JWK::new_from_strs(
+        "kid1", "RSA", "RS256", "AQAB", "13131",
+    ));
+    assert_eq!(expected, JWK::from(json));
+}
+
+#[test]
+fn display_jwk() {
+    // An RSA key should display its metadata, but never the full modulus
+    let n = "6S7asUuzq5Q_3U9rbs-PkDVIdjgmtgWreG5qWPsC9xXZKiMV1AiV9LXyqQsAYpCqEDM3XbfmZqGb48yLhb_XqZaKgSYaC_h2DjM7lgrIQAp9902Rr8fUmLN2ivr5tnLxUUOnMOc2SQtr9dgzTONYW5Zu3PwyvAWk5D6ueIUhLtYzpcB-etoNdL3Ir2746KIy_VUsDwAM7dhrqSK8U2xFCGlau4ikOTtvzDownAMHMrfE7q1B6WZQDAQlBmxRQsyKln5DIsKv6xauNsHRgBAKctUxZG8M4QJIx3S6Aughd3RZC4Ca5Ae9fd8L8mlNYBCrQhOZ7dS0f4at4arlLcajtw";
+    let jwk = JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", n));
+    let displayed = jwk.to_string();
+    let material_hash =
+        hex::encode(&HashValue::sha3_256_of(format!("{}.AQAB", n).as_bytes()).to_vec()[..4]);
+    assert_eq!(
+        format!("kty=RSA kid=kid1 alg=RS256 material={}", material_hash),
+        displayed
+    );
+    assert!(!displayed.contains(n));
+
+    // Missing metadata should be displayed as such
+    let jwk = JWK::RSA(RSA_JWK::new_from_strs("", "RSA", "", "AQAB", n));
+    assert!(jwk
+        .to_string()
+        .starts_with("kty=RSA kid=<none> alg=<none> material="));
+
+    // An unsupported (symmetric) key should never display its secret
+    let secret = "c2VjcmV0LXN5bW1ldHJpYy1rZXk";
+    let jwk =
+        JWK::from(serde_json::json!({"kid": "kid2", "kty": "oct", "alg": "HS256", "k": secret}));
+    let displayed = jwk.to_string();
+    assert!(displayed.starts_with("kty=oct kid=kid2 alg=HS256 material="));
+    assert!(!displayed.contains(secret));
+}
+
+#[test]
+fn redacted_jwk_omits_key_material() {
+    // An RSA key should keep its metadata, but not the body of its modulus
+    let n = "6S7asUuzq5Q_3U9rbs-PkDVIdjgmtgWreG5qWPsC9xXZKiMV1AiV9LXyqQsAYpCqEDM3XbfmZqGb48yLhb_XqZaKgSYaC_h2DjM7lgrIQAp9902Rr8fUmLN2ivr5tnLxUUOnMOc2SQtr9dgzTONYW5Zu3PwyvAWk5D6ueIUhLtYzpcB-etoNdL3Ir2746KIy_VUsDwAM7dhrqSK8U2xFCGlau4ikOTtvzDownAMHMrfE7q1B6WZQDAQlBmxRQsyKln5DIsKv6xauNsHRgBAKctUxZG8M4QJIx3S6Aughd3RZC4Ca5Ae9fd8L8mlNYBCrQhOZ7dS0f4at4arlLcajtw";
+    let redacted = JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", n)).redacted();
+    assert_eq!(redacted.field("kid").unwrap(), "kid1");
+    assert_eq!(redacted.field("kty").unwrap(), "RSA");
+    assert_eq!(redacted.field("alg").unwrap(), "RS256");
+    assert_eq!(redacted.field("n").unwrap(), "6S7asUuz...<342 chars>");
+    assert!(!redacted.to_string().contains(n));
+    assert!(!serde_json::to_string(&redacted).unwrap().contains(n));
+
+    // A symmetric key should have its secret fully redacted, and certificates truncated
+    let secret = "c2VjcmV0LXN5bW1ldHJpYy1rZXk";
+    let jwk = JWK::from(serde_json::json!({
+        "kid": "kid2", "kty": "oct", "alg": "HS256", "k": secret, "x5c": ["cert1", "cert2"],
+    }));
+    let redacted = jwk.redacted();
+    assert_eq!(redacted.field("kid").unwrap(), "kid2");
+    assert_eq!(redacted.field("k").unwrap(), "<redacted>");
+    assert_eq!(redacted.field("x5c").unwrap(), "<2 certificates>");
+    assert!(!redacted.to_string().contains(secret));
+}
+
+#[test]
+fn jwk_thumbprint() {
+    // The example from RFC 8037 (Appendix A.3)
+    let okp_jwk = JWK::from(serde_json::json!({
+        "kid": "kid1",
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+    }));
+    assert_eq!(
+        okp_jwk.thumbprint().unwrap(),
+        "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+    );
+
+    // Only the required members are hashed (so `kid` and `alg` don't matter)
+    let rsa_jwk = JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "6S7asUuzq5Q_3U9rbs-PkDVIdjgmtgWreG5qWPsC9xXZKiMV1AiV9LXyqQsAYpCqEDM3XbfmZqGb48yLhb_XqZaKgSYaC_h2DjM7lgrIQAp9902Rr8fUmLN2ivr5tnLxUUOnMOc2SQtr9dgzTONYW5Zu3PwyvAWk5D6ueIUhLtYzpcB-etoNdL3Ir2746KIy_VUsDwAM7dhrqSK8U2xFCGlau4ikOTtvzDownAMHMrfE7q1B6WZQDAQlBmxRQsyKln5DIsKv6xauNsHRgBAKctUxZG8M4QJIx3S6Aughd3RZC4Ca5Ae9fd8L8mlNYBCrQhOZ7dS0f4at4arlLcajtw"));
+    let other_rsa_jwk = JWK::RSA(RSA_JWK::new_from_strs("kid2", "RSA", "RS512", "AQAB", "6S7asUuzq5Q_3U9rbs-PkDVIdjgmtgWreG5qWPsC9xXZKiMV1AiV9LXyqQsAYpCqEDM3XbfmZqGb48yLhb_XqZaKgSYaC_h2DjM7lgrIQAp9902Rr8fUmLN2ivr5tnLxUUOnMOc2SQtr9dgzTONYW5Zu3PwyvAWk5D6ueIUhLtYzpcB-etoNdL3Ir2746KIy_VUsDwAM7dhrqSK8U2xFCGlau4ikOTtvzDownAMHMrfE7q1B6WZQDAQlBmxRQsyKln5DIsKv6xauNsHRgBAKctUxZG8M4QJIx3S6Aughd3RZC4Ca5Ae9fd8L8mlNYBCrQhOZ7dS0f4at4arlLcajtw"));
+    assert_eq!(
+        rsa_jwk.thumbprint().unwrap(),
+        "QEtvxSqph29a7jbJoscsPrBFQcG_XtC3LHHSjw1wwVE"
+    );
+    assert_eq!(rsa_jwk.thumbprint(), other_rsa_jwk.thumbprint());
+
+    // Keys with an unknown `kty`, or missing required members, have no thumbprint
+    let unknown_jwk = JWK::from(serde_json::json!({"kid": "kid1", "kty": "XYZ"}));
+    assert!(matches!(
+        unknown_jwk.thumbprint(),
+        Err(JwkError::UnsupportedKey(_))
+    ));
+    let incomplete_jwk =
+        JWK::from(serde_json::json!({"kid": "kid1", "kty": "OKP", "crv": "Ed25519"}));
+    assert!(matches!(
+        incomplete_jwk.thumbprint(),
+        Err(JwkError::UnsupportedKey(_))
+    ));
+}
+
+#[cfg(feature = "jsonwebtoken-jwk")]
+#[test]
+fn convert_jsonwebtoken_jwk_round_trip() {
+    let foreign_jwk: jsonwebtoken::jwk::Jwk = serde_json::from_value(serde_json::json!({
+        "kid": "kid1",
+        "kty": "RSA",
+        "alg": "RS256",
+        "use": "sig",
+        "e": "AQAB",
+        "n": "n1",
+    }))
+    .unwrap();
+
+    // Converting the foreign key should produce an RSA JWK
+    let jwk = JWK::try_from(&foreign_jwk).unwrap();
+    assert_eq!(
+        JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "n1")),
+        jwk
+    );
+
+    // Converting back should preserve the RSA parameters
+    let converted_jwk = jsonwebtoken::jwk::Jwk::try_from(&jwk).unwrap();
+    assert_eq!(Some("kid1".to_string()), converted_jwk.common.key_id);
+    assert_eq!(
+        Some(jsonwebtoken::Algorithm::RS256),
+        converted_jwk.common.algorithm
+    );
+    match converted_jwk.algorithm {
+        jsonwebtoken::jwk::AlgorithmParameters::RSA(params) => {
+            assert_eq!("AQAB", params.e);
+            assert_eq!("n1", params.n);
+        },
+        params => panic!("Expected RSA parameters, found: {:?}", params),
+    }
+    assert_eq!(jwk, JWK::try_from(&converted_jwk).unwrap());
+}
+
+#[test]
+fn try_parse_jwk_with_duplicate_members() {
+    let json = r#"{"kid":"kid1","kty":"RSA","alg":"RS256","e":"AQAB","n":"n1","kty":"EC"}"#;
+
+    // In strict mode, the duplicate `kty` should be rejected
+    match JWK::try_parse(json, &ParseOptions::strict()) {
+        Err(JwkError::MalformedDocument(reason)) => assert!(reason.contains("`kty`")),
+        result => panic!("Expected a malformed document, found: {:?}", result),
+    }
+
+    // In lenient mode, the last `kty` should win (so the key is not RSA)
+    let jwk = JWK::try_parse(json, &ParseOptions::lenient()).unwrap();
+    match jwk {
+        JWK::Unsupported(unsupported) => {
+            let payload: serde_json::Value = serde_json::from_slice(&unsupported.payload).unwrap();
+            assert_eq!("EC", payload["kty"]);
+        },
+        jwk => panic!("Expected an unsupported JWK, found: {:?}", jwk),
+    }
+
+    // Keys without duplicate members should parse the same way in both modes
+    let json = r#"{"kid":"kid1","kty":"RSA","alg":"RS256","e":"AQAB","n":"n1"}"#;
+    let expected_jwk = JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "n1"));
+    for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+        assert_eq!(expected_jwk, JWK::try_parse(json, &options).unwrap());
+    }
+}
+
+#[test]
+fn try_parse_jwk_rejecting_unsupported_keys() {
+    // An unsupported key should only be rejected if requested
+    let json = r#"{"kid":"kid1","kty":"EC","alg":"ES256","crv":"P-256","x":"x1","y":"y1"}"#;
+    let options = ParseOptions {
+        reject_unsupported: true,
+        ..ParseOptions::lenient()
+    };
+    assert!(matches!(
+        JWK::try_parse(json, &options),
+        Err(JwkError::UnsupportedKey(_))
+    ));
+    assert!(matches!(
+        JWK::try_parse(json, &ParseOptions::lenient()),
+        Ok(JWK::Unsupported(_))
+    ));
+}
+
+#[test]
+fn try_parse_jwk_records_jku() {
+    // A key with a `jku` should preserve it (without affecting the parsed key)
+    let json = r#"{"kid":"kid1","kty":"RSA","alg":"RS256","e":"AQAB","n":"n1","jku":"https://issuer.example/jwks"}"#;
+    let parsed = JWK::try_parse_with_metadata(json, &ParseOptions::strict()).unwrap();
+    assert_eq!(Some("https://issuer.example/jwks"), parsed.jku());
+    assert_eq!(
+        JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "n1")),
+        parsed.jwk
+    );
+
+    // A key without a `jku` should yield none
+    let json = r#"{"kid":"kid1","kty":"RSA","alg":"RS256","e":"AQAB","n":"n1"}"#;
+    let parsed = JWK::try_parse_with_metadata(json, &ParseOptions::strict()).unwrap();
+    assert_eq!(None, parsed.jku());
+}
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use self::{
-    jwk::JWK,
+    error::JwkError,
+    jwk::{alg_to_scheme, parse_json_value, ParseOptions, SignatureScheme, JWK},
+    okp::OKP_JWK,
     rsa::{INSECURE_TEST_RSA_JWK, RSA_JWK, SECURE_TEST_RSA_JWK},
 };
 use crate::{
@@ -11,6 +13,7 @@ use crate::{
 };
 use anyhow::{bail, Context};
 use aptos_crypto_derive::{BCSCryptoHash, CryptoHasher};
+use base64::URL_SAFE_NO_PAD;
 use jwk::JWKMoveStruct;
 use move_core_types::{
     ident_str,
@@ -20,15 +23,26 @@ use move_core_types::{
     value::{MoveStruct, MoveValue},
 };
 use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Formatter},
+    fs::File,
+    io::Read,
+    path::Path,
+    str::FromStr,
 };
 
+pub mod error;
+pub mod index;
 pub mod jwk;
+pub mod okp;
 pub mod patch;
 pub mod rsa;
+pub mod schedule;
 pub mod unsupported;
 
 pub type Issuer = Vec<u8>;
@@ -168,6 +182,299 @@ impl ProviderJWKs {
     }
 }
 
+/// The result of parsing a single provider's JWKS document (see `parse_jwk_set()`).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct JwkSetParse {
+    pub keys: Vec<JWK>,
+    /// True iff the document was valid but contained no keys (i.e., `{"keys": []}`).
+    /// Providers may temporarily serve an empty set (e.g., during key rotation), which
+    /// callers should distinguish from a parse failure ("no keys available").
+    pub was_empty: bool,
+}
+
+/// Parse a single provider's JWKS document (i.e., `{"keys": [...]}`) into its JWKs.
+///
+/// An empty `keys` array is not an error: it yields no keys, with `was_empty` set.
+/// Individual keys that are not supported are returned as `JWK::Unsupported`.
+/// With the revised parsing rules (see `ParseOptions::parsing_v2`), a bare JWK (i.e., an
+/// object with `kty` but no `keys`) is parsed as a set of one key.
+pub fn parse_jwk_set(value: &serde_json::Value) -> Result<JwkSetParse, JwkError> {
+    parse_jwk_set_with_options(value, &ParseOptions::lenient())
+}
+
+/// Parse a single provider's JWKS document (see `parse_jwk_set()`) with the given options.
+/// If `options.reject_unsupported` is set, the whole document is rejected if any of its
+/// keys is not supported (so that unknown key types are never trusted silently).
+///
+/// With the revised parsing rules (see `ParseOptions::parsing_v2`), documents with more
+/// than `options.max_keys` keys are rejected before any key is parsed.
+///
+/// Note: the document is already parsed, so `options.reject_duplicate_members` and
+/// `options.max_bytes` have no effect (see `parse_jwk_set_from_str()` to apply them).
+pub fn parse_jwk_set_with_options(
+    value: &serde_json::Value,
+    options: &ParseOptions,
+) -> Result<JwkSetParse, JwkError> {
+    let keys = jwk_set_entries(value, options)?;
+    Ok(JwkSetParse {
+        keys: keys
+            .iter()
+            .cloned()
+            .map(|key| JWK::try_from_value(key, options))
+            .collect::<Result<_, _>>()?,
+        was_empty: keys.is_empty(),
+    })
+}
+
+/// Parse a single provider's JWKS document (see `parse_jwk_set_with_options()`), but parse
+/// each of its keys independently: the keys that were parsed successfully are returned along
+/// with the index (in the document) and error of each key that failed. This allows the valid
+/// keys of a partially malformed document to be used (while reporting the invalid ones).
+/// Entries that are not JSON objects always fail with `JwkError::MalformedKey`.
+///
+/// Errors with the document itself (e.g., a missing `keys` array) still fail the whole parse.
+pub fn parse_jwk_set_lenient(
+    value: &serde_json::Value,
+    options: &ParseOptions,
+) -> Result<(Vec<JWK>, Vec<(usize, JwkError)>), JwkError> {
+    let mut keys = vec![];
+    let mut errors = vec![];
+    for (index, key) in jwk_set_entries(value, options)?.iter().enumerate() {
+        let result = if key.is_object() {
+            JWK::try_from_value(key.clone(), options)
+        } else {
+            Err(JwkError::MalformedKey("key is not an object".to_string()))
+        };
+        match result {
+            Ok(jwk) => keys.push(jwk),
+            Err(error) => errors.push((index, error)),
+        }
+    }
+    Ok((keys, errors))
+}
+
+/// Returns the key entries of a single provider's JWKS document (without parsing them)
+fn jwk_set_entries<'a>(
+    value: &'a serde_json::Value,
+    options: &ParseOptions,
+) -> Result<&'a [serde_json::Value], JwkError> {
+    let malformed = |reason: &str| JwkError::MalformedDocument(reason.to_string());
+    let document = value
+        .as_object()
+        .ok_or_else(|| malformed("JWKS is not an object"))?;
+
+    // Some noncompliant providers serve a single bare JWK instead of a JWKS. Such a document
+    // is detected by a `kty` member (which every JWK has, but a JWKS doesn't) without a `keys`
+    // member, and is treated as a set of that one key. A document with both is ambiguous.
+    // This changes the observed keys, so it only applies with the revised parsing rules.
+    let kty = document.get("kty").filter(|_| options.parsing_v2);
+    let keys = match (document.get("keys"), kty) {
+        (Some(_), Some(_)) => return Err(malformed("both `keys` and `kty` found")),
+        (Some(keys), None) => keys
+            .as_array()
+            .ok_or_else(|| malformed("field `keys` is not an array"))?
+            .as_slice(),
+        (None, Some(_)) => std::slice::from_ref(value),
+        (None, None) => return Err(malformed("field `keys` not found")),
+    };
+    // The limit changes the observed keys, so it only applies with the revised parsing rules
+    if options.parsing_v2 && keys.len() > options.max_keys {
+        return Err(JwkError::TooManyKeys {
+            num_keys: keys.len(),
+            max_keys: options.max_keys,
+        });
+    }
+    Ok(keys)
+}
+
+/// Parse a single provider's JWKS document from its JSON text (see `parse_jwk_set_with_options()`).
+/// Documents larger than `options.max_bytes` are rejected before they are parsed, and
+/// documents with duplicate members (in the document or any of its keys) are rejected if
+/// `options.reject_duplicate_members` is set.
+pub fn parse_jwk_set_from_str(json: &str, options: &ParseOptions) -> Result<JwkSetParse, JwkError> {
+    if json.len() > options.max_bytes {
+        return Err(JwkError::DocumentTooLarge(options.max_bytes));
+    }
+    let value = parse_json_value(json, options)
+        .map_err(|e| JwkError::MalformedDocument(format!("invalid JSON: {}", e)))?;
+    parse_jwk_set_with_options(&value, options)
+}
+
+/// Load a single provider's JWKS document from the given file (see `parse_jwk_set_from_str()`).
+/// At most `options.max_bytes` bytes are read, so oversized files are rejected without
+/// reading them fully.
+pub fn load_jwk_set_from_file(
+    path: &Path,
+    options: &ParseOptions,
+) -> Result<JwkSetParse, JwkError> {
+    let file =
+        File::open(path).map_err(|e| JwkError::ReadFailed(format!("{}: {}", path.display(), e)))?;
+    let mut json = String::new();
+    file.take(options.max_bytes as u64 + 1)
+        .read_to_string(&mut json)
+        .map_err(|e| JwkError::ReadFailed(format!("{}: {}", path.display(), e)))?;
+    parse_jwk_set_from_str(&json, options)
+}
+
+/// Parse a signed JWKS, i.e., a JWS in compact serialization (`header.payload.signature`)
+/// whose payload is a JWKS document (see `parse_jwk_set()`), as served by some providers.
+///
+/// The JWS signature is verified against the given trust anchor before the payload is
+/// parsed, so a tampered key set is rejected. The trust anchor may be an RSA key, or an
+/// Ed25519 OKP key (which is kept as an unsupported JWK, see `OKP_JWK`).
+pub fn parse_signed_jwks(jws: &str, trust_anchor: &JWK) -> Result<Vec<JWK>, JwkError> {
+    let malformed = JwkError::MalformedDocument;
+    let (signing_input, signature) = jws
+        .rsplit_once('.')
+        .ok_or_else(|| malformed("JWS is not in compact serialization".to_string()))?;
+    let (header, payload) = signing_input
+        .split_once('.')
+        .filter(|(_, payload)| !payload.contains('.'))
+        .ok_or_else(|| malformed("JWS is not in compact serialization".to_string()))?;
+    let decode_json = |name: &str, value: &str| -> Result<serde_json::Value, JwkError> {
+        let bytes = base64::decode_config(value, URL_SAFE_NO_PAD)
+            .map_err(|e| malformed(format!("JWS {} is not valid base64url: {}", name, e)))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| malformed(format!("JWS {} is not valid JSON: {}", name, e)))
+    };
+
+    // Verify the signature (using the `alg` of the protected header)
+    let header = decode_json("header", header)?;
+    let alg = header
+        .get("alg")
+        .and_then(|alg| alg.as_str())
+        .ok_or_else(|| malformed("JWS header has no `alg`".to_string()))?;
+    verify_jws_signature(trust_anchor, alg, signing_input.as_bytes(), signature)?;
+
+    // Only then parse the key set
+    Ok(parse_jwk_set(&decode_json("payload", payload)?)?.keys)
+}
+
+/// Verifies the (base64url encoded) JWS signature over the signing input with the trust anchor
+fn verify_jws_signature(
+    trust_anchor: &JWK,
+    alg: &str,
+    signing_input: &[u8],
+    signature: &str,
+) -> Result<(), JwkError> {
+    let invalid = JwkError::InvalidSignature;
+    match trust_anchor {
+        JWK::RSA(rsa) => {
+            if !RSA_JWK::SUPPORTED_JWT_ALGS.contains(&alg) || !rsa.is_compatible_with_jwt_alg(alg) {
+                return Err(invalid(format!(
+                    "`{}` is not supported by the trust anchor",
+                    alg
+                )));
+            }
+            let algorithm = jsonwebtoken::Algorithm::from_str(alg)
+                .map_err(|e| invalid(format!("unknown `alg` {}: {}", alg, e)))?;
+            let key = jsonwebtoken::DecodingKey::from_rsa_components(&rsa.n, &rsa.e)
+                .map_err(|e| JwkError::RsaKeyEncoding(e.to_string()))?;
+            match jsonwebtoken::crypto::verify(signature, signing_input, &key, algorithm) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(invalid("signature does not match".to_string())),
+                Err(e) => Err(invalid(e.to_string())),
+            }
+        },
+        JWK::Unsupported(unsupported) => {
+            let okp = OKP_JWK::try_from(unsupported)?;
+            if alg_to_scheme(alg) != Some(SignatureScheme::EdDsa) {
+                return Err(invalid(format!(
+                    "`{}` is not supported by the trust anchor",
+                    alg
+                )));
+            }
+            let signature = base64::decode_config(signature, URL_SAFE_NO_PAD)
+                .map_err(|e| invalid(format!("signature is not valid base64url: {}", e)))?;
+            okp.verify_signature(signing_input, &signature)
+        },
+    }
+}
+
+/// Parse a multi-issuer JWKS document into a list of `ProviderJWKs`, one per issuer.
+///
+/// The document is expected to be an object mapping each issuer to its JWKS, e.g.,
+/// `{"https://accounts.google.com": {"keys": [...]}, "https://www.facebook.com": {"keys": [...]}}`.
+/// Each JWKS is parsed with `parse_jwk_set()`. The returned `ProviderJWKs` are sorted by
+/// issuer, and each has version 0 and its JWKs sorted by id, as the JWK observer does.
+///
+/// Note: `serde_json` keeps only the last of several members with the same name, so issuers
+/// that appear more than once in the raw text can only be detected (and rejected) when parsing
+/// the text itself (see `parse_multi_issuer_from_str()`).
+pub fn parse_multi_issuer(value: &serde_json::Value) -> Result<Vec<ProviderJWKs>, JwkError> {
+    let entries = value.as_object().ok_or_else(|| {
+        JwkError::MalformedDocument("top-level value is not an object".to_string())
+    })?;
+    parse_issuer_entries(entries)
+}
+
+/// Parse a multi-issuer JWKS document from its JSON text (see `parse_multi_issuer()`).
+/// Documents in which an issuer appears more than once are rejected, as it is ambiguous
+/// which of its JWKS should be used.
+pub fn parse_multi_issuer_from_str(json: &str) -> Result<Vec<ProviderJWKs>, JwkError> {
+    let entries = serde_json::from_str::<IssuerEntries>(json)
+        .map_err(|e| JwkError::MalformedDocument(format!("invalid JSON: {}", e)))?;
+    parse_issuer_entries(entries.0.iter().map(|(issuer, jwks)| (issuer, jwks)))
+}
+
+/// Parses the JWKS of each issuer, rejecting issuers that appear more than once
+fn parse_issuer_entries<'a>(
+    entries: impl IntoIterator<Item = (&'a String, &'a serde_json::Value)>,
+) -> Result<Vec<ProviderJWKs>, JwkError> {
+    let mut issuers = HashSet::new();
+    let mut providers = vec![];
+    for (issuer, jwks_value) in entries {
+        if !issuers.insert(issuer) {
+            return Err(JwkError::DuplicateIssuer(issuer.clone()));
+        }
+
+        let JwkSetParse { mut keys, .. } =
+            parse_jwk_set(jwks_value).map_err(|error| match error {
+                JwkError::MalformedDocument(reason) => JwkError::MalformedIssuerEntry {
+                    issuer: issuer.clone(),
+                    reason,
+                },
+                error => error,
+            })?;
+        keys.sort();
+        providers.push(ProviderJWKs {
+            issuer: issuer_from_str(issuer),
+            version: 0,
+            jwks: keys.into_iter().map(JWKMoveStruct::from).collect(),
+        });
+    }
+    providers.sort_by(|a, b| a.issuer.cmp(&b.issuer));
+
+    Ok(providers)
+}
+
+/// The members of a multi-issuer JWKS document, in order (including duplicates)
+struct IssuerEntries(Vec<(String, serde_json::Value)>);
+
+impl<'de> Deserialize<'de> for IssuerEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IssuerEntriesVisitor;
+
+        impl<'de> Visitor<'de> for IssuerEntriesVisitor {
+            type Value = IssuerEntries;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a JSON object mapping each issuer to its JWKS")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = vec![];
+                while let Some(entry) = map.next_entry::<String, serde_json::Value>()? {
+                    entries.push(entry);
+                }
+                Ok(IssuerEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(IssuerEntriesVisitor)
+    }
+}
+
 impl AsMoveValue for ProviderJWKs {
     fn as_move_value(&self) -> MoveValue {
         MoveValue::Struct(MoveStruct::Runtime(vec![
@@ -177,6 +484,58 @@ impl AsMoveValue for ProviderJWKs {
         ]))
     }
 }
+
+/// A `ProviderJWKs` tagged with the epoch at which it became active.
+///
+/// `ProviderJWKs` mirrors the on-chain Move struct, so it cannot carry the epoch itself.
+/// Callers that retain key sets across epochs (e.g., to verify a token signed under the
+/// key set that was active at a past epoch) should wrap them in this type instead.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EpochScopedProviderJWKs {
+    pub epoch: u64,
+    pub provider_jwks: ProviderJWKs,
+}
+
+impl EpochScopedProviderJWKs {
+    pub fn new(epoch: u64, provider_jwks: ProviderJWKs) -> Self {
+        Self {
+            epoch,
+            provider_jwks,
+        }
+    }
+
+    /// Returns true iff the key set was active at (or before) the given epoch.
+    /// Key sets from future epochs are never valid.
+    pub fn valid_for_epoch(&self, epoch: u64) -> bool {
+        self.epoch <= epoch
+    }
+
+    /// Returns the key set to use at the given epoch, i.e., the one with the
+    /// highest epoch that is not after the given epoch (if any).
+    pub fn select_for_epoch<'a>(
+        candidates: impl IntoIterator<Item = &'a EpochScopedProviderJWKs>,
+        epoch: u64,
+    ) -> Option<&'a EpochScopedProviderJWKs> {
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.valid_for_epoch(epoch))
+            .max_by_key(|candidate| candidate.epoch)
+    }
+
+    /// Same as `select_for_epoch()`, but only considers the key sets of the given issuer.
+    pub fn select_for_issuer_and_epoch<'a>(
+        candidates: impl IntoIterator<Item = &'a EpochScopedProviderJWKs>,
+        issuer: &[u8],
+        epoch: u64,
+    ) -> Option<&'a EpochScopedProviderJWKs> {
+        Self::select_for_epoch(
+            candidates
+                .into_iter()
+                .filter(|candidate| candidate.provider_jwks.issuer == issuer),
+            epoch,
+        )
+    }
+}
 /// Move type `0x1::jwks::JWKs` in rust.
 /// See its doc in Move for more details.
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
@@ -273,3 +632,6 @@ impl MoveStructType for ObservedJWKsUpdated {
 
 pub static OBSERVED_JWK_UPDATED_MOVE_TYPE_TAG: Lazy<TypeTag> =
     Lazy::new(|| TypeTag::Struct(Box::new(ObservedJWKsUpdated::struct_tag())));
+
+#[cfg(test)]
+mod tests;
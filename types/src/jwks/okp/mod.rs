@@ -0,0 +1,195 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::jwks::{error::JwkError, jwk::normalize_alg, unsupported::UnsupportedJWK};
+use aptos_crypto::{
+    ed25519::{Ed25519PublicKey, Ed25519Signature},
+    Signature,
+};
+use base64::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+
+/// An Octet Key Pair (OKP) JWK, as defined in RFC 8037 (e.g., an Ed25519 key).
+/// X25519 keys are also recognized, but they are only used for key agreement (i.e.,
+/// ECDH), so they can never verify signatures (see `is_encryption_key()`).
+///
+/// Note: there is no Move counterpart of this type, so OKP keys are kept on chain
+/// as `UnsupportedJWK`s. Use `TryFrom<&UnsupportedJWK>` to recover the key.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OKP_JWK {
+    pub kid: String,
+    pub kty: String,
+    pub alg: String,
+    pub crv: String,
+    pub x: String,
+}
+
+impl OKP_JWK {
+    /// The JWE key agreement algorithms that X25519 keys may be used with.
+    pub const ECDH_ALGS: &'static [&'static str] = &[
+        "ECDH-ES",
+        "ECDH-ES+A128KW",
+        "ECDH-ES+A192KW",
+        "ECDH-ES+A256KW",
+    ];
+    /// The curve of Ed25519 keys (the only signature curve supported for OKP JWKs).
+    pub const ED25519_CURVE: &'static str = "Ed25519";
+    /// The JWS algorithm of Ed25519 keys.
+    pub const EDDSA_ALG: &'static str = "EdDSA";
+    /// The curve of X25519 keys (which are only used for key agreement).
+    pub const X25519_CURVE: &'static str = "X25519";
+    /// The length (in bytes) of X25519 public keys.
+    const X25519_KEY_LENGTH: usize = 32;
+
+    /// Returns true iff this is a key agreement (i.e., X25519) key
+    pub fn is_encryption_key(&self) -> bool {
+        self.crv == Self::X25519_CURVE
+    }
+
+    /// Returns true iff this key may be selected to verify signatures
+    pub fn can_verify_signatures(&self) -> bool {
+        self.crv == Self::ED25519_CURVE && (self.alg.is_empty() || self.alg == Self::EDDSA_ALG)
+    }
+
+    /// Verifies the signature over the message with this key. Keys with an `alg`
+    /// of `EdDSA` (or without an `alg`) on the Ed25519 curve are verified as
+    /// Ed25519 signatures. Any other key is rejected.
+    pub fn verify_signature(&self, message: &[u8], signature: &[u8]) -> Result<(), JwkError> {
+        let invalid = JwkError::InvalidOkpKey;
+        if self.is_encryption_key() {
+            return Err(invalid(
+                "X25519 keys are only used for key agreement, not signatures".to_string(),
+            ));
+        }
+        if !self.alg.is_empty() && self.alg != Self::EDDSA_ALG {
+            return Err(invalid(format!("unsupported alg `{}`", self.alg)));
+        }
+        if self.crv != Self::ED25519_CURVE {
+            return Err(invalid(format!("unsupported curve `{}`", self.crv)));
+        }
+
+        let public_key = base64::decode_config(&self.x, URL_SAFE_NO_PAD)
+            .map_err(|e| invalid(format!("field `x` is not valid base64url: {}", e)))?;
+        let public_key = Ed25519PublicKey::try_from(public_key.as_slice())
+            .map_err(|e| invalid(format!("field `x` is not an Ed25519 key: {}", e)))?;
+        let signature = Ed25519Signature::try_from(signature)
+            .map_err(|e| JwkError::InvalidSignature(e.to_string()))?;
+        signature
+            .verify_arbitrary_msg(message, &public_key)
+            .map_err(|e| JwkError::InvalidSignature(e.to_string()))
+    }
+
+    /// Parses an X25519 key. The key may only have a key agreement `alg` (if any), must
+    /// not claim to be for signatures (via `use`), and `x` must decode to a 32-byte key.
+    fn try_from_x25519_json(
+        json_value: &serde_json::Value,
+        kty: String,
+        crv: String,
+    ) -> Result<Self, JwkError> {
+        let invalid = JwkError::InvalidOkpKey;
+        let string_field = |name: &str| -> Result<Option<String>, JwkError> {
+            json_value
+                .get(name)
+                .map(|value| {
+                    value
+                        .as_str()
+                        .map(|value| value.to_string())
+                        .ok_or_else(|| invalid(format!("field `{}` is not a string", name)))
+                })
+                .transpose()
+        };
+        let required_field = |name: &str| -> Result<String, JwkError> {
+            string_field(name)?.ok_or_else(|| invalid(format!("field `{}` not found", name)))
+        };
+
+        let alg = string_field("alg")?.unwrap_or_default();
+        if !alg.is_empty() && !Self::ECDH_ALGS.contains(&alg.as_str()) {
+            return Err(invalid(format!("unsupported alg `{}` for X25519", alg)));
+        }
+        if string_field("use")?.as_deref() == Some("sig") {
+            return Err(invalid(
+                "X25519 keys cannot be used for signatures".to_string(),
+            ));
+        }
+
+        let x = required_field("x")?;
+        let public_key = base64::decode_config(&x, URL_SAFE_NO_PAD)
+            .map_err(|e| invalid(format!("field `x` is not valid base64url: {}", e)))?;
+        if public_key.len() != Self::X25519_KEY_LENGTH {
+            return Err(invalid(format!(
+                "field `x` is not an X25519 key: expected {} bytes, found {}",
+                Self::X25519_KEY_LENGTH,
+                public_key.len()
+            )));
+        }
+
+        Ok(Self {
+            kid: required_field("kid")?,
+            kty,
+            alg,
+            crv,
+            x,
+        })
+    }
+}
+
+impl TryFrom<&serde_json::Value> for OKP_JWK {
+    type Error = JwkError;
+
+    fn try_from(json_value: &serde_json::Value) -> Result<Self, Self::Error> {
+        let invalid = JwkError::InvalidOkpKey;
+        let string_field = |name: &str| -> Result<String, JwkError> {
+            json_value
+                .get(name)
+                .ok_or_else(|| invalid(format!("field `{}` not found", name)))?
+                .as_str()
+                .ok_or_else(|| invalid(format!("field `{}` is not a string", name)))
+                .map(|value| value.to_string())
+        };
+
+        let kty = string_field("kty")?;
+        if kty != "OKP" {
+            return Err(invalid(format!("incorrect kty `{}`", kty)));
+        }
+
+        // X25519 keys are only used for key agreement, so they are validated separately
+        let crv = string_field("crv")?;
+        if crv == Self::X25519_CURVE {
+            return Self::try_from_x25519_json(json_value, kty, crv);
+        }
+
+        // Only `EdDSA` is recognized for OKP keys (in its canonical form)
+        let alg = match json_value.get("alg") {
+            Some(_) => {
+                let alg = string_field("alg")?;
+                match normalize_alg(&alg) {
+                    Some(alg) if alg == Self::EDDSA_ALG => alg,
+                    _ => return Err(invalid(format!("unsupported alg `{}`", alg))),
+                }
+            },
+            None => String::new(),
+        };
+
+        Ok(Self {
+            kid: string_field("kid")?,
+            kty,
+            alg,
+            crv,
+            x: string_field("x")?,
+        })
+    }
+}
+
+impl TryFrom<&UnsupportedJWK> for OKP_JWK {
+    type Error = JwkError;
+
+    fn try_from(unsupported: &UnsupportedJWK) -> Result<Self, Self::Error> {
+        let json_value: serde_json::Value = serde_json::from_slice(&unsupported.payload)
+            .map_err(|e| JwkError::InvalidOkpKey(e.to_string()))?;
+        Self::try_from(&json_value)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,179 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::jwks::{
+    error::JwkError, jwk::JWK, okp::OKP_JWK, parse_signed_jwks, rsa::RSA_JWK,
+    unsupported::UnsupportedJWK,
+};
+use aptos_crypto::{ed25519::Ed25519PrivateKey, PrivateKey, SigningKey, Uniform};
+use base64::URL_SAFE_NO_PAD;
+use serde_json::json;
+
+/// Returns a test Ed25519 private key, and the OKP JWK (as JSON) of its public key
+fn create_ed25519_key_and_jwk() -> (Ed25519PrivateKey, serde_json::Value) {
+    let private_key = Ed25519PrivateKey::generate_for_testing();
+    let x = base64::encode_config(private_key.public_key().to_bytes(), URL_SAFE_NO_PAD);
+    let jwk_json = json!({"kid": "kid1", "kty": "OKP", "alg": "EdDSA", "crv": "Ed25519", "x": x});
+    (private_key, jwk_json)
+}
+
+#[test]
+fn parse_eddsa_okp_jwk() {
+    // The `alg` should be recognized and preserved (in its canonical form)
+    let (_, mut jwk_json) = create_ed25519_key_and_jwk();
+    let okp_jwk = OKP_JWK::try_from(&jwk_json).unwrap();
+    assert_eq!("EdDSA", okp_jwk.alg);
+    assert_eq!("Ed25519", okp_jwk.crv);
+    jwk_json["alg"] = json!("eddsa");
+    assert_eq!(okp_jwk, OKP_JWK::try_from(&jwk_json).unwrap());
+
+    // OKP keys are kept as unsupported JWKs (as there is no Move type for them)
+    let okp_jwk_from_unsupported = match JWK::from(jwk_json.clone()) {
+        JWK::Unsupported(unsupported) => OKP_JWK::try_from(&unsupported).unwrap(),
+        jwk => panic!("Expected an unsupported JWK, found: {:?}", jwk),
+    };
+    assert_eq!(okp_jwk, okp_jwk_from_unsupported);
+
+    // Any other `alg` should be rejected
+    jwk_json["alg"] = json!("RS256");
+    assert!(matches!(
+        OKP_JWK::try_from(&jwk_json),
+        Err(JwkError::InvalidOkpKey(_))
+    ));
+
+    // Non-OKP keys should be rejected
+    let unsupported =
+        UnsupportedJWK::from(json!({"kid": "kid1", "kty": "RSA", "e": "AQAB", "n": "n1"}));
+    assert!(OKP_JWK::try_from(&unsupported).is_err());
+}
+
+#[test]
+fn reject_eddsa_on_rsa_jwk() {
+    // An RSA key claiming `EdDSA` should be flagged as unsupported
+    let jwk_json = json!({"kid": "kid1", "kty": "RSA", "alg": "EdDSA", "e": "AQAB", "n": "n1"});
+    assert!(matches!(JWK::from(jwk_json), JWK::Unsupported(_)));
+
+    // An RSA key with an RSA `alg` should still be supported
+    let jwk_json = json!({"kid": "kid1", "kty": "RSA", "alg": "RS256", "e": "AQAB", "n": "n1"});
+    assert_eq!(
+        JWK::RSA(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "n1")),
+        JWK::from(jwk_json)
+    );
+}
+
+#[test]
+fn verify_eddsa_okp_jwk_signature() {
+    // Sign a message with the private key, and verify it with the OKP key
+    let (private_key, jwk_json) = create_ed25519_key_and_jwk();
+    let okp_jwk = OKP_JWK::try_from(&jwk_json).unwrap();
+    let message = b"header.payload";
+    let signature = private_key.sign_arbitrary_message(message).to_bytes();
+    okp_jwk.verify_signature(message, &signature).unwrap();
+
+    // Verify that a signature over another message is rejected
+    assert!(matches!(
+        okp_jwk.verify_signature(b"header.other_payload", &signature),
+        Err(JwkError::InvalidSignature(_))
+    ));
+
+    // Verify that a key on another curve is rejected
+    let mut okp_jwk = okp_jwk;
+    okp_jwk.crv = "X25519".to_string();
+    assert!(matches!(
+        okp_jwk.verify_signature(message, &signature),
+        Err(JwkError::InvalidOkpKey(_))
+    ));
+}
+
+/// Returns the OKP JWK (as JSON) of a test X25519 key
+fn create_x25519_jwk() -> serde_json::Value {
+    let x = base64::encode_config([7u8; 32], URL_SAFE_NO_PAD);
+    json!({"kid": "kid1", "kty": "OKP", "alg": "ECDH-ES", "crv": "X25519", "x": x, "use": "enc"})
+}
+
+#[test]
+fn parse_x25519_okp_jwk() {
+    // The key should be recognized as a key agreement key
+    let mut jwk_json = create_x25519_jwk();
+    let okp_jwk = OKP_JWK::try_from(&jwk_json).unwrap();
+    assert_eq!("X25519", okp_jwk.crv);
+    assert_eq!("ECDH-ES", okp_jwk.alg);
+    assert!(okp_jwk.is_encryption_key());
+    assert!(!okp_jwk.can_verify_signatures());
+
+    // The key should also be recoverable from its unsupported JWK
+    let okp_jwk_from_unsupported = match JWK::from(jwk_json.clone()) {
+        JWK::Unsupported(unsupported) => OKP_JWK::try_from(&unsupported).unwrap(),
+        jwk => panic!("Expected an unsupported JWK, found: {:?}", jwk),
+    };
+    assert_eq!(okp_jwk, okp_jwk_from_unsupported);
+
+    // A key without an `alg` should also be accepted
+    jwk_json.as_object_mut().unwrap().remove("alg");
+    assert!(OKP_JWK::try_from(&jwk_json).unwrap().alg.is_empty());
+
+    // Keys claiming to be for signatures should be rejected
+    for (field, value) in [("alg", "EdDSA"), ("use", "sig")] {
+        let mut jwk_json = create_x25519_jwk();
+        jwk_json[field] = json!(value);
+        assert!(matches!(
+            OKP_JWK::try_from(&jwk_json),
+            Err(JwkError::InvalidOkpKey(_))
+        ));
+    }
+
+    // Keys that don't decode to 32 bytes should be rejected
+    for x in [
+        base64::encode_config([7u8; 31], URL_SAFE_NO_PAD),
+        base64::encode_config([7u8; 33], URL_SAFE_NO_PAD),
+        "not base64!".to_string(),
+    ] {
+        let mut jwk_json = create_x25519_jwk();
+        jwk_json["x"] = json!(x);
+        assert!(matches!(
+            OKP_JWK::try_from(&jwk_json),
+            Err(JwkError::InvalidOkpKey(_))
+        ));
+    }
+}
+
+#[test]
+fn x25519_okp_jwk_never_verifies_signatures() {
+    // The Ed25519 key should be selectable for verification
+    let (private_key, jwk_json) = create_ed25519_key_and_jwk();
+    assert!(OKP_JWK::try_from(&jwk_json)
+        .unwrap()
+        .can_verify_signatures());
+
+    // Even with the same key material, the X25519 key should never verify signatures
+    let mut x25519_jwk_json = create_x25519_jwk();
+    x25519_jwk_json["x"] = jwk_json["x"].clone();
+    let x25519_jwk = OKP_JWK::try_from(&x25519_jwk_json).unwrap();
+    let message = b"header.payload";
+    let signature = private_key.sign_arbitrary_message(message).to_bytes();
+    assert!(matches!(
+        x25519_jwk.verify_signature(message, &signature),
+        Err(JwkError::InvalidOkpKey(_))
+    ));
+
+    // The X25519 key should not be usable as the trust anchor of a signed key set
+    let encode_json = |value: &serde_json::Value| {
+        base64::encode_config(value.to_string().as_bytes(), URL_SAFE_NO_PAD)
+    };
+    let signing_input = format!(
+        "{}.{}",
+        encode_json(&json!({"alg": "EdDSA"})),
+        encode_json(&json!({"keys": []}))
+    );
+    let signature = private_key.sign_arbitrary_message(signing_input.as_bytes());
+    let jws = format!(
+        "{}.{}",
+        signing_input,
+        base64::encode_config(signature.to_bytes(), URL_SAFE_NO_PAD)
+    );
+    assert!(parse_signed_jwks(&jws, &JWK::from(jwk_json)).is_ok());
+    assert!(matches!(
+        parse_signed_jwks(&jws, &JWK::from(x25519_jwk_json)),
+        Err(JwkError::InvalidOkpKey(_))
+    ));
+}
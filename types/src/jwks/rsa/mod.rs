@@ -1,7 +1,10 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{keyless::Claims, move_any::AsMoveAny, move_utils::as_move_value::AsMoveValue};
+use crate::{
+    jwks::error::JwkError, keyless::Claims, move_any::AsMoveAny,
+    move_utils::as_move_value::AsMoveValue,
+};
 use anyhow::{anyhow, bail, ensure, Result};
 use aptos_crypto::poseidon_bn254;
 use base64::URL_SAFE_NO_PAD;
@@ -10,7 +13,15 @@ use move_core_types::value::{MoveStruct, MoveValue};
 use once_cell::sync::Lazy;
 use poem_openapi_derive::Object;
 use ring::signature::RsaKeyPair;
-use rsa::{pkcs1::EncodeRsaPrivateKey, pkcs8::DecodePrivateKey};
+use rsa::{
+    pkcs1::EncodeRsaPrivateKey,
+    pkcs8::{
+        der::{asn1::AnyRef, Decode, Reader, SliceReader, Tag, TagNumber},
+        DecodePrivateKey, EncodePublicKey, SubjectPublicKeyInfoRef,
+    },
+    traits::PublicKeyParts,
+    BigUint, RsaPublicKey,
+};
 use serde::{Deserialize, Serialize};
 /// Move type `0x1::jwks::RSA_JWK` in rust.
 /// See its doc in Move for more details.
@@ -52,6 +63,8 @@ pub static INSECURE_TEST_RSA_KEY_PAIR: Lazy<RsaKeyPair> = Lazy::new(|| {
 impl RSA_JWK {
     /// The circuit-supported RSA modulus size.
     pub const RSA_MODULUS_BYTES: usize = 256;
+    /// The JWT signature algorithms allowed for RSA JWKs (see `verify_signature_without_exp_check`).
+    pub const SUPPORTED_JWT_ALGS: &'static [&'static str] = &["RS256"];
 
     /// Make an `RSA_JWK` from `kty="RSA", alg="RS256", e="AQAB"` (a popular setting)
     /// and caller-specified `kid` and `n`.
@@ -86,6 +99,25 @@ impl RSA_JWK {
         }
     }
 
+    /// Generates a fresh RSA key pair with a modulus of `bits` bits, and returns its JWK
+    /// (with `alg="RS256"` and a `kid` derived from the modulus) along with the private key.
+    /// Unlike the fixed test JWKs above, the private key is available, so tests can sign
+    /// JWTs that verify against the JWK (e.g., via `verify_signature_without_exp_check`).
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub fn generate_for_testing(bits: usize) -> (RSA_JWK, rsa::RsaPrivateKey) {
+        let private_key = rsa::RsaPrivateKey::new(&mut rsa::rand_core::OsRng, bits)
+            .expect("Failed to generate the RSA private key");
+        let modulus = private_key.n().to_bytes_be();
+        let jwk = RSA_JWK {
+            kid: format!("test-rsa-{}", hex::encode(&modulus[..8])),
+            kty: "RSA".to_string(),
+            alg: "RS256".to_string(),
+            e: base64::encode_config(private_key.e().to_bytes_be(), URL_SAFE_NO_PAD),
+            n: base64::encode_config(&modulus, URL_SAFE_NO_PAD),
+        };
+        (jwk, private_key)
+    }
+
     pub fn verify_signature_without_exp_check(&self, jwt_token: &str) -> Result<TokenData<Claims>> {
         let mut validation = Validation::new(Algorithm::RS256);
         validation.validate_exp = false;
@@ -94,10 +126,84 @@ impl RSA_JWK {
         Ok(claims)
     }
 
+    /// Parses an RSA JWK from its JSON form (see `TryFrom<&serde_json::Value>`), applying
+    /// the revised parsing rules (see `ParseOptions::parsing_v2`): a numeric `kid` is
+    /// coerced to its string form, a missing `alg` is left empty, keys that cannot
+    /// verify signatures (see `can_verify()`) are rejected, and the modulus is
+    /// canonicalized (see `canonicalize()`).
+    pub fn try_from_json_v2(json_value: &serde_json::Value) -> Result<Self> {
+        parse_json(json_value, true)
+    }
+
     pub fn id(&self) -> Vec<u8> {
         self.kid.as_bytes().to_vec()
     }
 
+    /// Returns true iff this key can be used to verify a JWT signed with the given `alg`
+    /// (from the JWT header). A key without an `alg` is compatible with any supported RSA
+    /// signature algorithm, while a key with an `alg` must match the JWT's `alg` exactly.
+    pub fn is_compatible_with_jwt_alg(&self, jwt_alg: &str) -> bool {
+        if self.alg.is_empty() {
+            Self::SUPPORTED_JWT_ALGS.contains(&jwt_alg)
+        } else {
+            self.alg == jwt_alg
+        }
+    }
+
+    /// Returns the decoded modulus in its canonical form: the big-endian octet
+    /// string produced by I2OSP (RFC 8017), without a leading zero byte. Some
+    /// encoders prepend a single zero byte to the modulus (e.g., so that it reads
+    /// as a positive signed integer), which is stripped here so that equivalent
+    /// keys have identical modulus bytes and bit lengths.
+    pub fn canonical_modulus(&self) -> Result<Vec<u8>, JwkError> {
+        let mut modulus = decode_field("n", &self.n)?;
+        if modulus.len() > 1 && modulus[0] == 0 {
+            modulus.remove(0);
+        }
+        Ok(modulus)
+    }
+
+    /// Returns the bit length of the (canonical) modulus.
+    pub fn modulus_bits(&self) -> Result<usize, JwkError> {
+        let modulus = self.canonical_modulus()?;
+        Ok(BigUint::from_bytes_be(&modulus).bits())
+    }
+
+    /// Returns a copy of this key with `n` re-encoded from its canonical form
+    /// (see `canonical_modulus()`), so that keys which only differ by a leading
+    /// zero byte in the modulus compare equal.
+    pub fn canonicalize(&self) -> Result<Self, JwkError> {
+        Ok(Self {
+            n: base64::encode_config(self.canonical_modulus()?, URL_SAFE_NO_PAD),
+            ..self.clone()
+        })
+    }
+
+    /// Returns the public key encoded as a DER SubjectPublicKeyInfo (e.g., for
+    /// verification libraries that only accept DER or PEM keys).
+    pub fn to_spki_der(&self) -> Result<Vec<u8>, JwkError> {
+        let public_key = RsaPublicKey::new(
+            BigUint::from_bytes_be(&self.canonical_modulus()?),
+            BigUint::from_bytes_be(&decode_field("e", &self.e)?),
+        )
+        .map_err(|e| JwkError::RsaKeyEncoding(e.to_string()))?;
+        let document = public_key
+            .to_public_key_der()
+            .map_err(|e| JwkError::RsaKeyEncoding(e.to_string()))?;
+        Ok(document.as_bytes().to_vec())
+    }
+
+    /// Returns true iff the public key embedded in the given DER-encoded X.509 certificate
+    /// is this key (i.e., it has the same modulus and exponent). This allows a key to be
+    /// checked against an independently obtained certificate. Fails if the certificate
+    /// cannot be parsed, or if it does not hold an RSA key.
+    pub fn matches_certificate(&self, cert_der: &[u8]) -> Result<bool, JwkError> {
+        let certificate_key = certificate_public_key(cert_der)?;
+        let modulus = BigUint::from_bytes_be(&self.canonical_modulus()?);
+        let exponent = BigUint::from_bytes_be(&decode_field("e", &self.e)?);
+        Ok(certificate_key.n() == &modulus && certificate_key.e() == &exponent)
+    }
+
     // TODO(keyless): Move this to aptos-crypto so other services can use this
     pub fn to_poseidon_scalar(&self) -> Result<ark_bn254::Fr> {
         let mut modulus = base64::decode_config(&self.n, URL_SAFE_NO_PAD)?;
@@ -125,6 +231,55 @@ impl RSA_JWK {
     }
 }
 
+/// Decodes the given base64url-encoded key field (e.g., `n` or `e`). On failure,
+/// the error names the field and carries the decode error (which includes the
+/// offset of the offending byte, if any).
+fn decode_field(field: &str, value: &str) -> Result<Vec<u8>, JwkError> {
+    base64::decode_config(value, URL_SAFE_NO_PAD).map_err(|e| JwkError::InvalidRsaField {
+        field: field.to_string(),
+        detail: e.to_string(),
+    })
+}
+
+/// Extracts the RSA public key from the given DER-encoded X.509 certificate
+fn certificate_public_key(cert_der: &[u8]) -> Result<RsaPublicKey, JwkError> {
+    let subject_public_key_info = SliceReader::new(cert_der)
+        .and_then(|mut reader| {
+            // Certificate ::= SEQUENCE { tbsCertificate, signatureAlgorithm, signatureValue }
+            let subject_public_key_info = reader.sequence(|certificate| {
+                let subject_public_key_info = certificate.sequence(|tbs_certificate| {
+                    // TBSCertificate ::= SEQUENCE { [0] version OPTIONAL, serialNumber, signature,
+                    //     issuer, validity, subject, subjectPublicKeyInfo, extensions, ... }
+                    let version_tag = Tag::ContextSpecific {
+                        constructed: true,
+                        number: TagNumber::N0,
+                    };
+                    if tbs_certificate.peek_tag()? == version_tag {
+                        AnyRef::decode(tbs_certificate)?;
+                    }
+                    for _ in 0..5 {
+                        // Skip the serialNumber, signature, issuer, validity and subject
+                        AnyRef::decode(tbs_certificate)?;
+                    }
+                    let subject_public_key_info = SubjectPublicKeyInfoRef::decode(tbs_certificate)?;
+                    while !tbs_certificate.is_finished() {
+                        AnyRef::decode(tbs_certificate)?;
+                    }
+                    Ok(subject_public_key_info)
+                })?;
+                AnyRef::decode(certificate)?; // signatureAlgorithm
+                AnyRef::decode(certificate)?; // signatureValue
+                Ok(subject_public_key_info)
+            })?;
+            reader.finish(subject_public_key_info)
+        })
+        .map_err(|e| JwkError::InvalidCertificate(e.to_string()))?;
+
+    RsaPublicKey::try_from(subject_public_key_info).map_err(|e| {
+        JwkError::InvalidCertificate(format!("the certificate key is not an RSA key: {}", e))
+    })
+}
+
 impl AsMoveAny for RSA_JWK {
     const MOVE_TYPE_NAME: &'static str = "0x1::jwks::RSA_JWK";
 }
@@ -132,48 +287,121 @@ impl AsMoveAny for RSA_JWK {
 impl TryFrom<&serde_json::Value> for RSA_JWK {
     type Error = anyhow::Error;
 
+    /// Note: this is used by the JWK observers of all validators, which must agree on the
+    /// parsed keys, so it must not change. See `RSA_JWK::try_from_json_v2()` for the
+    /// revised parsing rules.
     fn try_from(json_value: &serde_json::Value) -> Result<Self, Self::Error> {
-        let kty = json_value
-            .get("kty")
-            .ok_or_else(|| anyhow!("Field `kty` not found"))?
-            .as_str()
-            .ok_or_else(|| anyhow!("Field `kty` is not a string"))?
-            .to_string();
+        parse_json(json_value, false)
+    }
+}
 
-        ensure!(
-            kty.as_str() == "RSA",
-            "json to rsa jwk conversion failed with incorrect kty"
-        );
+/// Parses an RSA JWK from its JSON form. If `parsing_v2` is set, the revised parsing
+/// rules are applied (see `RSA_JWK::try_from_json_v2()`).
+fn parse_json(json_value: &serde_json::Value, parsing_v2: bool) -> Result<RSA_JWK> {
+    let kty = json_value
+        .get("kty")
+        .ok_or_else(|| anyhow!("Field `kty` not found"))?
+        .as_str()
+        .ok_or_else(|| anyhow!("Field `kty` is not a string"))?
+        .to_string();
 
-        let ret = Self {
-            kty,
-            kid: json_value
-                .get("kid")
-                .ok_or_else(|| anyhow!("Field `kid` not found"))?
-                .as_str()
-                .ok_or_else(|| anyhow!("Field `kid` is not a string"))?
-                .to_string(),
-            alg: json_value
-                .get("alg")
-                .ok_or_else(|| anyhow!("Field `alg` not found"))?
+    ensure!(
+        kty.as_str() == "RSA",
+        "json to rsa jwk conversion failed with incorrect kty"
+    );
+
+    let ret = RSA_JWK {
+        kty,
+        // Some malformed providers emit a numeric `kid`, which is coerced to its string form
+        // (e.g., `"kid": 12345` becomes `"12345"`) under the revised parsing rules. Any
+        // other non-string `kid` is rejected.
+        kid: match json_value
+            .get("kid")
+            .ok_or_else(|| anyhow!("Field `kid` not found"))?
+        {
+            serde_json::Value::String(kid) => kid.clone(),
+            serde_json::Value::Number(kid) if parsing_v2 => kid.to_string(),
+            _ => bail!("Field `kid` is not a string"),
+        },
+        // Providers often omit `alg`, in which case it is left empty (i.e., unspecified) under
+        // the revised parsing rules. See `is_compatible_with_jwt_alg()` for how such keys are
+        // matched against JWTs.
+        alg: match json_value.get("alg") {
+            Some(alg) => alg
                 .as_str()
                 .ok_or_else(|| anyhow!("Field `alg` is not a string"))?
                 .to_string(),
-            e: json_value
-                .get("e")
-                .ok_or_else(|| anyhow!("Field `e` not found"))?
-                .as_str()
-                .ok_or_else(|| anyhow!("Field `e` is not a string"))?
-                .to_string(),
-            n: json_value
-                .get("n")
-                .ok_or_else(|| anyhow!("Field `n` not found"))?
+            None if parsing_v2 => String::new(),
+            None => bail!("Field `alg` not found"),
+        },
+        e: json_value
+            .get("e")
+            .ok_or_else(|| anyhow!("Field `e` not found"))?
+            .as_str()
+            .ok_or_else(|| anyhow!("Field `e` is not a string"))?
+            .to_string(),
+        n: json_value
+            .get("n")
+            .ok_or_else(|| anyhow!("Field `n` not found"))?
+            .as_str()
+            .ok_or_else(|| anyhow!("Field `n` is not a string"))?
+            .to_string(),
+    };
+
+    if parsing_v2 {
+        ensure!(
+            can_verify(json_value)?,
+            "json to rsa jwk conversion failed with a key that cannot verify signatures"
+        );
+
+        // Re-encode the modulus in its canonical form (see `RSA_JWK::canonicalize()`), so that
+        // keys which only differ by a leading zero byte in the modulus compare equal. Moduli
+        // that can't be decoded are kept as is (such keys fail to verify any signature).
+        return Ok(ret.canonicalize().unwrap_or(ret));
+    }
+
+    Ok(ret)
+}
+
+/// Returns true iff the key can be used to verify signatures. Providers signal this with
+/// either `use` (i.e., `"sig"`) or `key_ops` (i.e., containing `"verify"`), so either one
+/// is accepted. Keys with neither are assumed to be usable (both fields are optional).
+/// Per RFC 7517, `use` and `key_ops` should not both be present with conflicting semantics,
+/// so keys where they disagree are rejected. This is only checked under the revised parsing
+/// rules (see `RSA_JWK::try_from_json_v2()`).
+fn can_verify(json_value: &serde_json::Value) -> Result<bool> {
+    let key_use = json_value
+        .get("use")
+        .map(|key_use| {
+            key_use
                 .as_str()
-                .ok_or_else(|| anyhow!("Field `n` is not a string"))?
-                .to_string(),
-        };
+                .ok_or_else(|| anyhow!("Field `use` is not a string"))
+        })
+        .transpose()?;
+    let key_ops = json_value
+        .get("key_ops")
+        .map(|key_ops| {
+            key_ops
+                .as_array()
+                .ok_or_else(|| anyhow!("Field `key_ops` is not an array"))
+        })
+        .transpose()?;
 
-        Ok(ret)
+    let contains_verify =
+        |key_ops: &Vec<serde_json::Value>| key_ops.iter().any(|op| op.as_str() == Some("verify"));
+    match (key_use, key_ops) {
+        (Some(key_use), Some(key_ops)) => {
+            ensure!(
+                (key_use == "sig") == contains_verify(key_ops),
+                "json to rsa jwk conversion failed with conflicting `use` ({}) and `key_ops` ({:?})",
+                key_use,
+                key_ops
+            );
+            Ok(key_use == "sig")
+        },
+        (Some(key_use), None) => Ok(key_use == "sig"),
+        (None, Some(key_ops)) => Ok(contains_verify(key_ops)),
+        (None, None) => Ok(true),
     }
 }
 
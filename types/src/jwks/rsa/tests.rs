@@ -2,10 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    jwks::rsa::RSA_JWK,
+    jwks::{error::JwkError, insecure_test_rsa_jwk, rsa::RSA_JWK, secure_test_rsa_jwk},
     move_any::{Any as MoveAny, AsMoveAny},
     move_utils::as_move_value::AsMoveValue,
 };
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rsa::pkcs1::EncodeRsaPrivateKey;
 use std::str::FromStr;
 
 #[test]
@@ -23,16 +25,29 @@ fn convert_json_to_rsa_jwk() {
     let json = serde_json::Value::from_str(json_str).unwrap();
     assert!(RSA_JWK::try_from(&json).is_err());
 
+    // JWK JSON with a numeric `kid` should be rejected, unless the revised parsing rules
+    // are applied (in which case the `kid` is coerced to a string).
+    let json_str =
+        r#"{"alg": "RS256", "kid": 12345, "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(RSA_JWK::try_from(&json).is_err());
+    let expected = RSA_JWK::new_from_strs("12345", "RSA", "RS256", "AQAB", "13131");
+    assert_eq!(expected, RSA_JWK::try_from_json_v2(&json).unwrap());
+
     // JWK JSON with wrong `kid` type should be rejected.
     let json_str =
         r#"{"alg": "RS256", "kid": {}, "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
     let json = serde_json::Value::from_str(json_str).unwrap();
     assert!(RSA_JWK::try_from(&json).is_err());
+    assert!(RSA_JWK::try_from_json_v2(&json).is_err());
 
-    // JWK JSON without `alg` should be rejected.
+    // JWK JSON without `alg` should be rejected, unless the revised parsing rules are applied
+    // (in which case the `alg` is left empty).
     let json_str = r#"{"kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
     let json = serde_json::Value::from_str(json_str).unwrap();
     assert!(RSA_JWK::try_from(&json).is_err());
+    let expected = RSA_JWK::new_from_strs("kid1", "RSA", "", "AQAB", "13131");
+    assert_eq!(expected, RSA_JWK::try_from_json_v2(&json).unwrap());
 
     // JWK JSON with wrong `alg` type should be rejected.
     let json_str =
@@ -74,6 +89,75 @@ fn convert_json_to_rsa_jwk() {
     assert!(RSA_JWK::try_from(&json).is_err());
 }
 
+#[test]
+fn convert_json_to_rsa_jwk_checks_use_and_key_ops() {
+    let expected = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "13131");
+
+    // A consistent `use` and `key_ops` pair should be accepted.
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "key_ops": ["verify"], "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert_eq!(expected, RSA_JWK::try_from_json_v2(&json).unwrap());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+
+    // A conflicting `use` and `key_ops` pair should be rejected (but only checked under the
+    // revised parsing rules).
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "key_ops": ["encrypt"], "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(RSA_JWK::try_from_json_v2(&json).is_err());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "enc", "key_ops": ["verify"], "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(RSA_JWK::try_from_json_v2(&json).is_err());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+
+    // A malformed `key_ops` should be rejected (but only checked under the revised parsing
+    // rules).
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "key_ops": "verify", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(RSA_JWK::try_from_json_v2(&json).is_err());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+
+    // Only `use` present should be accepted.
+    let json_str =
+        r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert_eq!(expected, RSA_JWK::try_from_json_v2(&json).unwrap());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+
+    // Only `key_ops` present should be accepted.
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "key_ops": ["verify"], "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert_eq!(expected, RSA_JWK::try_from_json_v2(&json).unwrap());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+
+    // Keys that cannot verify signatures (under either convention) should be rejected under
+    // the revised parsing rules, but are still accepted otherwise.
+    let json_str =
+        r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "enc", "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(RSA_JWK::try_from_json_v2(&json).is_err());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+    let json_str = r#"{"alg": "RS256", "kid": "kid1", "e": "AQAB", "key_ops": ["encrypt"], "kty": "RSA", "n": "13131"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    assert!(RSA_JWK::try_from_json_v2(&json).is_err());
+    assert_eq!(expected, RSA_JWK::try_from(&json).unwrap());
+}
+
+#[test]
+fn rsa_jwk_jwt_alg_compatibility() {
+    // A key without an `alg` should be compatible with any supported RSA signature alg.
+    let jwk_without_alg = RSA_JWK::new_from_strs("kid1", "RSA", "", "AQAB", "13131");
+    assert!(jwk_without_alg.is_compatible_with_jwt_alg("RS256"));
+    assert!(!jwk_without_alg.is_compatible_with_jwt_alg("ES256"));
+    assert!(!jwk_without_alg.is_compatible_with_jwt_alg(""));
+
+    // A key with an `alg` should only be compatible with that exact alg.
+    let jwk_with_alg = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "13131");
+    assert!(jwk_with_alg.is_compatible_with_jwt_alg("RS256"));
+    let jwk_with_mismatched_alg = RSA_JWK::new_from_strs("kid1", "RSA", "RS384", "AQAB", "13131");
+    assert!(!jwk_with_mismatched_alg.is_compatible_with_jwt_alg("RS256"));
+}
+
 #[test]
 fn rsa_jwk_as_move_value() {
     let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "13131");
@@ -97,3 +181,159 @@ fn rsa_jwk_as_move_any() {
     };
     assert_eq!(expected, actual);
 }
+
+#[test]
+fn equal_rsa_jwks_have_identical_move_any_data() {
+    // Construct the same key from code and from JSON with a shuffled field order
+    let rsa_jwk_from_strs = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "13131");
+    let json_str =
+        r#"{"n": "13131", "e": "AQAB", "use": "sig", "kty": "RSA", "kid": "kid1", "alg": "RS256"}"#;
+    let json = serde_json::Value::from_str(json_str).unwrap();
+    let rsa_jwk_from_json = RSA_JWK::try_from(&json).unwrap();
+
+    // Both keys should produce byte-identical `Any` data
+    let move_any_from_strs = rsa_jwk_from_strs.as_move_any();
+    let move_any_from_json = rsa_jwk_from_json.as_move_any();
+    assert_eq!(move_any_from_strs.data, move_any_from_json.data);
+
+    // The data should match the Move value serialization (in Move field order)
+    assert_eq!(
+        rsa_jwk_from_strs
+            .as_move_value()
+            .simple_serialize()
+            .unwrap(),
+        move_any_from_strs.data
+    );
+
+    // The data should round-trip back into the same key
+    let unpacked: RSA_JWK = MoveAny::unpack(RSA_JWK::MOVE_TYPE_NAME, move_any_from_json).unwrap();
+    assert_eq!(rsa_jwk_from_strs, unpacked);
+}
+
+#[test]
+fn rsa_jwk_to_spki_der() {
+    // A toy key with `n = 3233` and `e = 17`
+    let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "EQ", "DKE");
+    let expected_der = vec![
+        0x30, 0x1B, 0x30, 0x0D, 0x06, 0x09, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01,
+        0x05, 0x00, 0x03, 0x0A, 0x00, 0x30, 0x07, 0x02, 0x02, 0x0C, 0xA1, 0x02, 0x01, 0x11,
+    ];
+    assert_eq!(expected_der, rsa_jwk.to_spki_der().unwrap());
+
+    // Leading zero bytes in the modulus should not change the encoding.
+    let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "EQ", "AAyh");
+    assert_eq!(expected_der, rsa_jwk.to_spki_der().unwrap());
+
+    // Keys that are not valid base64url should be rejected.
+    let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "not base64!");
+    assert!(matches!(
+        rsa_jwk.to_spki_der(),
+        Err(JwkError::InvalidRsaField { .. })
+    ));
+}
+
+#[test]
+fn rsa_jwk_invalid_field_is_reported() {
+    // A bad modulus should be reported against `n`
+    let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "DK*E");
+    match rsa_jwk.to_spki_der() {
+        Err(JwkError::InvalidRsaField { field, detail }) => {
+            assert_eq!(field, "n");
+            assert!(detail.contains("offset 2"), "unexpected detail: {detail}");
+        },
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // A bad exponent should be reported against `e`
+    let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQ*B", "DKE");
+    match rsa_jwk.to_spki_der() {
+        Err(JwkError::InvalidRsaField { field, .. }) => assert_eq!(field, "e"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // The field name should also appear in the error message
+    let error = rsa_jwk.to_spki_der().unwrap_err();
+    assert!(error.to_string().contains("`e`"));
+}
+
+#[test]
+fn rsa_jwk_canonical_modulus() {
+    // Two encodings of `n = 3233`, with and without a leading zero byte
+    let rsa_jwk = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "EQ", "DKE");
+    let rsa_jwk_with_leading_zero = RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "EQ", "AAyh");
+    assert_ne!(rsa_jwk, rsa_jwk_with_leading_zero);
+
+    // Both should have the same canonical modulus and bit length
+    assert_eq!(vec![0x0C, 0xA1], rsa_jwk.canonical_modulus().unwrap());
+    assert_eq!(
+        rsa_jwk.canonical_modulus().unwrap(),
+        rsa_jwk_with_leading_zero.canonical_modulus().unwrap()
+    );
+    assert_eq!(12, rsa_jwk.modulus_bits().unwrap());
+    assert_eq!(12, rsa_jwk_with_leading_zero.modulus_bits().unwrap());
+
+    // Both should be parsed into the same key under the revised parsing rules
+    let parse_json_v2 = |n: &str| {
+        let json = serde_json::json!({"alg": "RS256", "kid": "kid1", "e": "EQ", "use": "sig", "kty": "RSA", "n": n});
+        RSA_JWK::try_from_json_v2(&json).unwrap()
+    };
+    assert_eq!(rsa_jwk, parse_json_v2("DKE"));
+    assert_eq!(rsa_jwk, parse_json_v2("AAyh"));
+
+    // The legacy parsing rules should preserve the original encoding
+    let json = serde_json::json!({"alg": "RS256", "kid": "kid1", "e": "EQ", "use": "sig", "kty": "RSA", "n": "AAyh"});
+    assert_eq!(rsa_jwk_with_leading_zero, RSA_JWK::try_from(&json).unwrap());
+}
+
+#[test]
+fn rsa_jwk_matches_certificate() {
+    // A self-signed certificate for the insecure test key
+    let cert_der = include_bytes!("insecure_test_cert.der");
+    assert!(insecure_test_rsa_jwk()
+        .matches_certificate(cert_der)
+        .unwrap());
+    assert!(!secure_test_rsa_jwk().matches_certificate(cert_der).unwrap());
+
+    // Certificates that can't be parsed should be rejected
+    assert!(matches!(
+        insecure_test_rsa_jwk().matches_certificate(&cert_der[..100]),
+        Err(JwkError::InvalidCertificate(_))
+    ));
+
+    // Certificates for non-RSA keys should be rejected
+    let ec_cert_der = include_bytes!("insecure_test_ec_cert.der");
+    assert!(matches!(
+        insecure_test_rsa_jwk().matches_certificate(ec_cert_der),
+        Err(JwkError::InvalidCertificate(_))
+    ));
+}
+
+#[test]
+fn rsa_jwk_generated_for_testing_verifies_signed_jwt() {
+    let (rsa_jwk, private_key) = RSA_JWK::generate_for_testing(2048);
+    assert_eq!(2048, rsa_jwk.modulus_bits().unwrap());
+    assert_eq!("AQAB", rsa_jwk.e);
+
+    // Sign a JWT with the generated private key
+    let claims = serde_json::json!({
+        "iss": "test.oidc.provider",
+        "aud": "test-client-id",
+        "sub": "test-account",
+        "nonce": "test-nonce",
+        "iat": 1700000000,
+        "exp": 2700000000u64,
+    });
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(rsa_jwk.kid.clone());
+    let encoding_key = EncodingKey::from_rsa_der(private_key.to_pkcs1_der().unwrap().as_bytes());
+    let jwt = jsonwebtoken::encode(&header, &claims, &encoding_key).unwrap();
+
+    // The JWT should verify against the generated JWK
+    let token_data = rsa_jwk.verify_signature_without_exp_check(&jwt).unwrap();
+    assert_eq!("test-account", token_data.claims.oidc_claims.sub);
+
+    // The JWT should not verify against a different key
+    assert!(insecure_test_rsa_jwk()
+        .verify_signature_without_exp_check(&jwt)
+        .is_err());
+}
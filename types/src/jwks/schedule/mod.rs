@@ -0,0 +1,80 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+/// Tracks when each issuer's JWKs should next be fetched. The schedule performs no
+/// I/O itself: the caller polls `due_for_refresh()`, fetches the JWKs of the returned
+/// issuers, and reports each fetch via `record_refresh()`.
+///
+/// Times are durations since an arbitrary (but fixed) origin, e.g., the unix epoch
+/// or the start of a `TimeService`. An issuer that has never been refreshed is due
+/// immediately.
+#[derive(Clone, Debug)]
+pub struct JwkRefreshSchedule {
+    default_interval: Duration,
+    interval_overrides: HashMap<String, Duration>,
+    last_refresh_times: BTreeMap<String, Option<Duration>>,
+}
+
+impl JwkRefreshSchedule {
+    pub fn new(default_interval: Duration) -> Self {
+        Self {
+            default_interval,
+            interval_overrides: HashMap::new(),
+            last_refresh_times: BTreeMap::new(),
+        }
+    }
+
+    /// Starts tracking the given issuer (if it isn't already tracked)
+    pub fn add_issuer(&mut self, issuer: impl Into<String>) {
+        self.last_refresh_times.entry(issuer.into()).or_insert(None);
+    }
+
+    /// Stops tracking the given issuer, and removes its interval override (if any)
+    pub fn remove_issuer(&mut self, issuer: &str) {
+        self.last_refresh_times.remove(issuer);
+        self.interval_overrides.remove(issuer);
+    }
+
+    /// Overrides the default refresh interval for the given issuer
+    pub fn set_interval_override(&mut self, issuer: impl Into<String>, interval: Duration) {
+        self.interval_overrides.insert(issuer.into(), interval);
+    }
+
+    /// Returns the refresh interval of the given issuer
+    pub fn interval(&self, issuer: &str) -> Duration {
+        self.interval_overrides
+            .get(issuer)
+            .copied()
+            .unwrap_or(self.default_interval)
+    }
+
+    /// Records that the JWKs of the given issuer were refreshed at `now`.
+    /// Untracked issuers are ignored.
+    pub fn record_refresh(&mut self, issuer: &str, now: Duration) {
+        if let Some(last_refresh_time) = self.last_refresh_times.get_mut(issuer) {
+            *last_refresh_time = Some(now);
+        }
+    }
+
+    /// Returns the issuers that are due for a refresh at `now` (sorted by issuer)
+    pub fn due_for_refresh(&self, now: Duration) -> Vec<String> {
+        self.last_refresh_times
+            .iter()
+            .filter(|(issuer, last_refresh_time)| match last_refresh_time {
+                Some(last_refresh_time) => last_refresh_time
+                    .checked_add(self.interval(issuer))
+                    .is_some_and(|next_refresh_time| now >= next_refresh_time),
+                None => true,
+            })
+            .map(|(issuer, _)| issuer.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests;
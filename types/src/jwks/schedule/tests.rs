@@ -0,0 +1,51 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::jwks::schedule::JwkRefreshSchedule;
+use std::time::Duration;
+
+#[test]
+fn due_for_refresh_respects_intervals() {
+    let google = "https://accounts.google.com";
+    let facebook = "https://www.facebook.com";
+    let mut schedule = JwkRefreshSchedule::new(Duration::from_secs(10));
+    schedule.add_issuer(google);
+    schedule.add_issuer(facebook);
+    schedule.set_interval_override(facebook, Duration::from_secs(25));
+    assert_eq!(schedule.interval(google), Duration::from_secs(10));
+    assert_eq!(schedule.interval(facebook), Duration::from_secs(25));
+
+    // Verify issuers that were never refreshed are due immediately
+    let mut now = Duration::ZERO;
+    assert_eq!(schedule.due_for_refresh(now), vec![facebook, google]);
+    schedule.record_refresh(google, now);
+    schedule.record_refresh(facebook, now);
+    assert!(schedule.due_for_refresh(now).is_empty());
+
+    // Simulate time passing, and verify each issuer is due according to its own interval
+    let mut due_lists = vec![];
+    for _ in 0..6 {
+        now += Duration::from_secs(5);
+        let due = schedule.due_for_refresh(now);
+        for issuer in &due {
+            schedule.record_refresh(issuer, now);
+        }
+        due_lists.push(due);
+    }
+    let expected_due_lists: Vec<Vec<String>> = vec![
+        vec![],                // t=5
+        vec![google.into()],   // t=10
+        vec![],                // t=15
+        vec![google.into()],   // t=20
+        vec![facebook.into()], // t=25
+        vec![google.into()],   // t=30
+    ];
+    assert_eq!(due_lists, expected_due_lists);
+
+    // Verify removed issuers are no longer scheduled
+    schedule.remove_issuer(google);
+    assert_eq!(schedule.due_for_refresh(Duration::from_secs(100)), vec![
+        facebook
+    ]);
+    assert_eq!(schedule.interval(google), Duration::from_secs(10));
+}
@@ -0,0 +1,500 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::jwks::{
+    error::JwkError,
+    issuer_from_str,
+    jwk::{JWKMoveStruct, ParseOptions, JWK},
+    load_jwk_set_from_file, parse_jwk_set, parse_jwk_set_from_str, parse_jwk_set_lenient,
+    parse_jwk_set_with_options, parse_multi_issuer, parse_multi_issuer_from_str, parse_signed_jwks,
+    rsa::{INSECURE_TEST_RSA_JWK, INSECURE_TEST_RSA_KEY_PAIR, RSA_JWK},
+    EpochScopedProviderJWKs, JwkSetParse, ProviderJWKs,
+};
+use aptos_crypto::{ed25519::Ed25519PrivateKey, PrivateKey, SigningKey, Uniform};
+use base64::URL_SAFE_NO_PAD;
+use ring::signature;
+use serde_json::json;
+
+#[test]
+fn parse_multi_issuer_document() {
+    let document = json!({
+        "https://alice.io": {
+            "keys": [
+                {"alg": "RS256", "kid": "kid2", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n2"},
+                {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"},
+            ]
+        },
+        "https://bob.dev": {
+            "keys": [
+                {"kid": "kid3", "kty": "EC", "crv": "P-256", "x": "x3", "y": "y3"},
+            ]
+        },
+    });
+
+    let providers = parse_multi_issuer(&document).unwrap();
+    let expected_alice = ProviderJWKs {
+        issuer: issuer_from_str("https://alice.io"),
+        version: 0,
+        jwks: vec![
+            JWKMoveStruct::from(RSA_JWK::new_from_strs("kid1", "RSA", "RS256", "AQAB", "n1")),
+            JWKMoveStruct::from(RSA_JWK::new_from_strs("kid2", "RSA", "RS256", "AQAB", "n2")),
+        ],
+    };
+    assert_eq!(2, providers.len());
+    assert_eq!(expected_alice, providers[0]);
+    assert_eq!(issuer_from_str("https://bob.dev"), providers[1].issuer);
+    assert_eq!(1, providers[1].jwks.len());
+    assert!(matches!(
+        JWK::try_from(&providers[1].jwks[0]).unwrap(),
+        JWK::Unsupported(_)
+    ));
+}
+
+#[test]
+fn parse_multi_issuer_rejects_malformed_documents() {
+    // A document that is not an object should be rejected.
+    assert!(matches!(
+        parse_multi_issuer(&json!([])),
+        Err(JwkError::MalformedDocument(_))
+    ));
+
+    // An issuer without `keys` should be rejected.
+    let document = json!({"https://alice.io": {"keys": []}, "https://bob.dev": {}});
+    assert!(matches!(
+        parse_multi_issuer(&document),
+        Err(JwkError::MalformedIssuerEntry { issuer, .. }) if issuer == "https://bob.dev"
+    ));
+
+    // An issuer whose `keys` is not an array should be rejected.
+    let document = json!({"https://alice.io": {"keys": {}}});
+    assert!(matches!(
+        parse_multi_issuer(&document),
+        Err(JwkError::MalformedIssuerEntry { .. })
+    ));
+
+    // An issuer whose JWKS is not an object should be rejected.
+    let document = json!({"https://alice.io": "keys"});
+    assert!(matches!(
+        parse_multi_issuer(&document),
+        Err(JwkError::MalformedIssuerEntry { .. })
+    ));
+
+    // An empty document yields no providers.
+    assert_eq!(
+        Vec::<ProviderJWKs>::new(),
+        parse_multi_issuer(&json!({})).unwrap()
+    );
+}
+
+#[test]
+fn parse_multi_issuer_from_str_rejects_duplicate_issuers() {
+    // A document without duplicate issuers parses the same as its JSON value.
+    let json = r#"{
+        "https://alice.io": {"keys": [{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"}]},
+        "https://bob.dev": {"keys": []}
+    }"#;
+    assert_eq!(
+        parse_multi_issuer(&serde_json::from_str(json).unwrap()).unwrap(),
+        parse_multi_issuer_from_str(json).unwrap()
+    );
+
+    // A document in which an issuer appears more than once should be rejected.
+    let json = r#"{
+        "https://alice.io": {"keys": []},
+        "https://bob.dev": {"keys": []},
+        "https://alice.io": {"keys": [{"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"}]}
+    }"#;
+    assert_eq!(
+        Err(JwkError::DuplicateIssuer("https://alice.io".to_string())),
+        parse_multi_issuer_from_str(json)
+    );
+
+    // A document that is not an object should be rejected.
+    assert!(matches!(
+        parse_multi_issuer_from_str("[]"),
+        Err(JwkError::MalformedDocument(_))
+    ));
+}
+
+#[test]
+fn select_epoch_scoped_provider_jwks() {
+    let alice = issuer_from_str("https://alice.io");
+    let bob = issuer_from_str("https://bob.dev");
+    let key_sets = vec![
+        EpochScopedProviderJWKs::new(3, ProviderJWKs::new(alice.clone())),
+        EpochScopedProviderJWKs::new(5, ProviderJWKs::new(alice.clone())),
+        EpochScopedProviderJWKs::new(4, ProviderJWKs::new(bob.clone())),
+    ];
+
+    // A key set is valid for its own epoch and later epochs, but not for earlier ones.
+    assert!(key_sets[0].valid_for_epoch(3));
+    assert!(key_sets[0].valid_for_epoch(10));
+    assert!(!key_sets[0].valid_for_epoch(2));
+
+    // The key set of the exact epoch should be selected.
+    let selected = EpochScopedProviderJWKs::select_for_epoch(&key_sets, 5).unwrap();
+    assert_eq!(5, selected.epoch);
+
+    // The highest older epoch should be selected if there is no exact match.
+    let selected = EpochScopedProviderJWKs::select_for_epoch(&key_sets, 4).unwrap();
+    assert_eq!(&key_sets[2], selected);
+    let selected =
+        EpochScopedProviderJWKs::select_for_issuer_and_epoch(&key_sets, &alice, 4).unwrap();
+    assert_eq!(&key_sets[0], selected);
+
+    // Key sets from future epochs should never be selected.
+    assert_eq!(
+        None,
+        EpochScopedProviderJWKs::select_for_epoch(&key_sets, 2)
+    );
+    assert_eq!(
+        None,
+        EpochScopedProviderJWKs::select_for_issuer_and_epoch(&key_sets, &bob, 3)
+    );
+}
+
+#[test]
+fn parse_jwk_set_document() {
+    // A document with keys should yield them.
+    let document = json!({
+        "keys": [
+            {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"},
+        ]
+    });
+    let expected = JwkSetParse {
+        keys: vec![JWK::RSA(RSA_JWK::new_from_strs(
+            "kid1", "RSA", "RS256", "AQAB", "n1",
+        ))],
+        was_empty: false,
+    };
+    assert_eq!(expected, parse_jwk_set(&document).unwrap());
+
+    // An empty key set is valid, but should be flagged as empty.
+    let parse = parse_jwk_set(&json!({"keys": []})).unwrap();
+    assert!(parse.keys.is_empty());
+    assert!(parse.was_empty);
+
+    // Malformed documents should still be rejected.
+    assert!(matches!(
+        parse_jwk_set(&json!({})),
+        Err(JwkError::MalformedDocument(_))
+    ));
+    assert!(matches!(
+        parse_jwk_set(&json!({"keys": {}})),
+        Err(JwkError::MalformedDocument(_))
+    ));
+}
+
+#[test]
+fn parse_jwk_set_with_bare_key() {
+    let key =
+        json!({"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"});
+    let expected = JwkSetParse {
+        keys: vec![JWK::RSA(RSA_JWK::new_from_strs(
+            "kid1", "RSA", "RS256", "AQAB", "n1",
+        ))],
+        was_empty: false,
+    };
+
+    let options = ParseOptions {
+        parsing_v2: true,
+        ..ParseOptions::lenient()
+    };
+
+    // A wrapped key and a bare key should yield the same set.
+    let document = json!({ "keys": [key.clone()] });
+    assert_eq!(
+        expected,
+        parse_jwk_set_with_options(&document, &options).unwrap()
+    );
+    assert_eq!(
+        expected,
+        parse_jwk_set_with_options(&key, &options).unwrap()
+    );
+
+    // An object with both `keys` and `kty` is ambiguous, and should be rejected.
+    let ambiguous_document = json!({ "keys": [key.clone()], "kty": "RSA" });
+    assert!(matches!(
+        parse_jwk_set_with_options(&ambiguous_document, &options),
+        Err(JwkError::MalformedDocument(_))
+    ));
+
+    // Without the revised parsing rules, a bare key should be rejected (as `keys` is
+    // missing), and `kty` should be ignored if `keys` is present.
+    assert!(matches!(
+        parse_jwk_set(&key),
+        Err(JwkError::MalformedDocument(_))
+    ));
+    assert_eq!(expected, parse_jwk_set(&ambiguous_document).unwrap());
+}
+
+/// Returns the base64url encoding of the given JSON value
+fn encode_json(value: &serde_json::Value) -> String {
+    base64::encode_config(value.to_string(), URL_SAFE_NO_PAD)
+}
+
+/// Returns a JWS (in compact serialization) over the given JWKS, signed with the test RSA key
+fn create_rsa_signed_jwks(jwks: &serde_json::Value) -> String {
+    let signing_input = format!(
+        "{}.{}",
+        encode_json(&json!({"alg": "RS256", "kid": "test-rsa"})),
+        encode_json(jwks)
+    );
+    let key_pair = &*INSECURE_TEST_RSA_KEY_PAIR;
+    let mut signature = vec![0u8; key_pair.public_modulus_len()];
+    key_pair
+        .sign(
+            &signature::RSA_PKCS1_SHA256,
+            &ring::rand::SystemRandom::new(),
+            signing_input.as_bytes(),
+            &mut signature,
+        )
+        .unwrap();
+    format!(
+        "{}.{}",
+        signing_input,
+        base64::encode_config(signature, URL_SAFE_NO_PAD)
+    )
+}
+
+#[test]
+fn parse_signed_jwks_document() {
+    let jwks = json!({
+        "keys": [
+            {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"},
+        ]
+    });
+    let expected_keys = vec![JWK::RSA(RSA_JWK::new_from_strs(
+        "kid1", "RSA", "RS256", "AQAB", "n1",
+    ))];
+
+    // A validly signed key set should be parsed
+    let trust_anchor = JWK::RSA(INSECURE_TEST_RSA_JWK.clone());
+    let jws = create_rsa_signed_jwks(&jwks);
+    assert_eq!(
+        expected_keys,
+        parse_signed_jwks(&jws, &trust_anchor).unwrap()
+    );
+
+    // A key set signed with an Ed25519 key should also be parsed
+    let private_key = Ed25519PrivateKey::generate_for_testing();
+    let x = base64::encode_config(private_key.public_key().to_bytes(), URL_SAFE_NO_PAD);
+    let okp_trust_anchor =
+        JWK::from(json!({"kid": "kid1", "kty": "OKP", "alg": "EdDSA", "crv": "Ed25519", "x": x}));
+    let signing_input = format!(
+        "{}.{}",
+        encode_json(&json!({"alg": "EdDSA"})),
+        encode_json(&jwks)
+    );
+    let signature = private_key.sign_arbitrary_message(signing_input.as_bytes());
+    let okp_jws = format!(
+        "{}.{}",
+        signing_input,
+        base64::encode_config(signature.to_bytes(), URL_SAFE_NO_PAD)
+    );
+    assert_eq!(
+        expected_keys,
+        parse_signed_jwks(&okp_jws, &okp_trust_anchor).unwrap()
+    );
+
+    // A key set signed by another key should be rejected
+    assert!(matches!(
+        parse_signed_jwks(&jws, &okp_trust_anchor),
+        Err(JwkError::InvalidSignature(_))
+    ));
+}
+
+#[test]
+fn parse_tampered_signed_jwks() {
+    let trust_anchor = JWK::RSA(INSECURE_TEST_RSA_JWK.clone());
+    let jws = create_rsa_signed_jwks(&json!({
+        "keys": [
+            {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"},
+        ]
+    }));
+
+    // Replace the payload with another key set (keeping the original signature)
+    let parts: Vec<_> = jws.split('.').collect();
+    let tampered_payload = encode_json(&json!({
+        "keys": [
+            {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "attacker"},
+        ]
+    }));
+    let tampered_jws = format!("{}.{}.{}", parts[0], tampered_payload, parts[2]);
+    assert!(matches!(
+        parse_signed_jwks(&tampered_jws, &trust_anchor),
+        Err(JwkError::InvalidSignature(_))
+    ));
+
+    // Malformed JWSs should be rejected
+    for malformed_jws in ["", "header.payload", "a.b.c.d"] {
+        assert!(matches!(
+            parse_signed_jwks(malformed_jws, &trust_anchor),
+            Err(JwkError::MalformedDocument(_))
+        ));
+    }
+}
+
+#[test]
+fn parse_jwk_set_rejecting_unsupported_keys() {
+    // A document with one supported and one unsupported key
+    let document = json!({
+        "keys": [
+            {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"},
+            {"alg": "ES256", "kid": "kid2", "use": "sig", "kty": "EC", "crv": "P-256", "x": "x2", "y": "y2"},
+        ]
+    });
+
+    // In lenient mode, the unsupported key should be kept as is
+    let parse = parse_jwk_set_with_options(&document, &ParseOptions::lenient()).unwrap();
+    assert_eq!(2, parse.keys.len());
+    assert!(matches!(parse.keys[1], JWK::Unsupported(_)));
+
+    // In strict mode, the whole document should be rejected
+    match parse_jwk_set_with_options(&document, &ParseOptions::strict()) {
+        Err(JwkError::UnsupportedKey(key)) => assert!(key.contains("kid=kid2")),
+        result => panic!("Expected an unsupported key error, found: {:?}", result),
+    }
+}
+
+#[test]
+fn parse_jwk_set_lenient_collecting_key_errors() {
+    // A document with one good key, and two malformed entries
+    let document = json!({
+        "keys": [
+            "not a key",
+            {"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"},
+            {"alg": "RS256", "kid": "kid2", "use": "sig", "kty": "RSA"},
+        ]
+    });
+
+    // In strict mode, the good key should be returned with the indexed errors of the others
+    let (keys, errors) = parse_jwk_set_lenient(&document, &ParseOptions::strict()).unwrap();
+    assert_eq!(keys, vec![JWK::RSA(RSA_JWK::new_from_strs(
+        "kid1", "RSA", "RS256", "AQAB", "n1"
+    ))]);
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], (0, JwkError::MalformedKey(_))));
+    assert!(matches!(errors[1], (2, JwkError::UnsupportedKey(_))));
+
+    // In lenient mode, only the entry that is not an object should fail
+    let (keys, errors) = parse_jwk_set_lenient(&document, &ParseOptions::lenient()).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert!(matches!(keys[1], JWK::Unsupported(_)));
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0], (0, JwkError::MalformedKey(_))));
+
+    // A malformed document should still fail as a whole
+    assert!(matches!(
+        parse_jwk_set_lenient(&json!({"keys": "not an array"}), &ParseOptions::strict()),
+        Err(JwkError::MalformedDocument(_))
+    ));
+}
+
+#[test]
+fn parse_jwk_set_exceeding_max_keys() {
+    let key =
+        json!({"alg": "RS256", "kid": "kid1", "e": "AQAB", "use": "sig", "kty": "RSA", "n": "n1"});
+    let options = ParseOptions {
+        max_keys: 2,
+        parsing_v2: true,
+        ..ParseOptions::lenient()
+    };
+
+    // A document with the maximum number of keys should be accepted
+    let document = json!({ "keys": [key.clone(), key.clone()] });
+    let parse = parse_jwk_set_with_options(&document, &options).unwrap();
+    assert_eq!(2, parse.keys.len());
+
+    // A document with more keys should be rejected
+    let document = json!({ "keys": [key.clone(), key.clone(), key] });
+    assert_eq!(
+        parse_jwk_set_with_options(&document, &options),
+        Err(JwkError::TooManyKeys {
+            num_keys: 3,
+            max_keys: 2
+        })
+    );
+
+    // Without the revised parsing rules, the limit should not apply
+    let options = ParseOptions {
+        parsing_v2: false,
+        ..options
+    };
+    let parse = parse_jwk_set_with_options(&document, &options).unwrap();
+    assert_eq!(3, parse.keys.len());
+}
+
+#[test]
+fn parse_jwk_set_exceeding_max_bytes() {
+    let json =
+        r#"{"keys": [{"alg": "RS256", "kid": "kid1", "e": "AQAB", "kty": "RSA", "n": "n1"}]}"#;
+    let options = ParseOptions {
+        max_bytes: json.len(),
+        ..ParseOptions::lenient()
+    };
+
+    // A document of the maximum size should be accepted
+    let parse = parse_jwk_set_from_str(json, &options).unwrap();
+    assert_eq!(1, parse.keys.len());
+
+    // A larger document should be rejected
+    let padded_json = format!("{} ", json);
+    assert_eq!(
+        parse_jwk_set_from_str(&padded_json, &options),
+        Err(JwkError::DocumentTooLarge(json.len()))
+    );
+
+    // The same limit should apply when loading the document from a file
+    let path = aptos_temppath::TempPath::new();
+    std::fs::write(path.path(), json).unwrap();
+    assert_eq!(
+        load_jwk_set_from_file(path.path(), &options).unwrap(),
+        parse
+    );
+    std::fs::write(path.path(), padded_json).unwrap();
+    assert_eq!(
+        load_jwk_set_from_file(path.path(), &options),
+        Err(JwkError::DocumentTooLarge(json.len()))
+    );
+}
+
+#[test]
+fn parse_jwk_set_with_duplicate_members() {
+    // A document with a duplicate member in one of its keys
+    let json = r#"{"keys": [{"alg": "RS256", "kid": "kid1", "e": "AQAB", "kty": "RSA", "n": "n1", "kid": "kid2"}]}"#;
+
+    // By default, the last occurrence of the member wins
+    let parse = parse_jwk_set_from_str(json, &ParseOptions::lenient()).unwrap();
+    assert_eq!(
+        vec![JWK::RSA(RSA_JWK::new_from_strs(
+            "kid2", "RSA", "RS256", "AQAB", "n1"
+        ))],
+        parse.keys
+    );
+
+    // If duplicate members are rejected, the whole document should be rejected
+    let options = ParseOptions {
+        reject_duplicate_members: true,
+        ..ParseOptions::lenient()
+    };
+    assert!(matches!(
+        parse_jwk_set_from_str(json, &options),
+        Err(JwkError::MalformedDocument(_))
+    ));
+
+    // The same applies to duplicate members of the document itself
+    let json = r#"{"keys": [], "keys": []}"#;
+    assert!(parse_jwk_set_from_str(json, &ParseOptions::lenient()).is_ok());
+    assert!(matches!(
+        parse_jwk_set_from_str(json, &options),
+        Err(JwkError::MalformedDocument(_))
+    ));
+
+    // Documents without duplicate members should be accepted
+    let json = r#"{"keys": [{"alg": "RS256", "kid": "kid1", "e": "AQAB", "kty": "RSA", "n": "n1", "key_ops": ["verify"]}]}"#;
+    assert_eq!(
+        1,
+        parse_jwk_set_from_str(json, &options).unwrap().keys.len()
+    );
+}
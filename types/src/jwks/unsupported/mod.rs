@@ -10,7 +10,10 @@ use std::fmt::{Debug, Formatter};
 
 /// Move type `0x1::jwks::UnsupportedJWK` in rust.
 /// See its doc in Move for more details.
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Object)]
+///
+/// Note: symmetric (`oct`) keys are kept as `UnsupportedJWK`s, so the payload may hold
+/// a secret (i.e., `k`). Hence, payloads are compared in constant time (see `PartialEq`).
+#[derive(Clone, Eq, Serialize, Deserialize, Object)]
 pub struct UnsupportedJWK {
     pub id: Vec<u8>,
     pub payload: Vec<u8>,
@@ -25,6 +28,23 @@ impl Debug for UnsupportedJWK {
     }
 }
 
+impl PartialEq for UnsupportedJWK {
+    /// Compares the payloads in constant time, i.e., the time taken only depends on
+    /// the payload lengths (and not on the position of the first differing byte).
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && constant_time_eq(&self.payload, &other.payload)
+    }
+}
+
+/// Returns true iff the given byte slices are equal, in time independent of their contents
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(difference) == 0
+}
+
 impl UnsupportedJWK {
     #[cfg(any(test, feature = "fuzzing"))]
     pub fn new_for_testing(id: &str, payload: &str) -> Self {
@@ -46,6 +66,40 @@ impl UnsupportedJWK {
     pub fn id(&self) -> Vec<u8> {
         self.id.clone()
     }
+
+    /// Converts the JSON value into an `UnsupportedJWK` (see `From<serde_json::Value>`),
+    /// after lowercasing every string (at any depth) that consists only of hex digits.
+    /// This way, keys that only differ in the case of hex-encoded fields (e.g., a
+    /// certificate thumbprint) have the same payload, and hence the same `id`.
+    ///
+    /// NOTE: the normalization is lossy. The payload no longer holds the key as served,
+    /// and strings that merely look like hex (e.g., a short base64url value such as
+    /// `"ABCD"`) are lowercased too, which changes their meaning. So this should only be
+    /// used when comparing keys, and not when the original payload must be preserved.
+    pub fn from_value_with_normalized_hex(json_value: serde_json::Value) -> Self {
+        Self::from(normalize_hex_case(json_value))
+    }
+}
+
+/// Lowercases every string in the JSON value that consists only of hex digits
+fn normalize_hex_case(json_value: serde_json::Value) -> serde_json::Value {
+    match json_value {
+        serde_json::Value::String(string)
+            if !string.is_empty() && string.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            serde_json::Value::String(string.to_ascii_lowercase())
+        },
+        serde_json::Value::Array(values) => {
+            serde_json::Value::Array(values.into_iter().map(normalize_hex_case).collect())
+        },
+        serde_json::Value::Object(members) => serde_json::Value::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name, normalize_hex_case(value)))
+                .collect(),
+        ),
+        json_value => json_value,
+    }
 }
 
 impl From<serde_json::Value> for UnsupportedJWK {
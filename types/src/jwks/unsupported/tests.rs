@@ -44,3 +44,44 @@ fn unsupported_jwk_as_move_any() {
     };
     assert_eq!(expected, actual);
 }
+
+#[test]
+fn normalized_hex_case_variants_have_same_id() {
+    let lowercase = serde_json::json!({"kty": "EXAMPLE", "x5t": "0a1b2c3d", "ext": ["ff"]});
+    let uppercase = serde_json::json!({"kty": "EXAMPLE", "x5t": "0A1B2C3D", "ext": ["FF"]});
+    assert_ne!(
+        UnsupportedJWK::from(lowercase.clone()).id,
+        UnsupportedJWK::from(uppercase.clone()).id
+    );
+    let normalized_lowercase = UnsupportedJWK::from_value_with_normalized_hex(lowercase);
+    let normalized_uppercase = UnsupportedJWK::from_value_with_normalized_hex(uppercase);
+    assert_eq!(normalized_lowercase.id, normalized_uppercase.id);
+    assert_eq!(normalized_lowercase.payload, normalized_uppercase.payload);
+}
+
+#[test]
+fn symmetric_jwk_equality() {
+    // The payload of a symmetric key holds its secret, which is compared in constant time
+    let jwk =
+        UnsupportedJWK::from(serde_json::json!({"kty": "oct", "alg": "HS256", "k": "c2VjcmV0"}));
+    assert_eq!(jwk, jwk.clone());
+
+    // Keys with different secrets (of the same or different lengths) are not equal
+    let other_secret = UnsupportedJWK {
+        id: jwk.id.clone(),
+        payload: jwk.payload.iter().rev().copied().collect(),
+    };
+    assert_ne!(jwk, other_secret);
+    let longer_secret = UnsupportedJWK {
+        id: jwk.id.clone(),
+        payload: [jwk.payload.as_slice(), b"0"].concat(),
+    };
+    assert_ne!(jwk, longer_secret);
+
+    // Keys with different ids are not equal
+    let other_id = UnsupportedJWK {
+        id: vec![],
+        payload: jwk.payload.clone(),
+    };
+    assert_ne!(jwk, other_id);
+}
@@ -92,6 +92,7 @@ pub enum FeatureFlag {
     ENABLE_ENUM_TYPES = 74,
     ENABLE_RESOURCE_ACCESS_CONTROL = 75,
     REJECT_UNSTABLE_BYTECODE_FOR_SCRIPT = 76,
+    JWK_PARSING_V2 = 77,
 }
 
 impl FeatureFlag {
@@ -292,6 +293,13 @@ impl Features {
         self.is_enabled(FeatureFlag::KEYLESS_ACCOUNTS_WITH_PASSKEYS)
     }
 
+    /// Whether JWKs observed by JWK consensus (and validated by the VM) follow the revised
+    /// parsing rules (see `ParseOptions::parsing_v2`). Observations must be identical across
+    /// validators, so the rules may only change with this flag (i.e., at an epoch boundary).
+    pub fn is_jwk_parsing_v2_enabled(&self) -> bool {
+        self.is_enabled(FeatureFlag::JWK_PARSING_V2)
+    }
+
     pub fn is_remove_detailed_error_from_hash_enabled(&self) -> bool {
         self.is_enabled(FeatureFlag::REMOVE_DETAILED_ERROR_FROM_HASH)
     }